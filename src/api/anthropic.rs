@@ -0,0 +1,253 @@
+use reqwest::header::{HeaderMap, HeaderValue};
+
+use crate::types::{
+    api::{Delta, EventSourceData, EventSourceDataChoices, EventSourceDataInterface},
+    chat::{ConfigInterface, MessageInterface, Role, TokenUsage},
+};
+
+use super::provider::ChatProvider;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Anthropic Messages API (`/v1/messages`).
+pub(crate) struct AnthropicProvider;
+
+impl ChatProvider for AnthropicProvider {
+    fn headers(&self, api_key: &Option<String>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("anthropic-version", HeaderValue::from_static(ANTHROPIC_VERSION));
+        // Required for calls made straight from the browser instead of a backend.
+        headers.insert(
+            "anthropic-dangerous-direct-browser-access",
+            HeaderValue::from_static("true"),
+        );
+        if let Some(api_key) = api_key
+            && let Ok(value) = HeaderValue::from_str(api_key)
+        {
+            headers.insert("x-api-key", value);
+        }
+        headers
+    }
+
+    fn request_body(
+        &self,
+        messages: &[MessageInterface],
+        config: &ConfigInterface,
+        stream: bool,
+    ) -> serde_json::Value {
+        let (system, messages) = split_system(messages);
+        let mut body = serde_json::json!({
+            "model": config.model,
+            "messages": messages,
+            "max_tokens": config.max_tokens,
+            "stream": stream,
+        });
+        // Current models refuse requests that set both; `top_p` only counts
+        // once it was moved off its default.
+        if config.top_p != 1.0 {
+            body["top_p"] = serde_json::json!(config.top_p);
+        } else {
+            // Anthropic only accepts temperatures in [0, 1].
+            body["temperature"] = serde_json::json!(config.temperature.clamp(0.0, 1.0));
+        }
+        if !system.is_empty() {
+            body["system"] = serde_json::Value::String(system);
+        }
+        if !config.stop.is_empty() {
+            body["stop_sequences"] = serde_json::json!(config.stop);
+        }
+        if let Some(user) = config.user.as_ref().filter(|u| !u.is_empty()) {
+            body["metadata"] = serde_json::json!({ "user_id": user });
+        }
+        body
+    }
+
+    fn parse_event(&self, data: &str) -> Option<EventSourceData> {
+        let event: serde_json::Value = serde_json::from_str(data).ok()?;
+        match event["type"].as_str()? {
+            "message_start" => {
+                let message = &event["message"];
+                Some(EventSourceData::Data(EventSourceDataInterface {
+                    choices: vec![],
+                    created: 0,
+                    id: message["id"].as_str().unwrap_or_default().to_string(),
+                    model: message["model"].as_str().unwrap_or_default().to_string(),
+                    object: "message".to_string(),
+                    usage: usage_from_json(&message["usage"]),
+                }))
+            }
+            "content_block_delta" => {
+                let text = event["delta"]["text"].as_str()?;
+                Some(EventSourceData::Data(chunk(Some(text.to_string()), None, None)))
+            }
+            "message_delta" => {
+                let stop_reason = event["delta"]["stop_reason"].as_str().map(finish_reason);
+                Some(EventSourceData::Data(chunk(
+                    None,
+                    stop_reason,
+                    usage_from_json(&event["usage"]),
+                )))
+            }
+            "message_stop" => Some(EventSourceData::Done("[DONE]".to_string())),
+            _ => None,
+        }
+    }
+
+    fn response_content(&self, response: &serde_json::Value) -> Option<String> {
+        let blocks = response["content"].as_array()?;
+        Some(
+            blocks
+                .iter()
+                .filter_map(|block| block["text"].as_str())
+                .collect::<String>(),
+        )
+    }
+
+    fn usage(&self, response: &serde_json::Value) -> Option<TokenUsage> {
+        usage_from_json(&response["usage"])
+    }
+
+    fn models_endpoint(&self, endpoint: &str) -> Option<String> {
+        endpoint
+            .strip_suffix("/messages")
+            .map(|base| format!("{}/models", base))
+    }
+}
+
+/// Maps a `stop_reason` to the OpenAI `finish_reason` the rest of the app
+/// checks for.
+fn finish_reason(stop_reason: &str) -> String {
+    match stop_reason {
+        "max_tokens" => "length",
+        "end_turn" | "stop_sequence" => "stop",
+        other => other,
+    }
+    .to_string()
+}
+
+/// Moves every system message into the top-level `system` field and merges
+/// consecutive turns of the same role, since the Messages API requires
+/// strictly alternating user/assistant turns.
+fn split_system(messages: &[MessageInterface]) -> (String, Vec<serde_json::Value>) {
+    let mut system = Vec::new();
+    let mut turns: Vec<(Role, String)> = Vec::new();
+    for message in messages {
+        match message.role {
+            Role::System => system.push(message.content.clone()),
+            _ => match turns.last_mut() {
+                Some((role, content)) if *role == message.role => {
+                    content.push_str("\n\n");
+                    content.push_str(&message.content);
+                }
+                _ => turns.push((message.role.clone(), message.content.clone())),
+            },
+        }
+    }
+    let turns = turns
+        .into_iter()
+        .map(|(role, content)| serde_json::json!({ "role": role.to_string(), "content": content }))
+        .collect();
+    (system.join("\n\n"), turns)
+}
+
+fn usage_from_json(usage: &serde_json::Value) -> Option<TokenUsage> {
+    if usage.is_null() {
+        return None;
+    }
+    Some(TokenUsage {
+        prompt_tokens: usage["input_tokens"].as_u64().unwrap_or_default() as u32,
+        completion_tokens: usage["output_tokens"].as_u64().unwrap_or_default() as u32,
+    })
+}
+
+fn chunk(
+    content: Option<String>,
+    finish_reason: Option<String>,
+    usage: Option<TokenUsage>,
+) -> EventSourceDataInterface {
+    EventSourceDataInterface {
+        choices: vec![EventSourceDataChoices {
+            delta: Delta { content, role: None },
+            finish_reason,
+            index: 0,
+        }],
+        created: 0,
+        id: String::new(),
+        model: String::new(),
+        object: "message".to_string(),
+        usage,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: Role, content: &str) -> MessageInterface {
+        MessageInterface {
+            role,
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn system_messages_move_to_top_level_field() {
+        let messages = vec![
+            message(Role::System, "be brief"),
+            message(Role::User, "hi"),
+            message(Role::User, "there"),
+            message(Role::Assistant, "hello"),
+        ];
+        let body = AnthropicProvider.request_body(&messages, &ConfigInterface::default(), true);
+
+        assert_eq!(body["system"], "be brief");
+        assert_eq!(body["stream"], true);
+        let turns = body["messages"].as_array().unwrap();
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0]["role"], "user");
+        assert_eq!(turns[0]["content"], "hi\n\nthere");
+        assert_eq!(turns[1]["role"], "assistant");
+    }
+
+    #[test]
+    fn request_body_sets_temperature_or_top_p() {
+        let config = ConfigInterface { temperature: 1.5, ..ConfigInterface::default() };
+        let body = AnthropicProvider.request_body(&[], &config, true);
+        assert_eq!(body["temperature"], 1.0);
+        assert!(body.get("top_p").is_none());
+
+        let config = ConfigInterface { top_p: 0.5, ..config };
+        let body = AnthropicProvider.request_body(&[], &config, true);
+        assert_eq!(body["top_p"], 0.5);
+        assert!(body.get("temperature").is_none());
+    }
+
+    #[test]
+    fn content_block_delta_becomes_choice_delta() {
+        let data = r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hel"}}"#;
+        match AnthropicProvider.parse_event(data) {
+            Some(EventSourceData::Data(chunk)) => {
+                assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("Hel"));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn message_delta_carries_stop_reason_and_usage() {
+        let data = r#"{"type":"message_delta","delta":{"stop_reason":"max_tokens"},"usage":{"output_tokens":12}}"#;
+        match AnthropicProvider.parse_event(data) {
+            Some(EventSourceData::Data(chunk)) => {
+                assert_eq!(chunk.choices[0].finish_reason.as_deref(), Some("length"));
+                assert_eq!(chunk.usage.unwrap().completion_tokens, 12);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(AnthropicProvider.parse_event(r#"{"type":"ping"}"#).is_none());
+        assert!(matches!(
+            AnthropicProvider.parse_event(r#"{"type":"message_stop"}"#),
+            Some(EventSourceData::Done(_))
+        ));
+    }
+}
@@ -0,0 +1,217 @@
+use std::time::Duration;
+
+/// How often a retryable request is re-sent before giving up.
+pub(crate) const MAX_RETRIES: u32 = 3;
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// How bad an [`ApiError`] is for the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Severity {
+    /// Passes, or can be worked around.
+    Warning,
+    Error,
+}
+
+/// A failed API call, classified by what the user can do about it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ApiError {
+    /// Missing, wrong or revoked key (401/403).
+    Auth(String),
+    /// 429, with the delay the server asked for in `Retry-After`.
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// The conversation doesn't fit into the model's context window.
+    ContextLengthExceeded(String),
+    /// Prompt or completion was blocked by the provider's content filter.
+    ContentFiltered(String),
+    /// 5xx, or an error event in the middle of the stream (`status` is `None`).
+    Server { status: Option<u16>, message: String },
+    /// Any other rejected request (bad parameters, unknown model, ...).
+    InvalidRequest { status: u16, message: String },
+    /// The request never got a response.
+    Network(String),
+    /// The response body could not be understood.
+    MalformedStream(String),
+    /// A custom header of the API profile can't be sent; nothing was requested.
+    InvalidHeader(String),
+}
+
+impl ApiError {
+    /// Classifies an unsuccessful response from its status code, `Retry-After`
+    /// header and body. Understands the OpenAI/Azure `{"error": {...}}` shape,
+    /// Anthropic's `{"type": "error", "error": {...}}` and plain `{"error": "..."}`.
+    pub(crate) fn from_response(status: u16, retry_after: Option<&str>, body: &str) -> Self {
+        let json = serde_json::from_str::<serde_json::Value>(body).unwrap_or_default();
+        let error = &json["error"];
+        let message = error["message"]
+            .as_str()
+            .or_else(|| error.as_str())
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| {
+                if body.trim().is_empty() {
+                    format!("HTTP {}", status)
+                } else {
+                    body.trim().to_string()
+                }
+            });
+        let code = error["code"].as_str().unwrap_or_default();
+        let kind = error["type"].as_str().unwrap_or_default();
+        let lowercase = message.to_lowercase();
+
+        if code == "context_length_exceeded"
+            || lowercase.contains("maximum context length")
+            || lowercase.contains("prompt is too long")
+        {
+            return ApiError::ContextLengthExceeded(message);
+        }
+        if code == "content_filter" || code == "content_policy_violation" {
+            return ApiError::ContentFiltered(message);
+        }
+        match status {
+            401 | 403 => ApiError::Auth(message),
+            429 => ApiError::RateLimited {
+                message,
+                retry_after: retry_after.and_then(parse_retry_after),
+            },
+            // Anthropic's `overloaded_error` uses the non-standard 529.
+            500..=599 => ApiError::Server {
+                status: Some(status),
+                message,
+            },
+            _ if kind == "authentication_error" || kind == "permission_error" => ApiError::Auth(message),
+            _ => ApiError::InvalidRequest { status, message },
+        }
+    }
+
+    /// Rate limits, overloaded servers and dropped connections usually go away
+    /// on their own; everything else needs the user to change something.
+    pub(crate) fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ApiError::RateLimited { .. } | ApiError::Server { .. } | ApiError::Network(_)
+        )
+    }
+
+    /// Delay before retry number `attempt` (starting at 0): whatever the server
+    /// asked for, otherwise exponential backoff from one second.
+    pub(crate) fn retry_delay(&self, attempt: u32) -> Duration {
+        if let ApiError::RateLimited {
+            retry_after: Some(retry_after),
+            ..
+        } = self
+        {
+            return (*retry_after).min(MAX_RETRY_DELAY);
+        }
+        BASE_RETRY_DELAY
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_RETRY_DELAY)
+    }
+
+    /// Severity and the `api` translation key explaining what to do.
+    pub(crate) fn explain(&self) -> (Severity, &'static str) {
+        match self {
+            ApiError::Auth(_) => (Severity::Error, "error.auth"),
+            ApiError::RateLimited { .. } => (Severity::Warning, "error.rateLimited"),
+            ApiError::ContextLengthExceeded(_) => (Severity::Warning, "error.contextLength"),
+            ApiError::ContentFiltered(_) => (Severity::Warning, "error.contentFiltered"),
+            ApiError::Server { .. } => (Severity::Error, "error.server"),
+            ApiError::InvalidRequest { .. } => (Severity::Error, "error.invalidRequest"),
+            ApiError::Network(_) => (Severity::Error, "error.network"),
+            ApiError::MalformedStream(_) => (Severity::Error, "error.malformedStream"),
+            ApiError::InvalidHeader(_) => (Severity::Error, "error.invalidHeader"),
+        }
+    }
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date; only the
+/// former is worth the trouble here. Absurd waits are cut to
+/// [`MAX_RETRY_DELAY`].
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let seconds = value.trim().parse::<f64>().ok().filter(|s| *s >= 0.0)?;
+    Duration::try_from_secs_f64(seconds.min(MAX_RETRY_DELAY.as_secs_f64())).ok()
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Auth(message) => write!(f, "Authentication failed: {}", message),
+            ApiError::RateLimited { message, .. } => write!(f, "Rate limited: {}", message),
+            ApiError::ContextLengthExceeded(message) => write!(f, "Context length exceeded: {}", message),
+            ApiError::ContentFiltered(message) => write!(f, "Content filtered: {}", message),
+            ApiError::Server {
+                status: Some(status),
+                message,
+            } => write!(f, "Server error ({}): {}", status, message),
+            ApiError::Server { status: None, message } => write!(f, "Server error: {}", message),
+            ApiError::InvalidRequest { status, message } => write!(f, "Request failed ({}): {}", status, message),
+            ApiError::Network(message) => write!(f, "Network error: {}", message),
+            ApiError::MalformedStream(message) => write!(f, "Malformed response: {}", message),
+            ApiError::InvalidHeader(message) => write!(f, "Custom header rejected: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            ApiError::MalformedStream(e.to_string())
+        } else {
+            ApiError::Network(e.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openai_and_azure_bodies_are_classified() {
+        let context = r#"{"error":{"message":"This model's maximum context length is 4097 tokens.","type":"invalid_request_error","param":"messages","code":"context_length_exceeded"}}"#;
+        assert!(matches!(ApiError::from_response(400, None, context), ApiError::ContextLengthExceeded(_)));
+
+        let filtered = r#"{"error":{"message":"The response was filtered","type":null,"param":"prompt","code":"content_filter","status":400}}"#;
+        assert!(matches!(ApiError::from_response(400, None, filtered), ApiError::ContentFiltered(_)));
+
+        let auth = r#"{"error":{"message":"Incorrect API key provided: sk-abc.","type":"invalid_request_error","code":"invalid_api_key"}}"#;
+        assert_eq!(
+            ApiError::from_response(401, None, auth),
+            ApiError::Auth("Incorrect API key provided: sk-abc.".to_string())
+        );
+
+        let anthropic = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        assert!(ApiError::from_response(529, None, anthropic).is_retryable());
+
+        let unknown_model = r#"{"error":"model 'llama9' not found"}"#;
+        assert_eq!(
+            ApiError::from_response(404, None, unknown_model),
+            ApiError::InvalidRequest {
+                status: 404,
+                message: "model 'llama9' not found".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn retry_after_wins_over_backoff() {
+        let limited = ApiError::from_response(429, Some("7"), r#"{"error":{"message":"slow down"}}"#);
+        assert!(limited.is_retryable());
+        assert_eq!(limited.retry_delay(0), Duration::from_secs(7));
+        assert_eq!(parse_retry_after("1e30"), Some(MAX_RETRY_DELAY));
+        assert_eq!(parse_retry_after("inf"), Some(MAX_RETRY_DELAY));
+        assert_eq!(parse_retry_after("NaN"), None);
+        assert_eq!(parse_retry_after("-1"), None);
+
+        let server = ApiError::from_response(502, None, "<html>Bad Gateway</html>");
+        assert_eq!(server.retry_delay(0), Duration::from_secs(1));
+        assert_eq!(server.retry_delay(2), Duration::from_secs(4));
+        assert_eq!(server.retry_delay(10), MAX_RETRY_DELAY);
+
+        assert!(!ApiError::Auth(String::new()).is_retryable());
+    }
+}
@@ -0,0 +1,252 @@
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
+use serde::Deserialize;
+
+use crate::types::{
+    api::{Delta, EventSourceData, EventSourceDataChoices, EventSourceDataInterface},
+    chat::{ConfigInterface, MessageInterface, ResponseFormat, Role, TokenUsage},
+};
+
+use super::{
+    openai::messages_to_json,
+    provider::{ChatProvider, StreamFormat},
+};
+
+/// Ollama's native chat API (`/api/chat`), streamed as newline-delimited JSON.
+pub(crate) struct OllamaProvider;
+
+impl ChatProvider for OllamaProvider {
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Ndjson
+    }
+
+    fn headers(&self, api_key: &Option<String>) -> HeaderMap {
+        bearer_headers(api_key)
+    }
+
+    fn request_body(
+        &self,
+        messages: &[MessageInterface],
+        config: &ConfigInterface,
+        stream: bool,
+    ) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "model": config.model,
+            "messages": messages_to_json(messages),
+            "stream": stream,
+            "options": {
+                "temperature": config.temperature,
+                "top_p": config.top_p,
+                "presence_penalty": config.presence_penalty,
+                "frequency_penalty": config.frequency_penalty,
+                "num_predict": config.max_tokens,
+            },
+        });
+        if !config.stop.is_empty() {
+            body["options"]["stop"] = serde_json::json!(config.stop);
+        }
+        if let Some(seed) = config.seed {
+            body["options"]["seed"] = serde_json::json!(seed);
+        }
+        if config.response_format == ResponseFormat::JsonObject {
+            body["format"] = serde_json::json!("json");
+        }
+        body
+    }
+
+    fn parse_event(&self, data: &str) -> Option<EventSourceData> {
+        let line: serde_json::Value = serde_json::from_str(data).ok()?;
+        let content = line["message"]["content"].as_str().map(|s| s.to_string());
+        if line["done"].as_bool().unwrap_or(false) {
+            let finish_reason = line["done_reason"].as_str().unwrap_or("stop").to_string();
+            return Some(EventSourceData::Data(chunk(
+                content,
+                Some(finish_reason),
+                self.usage(&line),
+            )));
+        }
+        Some(EventSourceData::Data(chunk(content, None, None)))
+    }
+
+    fn response_content(&self, response: &serde_json::Value) -> Option<String> {
+        response["message"]["content"].as_str().map(|s| s.to_string())
+    }
+
+    fn usage(&self, response: &serde_json::Value) -> Option<TokenUsage> {
+        let prompt_tokens = response["prompt_eval_count"].as_u64()?;
+        Some(TokenUsage {
+            prompt_tokens: prompt_tokens as u32,
+            completion_tokens: response["eval_count"].as_u64().unwrap_or_default() as u32,
+        })
+    }
+
+    fn models_endpoint(&self, endpoint: &str) -> Option<String> {
+        url::Url::parse(endpoint)
+            .and_then(|url| url.join("/api/tags"))
+            .map(|url| url.to_string())
+            .ok()
+    }
+
+    fn parse_models(&self, response: &serde_json::Value) -> Vec<String> {
+        serde_json::from_value::<OllamaTags>(response.clone())
+            .map(|tags| tags.models.into_iter().map(|m| m.name).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// llama.cpp server's raw `/completion` endpoint. It takes a single prompt
+/// string, so the conversation is flattened into a plain transcript.
+pub(crate) struct LlamaCppProvider;
+
+impl ChatProvider for LlamaCppProvider {
+    fn headers(&self, api_key: &Option<String>) -> HeaderMap {
+        bearer_headers(api_key)
+    }
+
+    fn request_body(
+        &self,
+        messages: &[MessageInterface],
+        config: &ConfigInterface,
+        stream: bool,
+    ) -> serde_json::Value {
+        let mut stop = vec!["\nUser:".to_string()];
+        stop.extend(config.stop.iter().cloned());
+        let mut body = serde_json::json!({
+            "prompt": transcript(messages),
+            "n_predict": config.max_tokens,
+            "temperature": config.temperature,
+            "top_p": config.top_p,
+            "presence_penalty": config.presence_penalty,
+            "frequency_penalty": config.frequency_penalty,
+            "stop": stop,
+            "stream": stream,
+        });
+        if let Some(seed) = config.seed {
+            body["seed"] = serde_json::json!(seed);
+        }
+        body
+    }
+
+    fn parse_event(&self, data: &str) -> Option<EventSourceData> {
+        let event: serde_json::Value = serde_json::from_str(data).ok()?;
+        let content = event["content"].as_str().map(|s| s.to_string());
+        if event["stop"].as_bool().unwrap_or(false) {
+            let finish_reason = if event["stopped_limit"].as_bool().unwrap_or(false) {
+                "length"
+            } else {
+                "stop"
+            };
+            return Some(EventSourceData::Data(chunk(
+                content,
+                Some(finish_reason.to_string()),
+                self.usage(&event),
+            )));
+        }
+        Some(EventSourceData::Data(chunk(content, None, None)))
+    }
+
+    fn response_content(&self, response: &serde_json::Value) -> Option<String> {
+        response["content"].as_str().map(|s| s.trim().to_string())
+    }
+
+    fn usage(&self, response: &serde_json::Value) -> Option<TokenUsage> {
+        let prompt_tokens = response["tokens_evaluated"].as_u64()?;
+        Some(TokenUsage {
+            prompt_tokens: prompt_tokens as u32,
+            completion_tokens: response["tokens_predicted"].as_u64().unwrap_or_default() as u32,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaTags {
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaModel {
+    name: String,
+}
+
+fn bearer_headers(api_key: &Option<String>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(api_key) = api_key.as_ref().filter(|k| !k.is_empty())
+        && let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", api_key))
+    {
+        headers.insert(AUTHORIZATION, value);
+    }
+    headers
+}
+
+fn transcript(messages: &[MessageInterface]) -> String {
+    let mut prompt = messages
+        .iter()
+        .map(|m| {
+            let speaker = match m.role {
+                Role::System => "System",
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+            };
+            format!("{}: {}\n", speaker, m.content)
+        })
+        .collect::<String>();
+    prompt.push_str("Assistant:");
+    prompt
+}
+
+fn chunk(
+    content: Option<String>,
+    finish_reason: Option<String>,
+    usage: Option<TokenUsage>,
+) -> EventSourceDataInterface {
+    EventSourceDataInterface {
+        choices: vec![EventSourceDataChoices {
+            delta: Delta { content, role: None },
+            finish_reason,
+            index: 0,
+        }],
+        created: 0,
+        id: String::new(),
+        model: String::new(),
+        object: "chat.completion.chunk".to_string(),
+        usage,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::sse::SseDecoder;
+
+    #[test]
+    fn ollama_ndjson_lines_map_to_deltas_and_usage() {
+        let data = concat!(
+            r#"{"model":"llama3","message":{"role":"assistant","content":"Hi"},"done":false}"#,
+            "\n",
+            r#"{"model":"llama3","message":{"role":"assistant","content":""},"done":true,"done_reason":"length","prompt_eval_count":7,"eval_count":3}"#,
+            "\n",
+        );
+        let events: Vec<EventSourceData> = SseDecoder::new(StreamFormat::Ndjson)
+            .feed(data.as_bytes())
+            .into_iter()
+            .filter_map(|event| event.into_data(&OllamaProvider))
+            .collect();
+        assert_eq!(events.len(), 2);
+        match &events[1] {
+            EventSourceData::Data(last) => {
+                assert_eq!(last.choices[0].finish_reason.as_deref(), Some("length"));
+                assert_eq!(last.usage.as_ref().unwrap().prompt_tokens, 7);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn llama_cpp_prompt_is_a_transcript() {
+        let messages = vec![
+            MessageInterface { role: Role::System, content: "be brief".into(), ..Default::default() },
+            MessageInterface { role: Role::User, content: "hi".into(), ..Default::default() },
+        ];
+        let body = LlamaCppProvider.request_body(&messages, &ConfigInterface::default(), true);
+        assert_eq!(body["prompt"], "System: be brief\nUser: hi\nAssistant:");
+    }
+}
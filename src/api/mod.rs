@@ -0,0 +1,201 @@
+pub(crate) mod anthropic;
+pub(crate) mod error;
+pub(crate) mod local;
+pub(crate) mod openai;
+pub(crate) mod provider;
+pub(crate) mod sse;
+
+use futures::Stream;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use crate::types::api::ShareGPTSubmitBodyInterface;
+use std::collections::HashMap;
+use reqwest::Error as ReqwestError;
+use crate::types::chat::{ConfigInterface, MessageInterface};
+use error::{ApiError, MAX_RETRIES};
+use provider::ChatProvider;
+
+/// Parses one user supplied header, e.g. from the API profile's header editor.
+pub(crate) fn parse_header(name: &str, value: &str) -> Result<(HeaderName, HeaderValue), ApiError> {
+    let header_name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|_| ApiError::InvalidHeader(format!("invalid header name {:?}", name)))?;
+    let header_value = HeaderValue::from_str(value.trim())
+        .map_err(|_| ApiError::InvalidHeader(format!("invalid value for header {:?}", name)))?;
+    Ok((header_name, header_value))
+}
+
+fn build_headers(
+    provider: &dyn ChatProvider,
+    api_key: &Option<String>,
+    custom_headers: Option<HashMap<String, String>>,
+) -> Result<HeaderMap, ApiError> {
+    let mut headers = provider.headers(api_key);
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    if let Some(custom_headers) = custom_headers {
+        for (key, value) in custom_headers {
+            let (name, value) = parse_header(&key, &value)?;
+            headers.insert(name, value);
+        }
+    }
+    Ok(headers)
+}
+
+/// Sends the request built by `request`, retrying rate limits, server errors
+/// and network failures with backoff. Error statuses become an [`ApiError`].
+async fn send_with_retry(
+    request: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<reqwest::Response, ApiError> {
+    let mut attempt = 0;
+    loop {
+        let error = match request().send().await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let status = response.status().as_u16();
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_string());
+                let body = response.text().await.unwrap_or_default();
+                ApiError::from_response(status, retry_after.as_deref(), &body)
+            }
+            Err(e) => ApiError::from(e),
+        };
+        if !error.is_retryable() || attempt >= MAX_RETRIES {
+            return Err(error);
+        }
+        let delay = error.retry_delay(attempt);
+        log::warn!("{}; retrying in {:?}", error, delay);
+        gloo_timers::future::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+pub async fn get_chat_completion(
+    provider: &dyn ChatProvider,
+    endpoint: String,
+    messages: &[MessageInterface],
+    config: &ConfigInterface,
+    api_key: &Option<String>,
+    custom_headers: Option<HashMap<String, String>>,
+) -> Result<serde_json::Value, ApiError> {
+    let headers = build_headers(provider, api_key, custom_headers)?;
+    let endpoint = provider.endpoint(&endpoint, config);
+    let body = provider.request_body(messages, config, false);
+
+    let client = reqwest::Client::new();
+    let response = send_with_retry(|| {
+        client
+            .post(&endpoint)
+            .headers(headers.clone())
+            .json(&body)
+    })
+    .await?;
+    Ok(response.json().await?)
+}
+
+pub async fn get_chat_completion_stream(
+    provider: &dyn ChatProvider,
+    endpoint: String,
+    messages: &[MessageInterface],
+    config: &ConfigInterface,
+    api_key: &Option<String>,
+    custom_headers: Option<HashMap<String, String>>,
+) -> Result<impl Stream<Item = reqwest::Result<bytes::Bytes>>, ApiError> {
+    let headers = build_headers(provider, api_key, custom_headers)?;
+    let endpoint = provider.endpoint(&endpoint, config);
+    let body = provider.request_body(messages, config, true);
+
+    let client = reqwest::Client::new();
+    let response = send_with_retry(|| {
+        client
+            .post(&endpoint)
+            .headers(headers.clone())
+            .json(&body)
+    })
+    .await?;
+    Ok(response.bytes_stream())
+}
+
+/// Model ids offered by the endpoint, e.g. `GET /v1/models` or Ollama's `/api/tags`.
+pub async fn list_models(
+    provider: &dyn ChatProvider,
+    endpoint: &str,
+    api_key: &Option<String>,
+    custom_headers: Option<HashMap<String, String>>,
+) -> anyhow::Result<Vec<String>> {
+    let Some(url) = provider.models_endpoint(endpoint) else {
+        anyhow::bail!("This provider does not support listing models");
+    };
+    let mut headers = build_headers(provider, api_key, custom_headers)?;
+    headers.remove(CONTENT_TYPE);
+
+    let response: serde_json::Value = reqwest::Client::new()
+        .get(url)
+        .headers(headers)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(provider.parse_models(&response))
+}
+
+/// Optional `models.json` served next to the app, letting deployments add or
+/// re-price models without a rebuild. See [`crate::types::model::ModelInfo`].
+pub async fn fetch_model_overrides(base: &str) -> anyhow::Result<String> {
+    let url = url::Url::parse(base)?.join("models.json")?;
+    Ok(reqwest::get(url).await?.error_for_status()?.text().await?)
+}
+
+pub async fn submit_share_gpt(body: ShareGPTSubmitBodyInterface) -> Result<(), ReqwestError> {
+    let client = reqwest::Client::new();
+    // TODO: FixMe - ShareGPT is deprecated, using the OpenAI builtin share api
+    let response = client
+        .post("https://sharegpt.com/api/conversations")
+        .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+        .json(&body)
+        .send()
+        .await?;
+
+    match response.error_for_status() {
+        Ok(response) => {
+            if let Ok(json) = response.json::<serde_json::Value>().await {
+                if let Some(id) = json.get("id").and_then(|id| id.as_str()) {
+                    // Open the URL in a new browser tab (requires WASM environment)
+                    gloo_utils::window()
+                        .open_with_url_and_target(&format!("https://shareg.pt/{}", id), "_blank")
+                        .ok();
+                }
+            }
+    
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::openai::OpenAIProvider;
+
+    #[test]
+    fn custom_headers_are_validated_instead_of_panicking() {
+        let headers = HashMap::from([
+            ("X-Org-Id".to_string(), "acme".to_string()),
+            ("traceparent".to_string(), " 00-abc-01 ".to_string()),
+        ]);
+        let built = build_headers(&OpenAIProvider, &None, Some(headers)).unwrap();
+        assert_eq!(built["x-org-id"], "acme");
+        assert_eq!(built["traceparent"], "00-abc-01");
+
+        let bad_name = HashMap::from([("X Org".to_string(), "acme".to_string())]);
+        assert!(matches!(
+            build_headers(&OpenAIProvider, &None, Some(bad_name)),
+            Err(ApiError::InvalidHeader(_))
+        ));
+        assert!(parse_header("X-Trace", "line\nbreak").is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
+
+use crate::types::{
+    api::{AzureAuth, AzureSettings, EventSourceData, EventSourceDataInterface},
+    chat::{ConfigInterface, MessageInterface, ResponseFormat, TokenUsage},
+};
+
+use super::provider::ChatProvider;
+
+/// OpenAI chat-completions API (`/v1/chat/completions`) and compatible proxies.
+pub(crate) struct OpenAIProvider;
+
+impl ChatProvider for OpenAIProvider {
+    fn headers(&self, api_key: &Option<String>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(api_key) = api_key
+            && let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", api_key))
+        {
+            headers.insert(AUTHORIZATION, value);
+        }
+        headers
+    }
+
+    fn request_body(
+        &self,
+        messages: &[MessageInterface],
+        config: &ConfigInterface,
+        stream: bool,
+    ) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "model": config.model,
+            "messages": messages_to_json(messages),
+            "temperature": config.temperature,
+            "top_p": config.top_p,
+            "presence_penalty": config.presence_penalty,
+            "frequency_penalty": config.frequency_penalty,
+            "max_tokens": config.max_tokens,
+            "n": config.n,
+            "stream": stream,
+        });
+        // Optional parameters are left out entirely when unset; some
+        // compatible servers reject `null` or empty values.
+        if !config.stop.is_empty() {
+            body["stop"] = serde_json::json!(config.stop);
+        }
+        if let Some(seed) = config.seed {
+            body["seed"] = serde_json::json!(seed);
+        }
+        if !config.logit_bias.is_empty() {
+            body["logit_bias"] = serde_json::json!(config.logit_bias);
+        }
+        if config.response_format != ResponseFormat::Text {
+            body["response_format"] = serde_json::json!({ "type": config.response_format.to_string() });
+        }
+        if let Some(user) = config.user.as_ref().filter(|u| !u.is_empty()) {
+            body["user"] = serde_json::json!(user);
+        }
+        body
+    }
+
+    fn parse_event(&self, data: &str) -> Option<EventSourceData> {
+        if data == "[DONE]" {
+            return Some(EventSourceData::Done(data.to_string()));
+        }
+        match serde_json::from_str::<EventSourceDataInterface>(data) {
+            Ok(data) => Some(EventSourceData::Data(data)),
+            Err(e) => {
+                log::warn!("Skipping malformed stream event {:?}: {}", data, e);
+                None
+            }
+        }
+    }
+
+    fn response_content(&self, response: &serde_json::Value) -> Option<String> {
+        response["choices"][0]["message"]["content"]
+            .as_str()
+            .map(|s| s.to_string())
+    }
+
+    fn usage(&self, response: &serde_json::Value) -> Option<TokenUsage> {
+        serde_json::from_value(response["usage"].clone()).ok()
+    }
+
+    fn models_endpoint(&self, endpoint: &str) -> Option<String> {
+        endpoint
+            .strip_suffix("/chat/completions")
+            .map(|base| format!("{}/models", base))
+    }
+}
+
+/// Azure OpenAI deployments. Same payloads as OpenAI, but the deployment is
+/// part of the URL, the key goes into the `api-key` header (or an Entra ID
+/// token into `Authorization`) and the stream carries content-filter results.
+pub(crate) struct AzureOpenAIProvider {
+    pub(crate) settings: AzureSettings,
+}
+
+impl ChatProvider for AzureOpenAIProvider {
+    fn endpoint(&self, endpoint: &str, config: &ConfigInterface) -> String {
+        // A full deployment URL is used as is, only the api-version is filled in.
+        if endpoint.contains("/openai/deployments/") {
+            if endpoint.contains("api-version=") {
+                return endpoint.to_string();
+            }
+            let separator = if endpoint.contains('?') { '&' } else { '?' };
+            return format!("{}{}api-version={}", endpoint, separator, self.settings.api_version);
+        }
+        format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            endpoint.trim_end_matches('/'),
+            self.settings.deployment(&config.model),
+            self.settings.api_version
+        )
+    }
+
+    fn headers(&self, api_key: &Option<String>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let Some(api_key) = api_key.as_ref().filter(|k| !k.is_empty()) else {
+            return headers;
+        };
+        match self.settings.auth {
+            AzureAuth::ApiKey => {
+                if let Ok(value) = HeaderValue::from_str(api_key) {
+                    headers.insert("api-key", value);
+                }
+            }
+            AzureAuth::EntraId => {
+                if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", api_key)) {
+                    headers.insert(AUTHORIZATION, value);
+                }
+            }
+        }
+        headers
+    }
+
+    fn request_body(
+        &self,
+        messages: &[MessageInterface],
+        config: &ConfigInterface,
+        stream: bool,
+    ) -> serde_json::Value {
+        OpenAIProvider.request_body(messages, config, stream)
+    }
+
+    fn parse_event(&self, data: &str) -> Option<EventSourceData> {
+        let Ok(mut event) = serde_json::from_str::<serde_json::Value>(data) else {
+            return OpenAIProvider.parse_event(data);
+        };
+        let filtered = filtered_categories(&event);
+        if !filtered.is_empty() {
+            return Some(EventSourceData::ContentFiltered(filtered.join(", ")));
+        }
+        // Asynchronous filtering annotates already streamed text with chunks
+        // that have filter results but no delta; they carry nothing to show.
+        if let Some(choices) = event["choices"].as_array_mut() {
+            choices.retain(|choice| choice.get("delta").is_some());
+        }
+        match serde_json::from_value::<EventSourceDataInterface>(event) {
+            Ok(data) => Some(EventSourceData::Data(data)),
+            Err(e) => {
+                log::warn!("Skipping malformed stream event {:?}: {}", data, e);
+                None
+            }
+        }
+    }
+
+    fn response_content(&self, response: &serde_json::Value) -> Option<String> {
+        OpenAIProvider.response_content(response)
+    }
+
+    fn usage(&self, response: &serde_json::Value) -> Option<TokenUsage> {
+        OpenAIProvider.usage(response)
+    }
+}
+
+/// Categories Azure's content filter blocked, from both the prompt
+/// (`prompt_filter_results`) and the completion (`choices[].content_filter_results`).
+/// Results with `"filtered": false` are mere annotations and ignored.
+fn filtered_categories(event: &serde_json::Value) -> Vec<String> {
+    let prompt_results = event["prompt_filter_results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|result| &result["content_filter_results"]);
+    let choices = event["choices"].as_array().into_iter().flatten();
+    let choice_results = choices.clone().map(|choice| &choice["content_filter_results"]);
+
+    let mut categories: Vec<String> = prompt_results
+        .chain(choice_results)
+        .filter_map(|results| results.as_object())
+        .flatten()
+        .filter(|(_, result)| result["filtered"].as_bool().unwrap_or(false))
+        .map(|(category, _)| category.clone())
+        .collect();
+    categories.sort();
+    categories.dedup();
+    if categories.is_empty()
+        && choices
+            .into_iter()
+            .any(|choice| choice["finish_reason"] == "content_filter")
+    {
+        categories.push("content_filter".to_string());
+    }
+    categories
+}
+
+/// Only `role` and `content` go over the wire; the rest of `MessageInterface`
+/// is local bookkeeping.
+pub(crate) fn messages_to_json(messages: &[MessageInterface]) -> Vec<serde_json::Value> {
+    messages
+        .iter()
+        .map(|m| serde_json::json!({ "role": m.role.to_string(), "content": m.content }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_body_carries_sampling_config() {
+        let config = ConfigInterface {
+            top_p: 0.5,
+            presence_penalty: 1.5,
+            stop: vec!["END".to_string()],
+            seed: Some(42),
+            logit_bias: [("50256".to_string(), -100)].into_iter().collect(),
+            response_format: ResponseFormat::JsonObject,
+            ..ConfigInterface::default()
+        };
+        let body = OpenAIProvider.request_body(&[], &config, true);
+        assert_eq!(body["top_p"], 0.5);
+        assert_eq!(body["presence_penalty"], 1.5);
+        assert_eq!(body["stop"][0], "END");
+        assert_eq!(body["seed"], 42);
+        assert_eq!(body["logit_bias"]["50256"], -100);
+        assert_eq!(body["response_format"]["type"], "json_object");
+        assert!(body.get("user").is_none());
+
+        let body = OpenAIProvider.request_body(&[], &ConfigInterface::default(), true);
+        assert!(body.get("stop").is_none());
+        assert!(body.get("response_format").is_none());
+    }
+
+    fn azure(auth: AzureAuth) -> AzureOpenAIProvider {
+        AzureOpenAIProvider {
+            settings: AzureSettings {
+                deployments: [("gpt-4o".to_string(), "prod-4o".to_string())].into_iter().collect(),
+                api_version: "2024-10-21".to_string(),
+                auth,
+            },
+        }
+    }
+
+    #[test]
+    fn azure_url_uses_deployment_map_and_api_version() {
+        let provider = azure(AzureAuth::ApiKey);
+        let config = ConfigInterface { model: "gpt-4o".to_string(), ..ConfigInterface::default() };
+        assert_eq!(
+            provider.endpoint("https://res.openai.azure.com/", &config),
+            "https://res.openai.azure.com/openai/deployments/prod-4o/chat/completions?api-version=2024-10-21"
+        );
+        let unmapped = ConfigInterface { model: "gpt-35-turbo".to_string(), ..ConfigInterface::default() };
+        assert_eq!(
+            provider.endpoint("https://res.openai.azure.com", &unmapped),
+            "https://res.openai.azure.com/openai/deployments/gpt-35-turbo/chat/completions?api-version=2024-10-21"
+        );
+        assert_eq!(
+            provider.endpoint("https://res.openai.azure.com/openai/deployments/x/chat/completions", &config),
+            "https://res.openai.azure.com/openai/deployments/x/chat/completions?api-version=2024-10-21"
+        );
+
+        let key = Some("secret".to_string());
+        assert_eq!(azure(AzureAuth::ApiKey).headers(&key)["api-key"], "secret");
+        let entra = azure(AzureAuth::EntraId).headers(&key);
+        assert_eq!(entra[AUTHORIZATION], "Bearer secret");
+        assert!(entra.get("api-key").is_none());
+    }
+
+    #[test]
+    fn azure_content_filter_results_in_the_stream() {
+        let provider = azure(AzureAuth::ApiKey);
+        let prompt_annotations = r#"{"choices":[],"created":0,"id":"","model":"","object":"","prompt_filter_results":[{"prompt_index":0,"content_filter_results":{"hate":{"filtered":false,"severity":"safe"}}}]}"#;
+        assert!(matches!(provider.parse_event(prompt_annotations), Some(EventSourceData::Data(d)) if d.choices.is_empty()));
+
+        let annotation_only = r#"{"choices":[{"content_filter_offsets":{"check_offset":0,"start_offset":0,"end_offset":5},"content_filter_results":{"violence":{"filtered":false,"severity":"safe"}},"finish_reason":null,"index":0}],"created":0,"id":"","model":"","object":""}"#;
+        assert!(matches!(provider.parse_event(annotation_only), Some(EventSourceData::Data(d)) if d.choices.is_empty()));
+
+        let blocked = r#"{"choices":[{"content_filter_results":{"violence":{"filtered":true,"severity":"medium"}},"delta":{},"finish_reason":"content_filter","index":0}],"created":1,"id":"x","model":"gpt-4o","object":"chat.completion.chunk"}"#;
+        assert_eq!(provider.parse_event(blocked), Some(EventSourceData::ContentFiltered("violence".to_string())));
+
+        assert!(matches!(provider.parse_event("[DONE]"), Some(EventSourceData::Done(_))));
+    }
+}
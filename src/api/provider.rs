@@ -0,0 +1,92 @@
+use reqwest::header::HeaderMap;
+
+use crate::types::{
+    api::{ApiProfile, EventSourceData, ProviderKind},
+    chat::{ConfigInterface, MessageInterface, TokenUsage},
+};
+
+use super::{
+    anthropic::AnthropicProvider,
+    local::{LlamaCppProvider, OllamaProvider},
+    openai::{AzureOpenAIProvider, OpenAIProvider},
+};
+
+/// How a streaming response is framed on the wire.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum StreamFormat {
+    /// `data: ...` events separated by blank lines.
+    Sse,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+/// Everything that differs between the chat completion APIs we talk to.
+///
+/// The transport (reqwest client, custom headers, byte stream) is shared in
+/// `api::get_chat_completion`/`api::get_chat_completion_stream`; a provider only
+/// describes how to shape the request and how to read the response.
+pub(crate) trait ChatProvider {
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Sse
+    }
+
+    /// Final URL for a completion request against the user supplied endpoint.
+    fn endpoint(&self, endpoint: &str, _config: &ConfigInterface) -> String {
+        endpoint.to_string()
+    }
+
+    /// Authentication and versioning headers for this provider.
+    fn headers(&self, api_key: &Option<String>) -> HeaderMap;
+
+    /// JSON request body for the given conversation.
+    fn request_body(
+        &self,
+        messages: &[MessageInterface],
+        config: &ConfigInterface,
+        stream: bool,
+    ) -> serde_json::Value;
+
+    /// Decodes the `data` payload of a single server-sent event (or a single
+    /// line for [`StreamFormat::Ndjson`] providers).
+    ///
+    /// Returns `None` for events that carry nothing the UI cares about (pings,
+    /// block start/stop markers, ...).
+    fn parse_event(&self, data: &str) -> Option<EventSourceData>;
+
+    /// Assistant text of a non-streaming response.
+    fn response_content(&self, response: &serde_json::Value) -> Option<String>;
+
+    /// Token usage reported by a non-streaming response.
+    fn usage(&self, response: &serde_json::Value) -> Option<TokenUsage>;
+
+    /// URL listing the models available behind `endpoint`, if the API has one.
+    fn models_endpoint(&self, _endpoint: &str) -> Option<String> {
+        None
+    }
+
+    /// Model ids from the response of [`ChatProvider::models_endpoint`].
+    /// Defaults to the `{"data": [{"id": ...}]}` shape of `GET /v1/models`.
+    fn parse_models(&self, response: &serde_json::Value) -> Vec<String> {
+        response["data"]
+            .as_array()
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|m| m["id"].as_str().map(|id| id.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+pub(crate) fn provider_for(profile: &ApiProfile) -> Box<dyn ChatProvider> {
+    match profile.provider {
+        ProviderKind::OpenAI => Box::new(OpenAIProvider),
+        ProviderKind::Azure => Box::new(AzureOpenAIProvider {
+            settings: profile.azure.clone(),
+        }),
+        ProviderKind::Anthropic => Box::new(AnthropicProvider),
+        ProviderKind::Ollama => Box::new(OllamaProvider),
+        ProviderKind::LlamaCpp => Box::new(LlamaCppProvider),
+    }
+}
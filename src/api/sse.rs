@@ -0,0 +1,293 @@
+use crate::types::api::EventSourceData;
+
+use super::provider::{ChatProvider, StreamFormat};
+
+/// A single dispatched server-sent event.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct SseEvent {
+    /// Value of the `event:` field, `None` for the default `message` type.
+    pub(crate) event: Option<String>,
+    pub(crate) data: String,
+}
+
+impl SseEvent {
+    /// Hands the payload to the provider, turning error payloads into
+    /// [`EventSourceData::Error`] on the way.
+    pub(crate) fn into_data(self, provider: &dyn ChatProvider) -> Option<EventSourceData> {
+        if let Some(message) = self.error_message() {
+            return Some(EventSourceData::Error(message));
+        }
+        provider.parse_event(&self.data)
+    }
+
+    /// OpenAI sends `data: {"error": {...}}` mid-stream, Anthropic an
+    /// `event: error`, Ollama a bare `{"error": "..."}` line.
+    fn error_message(&self) -> Option<String> {
+        let json = serde_json::from_str::<serde_json::Value>(&self.data).ok();
+        let error = json.as_ref().map(|json| &json["error"]).filter(|e| !e.is_null());
+        match error {
+            Some(serde_json::Value::String(message)) => Some(message.clone()),
+            Some(error) => Some(
+                error["message"]
+                    .as_str()
+                    .map(|m| m.to_string())
+                    .unwrap_or_else(|| error.to_string()),
+            ),
+            None if self.event.as_deref() == Some("error") => Some(self.data.clone()),
+            None => None,
+        }
+    }
+}
+
+/// Incremental decoder for a streamed response body.
+///
+/// Network chunks can end anywhere — in the middle of an event, a line, or a
+/// multi-byte character — so bytes are buffered until a full line is
+/// available. Line terminators never occur inside a UTF-8 sequence, which
+/// means a complete line is always complete text as well.
+#[derive(Debug)]
+pub(crate) struct SseDecoder {
+    format: StreamFormat,
+    line: Vec<u8>,
+    /// The previous chunk ended on `\r`; a leading `\n` belongs to it.
+    pending_cr: bool,
+    data: Vec<String>,
+    event: Option<String>,
+    last_event_id: Option<String>,
+    retry: Option<u32>,
+}
+
+impl SseDecoder {
+    pub(crate) fn new(format: StreamFormat) -> Self {
+        Self {
+            format,
+            line: Vec::new(),
+            pending_cr: false,
+            data: Vec::new(),
+            event: None,
+            last_event_id: None,
+            retry: None,
+        }
+    }
+
+    /// Last `id:` seen on the stream, for resuming with `Last-Event-ID`.
+    pub(crate) fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    /// Reconnection delay in milliseconds requested by the server.
+    pub(crate) fn retry(&self) -> Option<u32> {
+        self.retry
+    }
+
+    /// Consumes the next chunk of the body and returns every event it completed.
+    pub(crate) fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        for &byte in chunk {
+            if std::mem::take(&mut self.pending_cr) && byte == b'\n' {
+                continue;
+            }
+            match byte {
+                b'\n' | b'\r' => {
+                    self.pending_cr = byte == b'\r';
+                    let line = std::mem::take(&mut self.line);
+                    if let Some(event) = self.process_line(&String::from_utf8_lossy(&line)) {
+                        events.push(event);
+                    }
+                }
+                _ => self.line.push(byte),
+            }
+        }
+        events
+    }
+
+    /// Flushes whatever is left once the body has ended. Servers regularly
+    /// close the connection without a trailing blank line, so a pending event
+    /// is dispatched rather than dropped.
+    pub(crate) fn finish(&mut self) -> Vec<SseEvent> {
+        let mut events = self.feed(b"\n");
+        events.extend(self.dispatch());
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if self.format == StreamFormat::Ndjson {
+            let line = line.trim();
+            return (!line.is_empty()).then(|| SseEvent {
+                event: None,
+                data: line.to_string(),
+            });
+        }
+
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            // Comment, used as a keep-alive.
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "event" => self.event = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(retry) = value.parse() {
+                    self.retry = Some(retry);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(SseEvent {
+            event,
+            data: std::mem::take(&mut self.data).join("\n"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{local::OllamaProvider, openai::OpenAIProvider};
+
+    /// Small xorshift generator so the split points are random but reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    fn random_chunks(bytes: &[u8], seed: u64) -> Vec<&[u8]> {
+        let mut rng = Rng(seed);
+        let mut chunks = Vec::new();
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let len = (rng.next() % 8 + 1).min(rest.len() as u64) as usize;
+            let (chunk, tail) = rest.split_at(len);
+            chunks.push(chunk);
+            rest = tail;
+        }
+        chunks
+    }
+
+    fn decode(provider: &dyn ChatProvider, chunks: &[&[u8]]) -> Vec<EventSourceData> {
+        let mut decoder = SseDecoder::new(provider.stream_format());
+        let mut events = Vec::new();
+        for chunk in chunks {
+            events.extend(decoder.feed(chunk));
+        }
+        events.extend(decoder.finish());
+        events.into_iter().filter_map(|e| e.into_data(provider)).collect()
+    }
+
+    fn content(events: &[EventSourceData]) -> String {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                EventSourceData::Data(data) => data.choices.first()?.delta.content.clone(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn openai_chunk(content: &str) -> String {
+        format!(
+            "data: {{\"id\":\"1\",\"object\":\"chat.completion.chunk\",\"created\":0,\"model\":\"gpt-4o\",\"choices\":[{{\"index\":0,\"delta\":{{\"content\":{}}},\"finish_reason\":null}}]}}\n\n",
+            serde_json::to_string(content).unwrap()
+        )
+    }
+
+    #[test]
+    fn randomly_split_stream_decodes_like_a_whole_one() {
+        let parts = ["Grüß ", "dich, ", "世界", " 👋🏽", "!"];
+        let mut body = String::from(": keep-alive\n\n");
+        for part in parts {
+            body.push_str(&openai_chunk(part));
+        }
+        body.push_str("data: [DONE]\n\n");
+
+        let whole = decode(&OpenAIProvider, &[body.as_bytes()]);
+        assert_eq!(content(&whole), parts.concat());
+        assert!(matches!(whole.last(), Some(EventSourceData::Done(_))));
+
+        for seed in 1..200 {
+            let events = decode(&OpenAIProvider, &random_chunks(body.as_bytes(), seed));
+            assert_eq!(events, whole, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn fields_comments_and_line_endings() {
+        let body = ": comment\r\nretry: 3000\r\nid: 7\r\nevent: message\r\ndata: a\r\ndata: b\r\n\r\nevent: ping\rdata: c\r\r";
+        for seed in 1..50 {
+            let mut decoder = SseDecoder::new(StreamFormat::Sse);
+            let events: Vec<SseEvent> = random_chunks(body.as_bytes(), seed)
+                .into_iter()
+                .flat_map(|chunk| decoder.feed(chunk))
+                .collect();
+            assert_eq!(
+                events,
+                vec![
+                    SseEvent { event: Some("message".into()), data: "a\nb".into() },
+                    SseEvent { event: Some("ping".into()), data: "c".into() },
+                ],
+                "seed {}",
+                seed
+            );
+            assert_eq!(decoder.retry(), Some(3000));
+            assert_eq!(decoder.last_event_id(), Some("7"));
+        }
+    }
+
+    #[test]
+    fn error_payloads_become_errors() {
+        let body = concat!(
+            "event: error\n",
+            "data: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n",
+            "data: {\"error\":{\"message\":\"Rate limit reached\",\"type\":\"requests\"}}\n\n",
+            "event: error\n",
+            "data: upstream went away\n\n",
+        );
+        let events = decode(&OpenAIProvider, &[body.as_bytes()]);
+        assert_eq!(
+            events,
+            vec![
+                EventSourceData::Error("Overloaded".into()),
+                EventSourceData::Error("Rate limit reached".into()),
+                EventSourceData::Error("upstream went away".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ndjson_lines_survive_chunk_boundaries() {
+        let body = concat!(
+            r#"{"message":{"role":"assistant","content":"Ça "},"done":false}"#,
+            "\n",
+            r#"{"message":{"role":"assistant","content":"va?"},"done":false}"#,
+            "\n",
+            r#"{"error":"model not found"}"#,
+        );
+        for seed in 1..50 {
+            let events = decode(&OllamaProvider, &random_chunks(body.as_bytes(), seed));
+            assert_eq!(content(&events), "Ça va?");
+            assert_eq!(events.last(), Some(&EventSourceData::Error("model not found".into())));
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Sync through Google Drive, see [`DriveClient`].
+
+use futures::future::LocalBoxFuture;
+
+use super::{RemoteFile, SYNC_FILE_NAME, SyncBackend, SyncError};
+use crate::{google_api::DriveClient, types::api::GoogleFileResource};
+
+/// Drive can't make an upload conditional on the version, so a write racing
+/// one from another device goes through and the later one wins.
+pub(crate) struct DriveBackend {
+    client: DriveClient,
+    /// The file synced so far, looked up by name when there is none.
+    file_id: Option<String>,
+}
+
+impl DriveBackend {
+    pub(crate) fn new(client: DriveClient, file_id: Option<String>) -> Self {
+        Self { client, file_id }
+    }
+}
+
+impl From<GoogleFileResource> for RemoteFile {
+    fn from(file: GoogleFileResource) -> Self {
+        Self {
+            version: file.version.or(file.modified_time).unwrap_or_default(),
+            id: file.id,
+        }
+    }
+}
+
+impl SyncBackend for DriveBackend {
+    fn head(&self) -> LocalBoxFuture<'_, Result<Option<RemoteFile>, SyncError>> {
+        Box::pin(async move {
+            let file = match &self.file_id {
+                Some(id) => self.client.metadata(id).await?,
+                None => self.client.find_file(SYNC_FILE_NAME).await?,
+            };
+            Ok(file.map(RemoteFile::from))
+        })
+    }
+
+    fn download<'a>(&'a self, file: &'a RemoteFile) -> LocalBoxFuture<'a, Result<String, SyncError>> {
+        Box::pin(async move { Ok(self.client.download(&file.id).await?) })
+    }
+
+    fn upload<'a>(
+        &'a self,
+        data: &'a str,
+        file: Option<&'a RemoteFile>,
+    ) -> LocalBoxFuture<'a, Result<RemoteFile, SyncError>> {
+        Box::pin(async move {
+            let file = match file {
+                Some(file) => self.client.update_file(&file.id, data).await?,
+                None => self.client.create_file(SYNC_FILE_NAME, data).await?,
+            };
+            Ok(file.into())
+        })
+    }
+}
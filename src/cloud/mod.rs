@@ -0,0 +1,301 @@
+//! Syncing the state to a file in the cloud.
+
+use std::collections::HashSet;
+
+use futures::future::LocalBoxFuture;
+use reqwest::{
+    RequestBuilder, Response, StatusCode,
+    header::{ETAG, IF_MATCH, IF_NONE_MATCH},
+};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::{
+    google_api::{DriveClient, DriveError},
+    storage::{self, Envelope, PersistedState},
+    store::CloudAuthSlice,
+    types::api::SyncProvider,
+};
+
+mod drive;
+mod s3;
+mod sigv4;
+#[cfg(test)]
+pub(crate) mod stand_in;
+mod webdav;
+
+pub(crate) use drive::DriveBackend;
+pub(crate) use s3::S3Backend;
+pub(crate) use webdav::WebDavBackend;
+
+/// Name of the synced file.
+pub(crate) const SYNC_FILE_NAME: &str = "yewmind.json";
+
+/// The synced file as a backend last saw it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RemoteFile {
+    /// Drive file id, or the URL of the file.
+    pub(crate) id: String,
+    /// Drive version or ETag, changed by every write.
+    pub(crate) version: String,
+}
+
+/// The remote copy, downloaded when both sides changed.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RemoteCopy {
+    pub(crate) state: PersistedState,
+    pub(crate) file: RemoteFile,
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum SyncError {
+    #[error("the credentials were refused")]
+    Unauthorized,
+    /// Another device wrote the file since it was last read.
+    #[error("the file changed while it was written")]
+    Conflict,
+    #[error("the server sent no ETag, so changes elsewhere can't be told apart")]
+    MissingEtag,
+    #[error("the server returned {status}: {body}")]
+    Status { status: u16, body: String },
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+impl From<DriveError> for SyncError {
+    fn from(e: DriveError) -> Self {
+        match e {
+            DriveError::Unauthorized => Self::Unauthorized,
+            DriveError::Status { status, body } => Self::Status { status, body },
+            DriveError::Http(e) => Self::Http(e),
+        }
+    }
+}
+
+/// Somewhere to keep the synced file.
+pub(crate) trait SyncBackend {
+    /// The file as it is now, `None` when there is none yet.
+    fn head(&self) -> LocalBoxFuture<'_, Result<Option<RemoteFile>, SyncError>>;
+
+    fn download<'a>(&'a self, file: &'a RemoteFile) -> LocalBoxFuture<'a, Result<String, SyncError>>;
+
+    /// Writes `data` over `file`, or creates the file when there is none.
+    /// Backends that can tell fail with [`SyncError::Conflict`] when the file
+    /// is no longer `file`.
+    fn upload<'a>(
+        &'a self,
+        data: &'a str,
+        file: Option<&'a RemoteFile>,
+    ) -> LocalBoxFuture<'a, Result<RemoteFile, SyncError>>;
+}
+
+/// The backend `cloud` is set up for, `None` until it can be reached.
+pub(crate) fn backend(cloud: &CloudAuthSlice) -> Option<Box<dyn SyncBackend>> {
+    match cloud.provider {
+        SyncProvider::GoogleDrive => {
+            let client = DriveClient::new(cloud.google_access_token.clone()?);
+            Some(Box::new(DriveBackend::new(client, cloud.file_id.clone())))
+        }
+        SyncProvider::WebDav => Some(Box::new(WebDavBackend::new(&cloud.webdav)?)),
+        SyncProvider::S3 => Some(Box::new(S3Backend::new(&cloud.s3)?)),
+    }
+}
+
+/// Passes successful responses through and turns the others into errors.
+async fn check(response: Response) -> Result<Response, SyncError> {
+    match response.status() {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(SyncError::Unauthorized),
+        StatusCode::PRECONDITION_FAILED => Err(SyncError::Conflict),
+        status if status.is_success() => Ok(response),
+        status => Err(SyncError::Status {
+            status: status.as_u16(),
+            body: response.text().await.unwrap_or_default(),
+        }),
+    }
+}
+
+/// The file a WebDAV or S3 response describes.
+fn remote_file(id: &str, response: &Response) -> Result<RemoteFile, SyncError> {
+    let version = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .ok_or(SyncError::MissingEtag)?;
+    Ok(RemoteFile {
+        id: id.to_string(),
+        version: version.to_string(),
+    })
+}
+
+/// Makes a write fail with 412 Precondition Failed when the file is no
+/// longer `file`, or already exists when there was none.
+fn precondition(request: RequestBuilder, file: Option<&RemoteFile>) -> RequestBuilder {
+    match file {
+        Some(file) => request.header(IF_MATCH, &file.version),
+        None => request.header(IF_NONE_MATCH, "*"),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SyncAction {
+    UpToDate,
+    Upload,
+    Download,
+    /// Both sides changed since the last sync.
+    Conflict,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConflictChoice {
+    KeepLocal,
+    KeepRemote,
+    /// Everything from both sides, see [`merge`].
+    Merge,
+}
+
+/// The state as uploaded. API profiles never leave the browser, and which
+/// chat is open is up to each device.
+pub(crate) fn payload(state: &PersistedState) -> serde_json::Result<String> {
+    let mut envelope = Envelope::of(state)?;
+    if let Some(state) = envelope.state.as_object_mut() {
+        for key in ["auth", "cloud", "current_chat_index"] {
+            state.remove(key);
+        }
+    }
+    serde_json::to_string(&envelope)
+}
+
+/// Reads a [`payload`] written by this or an older version.
+pub(crate) fn parse_payload(raw: &str) -> anyhow::Result<PersistedState> {
+    Ok(storage::parse(raw)?.0)
+}
+
+/// Tells whether the state changed since it was last synced. The payload
+/// is serialized from sorted maps, so equal states give equal fingerprints.
+pub(crate) fn fingerprint(payload: &str) -> String {
+    hex(&Sha256::digest(payload.as_bytes()))
+}
+
+/// Decides what a sync does. `remote` is the version of the file in the
+/// cloud, `None` when there is none yet.
+pub(crate) fn plan(
+    local_fingerprint: &str,
+    synced_fingerprint: Option<&str>,
+    synced_version: Option<&str>,
+    remote: Option<&str>,
+) -> SyncAction {
+    let Some(remote) = remote else {
+        return SyncAction::Upload;
+    };
+    let local_changed = synced_fingerprint != Some(local_fingerprint);
+    match (synced_version == Some(remote), local_changed) {
+        (true, false) => SyncAction::UpToDate,
+        (true, true) => SyncAction::Upload,
+        (false, false) => SyncAction::Download,
+        (false, true) => SyncAction::Conflict,
+    }
+}
+
+/// Everything from both sides. A chat on both keeps the copy with the higher
+/// revision, the local one on a tie. Folders and prompts are matched by id,
+/// settings are kept.
+pub(crate) fn merge(local: PersistedState, remote: PersistedState) -> PersistedState {
+    let mut merged = local;
+    let local_ids: HashSet<String> = merged.chats.iter().map(|c| c.id.clone()).collect();
+    let mut remote_only = Vec::new();
+    for chat in remote.chats {
+        if !local_ids.contains(&chat.id) {
+            remote_only.push(chat);
+        } else if let Some(local) = merged.chats.iter_mut().find(|c| c.id == chat.id)
+            && chat.revision > local.revision
+        {
+            *local = chat;
+        }
+    }
+    // Chats only on the other side are likely the newest, so they go on top.
+    merged.current_chat_index += remote_only.len() as i32;
+    merged.chats.splice(0..0, remote_only);
+
+    for (id, folder) in remote.folders {
+        merged.folders.entry(id).or_insert(folder);
+    }
+    for prompt in remote.prompts {
+        if !merged.prompts.iter().any(|p| p.id == prompt.id) {
+            merged.prompts.push(prompt);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::chat::{ChatInterface, Prompt};
+
+    fn chat(id: &str, title: &str, revision: u64) -> ChatInterface {
+        ChatInterface {
+            id: id.to_string(),
+            title: Some(title.to_string()),
+            revision,
+            ..ChatInterface::default()
+        }
+    }
+
+    #[test]
+    fn payload_leaves_credentials_behind_and_reads_back() {
+        let mut state = PersistedState {
+            chats: vec![chat("a", "A", 1)],
+            current_chat_index: 0,
+            ..PersistedState::default()
+        };
+        state.auth.default_profile_mut().api_key = Some("sk-secret".to_string());
+        state.cloud.file_id = Some("file".to_string());
+
+        let payload = payload(&state).unwrap();
+        assert!(!payload.contains("sk-secret"));
+        assert!(!payload.contains("file_id"));
+        let parsed = parse_payload(&payload).unwrap();
+        assert_eq!(parsed.chats, state.chats);
+        assert_eq!(fingerprint(&payload), fingerprint(&super::payload(&state).unwrap()));
+    }
+
+    #[test]
+    fn plan_compares_both_sides_with_the_last_sync() {
+        use SyncAction::*;
+        assert_eq!(plan("a", None, None, None), Upload);
+        assert_eq!(plan("a", Some("a"), Some("3"), Some("3")), UpToDate);
+        assert_eq!(plan("b", Some("a"), Some("3"), Some("3")), Upload);
+        assert_eq!(plan("a", Some("a"), Some("3"), Some("4")), Download);
+        assert_eq!(plan("b", Some("a"), Some("3"), Some("4")), Conflict);
+        assert_eq!(plan("a", None, None, Some("1")), Conflict, "never synced with that file");
+    }
+
+    #[test]
+    fn merge_keeps_the_newest_copy_of_each_chat() {
+        let prompt = |id: &str| Prompt {
+            id: id.to_string(),
+            ..Prompt::default()
+        };
+        let local = PersistedState {
+            chats: vec![chat("a", "Local A", 2), chat("b", "Local B", 1)],
+            current_chat_index: 1,
+            prompts: vec![prompt("p1")],
+            ..PersistedState::default()
+        };
+        let remote = PersistedState {
+            chats: vec![chat("c", "Remote C", 1), chat("a", "Remote A", 1), chat("b", "Remote B", 3)],
+            prompts: vec![prompt("p1"), prompt("p2")],
+            ..PersistedState::default()
+        };
+
+        let merged = merge(local, remote);
+        let titles: Vec<_> = merged.chats.iter().map(|c| c.title.as_deref().unwrap()).collect();
+        assert_eq!(titles, ["Remote C", "Local A", "Remote B"]);
+        assert_eq!(merged.chats[merged.current_chat_index as usize].id, "b");
+        assert_eq!(merged.prompts.len(), 2);
+    }
+}
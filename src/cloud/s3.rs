@@ -0,0 +1,237 @@
+//! Sync through an S3 compatible bucket, e.g. on MinIO. Requests are either
+//! signed here with an access key, or sent to URLs presigned elsewhere so
+//! no key has to be kept in the browser. Conditional writes keep one device
+//! from overwriting another's changes.
+//!
+//! In the browser the bucket's CORS rules have to allow the app's origin,
+//! and expose the `ETag` header to it.
+
+use futures::future::LocalBoxFuture;
+use reqwest::{Client, Method, RequestBuilder, StatusCode, header::CONTENT_TYPE};
+use url::Url;
+
+use super::{
+    RemoteFile, SyncBackend, SyncError, check, precondition, remote_file,
+    sigv4::{self, Credentials},
+};
+use crate::types::api::{S3Auth, S3Settings};
+
+pub(crate) struct S3Backend {
+    client: Client,
+    settings: S3Settings,
+    /// Where the object is read from, and written to.
+    get_url: Url,
+    put_url: Url,
+}
+
+impl S3Backend {
+    /// `None` until the settings say where the object is.
+    pub(crate) fn new(settings: &S3Settings) -> Option<Self> {
+        let (get_url, put_url) = match settings.auth {
+            S3Auth::AccessKey => {
+                let url = object_url(settings)?;
+                (url.clone(), url)
+            }
+            S3Auth::Presigned => (
+                Url::parse(settings.presigned_get_url.trim()).ok()?,
+                Url::parse(settings.presigned_put_url.trim()).ok()?,
+            ),
+        };
+        Some(Self {
+            client: Client::new(),
+            settings: settings.clone(),
+            get_url,
+            put_url,
+        })
+    }
+
+    /// A request to `url`, signed unless the URL is presigned.
+    fn request(&self, method: Method, url: &Url, payload: &[u8]) -> RequestBuilder {
+        let request = self.client.request(method.clone(), url.clone());
+        if self.settings.auth == S3Auth::Presigned {
+            return request;
+        }
+        let credentials = Credentials {
+            access_key_id: &self.settings.access_key_id,
+            secret_access_key: &self.settings.secret_access_key,
+            region: &self.settings.region,
+        };
+        let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = sigv4::payload_hash(payload);
+        // Sent by the HTTP client itself, but signed all the same.
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let authorization = sigv4::authorization(
+            &credentials,
+            method.as_str(),
+            url,
+            &[
+                ("host", &host),
+                ("x-amz-content-sha256", &payload_hash),
+                ("x-amz-date", &amz_date),
+            ],
+            &payload_hash,
+            &amz_date,
+        );
+        request
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header("authorization", authorization)
+    }
+}
+
+/// `{endpoint}/{bucket}/{key}`, or `{bucket}.{endpoint host}/{key}` unless
+/// the bucket is addressed path style.
+fn object_url(settings: &S3Settings) -> Option<Url> {
+    let mut url = Url::parse(settings.endpoint.trim()).ok()?;
+    let bucket = settings.bucket.trim();
+    let key = sigv4::uri_encode(settings.key.trim().trim_start_matches('/'), false);
+    if bucket.is_empty() || key.is_empty() {
+        return None;
+    }
+    let base = url.path().trim_end_matches('/').to_string();
+    if settings.path_style {
+        url.set_path(&format!("{}/{}/{}", base, bucket, key));
+    } else {
+        let host = format!("{}.{}", bucket, url.host_str()?);
+        url.set_host(Some(&host)).ok()?;
+        url.set_path(&format!("{}/{}", base, key));
+    }
+    Some(url)
+}
+
+impl SyncBackend for S3Backend {
+    fn head(&self) -> LocalBoxFuture<'_, Result<Option<RemoteFile>, SyncError>> {
+        Box::pin(async move {
+            // A presigned URL is only good for the method it was signed for,
+            // so the object's first byte stands in for a HEAD request.
+            let request = match self.settings.auth {
+                S3Auth::AccessKey => self.request(Method::HEAD, &self.get_url, b""),
+                S3Auth::Presigned => self.request(Method::GET, &self.get_url, b"").header("range", "bytes=0-0"),
+            };
+            let response = request.send().await?;
+            if response.status() == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            let response = check(response).await?;
+            Ok(Some(remote_file(self.get_url.as_str(), &response)?))
+        })
+    }
+
+    fn download<'a>(&'a self, _file: &'a RemoteFile) -> LocalBoxFuture<'a, Result<String, SyncError>> {
+        Box::pin(async move {
+            let response = self.request(Method::GET, &self.get_url, b"").send().await?;
+            Ok(check(response).await?.text().await?)
+        })
+    }
+
+    fn upload<'a>(
+        &'a self,
+        data: &'a str,
+        file: Option<&'a RemoteFile>,
+    ) -> LocalBoxFuture<'a, Result<RemoteFile, SyncError>> {
+        Box::pin(async move {
+            let request = self
+                .request(Method::PUT, &self.put_url, data.as_bytes())
+                .header(CONTENT_TYPE, "application/json")
+                .body(data.to_string());
+            let response = check(precondition(request, file).send().await?).await?;
+            remote_file(self.get_url.as_str(), &response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloud::stand_in::ObjectStore;
+
+    fn settings(endpoint: &str) -> S3Settings {
+        S3Settings {
+            endpoint: endpoint.to_string(),
+            bucket: "chats".to_string(),
+            key: "backups/yewmind.json".to_string(),
+            access_key_id: "minioadmin".to_string(),
+            secret_access_key: "minioadmin".to_string(),
+            ..S3Settings::default()
+        }
+    }
+
+    #[test]
+    fn objects_are_addressed_path_or_virtual_host_style() {
+        let path_style = object_url(&settings("http://localhost:9000")).unwrap();
+        assert_eq!(path_style.as_str(), "http://localhost:9000/chats/backups/yewmind.json");
+        let virtual_host = object_url(&S3Settings {
+            path_style: false,
+            ..settings("https://s3.eu-central-1.amazonaws.com")
+        })
+        .unwrap();
+        assert_eq!(virtual_host.as_str(), "https://chats.s3.eu-central-1.amazonaws.com/backups/yewmind.json");
+        assert_eq!(object_url(&settings("")), None);
+    }
+
+    #[tokio::test]
+    async fn signed_writes_only_go_over_the_version_last_seen() {
+        let store = ObjectStore::start(|request| {
+            request.header("authorization").is_some_and(|a| {
+                a.starts_with("AWS4-HMAC-SHA256 Credential=minioadmin/")
+                    && a.contains("/us-east-1/s3/aws4_request,SignedHeaders=host;x-amz-content-sha256;x-amz-date,")
+            }) && request.header("x-amz-content-sha256") == Some(&sigv4::payload_hash(request.body.as_bytes()))
+        })
+        .await;
+        let s3 = S3Backend::new(&settings(&store.url)).unwrap();
+        assert_eq!(s3.head().await.unwrap(), None);
+
+        let created = s3.upload("one", None).await.unwrap();
+        assert_eq!(s3.head().await.unwrap().as_ref(), Some(&created));
+        assert_eq!(s3.download(&created).await.unwrap(), "one");
+        store.edit_remotely("/chats/backups/yewmind.json", "two");
+        assert!(matches!(s3.upload("three", Some(&created)).await, Err(SyncError::Conflict)));
+    }
+
+    #[tokio::test]
+    async fn presigned_urls_are_used_as_given() {
+        let store = ObjectStore::start(|request| {
+            request.header("authorization").is_none() && request.target.contains("X-Amz-Signature=")
+        })
+        .await;
+        let s3 = S3Backend::new(&S3Settings {
+            auth: S3Auth::Presigned,
+            presigned_get_url: format!("{}/chats/yewmind.json?X-Amz-Signature=get", store.url),
+            presigned_put_url: format!("{}/chats/yewmind.json?X-Amz-Signature=put", store.url),
+            ..S3Settings::default()
+        })
+        .unwrap();
+        assert_eq!(s3.head().await.unwrap(), None);
+        let created = s3.upload("one", None).await.unwrap();
+        assert_eq!(s3.head().await.unwrap().as_ref(), Some(&created));
+        assert_eq!(s3.download(&created).await.unwrap(), "one");
+    }
+
+    /// Runs against a real MinIO, e.g. one started with
+    /// `docker run -p 9000:9000 minio/minio server /data` and a bucket
+    /// named by `MINIO_BUCKET`.
+    #[tokio::test]
+    #[ignore = "needs a local MinIO, see MINIO_ENDPOINT"]
+    async fn syncs_with_minio() {
+        let var = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+        let s3 = S3Backend::new(&S3Settings {
+            endpoint: var("MINIO_ENDPOINT", "http://localhost:9000"),
+            bucket: var("MINIO_BUCKET", "yewmind"),
+            key: format!("test-{}.json", uuid::Uuid::new_v4()),
+            access_key_id: var("MINIO_ACCESS_KEY", "minioadmin"),
+            secret_access_key: var("MINIO_SECRET_KEY", "minioadmin"),
+            ..S3Settings::default()
+        })
+        .unwrap();
+        assert_eq!(s3.head().await.unwrap(), None);
+        let created = s3.upload("one", None).await.unwrap();
+        assert_eq!(s3.download(&created).await.unwrap(), "one");
+        let updated = s3.upload("two", Some(&created)).await.unwrap();
+        assert!(matches!(s3.upload("three", Some(&created)).await, Err(SyncError::Conflict)));
+        assert!(matches!(s3.upload("three", None).await, Err(SyncError::Conflict)));
+        assert_eq!(s3.head().await.unwrap(), Some(updated));
+    }
+}
@@ -0,0 +1,202 @@
+//! A stand-in for the servers the sync backends talk to, enough of HTTP
+//! and of the WebDAV and S3 object semantics to exercise them in tests.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+pub(crate) struct Request {
+    pub(crate) method: String,
+    /// Path and query, as sent.
+    pub(crate) target: String,
+    headers: Vec<(String, String)>,
+    pub(crate) body: String,
+}
+
+impl Request {
+    /// The value of the header `name`, whatever its case.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or_default()
+    }
+}
+
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    pub(crate) headers: Vec<(&'static str, String)>,
+    pub(crate) body: String,
+}
+
+impl Response {
+    pub(crate) fn new(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+}
+
+/// Accepts connections on a free local port and answers each request with
+/// `handle`. Returns the base URL.
+pub(crate) async fn serve(handle: impl Fn(Request) -> Response + Send + Sync + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = Arc::new(handle);
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            let handle = handle.clone();
+            tokio::spawn(async move {
+                let (mut socket, request) = match read_request(socket).await {
+                    Some(read) => read,
+                    None => return,
+                };
+                let head_only = request.method == "HEAD";
+                let response = handle(request);
+                write_response(&mut socket, response, head_only).await;
+            });
+        }
+    });
+    url
+}
+
+async fn read_request(mut socket: TcpStream) -> Option<(TcpStream, Request)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+    let head_end = loop {
+        let read = socket.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(i) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(n, v)| (n.trim().to_string(), v.trim().to_string()))
+        .collect();
+    let length = headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+        .map_or(0, |(_, v)| v.parse::<usize>().unwrap());
+    while buffer.len() < head_end + length {
+        let read = socket.read(&mut chunk).await.ok()?;
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    let body = String::from_utf8_lossy(&buffer[head_end..head_end + length]).to_string();
+    Some((
+        socket,
+        Request {
+            method,
+            target,
+            headers,
+            body,
+        },
+    ))
+}
+
+async fn write_response(socket: &mut TcpStream, response: Response, head_only: bool) {
+    let mut out = format!(
+        "HTTP/1.1 {} Stand-in\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        out.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    out.push_str("\r\n");
+    if !head_only {
+        out.push_str(&response.body);
+    }
+    let _ = socket.write_all(out.as_bytes()).await;
+}
+
+type Objects = Arc<Mutex<HashMap<String, (String, String)>>>;
+
+/// Files by path, each with an ETag that changes on every write. Writes
+/// honour `If-Match` and `If-None-Match: *` like WebDAV servers and S3 do.
+pub(crate) struct ObjectStore {
+    pub(crate) url: String,
+    objects: Objects,
+}
+
+impl ObjectStore {
+    /// Requests `authorize` refuses are answered with 401.
+    pub(crate) async fn start(authorize: impl Fn(&Request) -> bool + Send + Sync + 'static) -> Self {
+        let objects = Objects::default();
+        let url = serve({
+            let objects = objects.clone();
+            move |request| {
+                if authorize(&request) {
+                    Self::handle(&objects, request)
+                } else {
+                    Response::new(401, "")
+                }
+            }
+        })
+        .await;
+        Self { url, objects }
+    }
+
+    /// What another device writing the file at `path` looks like.
+    pub(crate) fn edit_remotely(&self, path: &str, data: &str) {
+        Self::write(&mut self.objects.lock().unwrap(), path, data);
+    }
+
+    /// The file at `path`, as the server holds it.
+    pub(crate) fn object(&self, path: &str) -> Option<String> {
+        self.objects.lock().unwrap().get(path).map(|(_, data)| data.clone())
+    }
+
+    fn write(objects: &mut HashMap<String, (String, String)>, path: &str, data: &str) -> String {
+        let etag = format!("\"{}\"", uuid::Uuid::new_v4().simple());
+        objects.insert(path.to_string(), (etag.clone(), data.to_string()));
+        etag
+    }
+
+    fn handle(objects: &Objects, request: Request) -> Response {
+        let mut objects = objects.lock().unwrap();
+        let path = request.path().to_string();
+        let found = objects.get(&path).cloned();
+        let mut response = match (request.method.as_str(), found) {
+            ("HEAD" | "GET", None) => return Response::new(404, ""),
+            ("HEAD" | "GET", Some((etag, body))) => {
+                let mut response = Response::new(200, body);
+                response.headers.push(("ETag", etag));
+                return response;
+            }
+            ("PUT", found) => {
+                let current = found.map(|(etag, _)| etag);
+                let stale = request.header("if-match").is_some_and(|m| Some(m) != current.as_deref());
+                let exists = request.header("if-none-match") == Some("*") && current.is_some();
+                if stale || exists {
+                    return Response::new(412, "");
+                }
+                Response::new(if current.is_some() { 200 } else { 201 }, "")
+            }
+            _ => return Response::new(405, ""),
+        };
+        let etag = Self::write(&mut objects, &path, &request.body);
+        response.headers.push(("ETag", etag));
+        response
+    }
+}
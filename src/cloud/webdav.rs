@@ -0,0 +1,127 @@
+//! Sync through a WebDAV folder, e.g. on Nextcloud. Only plain HTTP verbs
+//! are needed: the ETag the server keeps for the file tells writes apart,
+//! and `If-Match` keeps one device from overwriting another's changes.
+//!
+//! In the browser the server has to allow the app's origin, and expose the
+//! `ETag` header to it.
+
+use futures::future::LocalBoxFuture;
+use reqwest::{Client, RequestBuilder, StatusCode, header::CONTENT_TYPE};
+
+use super::{RemoteFile, SYNC_FILE_NAME, SyncBackend, SyncError, check, precondition, remote_file};
+use crate::types::api::WebDavSettings;
+
+pub(crate) struct WebDavBackend {
+    client: Client,
+    /// URL of the synced file.
+    url: String,
+    username: String,
+    password: String,
+}
+
+impl WebDavBackend {
+    /// `None` until a folder is set.
+    pub(crate) fn new(settings: &WebDavSettings) -> Option<Self> {
+        let folder = settings.url.trim().trim_end_matches('/');
+        if folder.is_empty() {
+            return None;
+        }
+        Some(Self {
+            client: Client::new(),
+            url: format!("{}/{}", folder, SYNC_FILE_NAME),
+            username: settings.username.clone(),
+            password: settings.password.clone(),
+        })
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        if self.username.is_empty() {
+            request
+        } else {
+            request.basic_auth(&self.username, Some(&self.password))
+        }
+    }
+}
+
+impl SyncBackend for WebDavBackend {
+    fn head(&self) -> LocalBoxFuture<'_, Result<Option<RemoteFile>, SyncError>> {
+        Box::pin(async move {
+            let response = self.authorized(self.client.head(&self.url)).send().await?;
+            if response.status() == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            let response = check(response).await?;
+            Ok(Some(remote_file(&self.url, &response)?))
+        })
+    }
+
+    fn download<'a>(&'a self, file: &'a RemoteFile) -> LocalBoxFuture<'a, Result<String, SyncError>> {
+        Box::pin(async move {
+            let response = self.authorized(self.client.get(&file.id)).send().await?;
+            Ok(check(response).await?.text().await?)
+        })
+    }
+
+    fn upload<'a>(
+        &'a self,
+        data: &'a str,
+        file: Option<&'a RemoteFile>,
+    ) -> LocalBoxFuture<'a, Result<RemoteFile, SyncError>> {
+        Box::pin(async move {
+            let request = self
+                .client
+                .put(&self.url)
+                .header(CONTENT_TYPE, "application/json")
+                .body(data.to_string());
+            let response = self.authorized(precondition(request, file)).send().await?;
+            let response = check(response).await?;
+            // Not every server sends the new ETag along.
+            match remote_file(&self.url, &response) {
+                Ok(file) => Ok(file),
+                Err(SyncError::MissingEtag) => self.head().await?.ok_or(SyncError::MissingEtag),
+                Err(e) => Err(e),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloud::stand_in::ObjectStore;
+
+    fn backend(url: &str, password: &str) -> WebDavBackend {
+        WebDavBackend::new(&WebDavSettings {
+            url: format!("{}/remote.php/dav/files/alice/", url),
+            username: "alice".to_string(),
+            password: password.to_string(),
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn writes_only_over_the_version_last_seen() {
+        let store = ObjectStore::start(|request| request.header("authorization") == Some("Basic YWxpY2U6c2VjcmV0")).await;
+        let dav = backend(&store.url, "secret");
+        assert_eq!(dav.head().await.unwrap(), None);
+
+        let created = dav.upload("one", None).await.unwrap();
+        assert_eq!(created.id, format!("{}/remote.php/dav/files/alice/yewmind.json", store.url));
+        assert_eq!(dav.head().await.unwrap().as_ref(), Some(&created));
+        assert_eq!(dav.download(&created).await.unwrap(), "one");
+        assert!(matches!(dav.upload("again", None).await, Err(SyncError::Conflict)), "it exists by now");
+
+        let updated = dav.upload("two", Some(&created)).await.unwrap();
+        assert_ne!(updated.version, created.version);
+        store.edit_remotely("/remote.php/dav/files/alice/yewmind.json", "three");
+        assert!(matches!(dav.upload("four", Some(&updated)).await, Err(SyncError::Conflict)));
+        assert_eq!(dav.download(&updated).await.unwrap(), "three", "the edit elsewhere is kept");
+    }
+
+    #[tokio::test]
+    async fn a_wrong_password_is_reported_as_such() {
+        let store = ObjectStore::start(|request| request.header("authorization") == Some("Basic YWxpY2U6c2VjcmV0")).await;
+        assert!(matches!(backend(&store.url, "wrong").head().await, Err(SyncError::Unauthorized)));
+        assert!(WebDavBackend::new(&WebDavSettings::default()).is_none());
+    }
+}
@@ -13,8 +13,9 @@ pub fn ApiPopup() -> Html {
 
     let (state, state_dispatch) = use_store::<AuthSlice>();
     let first_visit = use_state(|| false);
-    let api_key = use_state(|| state.api_key.clone());
-    let is_modal_open = use_state(|| state.api_key.is_none() && state.first_version);
+    let profile = state.profile(None);
+    let api_key = use_state(|| profile.api_key.clone());
+    let is_modal_open = use_state(|| profile.api_key.is_none() && state.first_version && !profile.provider.is_local());
     let error = use_state(|| "".to_string());
 
     let handle_confirm = {
@@ -35,7 +36,7 @@ pub fn ApiPopup() -> Html {
                 error.set("".to_string());
                 state_dispatch.reduce_mut(|s| {
                     if let Some(apikey) = &*api_key {
-                        s.api_key = Some(apikey.clone());
+                        s.default_profile_mut().api_key = Some(apikey.clone());
                     }
                 })
             }
@@ -1,18 +1,27 @@
 use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
-use std::str::FromStr;
+use std::collections::BTreeMap;
+use yewdux::use_store;
 
 use crate::{
-    components::{icons::DownChevronArrow, popup_modal::PopupModal},
-    constants::{MODEL_MAX_TOKEN, MODEL_OPTIONS},
-    hooks::translation::use_translation,
-    types::chat::{ConfigInterface, ModelOptions},
+    api::{list_models, provider::provider_for},
+    components::{icons::DownChevronArrow, menu::api::ApiProfileSelector, popup_modal::PopupModal},
+    hooks::translation::{use_translation, Namespace},
+    store::AuthSlice,
+    types::{
+        chat::{ConfigInterface, ResponseFormat},
+        model::ModelRegistry,
+    },
 };
 
 #[derive(Debug, Properties, PartialEq)]
 pub(crate) struct ConfigMenuProps {
     pub(crate) set_is_modal_open: Callback<bool>,
-    pub(crate) config: UseStateHandle<Option<ConfigInterface>>,
+    pub(crate) config: ConfigInterface,
+    pub(crate) set_config: Callback<ConfigInterface>,
+    pub(crate) api_profile: Option<String>,
+    pub(crate) set_api_profile: Callback<Option<String>>,
 }
 
 #[function_component]
@@ -20,27 +29,72 @@ pub(crate) fn ConfigMenu(
     ConfigMenuProps {
         set_is_modal_open,
         config,
+        set_config,
+        api_profile,
+        set_api_profile,
     }: &ConfigMenuProps,
 ) -> Html {
-    if config.is_none() {
-        return html! {
-          <></>
-        };
-    }
-    let cfg_inner = config.as_ref().unwrap().clone();
-    let max_token = use_state(|| cfg_inner.max_tokens);
-    let model: UseStateHandle<ModelOptions> = use_state(|| cfg_inner.model.clone());
-    let temperature = use_state(|| cfg_inner.temperature);
-    let presence_penalty = use_state(|| cfg_inner.presence_penalty);
-    let top_p = use_state(|| cfg_inner.top_p);
-    let freq_penalty = use_state(|| cfg_inner.frequency_penalty);
-    let xtrans = use_translation(vec!["model".to_string()]);
+    let (auth, _) = use_store::<AuthSlice>();
+    let api_profile = use_state(|| api_profile.clone());
+    let max_token = use_state(|| config.max_tokens);
+    let model: UseStateHandle<String> = use_state(|| config.model.clone());
+    let temperature = use_state(|| config.temperature);
+    let presence_penalty = use_state(|| config.presence_penalty);
+    let top_p = use_state(|| config.top_p);
+    let freq_penalty = use_state(|| config.frequency_penalty);
+    let stop = use_state(|| config.stop.clone());
+    let seed = use_state(|| config.seed);
+    let n = use_state(|| config.n);
+    let logit_bias = use_state(|| config.logit_bias.clone());
+    let response_format = use_state(|| config.response_format);
+    let user = use_state(|| config.user.clone());
+    let xtrans = use_translation(vec!["model".to_string(), "api".to_string()]);
+
+    let select_profile = {
+        let api_profile = api_profile.clone();
+        let model = model.clone();
+        let auth = auth.clone();
+        Callback::from(move |id: Option<String>| {
+            if let Some(default_model) = auth.profile(id.as_deref()).default_model {
+                model.set(default_model);
+            }
+            api_profile.set(id);
+        })
+    };
 
     let handle_confirm = {
-        let config = config.clone();
+        let set_config = set_config.clone();
+        let set_api_profile = set_api_profile.clone();
+        let api_profile = api_profile.clone();
         let set_is_modal_open = set_is_modal_open.clone();
+        let model = model.clone();
+        let max_token = max_token.clone();
+        let temperature = temperature.clone();
+        let presence_penalty = presence_penalty.clone();
+        let top_p = top_p.clone();
+        let freq_penalty = freq_penalty.clone();
+        let stop = stop.clone();
+        let seed = seed.clone();
+        let n = n.clone();
+        let logit_bias = logit_bias.clone();
+        let response_format = response_format.clone();
+        let user = user.clone();
         move |_e| {
-            config.set(Some(ConfigInterface::default()));
+            set_config.emit(ConfigInterface {
+                model: (*model).clone(),
+                max_tokens: *max_token,
+                temperature: *temperature,
+                presence_penalty: *presence_penalty,
+                top_p: *top_p,
+                frequency_penalty: *freq_penalty,
+                stop: (*stop).clone(),
+                seed: *seed,
+                n: *n,
+                logit_bias: (*logit_bias).clone(),
+                response_format: *response_format,
+                user: (*user).clone(),
+            });
+            set_api_profile.emit((*api_profile).clone());
             set_is_modal_open.emit(false);
         }
     };
@@ -49,16 +103,27 @@ pub(crate) fn ConfigMenu(
       <PopupModal
         title={ xtrans("configuration".to_string(), None) }
         set_is_modal_open={set_is_modal_open}
-        handle_confirm={handle_confirm.clone()}
-        handle_click_backdrop={handle_confirm.clone()}
+        handle_confirm={handle_confirm}
       >
         <div class="p-6 border-b border-gray-200 dark:border-gray-600">
-          <ModelSelector model={model.clone()}  />
+          <div class="flex gap-2 items-center mb-4">
+            <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm">
+              {xtrans("profile.label".to_string(), Some(Namespace { ns: "api".to_string() }))}
+            </div>
+            <ApiProfileSelector
+              profiles={auth.profiles.clone()}
+              selected={(*api_profile).clone()}
+              default_label={xtrans("profile.useDefault".to_string(), Some(Namespace { ns: "api".to_string() }))}
+              on_select={select_profile}
+            />
+          </div>
+          <ModelSelector model={model.clone()} profile={(*api_profile).clone()} />
           <MaxTokenSlider {max_token} {model} />
           <TemperatureSlider {temperature} />
           <TopPSlider {top_p} />
           <PresencePenaltySlider {presence_penalty} />
           <FrequencyPenaltySlider {freq_penalty} />
+          <AdvancedParams {stop} {seed} {n} {logit_bias} {response_format} {user} />
         </div>
       </PopupModal>
     }
@@ -66,7 +131,7 @@ pub(crate) fn ConfigMenu(
 
 #[derive(Debug, Properties, PartialEq)]
 pub struct FreqPenaltyProps {
-    pub freq_penalty: UseStateHandle<i32>,
+    pub freq_penalty: UseStateHandle<f32>,
 }
 
 #[function_component]
@@ -85,7 +150,9 @@ pub(crate) fn FrequencyPenaltySlider(FreqPenaltyProps { freq_penalty }: &FreqPen
             let freq_penalty = freq_penalty.clone();
                 move |e: Event| {
                 let input = e.dyn_ref::<web_sys::HtmlInputElement>().unwrap();
-                freq_penalty.set(input.value().parse::<i32>().unwrap());
+                if let Ok(value) = input.value().parse::<f32>() {
+                    freq_penalty.set(value);
+                }
             }
           }
           min={"-2"}
@@ -102,7 +169,7 @@ pub(crate) fn FrequencyPenaltySlider(FreqPenaltyProps { freq_penalty }: &FreqPen
 
 #[derive(Debug, PartialEq, Properties)]
 pub(crate) struct TempSliderProps {
-    pub temperature: UseStateHandle<i32>,
+    pub temperature: UseStateHandle<f32>,
 }
 
 #[function_component]
@@ -121,7 +188,9 @@ pub(crate) fn TemperatureSlider(TempSliderProps { temperature }: &TempSliderProp
                 let temperature = temperature.clone();
                 move |e: Event| {
                 let input = e.dyn_ref::<web_sys::HtmlInputElement>().unwrap();
-                temperature.set(input.value().parse::<i32>().unwrap());
+                if let Ok(value) = input.value().parse::<f32>() {
+                    temperature.set(value);
+                }
             }
         }
           min={0}
@@ -138,7 +207,7 @@ pub(crate) fn TemperatureSlider(TempSliderProps { temperature }: &TempSliderProp
 
 #[derive(Debug, Properties, PartialEq)]
 pub(crate) struct TopPSliderProps {
-    pub(crate) top_p: UseStateHandle<i32>,
+    pub(crate) top_p: UseStateHandle<f32>,
 }
 
 #[function_component]
@@ -157,7 +226,9 @@ pub(crate) fn TopPSlider(TopPSliderProps { top_p }: &TopPSliderProps) -> Html {
             let top_p = top_p.clone();
                 move |e: Event| {
                 let input = e.dyn_ref::<web_sys::HtmlInputElement>().unwrap();
-                top_p.set(input.value().parse::<i32>().unwrap());
+                if let Ok(value) = input.value().parse::<f32>() {
+                    top_p.set(value);
+                }
             }
           }
           min={0}
@@ -174,7 +245,7 @@ pub(crate) fn TopPSlider(TopPSliderProps { top_p }: &TopPSliderProps) -> Html {
 
 #[derive(Debug, Properties, PartialEq)]
 pub(crate) struct PenaltySliderProps {
-    pub(crate) presence_penalty: UseStateHandle<i32>,
+    pub(crate) presence_penalty: UseStateHandle<f32>,
 }
 
 #[function_component]
@@ -195,7 +266,9 @@ pub(crate) fn PresencePenaltySlider(
             let presence_penalty = presence_penalty.clone();
                 move |e: Event| {
                 let input = e.dyn_ref::<web_sys::HtmlInputElement>().unwrap();
-                presence_penalty.set(input.value().parse::<i32>().unwrap());
+                if let Ok(value) = input.value().parse::<f32>() {
+                    presence_penalty.set(value);
+                }
             }
           }
           min={"-2".to_string()}
@@ -212,23 +285,60 @@ pub(crate) fn PresencePenaltySlider(
 
 #[derive(Debug, Properties, PartialEq)]
 pub(crate) struct ModelSelectorProps {
-    pub(crate) model: UseStateHandle<ModelOptions>,
+    pub(crate) model: UseStateHandle<String>,
+    /// API profile whose endpoint is asked for models; `None` is the default profile.
+    #[prop_or_default]
+    pub(crate) profile: Option<String>,
 }
 
 #[function_component]
-pub(crate) fn ModelSelector(ModelSelectorProps { model }: &ModelSelectorProps) -> Html {
+pub(crate) fn ModelSelector(ModelSelectorProps { model, profile }: &ModelSelectorProps) -> Html {
     let dropdown = use_state(|| false);
+    let refresh = use_state(|| 0u32);
+    let (auth, _) = use_store::<AuthSlice>();
+    let profile = auth.profile(profile.as_deref());
+    let (registry, registry_dispatch) = use_store::<ModelRegistry>();
+    let xtrans = use_translation(vec!["model".to_string()]);
+
+    // Ask the endpoint which models it serves (`/v1/models`, Ollama's `/api/tags`, ...)
+    // so fine-tunes and locally pulled models show up next to the built-in list.
+    {
+        use_effect_with(
+            (profile.clone(), *refresh),
+            move |(profile, _)| {
+                if !profile.endpoint.is_empty() {
+                    let profile = profile.clone();
+                    spawn_local(async move {
+                        let headers = Some(profile.custom_headers.clone().into_iter().collect());
+                        match list_models(provider_for(&profile).as_ref(), &profile.endpoint, &profile.api_key, headers).await {
+                            Ok(ids) => registry_dispatch.reduce_mut(|r| r.merge_ids(ids, &profile.provider)),
+                            Err(e) => log::warn!("Failed to list models: {}", e),
+                        }
+                    });
+                }
+                || ()
+            },
+        );
+    }
+    let selected = registry.info(model);
     html! {
-      <div class="mb-4">
+      <div class="mb-4 flex gap-2">
         <button
           class="btn btn-neutral btn-small flex gap-1"
           type="button"
           onclick={let dropdown = dropdown.clone(); move |_e| dropdown.set(!*dropdown)}
           aria-label="model"
         >
-          {model.to_string()}
+          {selected.display_name().to_string()}
           <DownChevronArrow />
         </button>
+        <button
+          class="btn btn-neutral btn-small"
+          type="button"
+          onclick={let refresh = refresh.clone(); move |_e| refresh.set(*refresh + 1)}
+        >
+          {xtrans("refreshModels".to_string(), None)}
+        </button>
         <div
           id="dropdown"
           class={classes!(if *dropdown {""} else {"hidden"}, "absolute", "top-100", "bottom-100", "z-10", "bg-white", "rounded-lg", "shadow-xl", "border-b", "border-black/10", "dark:border-gray-900/50", "text-gray-800", "dark:text-gray-100", "group", "dark:bg-gray-800", "opacity-90")}
@@ -238,21 +348,22 @@ pub(crate) fn ModelSelector(ModelSelectorProps { model }: &ModelSelectorProps) -
             aria-labelledby="dropdownDefaultButton"
           >
           {
-            MODEL_OPTIONS.iter().map(|m| {
+            registry.for_provider(&profile.provider).into_iter().map(|m| {
               html! {
                 <li
                   class="px-4 py-2 hover:bg-gray-100 dark:hover:bg-gray-600 dark:hover:text-white cursor-pointer"
                   onclick={
                     let model = model.clone();
                     let dropdown = dropdown.clone();
+                    let id = m.id.clone();
                     move |_e| {
-                        model.set(ModelOptions::from_str(m).unwrap());
+                        model.set(id.clone());
                         dropdown.set(false);
                     }
                 }
-                  key={m.to_string()}
+                  key={m.id.clone()}
                 >
-                  {m.to_string()}
+                  {m.display_name().to_string()}
                 </li>
               }
             }).collect::<Html>()
@@ -266,14 +377,15 @@ pub(crate) fn ModelSelector(ModelSelectorProps { model }: &ModelSelectorProps) -
 #[derive(Debug, Properties, PartialEq)]
 pub(crate) struct TokenSliderProps {
     pub(crate) max_token: UseStateHandle<i32>,
-    pub(crate) model: UseStateHandle<ModelOptions>,
+    pub(crate) model: UseStateHandle<String>,
 }
 
 #[function_component]
 pub(crate) fn MaxTokenSlider(TokenSliderProps { max_token, model }: &TokenSliderProps) -> Html {
     let xtrans = use_translation(vec!["model".to_string()]);
     let input_ref = use_node_ref();
-    let model_max_token = MODEL_MAX_TOKEN[&*model.to_string()];
+    let (registry, _) = use_store::<ModelRegistry>();
+    let model_max_token = registry.info(model).context_window;
     let set_max_token = {
         let max_token = max_token.clone();
         move |e: Event| {
@@ -302,3 +414,184 @@ pub(crate) fn MaxTokenSlider(TokenSliderProps { max_token, model }: &TokenSlider
       </div>
     }
 }
+
+#[derive(Debug, Properties, PartialEq)]
+pub(crate) struct AdvancedParamsProps {
+    pub(crate) stop: UseStateHandle<Vec<String>>,
+    pub(crate) seed: UseStateHandle<Option<i64>>,
+    pub(crate) n: UseStateHandle<u32>,
+    pub(crate) logit_bias: UseStateHandle<BTreeMap<String, i32>>,
+    pub(crate) response_format: UseStateHandle<ResponseFormat>,
+    pub(crate) user: UseStateHandle<Option<String>>,
+}
+
+/// Less common request parameters, collapsed by default.
+#[function_component]
+pub(crate) fn AdvancedParams(
+    AdvancedParamsProps {
+        stop,
+        seed,
+        n,
+        logit_bias,
+        response_format,
+        user,
+    }: &AdvancedParamsProps,
+) -> Html {
+    let translate = use_translation(vec!["model".to_string()]);
+    let expanded = use_state(|| false);
+    let input_class = "w-full mt-2 px-2 py-1 rounded-md bg-transparent border border-gray-400/50 focus:ring-1 focus:ring-blue text-gray-800 dark:text-white text-sm";
+    let description_class = "min-w-fit text-gray-500 dark:text-gray-300 text-sm mt-2";
+    let label_class = "block text-sm font-medium text-gray-900 dark:text-white";
+
+    html! {
+      <div class="mt-5 pt-5 border-t border-gray-500">
+        <button
+          class="btn btn-neutral btn-small flex gap-1"
+          type="button"
+          onclick={let expanded = expanded.clone(); move |_e| expanded.set(!*expanded)}
+        >
+          {translate("advanced".to_string(), None)}
+          <DownChevronArrow />
+        </button>
+        if *expanded {
+          <div class="mt-3">
+            <label class={label_class}>{translate("stop.label".to_string(), None)}</label>
+            <textarea
+              class={input_class}
+              rows={2}
+              value={stop.join("\n")}
+              onchange={
+                let stop = stop.clone();
+                move |e: Event| {
+                    if let Some(target) = e.target_dyn_into::<web_sys::HtmlTextAreaElement>() {
+                        stop.set(parse_stop_sequences(&target.value()));
+                    }
+                }
+              }
+            />
+            <div class={description_class}>{translate("stop.description".to_string(), None)}</div>
+          </div>
+          <div class="mt-3 flex gap-4">
+            <div class="flex-1">
+              <label class={label_class}>{translate("seed.label".to_string(), None)}</label>
+              <input
+                type="number"
+                class={input_class}
+                value={seed.map(|s| s.to_string()).unwrap_or_default()}
+                onchange={
+                  let seed = seed.clone();
+                  move |e: Event| {
+                      let input = e.dyn_ref::<web_sys::HtmlInputElement>().unwrap();
+                      seed.set(input.value().trim().parse::<i64>().ok());
+                  }
+                }
+              />
+            </div>
+            <div class="flex-1">
+              <label class={label_class}>{translate("n.label".to_string(), None)}</label>
+              <input
+                type="number"
+                min={1}
+                max={10}
+                class={input_class}
+                value={n.to_string()}
+                onchange={
+                  let n = n.clone();
+                  move |e: Event| {
+                      let input = e.dyn_ref::<web_sys::HtmlInputElement>().unwrap();
+                      if let Ok(value) = input.value().parse::<u32>() {
+                          n.set(value.clamp(1, 10));
+                      }
+                  }
+                }
+              />
+            </div>
+          </div>
+          <div class={description_class}>{translate("seed.description".to_string(), None)}</div>
+          <div class="mt-3">
+            <label class={label_class}>{translate("logitBias.label".to_string(), None)}</label>
+            <textarea
+              class={input_class}
+              rows={2}
+              value={logit_bias.iter().map(|(token, bias)| format!("{}: {}", token, bias)).collect::<Vec<_>>().join("\n")}
+              onchange={
+                let logit_bias = logit_bias.clone();
+                move |e: Event| {
+                    if let Some(target) = e.target_dyn_into::<web_sys::HtmlTextAreaElement>() {
+                        logit_bias.set(parse_logit_bias(&target.value()));
+                    }
+                }
+              }
+            />
+            <div class={description_class}>{translate("logitBias.description".to_string(), None)}</div>
+          </div>
+          <div class="mt-3">
+            <label class={label_class}>{translate("responseFormat.label".to_string(), None)}</label>
+            <select
+              class={input_class}
+              onchange={
+                let response_format = response_format.clone();
+                move |e: Event| {
+                    if let Some(target) = e.target_dyn_into::<web_sys::HtmlSelectElement>()
+                        && let Some(format) = ResponseFormat::ALL.iter().find(|f| f.to_string() == target.value())
+                    {
+                        response_format.set(*format);
+                    }
+                }
+              }
+            >
+              {
+                ResponseFormat::ALL.iter().map(|format| html! {
+                  <option value={format.to_string()} selected={**response_format == *format}>
+                    {translate(format!("responseFormat.{}", format), None)}
+                  </option>
+                }).collect::<Html>()
+              }
+            </select>
+          </div>
+          <div class="mt-3">
+            <label class={label_class}>{translate("user.label".to_string(), None)}</label>
+            <input
+              type="text"
+              class={input_class}
+              value={(**user).clone().unwrap_or_default()}
+              onchange={
+                let user = user.clone();
+                move |e: Event| {
+                    let input = e.dyn_ref::<web_sys::HtmlInputElement>().unwrap();
+                    let value = input.value().trim().to_string();
+                    user.set(if value.is_empty() { None } else { Some(value) });
+                }
+              }
+            />
+            <div class={description_class}>{translate("user.description".to_string(), None)}</div>
+          </div>
+        }
+      </div>
+    }
+}
+
+/// One stop sequence per line; the API accepts at most four.
+fn parse_stop_sequences(value: &str) -> Vec<String> {
+    value
+        .lines()
+        .filter(|line| !line.is_empty())
+        .take(4)
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// `token_id: bias` pairs, one per line. Malformed lines are dropped and
+/// biases are clamped to the [-100, 100] range the API accepts.
+fn parse_logit_bias(value: &str) -> BTreeMap<String, i32> {
+    value
+        .lines()
+        .filter_map(|line| {
+            let (token, bias) = line.split_once(':')?;
+            let token = token.trim();
+            token.parse::<u32>().ok()?;
+            let bias = bias.trim().parse::<i32>().ok()?;
+            Some((token.to_string(), bias.clamp(-100, 100)))
+        })
+        .collect()
+}
@@ -14,7 +14,7 @@ use crate::components::icons::{CrossIcon, TickIcon};
 use crate::{
     hooks::submit::use_submit,
     store::{ChatSlice, ConfigSlice},
-    types::chat::Role,
+    types::chat::{MessageInterface, Role},
 };
 
 enum Direction {
@@ -47,56 +47,61 @@ pub fn ContentView(
     let content = content.clone();
     let curr_chat_index = use_state(|| state.curr_chat_index);
 
-    let last_message_index = use_state(|| {
-        if !state.chats.is_empty() {
-            state.chats[state.curr_chat_index as usize].messages.len() as i32 - 1
-        } else {
-            0
-        }
-    });
+    let chat = state.chats.get(state.curr_chat_index as usize);
+    let last_message_index = chat.map_or(0, |c| c.messages.len() as i32 - 1);
+    let can_swap = |other: i32| {
+        other >= 0 && chat.is_some_and(|c| c.can_swap(*message_index as usize, other as usize))
+    };
     // let inlineLatex = use_state(|| cfg_state.inline_latex);
     let markdown_mode = use_state(|| cfg_state.markdown_mode);
 
     let handle_move = {
         let chat_dispatch = chat_dispatch.clone();
-        let state = state.clone();
         let message_index = *message_index as usize;
         let curr_chat_index = curr_chat_index.clone();
         move |direction: Direction| {
-            let temp = state.messages[message_index].clone();
-            chat_dispatch.reduce_mut(|e| match direction {
-                Direction::Up => {
-                    e.chats[(*curr_chat_index) as usize].messages[message_index as usize] =
-                        state.messages[message_index - 1].clone();
-                    e.messages[message_index - 1] = temp;
-                }
-                Direction::Down => {
-                    e.chats[(*curr_chat_index) as usize].messages[message_index] =
-                        state.messages[message_index + 1].clone();
-                    e.messages[message_index + 1] = temp;
+            chat_dispatch.reduce_mut(|e| {
+                let Some(chat) = e.chats.get_mut(*curr_chat_index as usize) else {
+                    return;
+                };
+                let other = match direction {
+                    Direction::Up => message_index.checked_sub(1),
+                    Direction::Down => Some(message_index + 1),
+                };
+                if let Some(other) = other {
+                    chat.swap_messages(message_index, other);
                 }
             });
         }
     };
 
+    // Regenerating keeps the old answer as an alternative branch; `use_submit`
+    // streams into the empty message that replaces it.
     let handle_refresh = {
         let chat_dispatch = chat_dispatch.clone();
-        let state = state.clone();
         let handle_submit = handle_submit.clone();
         let curr_chat_index = curr_chat_index.clone();
-        move |e| {
-            let messeges = &state.chats[(*curr_chat_index) as usize].messages;
+        let message_index = *message_index as usize;
+        move |_e| {
             chat_dispatch.reduce_mut(|e| {
-                e.chats[(*curr_chat_index) as usize]
-                    .messages
-                    .remove(messeges.len() - 1)
+                if let Some(chat) = e.chats.get_mut(*curr_chat_index as usize) {
+                    let placeholder = MessageInterface {
+                        role: Role::Assistant,
+                        ..Default::default()
+                    };
+                    chat.branch_off(message_index, vec![placeholder]);
+                }
             });
             handle_submit.emit(());
         }
     };
 
-    fn handle_delete(chat_dispatch: &Dispatch<ChatSlice>, message_index: usize) {
-        chat_dispatch.reduce_mut(|f| f.chats.remove(message_index));
+    fn handle_delete(chat_dispatch: &Dispatch<ChatSlice>, chat_index: usize, message_index: usize) {
+        chat_dispatch.reduce_mut(|f| {
+            if let Some(chat) = f.chats.get_mut(chat_index) {
+                chat.delete_message(message_index);
+            }
+        });
     }
 
     fn handle_copy(clipboard: &UseClipboardHandle, content: String) {
@@ -113,16 +118,16 @@ pub fn ContentView(
          }
          </div>
          <div class="flex justify-end gap-2 w-full mt-2">
-           if *is_delete {
+           if !*is_delete {
                <>
-               if state.generating && Role::from_str(role).unwrap() == Role::Assistant &&
-                 *message_index == *last_message_index {
+               if !state.generating && Role::from_str(role).unwrap() == Role::Assistant &&
+                 *message_index == last_message_index {
                    <RefreshButton on_click={handle_refresh} />
                  }
-               if *message_index != 0 {
+               if can_swap(*message_index - 1) {
                    <UpButton on_click={ let handle_move=handle_move.clone(); move |e| handle_move(Direction::Up) } />
                }
-               if *message_index != *last_message_index {
+               if can_swap(*message_index + 1) {
                  <DownButton on_click={ let handle_move=handle_move.clone(); move |e| handle_move(Direction::Down) } />
                }
 
@@ -136,7 +141,7 @@ pub fn ContentView(
                <EditButton set_is_edit={ let is_edit=is_edit.clone(); move |val| is_edit.set(val) } />
                <DeleteButton set_is_delete={ let is_delete = is_delete.clone(); move |value| is_delete.set(value) } />
              </>
-
+           } else {
              <>
                <button
                  class="p-1 hover:text-white"
@@ -150,8 +155,13 @@ pub fn ContentView(
                 aria-label="confirm"
                 onclick={
                     let chat_dispatch = chat_dispatch.clone();
+                    let chat_index = *curr_chat_index as usize;
                     let message_index = *message_index as usize;
-                    move |_| handle_delete(&chat_dispatch, message_index)
+                    let is_delete = is_delete.clone();
+                    move |_| {
+                        handle_delete(&chat_dispatch, chat_index, message_index);
+                        is_delete.set(false);
+                    }
                 }
                >
                  <TickIcon />
@@ -79,14 +79,18 @@ pub fn EditView(
                     f.chats[curr_chat_index].messages.push(MessageInterface {
                         role: (*input_role).clone(),
                         content: (*content).clone(),
-                        folder: None,
-                        messages: vec![],
+                        created_at: Some(js_sys::Date::now() as i64),
+                        ..Default::default()
                     })
                 });
                 content.set("".to_string());
                 reset_text_area_height();
             } else {
-                chat_dispatch.reduce_mut(|f| f.messages[mess_idx].content = (*content).clone());
+                chat_dispatch.reduce_mut(|f| {
+                    if let Some(message) = f.chats.get_mut(curr_chat_index).and_then(|c| c.messages.get_mut(mess_idx)) {
+                        message.content = (*content).clone();
+                    }
+                });
                 is_edit.set(false);
             }
         }
@@ -109,27 +113,34 @@ pub fn EditView(
             if chat_slice.generating {
                 return;
             }
-            let mut updated_messages = chat_slice.chats[curr_chat_index].messages.clone();
             if sticky {
                 if !content.is_empty() {
                     chat_dispatch.reduce_mut(|f| {
                         f.chats[curr_chat_index].messages.push(MessageInterface {
                             role: (*input_role).clone(),
                             content: (*content).clone(),
-                            folder: None,
-                            messages: vec![],
+                            created_at: Some(js_sys::Date::now() as i64),
+                            ..Default::default()
                         })
                     });
                 }
                 content.set("".to_string());
                 reset_text_area_height();
             } else {
+                // The edited message starts a new branch; the original and
+                // everything after it stay reachable through the branch navigation.
                 chat_dispatch.reduce_mut(|f| {
-                    updated_messages[message_index].content = (*content).clone();
-                    f.messages = updated_messages
-                        .get(..=message_index) // Safely handle bounds
-                        .unwrap_or(&[]) // Fallback to empty slice if out of bounds
-                        .to_vec();
+                    if let Some(chat) = f.chats.get_mut(curr_chat_index)
+                        && let Some(original) = chat.messages.get(message_index)
+                    {
+                        let edited = MessageInterface {
+                            role: original.role.clone(),
+                            content: (*content).clone(),
+                            created_at: Some(js_sys::Date::now() as i64),
+                            ..Default::default()
+                        };
+                        chat.branch_off(message_index, vec![edited]);
+                    }
                 });
                 is_edit.set(false);
             }
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Local};
 use yew::prelude::*;
 use yewdux::use_store;
 
@@ -9,8 +10,9 @@ use crate::{
         },
         icons::PlusIcon,
     },
+    hooks::{submit::use_continue, translation::use_translation},
     store::{ChatSlice, ConfigSlice},
-    types::chat::{ChatInterface, MessageInterface, Role},
+    types::chat::{ChatInterface, MessageInterface, Role, TokenUsage},
 };
 
 #[derive(Debug, PartialEq, Properties)]
@@ -20,6 +22,21 @@ pub struct MessageProps {
     pub message_index: i32,
     #[prop_or(false)]
     pub sticky: bool,
+    #[prop_or(false)]
+    pub interrupted: bool,
+    /// Which version of this message is shown, out of `branch_count`.
+    #[prop_or(0)]
+    pub branch: usize,
+    #[prop_or(1)]
+    pub branch_count: usize,
+    #[prop_or_default]
+    pub created_at: Option<i64>,
+    #[prop_or_default]
+    pub model: Option<String>,
+    #[prop_or_default]
+    pub usage: Option<TokenUsage>,
+    #[prop_or_default]
+    pub finish_reason: Option<String>,
 }
 
 const BACKGROUND_STYLE: [&str; 2] = ["dark:bg-gray-800", "bg-gray-50 dark:bg-gray-650"];
@@ -31,8 +48,16 @@ pub fn Message(
         content,
         message_index,
         sticky,
+        interrupted,
+        branch,
+        branch_count,
+        created_at,
+        model,
+        usage,
+        finish_reason,
     }: &MessageProps,
 ) -> Html {
+    let t = use_translation(vec!["main".to_string()]);
     let (config, _) = use_store::<ConfigSlice>();
     let hide_side_menu = use_state(|| config.hide_side_menu);
     let advanced_mode = use_state(|| config.advanced_mode);
@@ -61,12 +86,166 @@ pub fn Message(
               message_index={message_index}
               sticky={sticky}
             />
+            if *interrupted {
+              <div class="text-xs italic text-gray-500 dark:text-gray-400 mt-2">
+                {t("interrupted".to_string(), None)}
+              </div>
+            }
+            if *branch_count > 1 && !*sticky {
+              <BranchNavigator {message_index} {branch} {branch_count} />
+            }
+            if !*sticky && finish_reason.as_deref() == Some("length") {
+              <ContinueButton {message_index} />
+            }
+            if !*sticky {
+              <MessageMeta
+                created_at={*created_at}
+                model={model.clone()}
+                usage={usage.clone()}
+                finish_reason={finish_reason.clone()}
+              />
+            }
           </div>
         </div>
       </div>
     }
 }
 
+#[derive(Debug, Properties, PartialEq)]
+struct ContinueButtonProps {
+    message_index: i32,
+}
+
+/// Offered on the last answer of the chat when the token limit cut it off.
+#[function_component]
+fn ContinueButton(ContinueButtonProps { message_index }: &ContinueButtonProps) -> Html {
+    let t = use_translation(vec!["main".to_string()]);
+    let (store, _) = use_store::<ChatSlice>();
+    let handle_continue = use_continue();
+    let is_last_cut_off = store
+        .chats
+        .get(store.curr_chat_index as usize)
+        .and_then(|chat| chat.messages.last().filter(|_| chat.messages.len() == *message_index as usize + 1))
+        .is_some_and(|m| m.is_cut_off());
+    if !is_last_cut_off || store.generating {
+        return html! {};
+    }
+
+    html! {
+      <button class="btn btn-neutral btn-small mt-2" onclick={move |_| handle_continue.emit(())}>
+        {t("continue".to_string(), None)}
+      </button>
+    }
+}
+
+#[derive(Debug, Properties, PartialEq)]
+struct MessageMetaProps {
+    created_at: Option<i64>,
+    model: Option<String>,
+    usage: Option<TokenUsage>,
+    finish_reason: Option<String>,
+}
+
+/// When the message was written, by which model, what it cost and why the
+/// model stopped; shown while hovering the message.
+#[function_component]
+fn MessageMeta(
+    MessageMetaProps {
+        created_at,
+        model,
+        usage,
+        finish_reason,
+    }: &MessageMetaProps,
+) -> Html {
+    let translate = use_translation(vec!["main".to_string()]);
+    let t = move |key: &str| translate(key.to_string(), None);
+
+    let mut parts = vec![];
+    if let Some(time) = created_at.and_then(DateTime::from_timestamp_millis) {
+        parts.push(time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string());
+    }
+    if let Some(model) = model {
+        parts.push(model.clone());
+    }
+    if let Some(usage) = usage {
+        parts.push(format!(
+            "{} {} + {} {}",
+            usage.prompt_tokens,
+            t("messageMeta.prompt"),
+            usage.completion_tokens,
+            t("messageMeta.completion")
+        ));
+    }
+    match finish_reason.as_deref() {
+        None | Some("stop") => {}
+        Some("length") => parts.push(t("messageMeta.cutOff")),
+        Some(reason) => parts.push(format!("{}{}", t("messageMeta.finishReason"), reason)),
+    }
+    if parts.is_empty() {
+        return html! {};
+    }
+
+    html! {
+      <div class="invisible group-hover:visible text-xs text-gray-500 dark:text-gray-400 mt-2">
+        { parts.join(" · ") }
+      </div>
+    }
+}
+
+#[derive(Debug, Properties, PartialEq)]
+pub struct BranchNavigatorProps {
+    pub message_index: i32,
+    pub branch: usize,
+    pub branch_count: usize,
+}
+
+/// "< 2/3 >" switcher between the versions of an edited or regenerated message.
+#[function_component]
+fn BranchNavigator(
+    BranchNavigatorProps {
+        message_index,
+        branch,
+        branch_count,
+    }: &BranchNavigatorProps,
+) -> Html {
+    let (store, dispatch) = use_store::<ChatSlice>();
+    let switch_to = {
+        let dispatch = dispatch.clone();
+        let chat_index = store.curr_chat_index as usize;
+        let message_index = *message_index as usize;
+        move |branch: usize| {
+            dispatch.reduce_mut(|s| {
+                if let Some(chat) = s.chats.get_mut(chat_index) {
+                    chat.switch_branch(message_index, branch);
+                }
+            })
+        }
+    };
+    // Switching while streaming would pull the message being written out of view.
+    let disabled = store.generating;
+    html! {
+      <div class="flex items-center gap-1 text-xs text-gray-500 dark:text-gray-400 mt-2 select-none">
+        <button
+          class="px-1 hover:text-gray-800 dark:hover:text-white disabled:opacity-40"
+          aria-label="previous version"
+          disabled={disabled || *branch == 0}
+          onclick={let switch_to = switch_to.clone(); let branch = *branch; move |_| switch_to(branch - 1)}
+        >
+          {"<"}
+        </button>
+        <span>{format!("{}/{}", branch + 1, branch_count)}</span>
+        <button
+          class="px-1 hover:text-gray-800 dark:hover:text-white disabled:opacity-40"
+          aria-label="next version"
+          disabled={disabled || branch + 1 >= *branch_count}
+          onclick={let branch = *branch; move |_| switch_to(branch + 1)}
+        >
+          {">"}
+        </button>
+      </div>
+    }
+}
+
 #[derive(Debug, Properties, PartialEq)]
 pub struct MsgContentProps {
     role: Role,
@@ -147,8 +326,7 @@ pub fn NewMessageButton(MessageBtnProps { msg_index }: &MessageBtnProps) -> Html
                     d.messages.push(MessageInterface {
                         role: Role::User,
                         content: "".to_string(),
-                        folder: None,
-                        messages: vec![],
+                        ..Default::default()
                     })
                 }
             });
@@ -19,8 +19,8 @@ use crate::{
         share_gpt::ShareGPT,
         stop_gen_btn::StopGeneratingButton,
     },
-    hooks::translation::use_translation,
-    store::{ChatSlice, ConfigSlice, InputSlice},
+    hooks::{submit::use_abort_on_chat_change, translation::{use_translation, Namespace}},
+    store::{AuthSlice, ChatSlice, ConfigSlice, InputSlice},
     types::chat::{ConfigInterface, Role},
 };
 use config_menu::ConfigMenu;
@@ -28,9 +28,13 @@ use message::NewMessageButton;
 use yew::prelude::*;
 use yewdux::prelude::*;
 
+/// How long a message jumped to stays outlined.
+const JUMP_FLASH_MS: u32 = 2_000;
+
 #[function_component]
 pub(crate) fn Chat() -> Html {
     let (state, _) = use_store::<ConfigSlice>();
+    use_abort_on_chat_change();
     html! {
       <div
         class={classes!("flex", "h-full", "flex-1", "flex-col", if state.hide_side_menu {"md:pl-0"} else {"md:pl-[260px]"})}
@@ -46,34 +50,42 @@ pub(crate) fn Chat() -> Html {
 
 #[function_component]
 pub(crate) fn ChatTitle() -> Html {
-    let x_translation = use_translation(vec!["model".to_string()]);
+    let x_translation = use_translation(vec!["model".to_string(), "api".to_string()]);
     let (chat_state, _dispatch) = use_store::<ChatSlice>();
-    let config = use_state(|| None::<ConfigInterface>);
-    if !chat_state.chats.is_empty() && chat_state.curr_chat_index < chat_state.chats.len() as i32 {
-        let c = chat_state.chats[chat_state.curr_chat_index as usize]
-            .config
-            .clone();
-        config.set(c.clone());
-    }
-    let curr_chat_index = use_state(|| chat_state.curr_chat_index);
+    let (auth, _) = use_store::<AuthSlice>();
+    let curr_chat_index = chat_state.curr_chat_index as usize;
+    let config = chat_state.chats.get(curr_chat_index).and_then(|c| c.config.clone());
+    let api_profile = chat_state.chats.get(curr_chat_index).and_then(|c| c.api_profile.clone());
     let set_config = {
-        let curr_chat_index = curr_chat_index.clone();
         let _dispatch = _dispatch.clone();
-        move |config| {
-            _dispatch.reduce_mut(|e| e.chats[*curr_chat_index as usize].config = Some(config));
-        }
+        Callback::from(move |config: ConfigInterface| {
+            _dispatch.reduce_mut(|e| {
+                if let Some(chat) = e.chats.get_mut(curr_chat_index) {
+                    chat.config = Some(config);
+                }
+            });
+        })
+    };
+
+    let set_api_profile = {
+        let _dispatch = _dispatch.clone();
+        Callback::from(move |api_profile: Option<String>| {
+            _dispatch.reduce_mut(|e| {
+                if let Some(chat) = e.chats.get_mut(curr_chat_index) {
+                    chat.api_profile = api_profile;
+                }
+            });
+        })
     };
 
     let is_modal_open = use_state(|| false);
 
     // for migrating from old ChatInterface to new ChatInterface (with config)
     {
-        let _dispatch = _dispatch.clone();
-        use_effect_with(curr_chat_index, move |_curr_idx| {
-            let chats = chat_state.chats.clone();
-            let set_config = set_config.clone();
-            if chats.len() > 0 {
-                set_config(ConfigInterface::default());
+        let set_config = set_config.clone();
+        use_effect_with((curr_chat_index, config.is_none()), move |(_, missing)| {
+            if *missing {
+                set_config.emit(ConfigInterface::default());
             }
         });
     }
@@ -84,13 +96,18 @@ pub(crate) fn ChatTitle() -> Html {
             is_modal_open.set(val);
         }
     };
-    if let Some(cfg) = &*config {
+    if let Some(cfg) = &config {
         return html! {
           <>
             <div
               class="flex gap-x-4 gap-y-1 flex-wrap w-full items-center justify-center border-b border-black/10 bg-gray-50 p-3 dark:border-gray-900/50 dark:bg-gray-700 text-gray-600 dark:text-gray-300 cursor-pointer"
               onclick={ let set_is_modal_open = set_is_modal_open.clone(); move |_e| set_is_modal_open(true) }
             >
+              if auth.profiles.len() > 1 {
+                <div class="text-center p-1 rounded-md bg-gray-300/20 dark:bg-gray-900/10 hover:bg-gray-300/50 dark:hover:bg-gray-900/50">
+                  {format!("{}: {}", x_translation("profile.label".to_string(), Some(Namespace { ns: "api".to_string() })), auth.profile(api_profile.as_deref()).name)}
+                </div>
+              }
               <div class="text-center p-1 rounded-md bg-gray-300/20 dark:bg-gray-900/10 hover:bg-gray-300/50 dark:hover:bg-gray-900/50">
                 {format!("{}: {}", x_translation("model".to_string(), None), cfg.model)}
               </div>
@@ -111,7 +128,7 @@ pub(crate) fn ChatTitle() -> Html {
               </div>
             </div>
             if *is_modal_open {
-              <ConfigMenu { set_is_modal_open } config={config.clone()} />
+              <ConfigMenu { set_is_modal_open } config={cfg.clone()} {set_config} {api_profile} {set_api_profile} />
             }
           </>
         };
@@ -160,29 +177,48 @@ pub(crate) fn TextField() -> Html {
 #[function_component]
 pub(crate) fn ChatContent() -> Html {
     let (input_store, _) = use_store::<InputSlice>();
-    let (chat_store, _) = use_store::<ChatSlice>();
+    let (chat_store, chat_dispatch) = use_store::<ChatSlice>();
     let (config_store, _) = use_store::<ConfigSlice>();
     let input_role = use_state(|| input_store.input_role.clone());
     let error = use_state(|| chat_store.error.clone());
-    let sticky_index = use_state(|| 0);
-    let messages = use_state(|| {
-        if !chat_store.chats.is_empty()
-            && chat_store.curr_chat_index >= 0
-            && chat_store.curr_chat_index < chat_store.chats.len() as i32
-        {
-            let messages = chat_store.chats[chat_store.curr_chat_index as usize].messages.clone();
-            sticky_index.set(messages.len() as i32);
-            messages
-        } else {
-            vec![]
-        }
-    });
+    // The active branch of the current chat; re-read on every store update so
+    // edits, regenerations and branch switches show up immediately.
+    let messages = chat_store
+        .chats
+        .get(chat_store.curr_chat_index as usize)
+        .map(|c| c.messages.clone())
+        .unwrap_or_default();
+    let sticky_index = messages.len() as i32;
 
     let advance_mode = use_state(|| config_store.advanced_mode);
-    let generating = use_state(|| chat_store.generating);
+    let generating = chat_store.generating;
     let hide_side_menu = use_state(|| config_store.hide_side_menu);
     let save_ref = use_node_ref();
 
+    // Scrolls to a message picked elsewhere, e.g. a search hit, and flashes it.
+    use_effect_with(
+        (chat_store.curr_chat_index, chat_store.jump_to),
+        move |(_, jump_to)| {
+            if let Some(index) = *jump_to {
+                let element = gloo_utils::document().get_element_by_id(&format!("message-{}", index));
+                if let Some(element) = element {
+                    let options = web_sys::ScrollIntoViewOptions::new();
+                    options.set_behavior(web_sys::ScrollBehavior::Smooth);
+                    options.set_block(web_sys::ScrollLogicalPosition::Center);
+                    element.scroll_into_view_with_scroll_into_view_options(&options);
+                    let classes = element.class_list();
+                    let _ = classes.add_2("ring-2", "ring-yellow-400");
+                    gloo_timers::callback::Timeout::new(JUMP_FLASH_MS, move || {
+                        let _ = classes.remove_2("ring-2", "ring-yellow-400");
+                    })
+                    .forget();
+                }
+                chat_dispatch.reduce_mut(|s| s.jump_to = None);
+            }
+            || ()
+        },
+    );
+
     html! {
       <div class="flex-1 overflow-hidden">
         <ScrollToBottom
@@ -197,20 +233,27 @@ pub(crate) fn ChatContent() -> Html {
               if *advance_mode {
                 <ChatTitle />
               }
-              if !*generating && *advance_mode && messages.is_empty() {
+              if !generating && *advance_mode && messages.is_empty() {
                   <NewMessageButton msg_index={-1} />
               }
               {
                 messages.iter().enumerate().map(|(index, message)| {
                     html! {
                         if *advance_mode || index != 0 || message.role != Role::System {
-                            <div key={index}>
+                            <div key={index} id={format!("message-{}", index)}>
                                 <Message
                                 role={message.role.clone()}
                                 content={message.content.clone()}
                                 message_index={index as i32}
+                                interrupted={message.interrupted}
+                                branch={message.branch}
+                                branch_count={message.alternatives.len() + 1}
+                                created_at={message.created_at}
+                                model={message.model.clone()}
+                                usage={message.usage.clone()}
+                                finish_reason={message.finish_reason.clone()}
                                 />
-                                if !*generating && *advance_mode {
+                                if !generating && *advance_mode {
                                     <NewMessageButton msg_index={index as i32} />
                                 }
                             </div>
@@ -225,7 +268,7 @@ pub(crate) fn ChatContent() -> Html {
             <Message
                 role={(*input_role).clone()}
                 content=""
-                message_index={*sticky_index}
+                message_index={sticky_index}
                 sticky={false}
             />
             if !error.is_empty() {
@@ -244,7 +287,7 @@ pub(crate) fn ChatContent() -> Html {
             <div
               class={classes!("mt-4", "w-full", "m-auto", if *hide_side_menu { "md:max-w-5xl lg:max-w-5xl xl:max-w-6xl" } else {"md:max-w-3xl lg:max-w-3xl xl:max-w-4xl"} )}
             >
-              if *generating {
+              if generating {
                 <div class="md:w-[calc(100%-50px)] flex gap-4 flex-wrap justify-center">
                   // <DownloadChat {save_ref} />
                   <ShareGPT />
@@ -0,0 +1,330 @@
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::use_store;
+
+use crate::{
+    cloud::ConflictChoice,
+    components::{
+        icons::{GoogleIcon, RefreshIcon},
+        popup_modal::PopupModal,
+    },
+    hooks::{
+        cloud_sync::use_cloud_sync,
+        translation::{Namespace, use_translation},
+    },
+    store::CloudAuthSlice,
+    types::api::{S3Auth, S3Settings, SyncProvider, SyncStatus, WebDavSettings},
+};
+
+const MENU_ITEM_CLASS: &str = "flex py-2 px-2 items-center gap-3 rounded-md hover:bg-gray-500/10 transition-colors duration-200 text-white cursor-pointer text-sm";
+const INPUT_CLASS: &str = "text-gray-800 dark:text-white p-3 text-sm border-none bg-gray-200 dark:bg-gray-600 rounded-md m-0 w-full mr-0 h-8 focus:outline-none";
+
+#[derive(Properties, PartialEq)]
+pub(crate) struct CloudSyncMenuProps {
+    /// Google Drive is only offered when the build has an OAuth client.
+    #[prop_or_default]
+    pub(crate) google_client_id: Option<String>,
+}
+
+/// Menu entries for cloud sync: sets up the provider, shows the
+/// [`SyncStatus`] and asks which copy to keep on a conflict.
+#[function_component]
+pub(crate) fn CloudSyncMenu(CloudSyncMenuProps { google_client_id }: &CloudSyncMenuProps) -> Html {
+    let translate = use_translation(vec!["drive".to_string()]);
+    let t = move |key: &str| translate(key.to_string(), Some(Namespace { ns: "drive".to_string() }));
+    let (cloud, _) = use_store::<CloudAuthSlice>();
+    let sync = use_cloud_sync();
+    let conflict_dismissed = use_state(|| false);
+    let is_settings_open = use_state(|| false);
+
+    let on_google = cloud.provider == SyncProvider::GoogleDrive;
+    let signed_in = !on_google || cloud.google_access_token.is_some();
+    let on_click = {
+        let sync = sync.clone();
+        let client_id = google_client_id.clone();
+        let conflict_dismissed = conflict_dismissed.clone();
+        let is_settings_open = is_settings_open.clone();
+        let cloud_sync = cloud.cloud_sync;
+        move |_e: MouseEvent| {
+            if !cloud_sync {
+                is_settings_open.set(true);
+            } else if !signed_in {
+                if let Some(client_id) = &client_id {
+                    sync.sign_in(client_id);
+                }
+            } else {
+                conflict_dismissed.set(false);
+                let sync = sync.clone();
+                spawn_local(async move { sync.sync().await });
+            }
+        }
+    };
+    let on_settings = {
+        let is_settings_open = is_settings_open.clone();
+        move |_e: MouseEvent| is_settings_open.set(true)
+    };
+    let on_stop = {
+        let sync = sync.clone();
+        move |_e: MouseEvent| sync.stop()
+    };
+    let resolve = |choice: ConflictChoice| {
+        let sync = sync.clone();
+        move |_e: MouseEvent| {
+            let sync = sync.clone();
+            spawn_local(async move { sync.resolve(choice).await });
+        }
+    };
+    let set_is_modal_open = {
+        let conflict_dismissed = conflict_dismissed.clone();
+        move |open: bool| conflict_dismissed.set(!open)
+    };
+    let set_is_settings_open = {
+        let is_settings_open = is_settings_open.clone();
+        move |open: bool| is_settings_open.set(open)
+    };
+
+    let label = if !cloud.cloud_sync {
+        t("button.sync")
+    } else if !signed_in {
+        t("status.unauthenticated")
+    } else {
+        t(match cloud.sync_status {
+            SyncStatus::Unauthenticated if on_google => "status.unauthenticated",
+            SyncStatus::Unauthenticated => "status.rejected",
+            SyncStatus::Syncing => "status.syncing",
+            SyncStatus::Synced => "status.synced",
+            SyncStatus::Conflict => "status.conflict",
+            SyncStatus::Failed => "status.failed",
+        })
+    };
+
+    html! {
+      <>
+        <a class={MENU_ITEM_CLASS} onclick={on_click} title={t("tagline")}>
+          if on_google && cloud.cloud_sync {
+            <GoogleIcon />
+          } else {
+            <RefreshIcon />
+          }
+          { label }
+        </a>
+        if cloud.cloud_sync {
+          <a class={MENU_ITEM_CLASS} onclick={on_settings}>
+            { t("button.settings") }
+          </a>
+          <a class={MENU_ITEM_CLASS} onclick={on_stop}>
+            { t("button.stop") }
+          </a>
+        }
+        if *is_settings_open {
+          <CloudSyncSettings
+            google_client_id={google_client_id.clone()}
+            set_is_modal_open={set_is_settings_open}
+          />
+        }
+        if cloud.conflict.is_some() && !*conflict_dismissed {
+          <PopupModal
+            title={t("conflict.title")}
+            message={Some(t("conflict.message"))}
+            { set_is_modal_open }
+            cancel_button={false}
+          >
+            <div class="flex flex-wrap items-center justify-center gap-2 p-4">
+              <button class="btn btn-neutral" onclick={resolve(ConflictChoice::KeepLocal)}>
+                { t("conflict.keepLocal") }
+              </button>
+              <button class="btn btn-neutral" onclick={resolve(ConflictChoice::KeepRemote)}>
+                { t("conflict.keepRemote") }
+              </button>
+              <button class="btn btn-primary" onclick={resolve(ConflictChoice::Merge)}>
+                { t("conflict.merge") }
+              </button>
+            </div>
+          </PopupModal>
+        }
+      </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub(crate) struct CloudSyncSettingsProps {
+    pub(crate) google_client_id: Option<String>,
+    pub(crate) set_is_modal_open: Callback<bool>,
+}
+
+/// Picks the provider and its settings, then starts syncing.
+#[function_component]
+pub(crate) fn CloudSyncSettings(
+    CloudSyncSettingsProps { google_client_id, set_is_modal_open }: &CloudSyncSettingsProps,
+) -> Html {
+    let translate = use_translation(vec!["drive".to_string()]);
+    let t = move |key: &str| translate(key.to_string(), Some(Namespace { ns: "drive".to_string() }));
+    let (cloud, cloud_dispatch) = use_store::<CloudAuthSlice>();
+    let sync = use_cloud_sync();
+
+    let providers: Vec<SyncProvider> = SyncProvider::ALL
+        .into_iter()
+        .filter(|p| *p != SyncProvider::GoogleDrive || google_client_id.is_some())
+        .collect();
+    let provider = use_state(|| {
+        if providers.contains(&cloud.provider) {
+            cloud.provider
+        } else {
+            SyncProvider::WebDav
+        }
+    });
+    let webdav = use_state(|| cloud.webdav.clone());
+    let s3 = use_state(|| cloud.s3.clone());
+
+    let handle_save = {
+        let provider = provider.clone();
+        let webdav = webdav.clone();
+        let s3 = s3.clone();
+        let client_id = google_client_id.clone();
+        let set_is_modal_open = set_is_modal_open.clone();
+        move |_e| {
+            cloud_dispatch.reduce_mut(|c| {
+                c.configure(*provider, (*webdav).clone(), (*s3).clone());
+                c.cloud_sync = true;
+                c.sync_status = SyncStatus::Synced;
+            });
+            set_is_modal_open.emit(false);
+            match (*provider, &client_id) {
+                (SyncProvider::GoogleDrive, Some(client_id)) if cloud_dispatch.get().google_access_token.is_none() => {
+                    sync.sign_in(client_id)
+                }
+                _ => {
+                    let sync = sync.clone();
+                    spawn_local(async move { sync.sync().await });
+                }
+            }
+        }
+    };
+
+    let field = |label: &str, value: &str, secret: bool, on_change: Callback<String>| {
+        html! {
+          <div class="flex gap-2 items-center mb-4">
+            <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm">{ t(label) }</div>
+            <input
+              type={if secret { "password" } else { "text" }}
+              class={INPUT_CLASS}
+              value={value.to_string()}
+              onchange={move |e: Event| {
+                  let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+                  on_change.emit(input.value().trim().to_string());
+              }}
+            />
+          </div>
+        }
+    };
+    let set_webdav = |update: fn(&mut WebDavSettings, String)| {
+        let webdav = webdav.clone();
+        Callback::from(move |value: String| {
+            let mut settings = (*webdav).clone();
+            update(&mut settings, value);
+            webdav.set(settings);
+        })
+    };
+    let set_s3 = |update: fn(&mut S3Settings, String)| {
+        let s3 = s3.clone();
+        Callback::from(move |value: String| {
+            let mut settings = (*s3).clone();
+            update(&mut settings, value);
+            s3.set(settings);
+        })
+    };
+
+    html! {
+      <PopupModal
+        title={t("settings.title")}
+        {set_is_modal_open}
+        handle_confirm={handle_save}
+      >
+        <div class="p-6 border-b border-gray-200 dark:border-gray-600">
+          <div class="flex gap-2 items-center mb-4">
+            <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm">{ t("settings.provider") }</div>
+            <select
+              class={INPUT_CLASS}
+              onchange={
+                let provider = provider.clone();
+                move |e: Event| {
+                    if let Some(target) = e.target_dyn_into::<web_sys::HtmlSelectElement>()
+                        && let Some(kind) = SyncProvider::ALL.iter().find(|p| p.to_string() == target.value())
+                    {
+                        provider.set(*kind);
+                    }
+                }
+              }
+            >
+              {
+                providers.iter().map(|kind| html! {
+                  <option value={kind.to_string()} selected={*provider == *kind}>{kind.to_string()}</option>
+                }).collect::<Html>()
+              }
+            </select>
+          </div>
+
+          if *provider == SyncProvider::GoogleDrive {
+            <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm flex flex-col gap-3 leading-relaxed">
+              <p>{ t("notice") }</p>
+              <p>{ t("privacy") }</p>
+            </div>
+          }
+          if *provider == SyncProvider::WebDav {
+            { field("settings.webdav.url", &webdav.url, false, set_webdav(|s, v| s.url = v)) }
+            { field("settings.username", &webdav.username, false, set_webdav(|s, v| s.username = v)) }
+            { field("settings.password", &webdav.password, true, set_webdav(|s, v| s.password = v)) }
+            <div class="min-w-fit text-gray-500 dark:text-gray-300 text-sm">{ t("settings.webdav.hint") }</div>
+          }
+          if *provider == SyncProvider::S3 {
+            <div class="flex gap-2 items-center mb-4">
+              <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm">{ t("settings.s3.auth") }</div>
+              <select
+                class={INPUT_CLASS}
+                onchange={
+                  let s3 = s3.clone();
+                  move |e: Event| {
+                      if let Some(target) = e.target_dyn_into::<web_sys::HtmlSelectElement>()
+                          && let Some(auth) = S3Auth::ALL.iter().find(|a| a.to_string() == target.value())
+                      {
+                          s3.set(S3Settings { auth: *auth, ..(*s3).clone() });
+                      }
+                  }
+                }
+              >
+                {
+                  S3Auth::ALL.iter().map(|auth| html! {
+                    <option value={auth.to_string()} selected={s3.auth == *auth}>{auth.to_string()}</option>
+                  }).collect::<Html>()
+                }
+              </select>
+            </div>
+            if s3.auth == S3Auth::AccessKey {
+              { field("settings.s3.endpoint", &s3.endpoint, false, set_s3(|s, v| s.endpoint = v)) }
+              { field("settings.s3.region", &s3.region, false, set_s3(|s, v| s.region = v)) }
+              { field("settings.s3.bucket", &s3.bucket, false, set_s3(|s, v| s.bucket = v)) }
+              { field("settings.s3.key", &s3.key, false, set_s3(|s, v| s.key = v)) }
+              { field("settings.s3.accessKeyId", &s3.access_key_id, false, set_s3(|s, v| s.access_key_id = v)) }
+              { field("settings.s3.secretAccessKey", &s3.secret_access_key, true, set_s3(|s, v| s.secret_access_key = v)) }
+              <label class="flex gap-2 text-gray-900 dark:text-gray-300 text-sm items-center mb-4">
+                <input
+                  type="checkbox"
+                  checked={s3.path_style}
+                  class="w-4 h-4"
+                  onchange={
+                    let s3 = s3.clone();
+                    move |_e| s3.set(S3Settings { path_style: !s3.path_style, ..(*s3).clone() })
+                  }
+                />
+                { t("settings.s3.pathStyle") }
+              </label>
+            } else {
+              { field("settings.s3.getUrl", &s3.presigned_get_url, false, set_s3(|s, v| s.presigned_get_url = v)) }
+              { field("settings.s3.putUrl", &s3.presigned_put_url, false, set_s3(|s, v| s.presigned_put_url = v)) }
+            }
+            <div class="min-w-fit text-gray-500 dark:text-gray-300 text-sm">{ t("settings.s3.hint") }</div>
+          }
+        </div>
+      </PopupModal>
+    }
+}
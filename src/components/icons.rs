@@ -419,6 +419,27 @@ pub(crate) fn LinkIcon() -> Html {
     }
 }
 
+#[function_component]
+pub(crate) fn LockIcon() -> Html {
+    html! {
+      <svg
+        stroke="currentColor"
+        fill="none"
+        strokeWidth="2"
+        viewBox="0 0 24 24"
+        strokeLinecap="round"
+        strokeLinejoin="round"
+        className="h-4 w-4"
+        height="1em"
+        width="1em"
+        xmlns="http://www.w3.org/2000/svg"
+      >
+        <rect x="3" y="11" width="18" height="11" rx="2" ry="2"></rect>
+        <path d="M7 11V7a5 5 0 0 1 10 0v4"></path>
+      </svg>
+    }
+}
+
 #[function_component]
 pub(crate) fn LogoutIcon() -> Html {
     html! {
@@ -1,13 +1,18 @@
+use std::collections::HashSet;
 use std::fmt::Debug;
 
 use crate::components::icons::ExportIcon;
 use crate::components::popup_modal::PopupModal;
 use crate::components::toast::ToastStatus;
-use crate::hooks::translation::use_translation;
-use crate::store::{ChatSlice, ToastSlice};
-use crate::types::export::{ExportBase, ExportV1, OpenAIChat};
-use crate::utils::{download_file, get_today, import_openai_chat_export};
-use wasm_bindgen::{JsCast, JsValue};
+use crate::hooks::translation::{Namespace, use_translation};
+use crate::store::{ChatSlice, ConfigSlice, PromptSlice, ToastSlice, VaultSlice};
+use crate::types::export::{parse_export, plan_import, DuplicateStrategy, ExportV2, OpenAIChat};
+use crate::export::{export_chats, fine_tune_dataset, DocumentFormat, FineTuneDataset, FineTuneOptions};
+use crate::types::chat::{ChatInterface, Folder};
+use crate::utils::{download_bytes, download_file, get_today, import_openai_chat_export};
+use crate::vault::{self, SealedFile, VaultError, WebCryptoPassphrase};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
 use web_sys::{FileReader, HtmlInputElement, ProgressEvent};
 use yew::prelude::*;
 use yewdux::use_store;
@@ -26,7 +31,10 @@ pub(crate) fn ImportExportChat() -> Html {
     <>
       <a
         class="flex py-2 px-2 items-center gap-3 rounded-md hover:bg-gray-500/10 transition-colors duration-200 text-white cursor-pointer text-sm"
-        onclick={|e| {} }
+        onclick={
+          let set_is_modal_open = set_is_modal_open.clone();
+          move |_| set_is_modal_open.emit(true)
+        }
       >
         <ExportIcon />
         {format!("{} / {}", translate("import".to_string(), None), translate("export".to_string(), None))}
@@ -40,6 +48,7 @@ pub(crate) fn ImportExportChat() -> Html {
           <div class="p-6 border-b border-gray-200 dark:border-gray-600">
             <ImportChat />
             <ExportChat />
+            <FineTuneExport />
             <div class="border-t my-3 border-gray-200 dark:border-gray-600" />
             <ImportChatOpenAI {set_is_modal_open} />
           </div>
@@ -54,26 +63,64 @@ struct Alert {
   success: bool,
 }
 
-use serde_wasm_bindgen::from_value;
-fn deserialize_js_value<T: serde::de::DeserializeOwned>(
-  js_value: JsValue,
-) -> Result<T, serde_wasm_bindgen::Error> {
-  from_value::<T>(js_value)
-}
-
+/// Reads a JSON export, previews how it merges into the current chats and
+/// applies it once confirmed. Encrypted exports are opened with the vault,
+/// or with a passphrase asked for when that doesn't fit.
 #[function_component]
 pub(crate) fn ImportChat() -> Html {
   let translate = use_translation(vec![]);
+  let vault_translate = use_translation(vec!["vault".to_string()]);
+  let tv = move |key: &str| vault_translate(key.to_string(), Some(Namespace { ns: "vault".to_string() }));
   let alert = use_state(|| None::<Alert>);
+  let parsed = use_state(|| None::<ExportV2>);
+  let sealed = use_state(|| None::<SealedFile>);
+  let passphrase = use_state(String::new);
+  let (vault_store, _) = use_store::<VaultSlice>();
+  let strategy = use_state(DuplicateStrategy::default);
+  let import_settings = use_state(|| false);
   let input_ref = use_node_ref();
   let (store, dispatch) = use_store::<ChatSlice>();
+  let (prompt_store, prompt_dispatch) = use_store::<PromptSlice>();
+  let (_, config_dispatch) = use_store::<ConfigSlice>();
 
-  let handle_file_upload = {
-    let input_ref = input_ref.clone();
-    let store = store.clone();
+  let accept = {
     let alert = alert.clone();
-    let dispatch = dispatch.clone();
-    move |_e| {
+    let parsed = parsed.clone();
+    Callback::from(move |text: String| match parse_export(&text) {
+      Ok(export) => {
+        alert.set(None);
+        parsed.set(Some(export));
+      }
+      Err(e) => {
+        parsed.set(None);
+        alert.set(Some(Alert {
+          message: e.to_string(),
+          success: false,
+        }));
+      }
+    })
+  };
+  let fail = {
+    let alert = alert.clone();
+    let tv = tv.clone();
+    Callback::from(move |e: VaultError| {
+      alert.set(Some(Alert {
+        message: match e {
+          VaultError::WrongPassphrase => tv("import.wrong"),
+          e => e.to_string(),
+        },
+        success: false,
+      }));
+    })
+  };
+
+  let handle_file_change = {
+    let input_ref = input_ref.clone();
+    let parsed = parsed.clone();
+    let sealed = sealed.clone();
+    let accept = accept.clone();
+    let vault = vault_store.vault.clone();
+    move |_: Event| {
       let input = input_ref.cast::<web_sys::HtmlInputElement>().unwrap();
       if input.files().is_none() {
         return;
@@ -83,52 +130,34 @@ pub(crate) fn ImportChat() -> Html {
         let reader = FileReader::new().unwrap();
         let onload = {
           let reader = reader.clone();
-          let alert = alert.clone();
-          let store = store.clone();
-          let dispatch = dispatch.clone();
-          wasm_bindgen::closure::Closure::wrap(Box::new(move |e: web_sys::ProgressEvent| {
-            let parsed_data = deserialize_js_value::<ExportV1>(reader.result().unwrap());
-            if let Err(e) = parsed_data {
-              alert.set(Some(Alert {
-                message: format!("Parse error: {}", e),
-                success: false,
-              }));
+          let parsed = parsed.clone();
+          let sealed = sealed.clone();
+          let accept = accept.clone();
+          let vault = vault.clone();
+          wasm_bindgen::closure::Closure::wrap(Box::new(move |_: web_sys::ProgressEvent| {
+            let text = reader.result().ok().and_then(|r| r.as_string()).unwrap_or_default();
+            let Some(file) = SealedFile::parse(&text) else {
+              sealed.set(None);
+              accept.emit(text);
               return;
-            }
-            let parsed_data = parsed_data.ok().unwrap();
-            match parsed_data.base.version {
-              1 => {
-                let mut current_folders = store.folders.clone();
-                let offset = parsed_data.folders.len() as i32;
-
-                for folder in current_folders.values_mut() {
-                  folder.order += offset;
+            };
+            parsed.set(None);
+            let Some(vault) = vault.clone() else {
+              sealed.set(Some(file));
+              return;
+            };
+            // Sealed with the vault's passphrase, most likely.
+            let sealed = sealed.clone();
+            let accept = accept.clone();
+            spawn_local(async move {
+              match vault.open_file(&file).await {
+                Ok(text) => {
+                  sealed.set(None);
+                  accept.emit(text);
                 }
-
-                let merged_folders = parsed_data
-                  .folders
-                  .into_iter()
-                  .chain(current_folders)
-                  .collect();
-                dispatch.reduce_mut(|d| d.folders = merged_folders);
-
-                // Merge chats
-                let current_chats = store.chats.clone();
-                let merged_chats = parsed_data.chats.into_iter().chain(current_chats).collect();
-                dispatch.reduce_mut(|f| f.chats = merged_chats);
-
-                alert.set(Some(Alert {
-                  message: "Successfully imported!".into(),
-                  success: true,
-                }));
+                Err(_) => sealed.set(Some(file)),
               }
-              _ => {
-                alert.set(Some(Alert {
-                  message: "Unsupported version".into(),
-                  success: false,
-                }));
-              }
-            }
+            });
           })
             as Box<dyn FnMut(web_sys::ProgressEvent)>)
         };
@@ -136,9 +165,90 @@ pub(crate) fn ImportChat() -> Html {
         reader.set_onload(Some(onload.as_ref().unchecked_ref()));
         let _ = reader.read_as_text(&file);
         onload.forget();
+      }
+    }
+  };
 
-        let _ = reader.read_as_text(&file);
+  let handle_decrypt = {
+    let sealed = sealed.clone();
+    let passphrase = passphrase.clone();
+    move |_| {
+      let Some(file) = (*sealed).clone() else {
+        return;
+      };
+      let passphrase = passphrase.clone();
+      let sealed = sealed.clone();
+      let accept = accept.clone();
+      let fail = fail.clone();
+      spawn_local(async move {
+        let opened = match WebCryptoPassphrase::import(&passphrase).await {
+          Ok(key) => vault::open_file(&file, key.as_ref()).await,
+          Err(e) => Err(e),
+        };
+        match opened {
+          Ok(text) => {
+            sealed.set(None);
+            passphrase.set(String::new());
+            accept.emit(text);
+          }
+          Err(e) => fail.emit(e),
+        }
+      });
+    }
+  };
+
+  let plan = parsed
+    .as_ref()
+    .map(|export| plan_import(export, &store.chats, &store.folders, &prompt_store.prompts, *strategy));
+
+  let handle_import = {
+    let alert = alert.clone();
+    let parsed = parsed.clone();
+    let strategy = *strategy;
+    let import_settings = *import_settings;
+    move |_| {
+      let Some(export) = (*parsed).clone() else {
+        return;
+      };
+      // Planned again against the store as it is now, not as it was previewed.
+      dispatch.reduce_mut(|d| {
+        let prompts = prompt_dispatch.get().prompts.clone();
+        let plan = plan_import(&export, &d.chats, &d.folders, &prompts, strategy);
+        let current_id = d.chats.get(d.curr_chat_index as usize).map(|c| c.id.clone());
+        d.chats = plan.chats;
+        d.folders = plan.folders;
+        if let Some(index) = current_id.and_then(|id| d.chats.iter().position(|c| c.id == id)) {
+          d.curr_chat_index = index as i32;
+        }
+        prompt_dispatch.reduce_mut(|p| p.prompts = plan.prompts);
+      });
+      if import_settings {
+        config_dispatch.reduce_mut(|c| {
+          if let Some(settings) = export.settings.clone() {
+            c.import_settings(settings);
+          }
+          if !export.total_token_used.is_empty() {
+            c.total_token_used = export.total_token_used.clone();
+          }
+        });
       }
+      parsed.set(None);
+      alert.set(Some(Alert {
+        message: "Successfully imported!".into(),
+        success: true,
+      }));
+    }
+  };
+
+  let preview_section = |key: &str, items: &[String]| {
+    if items.is_empty() {
+      return html! {};
+    }
+    html! {
+      <div class="mt-1">
+        <span class="font-medium">{format!("{} ({}): ", translate(format!("importPreview.{}", key), None), items.len())}</span>
+        {items.join(", ")}
+      </div>
     }
   };
 
@@ -150,15 +260,99 @@ pub(crate) fn ImportChat() -> Html {
     <input
       class="w-full text-sm file:p-2 text-gray-800 file:text-gray-700 dark:text-gray-300 dark:file:text-gray-200 rounded-md cursor-pointer focus:outline-none bg-gray-50 file:bg-gray-100 dark:bg-gray-800 dark:file:bg-gray-700 file:border-0 border border-gray-300 dark:border-gray-600 placeholder-gray-900 dark:placeholder-gray-300 file:cursor-pointer"
       type="file"
+      accept=".json,application/json"
       ref={input_ref}
+      onchange={handle_file_change}
     />
-    <button
-      class="btn btn-small btn-primary mt-3"
-      onclick={handle_file_upload}
-      aria-label={translate("import".to_string(), None)}
-    >
-      { translate("import".to_string(), None) }
-    </button>
+    if sealed.is_some() {
+      <div class="mt-3 text-sm text-gray-900 dark:text-gray-300">
+        {tv("import.message")}
+        <div class="flex gap-2 items-center mt-2">
+          <input
+            type="password"
+            class="w-full text-sm p-1 rounded-md text-gray-800 dark:text-white bg-gray-50 dark:bg-gray-800 border border-gray-300 dark:border-gray-600"
+            placeholder={tv("import.passphrase")}
+            value={(*passphrase).clone()}
+            oninput={
+              let passphrase = passphrase.clone();
+              move |e: InputEvent| {
+                let input = e.target_unchecked_into::<HtmlInputElement>();
+                passphrase.set(input.value());
+              }
+            }
+          />
+          <button
+            class="btn btn-small btn-primary"
+            onclick={handle_decrypt}
+            disabled={passphrase.is_empty()}
+          >
+            {tv("import.decrypt")}
+          </button>
+        </div>
+      </div>
+    }
+    if let (Some(export), Some(plan)) = (&*parsed, &plan) {
+      <div class="mt-3 text-sm text-gray-900 dark:text-gray-300">
+        <label class="flex gap-2 items-center">
+          {translate("importPreview.duplicates".to_string(), None)}
+          <select
+            class="text-sm p-1 rounded-md text-gray-800 dark:text-white bg-gray-50 dark:bg-gray-800 border border-gray-300 dark:border-gray-600"
+            onchange={
+              let strategy = strategy.clone();
+              move |e: Event| {
+                if let Some(target) = e.target_dyn_into::<web_sys::HtmlSelectElement>()
+                  && let Some(s) = DuplicateStrategy::ALL.iter().find(|s| s.to_string() == target.value())
+                {
+                  strategy.set(*s);
+                }
+              }
+            }
+          >
+            {
+              DuplicateStrategy::ALL.iter().map(|s| html! {
+                <option value={s.to_string()} selected={*strategy == *s}>
+                  {translate(format!("importPreview.strategy.{}", s), None)}
+                </option>
+              }).collect::<Html>()
+            }
+          </select>
+        </label>
+        if export.settings.is_some() || !export.total_token_used.is_empty() {
+          <label class="flex gap-2 items-center mt-1">
+            <input
+              type="checkbox"
+              class="w-4 h-4"
+              checked={*import_settings}
+              onchange={
+                let import_settings = import_settings.clone();
+                move |_: Event| import_settings.set(!*import_settings)
+              }
+            />
+            {translate("importPreview.importSettings".to_string(), None)}
+          </label>
+        }
+        <div class="mt-2 max-h-40 overflow-y-auto">
+          {preview_section("added", &plan.added)}
+          {preview_section("overwritten", &plan.overwritten)}
+          {preview_section("keptBoth", &plan.kept_both)}
+          {preview_section("skipped", &plan.skipped)}
+          {preview_section("unchanged", &plan.unchanged)}
+          {preview_section("newFolders", &plan.new_folders)}
+          {preview_section("remappedFolders", &plan.remapped_folders)}
+          if plan.new_prompts > 0 {
+            <div class="mt-1">{format!("{}: {}", translate("importPreview.newPrompts".to_string(), None), plan.new_prompts)}</div>
+          }
+          {preview_section("warnings", &plan.warnings)}
+        </div>
+      </div>
+      <button
+        class="btn btn-small btn-primary mt-3"
+        onclick={handle_import}
+        aria-label={translate("import".to_string(), None)}
+      >
+        { translate("import".to_string(), None) }
+      </button>
+    }
     if alert.is_some() {
         <div
             class={classes!("relative", "py-2", "px-3", "w-full", "mt-3", "border", "rounded-md", "text-gray-600", "dark:text-gray-100", "text-sm", "whitespace-pre-wrap", if alert.as_ref().unwrap().success {"border-green-500 bg-green-500/10"} else {"border-red-500 bg-red-500/10"}) }
@@ -173,19 +367,71 @@ pub(crate) fn ImportChat() -> Html {
 #[function_component]
 pub(crate) fn ExportChat() -> Html {
   let translate = use_translation(vec![]);
+  let vault_translate = use_translation(vec!["vault".to_string()]);
   let (store, _) = use_store::<ChatSlice>();
+  let (prompt_store, _) = use_store::<PromptSlice>();
+  let (config, _) = use_store::<ConfigSlice>();
+  let (vault_store, _) = use_store::<VaultSlice>();
+  let scope = use_state(|| ExportScope::Current);
+  let encrypt = use_state(|| true);
 
   let on_click = {
     let store = store.clone();
+    let vault = vault_store.vault.clone().filter(|_| *encrypt);
     move |_| {
-      let file_data = ExportV1 {
-        chats: store.chats.clone(),
-        folders: store.folders.clone(),
-        base: ExportBase { version: 1 },
+      let file_data = ExportV2 {
+        prompts: prompt_store.prompts.clone(),
+        settings: Some(config.export_settings()),
+        total_token_used: config.total_token_used.clone(),
+        ..ExportV2::new(store.chats.clone(), store.folders.clone())
       };
-      download_file(&file_data, get_today());
+      let Some(vault) = vault.clone() else {
+        download_file(&file_data, get_today());
+        return;
+      };
+      spawn_local(async move {
+        let sealed = match serde_json::to_string(&file_data) {
+          Ok(json) => vault.seal_file(&json).await,
+          Err(e) => Err(e.into()),
+        };
+        match sealed {
+          Ok(file) => download_bytes(file.as_bytes(), "application/json", format!("{}.json", get_today())),
+          Err(e) => log::error!("Could not encrypt the export: {}", e),
+        }
+      });
+    }
+  };
+  let on_scope_change = {
+    let scope = scope.clone();
+    move |e: Event| {
+      if let Some(target) = e.target_dyn_into::<web_sys::HtmlSelectElement>() {
+        scope.set(match target.value().as_str() {
+          "current" => ExportScope::Current,
+          "all" => ExportScope::All,
+          folder => ExportScope::Folder(folder.to_string()),
+        });
+      }
+    }
+  };
+  let export_document = |format: DocumentFormat| {
+    let store = store.clone();
+    let scope = scope.clone();
+    move |_| {
+      let chats: Vec<&ChatInterface> = match &*scope {
+        ExportScope::Current => store.chats.get(store.curr_chat_index as usize).into_iter().collect(),
+        ExportScope::Folder(folder) => store.chats.iter().filter(|c| c.folder.as_ref() == Some(folder)).collect(),
+        ExportScope::All => store.chats.iter().collect(),
+      };
+      if chats.is_empty() {
+        return;
+      }
+      let file = export_chats(&chats, &store.folders, format, chrono::Local::now().naive_local());
+      download_bytes(&file.data, file.mime, file.name);
     }
   };
+  let mut folders: Vec<&Folder> = store.folders.values().collect();
+  folders.sort_by_key(|f| f.order);
+
   html! {
     <div class="mt-6">
       <div class="block mb-2 text-sm font-medium text-gray-900 dark:text-gray-300">
@@ -198,6 +444,196 @@ pub(crate) fn ExportChat() -> Html {
       >
         {translate("export".to_string(), None)}
       </button>
+      if vault_store.vault.is_some() {
+        <label class="flex gap-2 text-gray-900 dark:text-gray-300 text-sm items-center mt-2">
+          <input
+            type="checkbox"
+            class="w-4 h-4"
+            checked={*encrypt}
+            onchange={
+              let encrypt = encrypt.clone();
+              move |_: Event| encrypt.set(!*encrypt)
+            }
+          />
+          {vault_translate("export.encrypt".to_string(), Some(Namespace { ns: "vault".to_string() }))}
+        </label>
+      }
+      <div class="block mt-6 mb-2 text-sm font-medium text-gray-900 dark:text-gray-300">
+        {format!("{} ({})", translate("export".to_string(), None), DocumentFormat::ALL.map(|f| f.to_string()).join(" / "))}
+      </div>
+      <select
+        class="w-full mb-3 text-sm p-2 rounded-md text-gray-800 dark:text-white bg-gray-50 dark:bg-gray-800 border border-gray-300 dark:border-gray-600"
+        onchange={on_scope_change}
+      >
+        <option value="current" selected={*scope == ExportScope::Current}>{translate("exportCurrentChat".to_string(), None)}</option>
+        {
+          folders.iter().map(|folder| html! {
+            <option value={folder.id.clone()} selected={*scope == ExportScope::Folder(folder.id.clone())}>
+              {format!("{}: {}", translate("exportFolder".to_string(), None), folder.name)}
+            </option>
+          }).collect::<Html>()
+        }
+        <option value="all" selected={*scope == ExportScope::All}>{translate("exportAllChats".to_string(), None)}</option>
+      </select>
+      <div class="flex gap-2">
+        {
+          DocumentFormat::ALL.iter().map(|format| html! {
+            <button
+              class="btn btn-small btn-primary"
+              onclick={export_document(*format)}
+              aria-label={format!("{} {}", translate("export".to_string(), None), format)}
+            >
+              {format.to_string()}
+            </button>
+          }).collect::<Html>()
+        }
+      </div>
+    </div>
+  }
+}
+
+/// Which chats the Markdown/HTML export covers.
+#[derive(Debug, Clone, PartialEq)]
+enum ExportScope {
+  Current,
+  /// All chats in the folder with this id.
+  Folder(String),
+  All,
+}
+
+/// Exports the chosen chats as an OpenAI fine-tuning dataset and lists the
+/// examples that had to be left out.
+#[function_component]
+pub(crate) fn FineTuneExport() -> Html {
+  let translate = use_translation(vec![]);
+  let (store, _) = use_store::<ChatSlice>();
+  let folder = use_state(|| None::<String>);
+  // Chats are included unless unticked.
+  let excluded = use_state(HashSet::<String>::new);
+  let options = use_state(FineTuneOptions::default);
+  let report = use_state(|| None::<FineTuneDataset>);
+
+  let chats: Vec<&ChatInterface> = store
+    .chats
+    .iter()
+    .filter(|c| folder.is_none() || c.folder == *folder)
+    .collect();
+  let mut folders: Vec<&Folder> = store.folders.values().collect();
+  folders.sort_by_key(|f| f.order);
+
+  let on_folder_change = {
+    let folder = folder.clone();
+    move |e: Event| {
+      if let Some(target) = e.target_dyn_into::<web_sys::HtmlSelectElement>() {
+        folder.set(Some(target.value()).filter(|v| !v.is_empty()));
+      }
+    }
+  };
+  let toggle_chat = |id: String| {
+    let excluded = excluded.clone();
+    move |_: Event| {
+      let mut next = (*excluded).clone();
+      if !next.remove(&id) {
+        next.insert(id.clone());
+      }
+      excluded.set(next);
+    }
+  };
+  let toggle_option = |update: fn(&mut FineTuneOptions)| {
+    let options = options.clone();
+    move |_: Event| {
+      let mut next = (*options).clone();
+      update(&mut next);
+      options.set(next);
+    }
+  };
+  let on_limit_change = {
+    let options = options.clone();
+    move |e: Event| {
+      if let Some(target) = e.target_dyn_into::<HtmlInputElement>()
+        && let Ok(token_limit) = target.value().parse()
+      {
+        options.set(FineTuneOptions { token_limit, ..(*options).clone() });
+      }
+    }
+  };
+  let on_export = {
+    let selected: Vec<ChatInterface> = chats.iter().filter(|c| !excluded.contains(&c.id)).map(|c| (*c).clone()).collect();
+    let options = options.clone();
+    let report = report.clone();
+    move |_| {
+      let dataset = fine_tune_dataset(&selected.iter().collect::<Vec<_>>(), &options);
+      if dataset.examples > 0 {
+        download_bytes(dataset.jsonl.as_bytes(), "application/jsonl", format!("fine-tune-{}.jsonl", get_today()));
+      }
+      report.set(Some(dataset));
+    }
+  };
+
+  html! {
+    <div class="mt-6">
+      <div class="block mb-2 text-sm font-medium text-gray-900 dark:text-gray-300">
+        {translate("fineTune.title".to_string(), None)}
+      </div>
+      <select
+        class="w-full mb-2 text-sm p-2 rounded-md text-gray-800 dark:text-white bg-gray-50 dark:bg-gray-800 border border-gray-300 dark:border-gray-600"
+        onchange={on_folder_change}
+      >
+        <option value="" selected={folder.is_none()}>{translate("exportAllChats".to_string(), None)}</option>
+        {
+          folders.iter().map(|f| html! {
+            <option value={f.id.clone()} selected={*folder == Some(f.id.clone())}>
+              {format!("{}: {}", translate("exportFolder".to_string(), None), f.name)}
+            </option>
+          }).collect::<Html>()
+        }
+      </select>
+      <div class="max-h-32 overflow-y-auto mb-2 text-sm text-gray-900 dark:text-gray-300">
+        {
+          chats.iter().map(|chat| html! {
+            <label class="flex gap-2 items-center">
+              <input type="checkbox" class="w-4 h-4" checked={!excluded.contains(&chat.id)} onchange={toggle_chat(chat.id.clone())} />
+              {chat.title.clone().unwrap_or_default()}
+            </label>
+          }).collect::<Html>()
+        }
+      </div>
+      <label class="flex gap-2 text-gray-900 dark:text-gray-300 text-sm items-center">
+        <input type="checkbox" class="w-4 h-4" checked={options.drop_system} onchange={toggle_option(|o| o.drop_system = !o.drop_system)} />
+        {translate("fineTune.dropSystem".to_string(), None)}
+      </label>
+      <label class="flex gap-2 text-gray-900 dark:text-gray-300 text-sm items-center">
+        <input type="checkbox" class="w-4 h-4" checked={options.active_branch_only} onchange={toggle_option(|o| o.active_branch_only = !o.active_branch_only)} />
+        {translate("fineTune.activeBranchOnly".to_string(), None)}
+      </label>
+      <label class="flex gap-2 text-gray-900 dark:text-gray-300 text-sm items-center mt-2">
+        {translate("fineTune.tokenLimit".to_string(), None)}
+        <input
+          type="number"
+          min="1"
+          class="w-28 p-1 rounded-md text-gray-800 dark:text-white bg-gray-50 dark:bg-gray-800 border border-gray-300 dark:border-gray-600"
+          value={options.token_limit.to_string()}
+          onchange={on_limit_change}
+        />
+      </label>
+      <button
+        class="btn btn-small btn-primary mt-3"
+        onclick={on_export}
+        aria-label={translate("fineTune.title".to_string(), None)}
+      >
+        {translate("export".to_string(), None)}
+      </button>
+      if let Some(report) = &*report {
+        <div class="mt-3 text-sm text-gray-900 dark:text-gray-300">
+          {format!("{}: {}", translate("fineTune.examples".to_string(), None), report.examples)}
+          if !report.skipped.is_empty() {
+            <div class="mt-1">{format!("{}:", translate("fineTune.skipped".to_string(), None))}</div>
+            <ul class="max-h-40 overflow-y-auto list-disc pl-5 text-yellow-700 dark:text-yellow-400">
+              { for report.skipped.iter().map(|s| html! { <li>{s.to_string()}</li> }) }
+            </ul>
+          }
+        </div>
+      }
     </div>
   }
 }
@@ -211,11 +647,18 @@ pub(crate) struct ChatOpenAIProps {
 pub(crate) fn ImportChatOpenAI(ChatOpenAIProps { set_is_modal_open }: &ChatOpenAIProps) -> Html {
   let translate = use_translation(vec![]);
   let input_ref = use_node_ref();
+  let keep_branches = use_state(|| true);
+  let warnings = use_state(Vec::<String>::new);
   let (_toast, toast_dispatch) = use_store::<ToastSlice>();
   let (_, chat_dispatch) = use_store::<ChatSlice>();
+  let toggle_branches = {
+    let keep_branches = keep_branches.clone();
+    Callback::from(move |_: Event| keep_branches.set(!*keep_branches))
+  };
   let handle_file_upload = {
     let input_ref = input_ref.clone();
-    // let toast = toast.clone();
+    let keep_branches = *keep_branches;
+    let warnings = warnings.clone();
     let toast_dispatch = toast_dispatch.clone();
     let chat_dispatch = chat_dispatch.clone();
     let set_is_modal_open = set_is_modal_open.clone();
@@ -223,6 +666,7 @@ pub(crate) fn ImportChatOpenAI(ChatOpenAIProps { set_is_modal_open }: &ChatOpenA
       let input_element = input_ref.cast::<HtmlInputElement>();
       let toast_dispatch = toast_dispatch.clone();
       let chat_dispatch = chat_dispatch.clone();
+      let warnings = warnings.clone();
       let reader: FileReader = web_sys::FileReader::new().unwrap();
       if input_element.is_none() {
         return;
@@ -235,28 +679,40 @@ pub(crate) fn ImportChatOpenAI(ChatOpenAIProps { set_is_modal_open }: &ChatOpenA
       }
       let file = file.unwrap();
       let onload = {
-        wasm_bindgen::closure::Closure::wrap(Box::new(move |e: ProgressEvent| {
-          let parsed_data = deserialize_js_value::<Vec<OpenAIChat>>(file_reader.result().unwrap());
+        wasm_bindgen::closure::Closure::wrap(Box::new(move |_: ProgressEvent| {
+          let text = file_reader.result().ok().and_then(|r| r.as_string()).unwrap_or_default();
+          let parsed_data = match serde_json::from_str::<Vec<OpenAIChat>>(&text) {
+            Ok(parsed_data) => parsed_data,
+            Err(e) => {
+              log::error!("Invalid ChatGPT export: {}", e);
+              toast_dispatch.reduce_mut(|d| {
+                d.status = ToastStatus::Error;
+                d.message = "Invalid format!".to_string();
+                d.show = true;
+              });
+              return;
+            }
+          };
+          let import = import_openai_chat_export(parsed_data, keep_branches);
+          let imported = import.chats.len();
+          chat_dispatch.reduce_mut(|c| c.chats.extend(import.chats));
 
-          if let Err(e) = parsed_data {
-            log::error!("Error:");
+          if import.warnings.is_empty() {
             toast_dispatch.reduce_mut(|d| {
-              d.status = ToastStatus::Error;
-              d.message = "Invalid format!".to_string();
+              d.status = ToastStatus::Success;
+              d.message = "Imported successfully!".to_string();
               d.show = true;
             });
-
-            return;
+            set_is_modal_open.emit(false);
+          } else {
+            // Keep the dialog open so the warnings can be read.
+            toast_dispatch.reduce_mut(|d| {
+              d.status = ToastStatus::Warning;
+              d.message = format!("Imported {} chats with {} warnings", imported, import.warnings.len());
+              d.show = true;
+            });
+            warnings.set(import.warnings);
           }
-          let chats = import_openai_chat_export(parsed_data.unwrap());
-          chat_dispatch.reduce_mut(|c| c.chats.extend_from_slice(&chats));
-
-          toast_dispatch.reduce_mut(|d| {
-            d.status = ToastStatus::Success;
-            d.message = "Imported successfully!".to_string();
-            d.show = true;
-          });
-          set_is_modal_open.emit(false);
         }) as Box<dyn FnMut(ProgressEvent)>)
       };
       reader.set_onload(Some(onload.as_ref().unchecked_ref()));
@@ -278,6 +734,10 @@ pub(crate) fn ImportChatOpenAI(ChatOpenAIProps { set_is_modal_open }: &ChatOpenA
       type="file"
       ref={input_ref}
     />
+    <label class="flex gap-2 text-gray-900 dark:text-gray-300 text-sm items-center mt-3">
+      <input type="checkbox" class="w-4 h-4" checked={*keep_branches} onchange={toggle_branches} />
+      {translate("keepBranches".to_string(), None)}
+    </label>
     <button
       class="btn btn-small btn-primary mt-3"
       onclick={ handle_file_upload }
@@ -285,6 +745,11 @@ pub(crate) fn ImportChatOpenAI(ChatOpenAIProps { set_is_modal_open }: &ChatOpenA
     >
       { translate("import".to_string(), None) }
     </button>
+    if !warnings.is_empty() {
+      <ul class="mt-3 max-h-40 overflow-y-auto list-disc pl-5 text-sm text-yellow-700 dark:text-yellow-400">
+        { for warnings.iter().map(|w| html! { <li>{w}</li> }) }
+      </ul>
+    }
   </>
   }
 }
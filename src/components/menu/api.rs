@@ -1,15 +1,19 @@
+use std::collections::BTreeMap;
+
 use yew::prelude::*;
 use yewdux::use_store;
 
 use crate::{
+    api::parse_header,
     components::{
-        icons::{DownChevronArrow, PersonIcon},
+        icons::{CrossIcon, DownChevronArrow, PersonIcon},
         popup_modal::PopupModal,
         trans::Trans,
     }, constants::AVAILABLE_ENDPOINTS, hooks::{
         hide_on_outside::use_hideon_outside_click,
         translation::{use_translation, Namespace},
-    }, store::AuthSlice
+    }, store::AuthSlice,
+    types::api::{ApiProfile, AzureAuth, AzureSettings, ProviderKind, AZURE_API_VERSION, OFFICIAL_APIENDPOINT},
 };
 
 #[function_component]
@@ -48,25 +52,171 @@ pub struct ApiMenuProps {
 }
 
 pub const DEFAULT_API_ENDPOINT: &str = "";
+
+/// Replaces the stored copy of `profile` (matched by id) with the edited one.
+fn commit_profile(profiles: &[ApiProfile], profile: ApiProfile) -> Vec<ApiProfile> {
+    profiles
+        .iter()
+        .map(|p| if p.id == profile.id { profile.clone() } else { p.clone() })
+        .collect()
+}
+
 #[function_component]
 pub fn ApiMenu(ApiMenuProps { set_is_modal_open }: &ApiMenuProps) -> Html {
     let t = use_translation(vec!["main".to_string(), "api".to_string()]);
-    let (_state, state_dispatch) = use_store::<AuthSlice>();
-    let api_key = use_state(|| "".to_string());
-    let api_endpoint = use_state(|| "".to_string());
-    let custom_endpoint = use_state(|| false);
+    let (state, state_dispatch) = use_store::<AuthSlice>();
+    let initial = state.profile(None);
 
-    let handle_save = {
+    // Profiles are edited on a local copy and only written back on save. The
+    // form fields below always describe the profile with id `profile_id`.
+    let profiles = use_state(|| state.profiles.clone());
+    let default_profile = use_state(|| state.default_profile.clone());
+    let profile_id = use_state(|| initial.id.clone());
+    let name = use_state(|| initial.name.clone());
+    let api_key = use_state(|| initial.api_key.clone().unwrap_or_default());
+    let api_endpoint = use_state(|| initial.endpoint.clone());
+    let provider = use_state(|| initial.provider.clone());
+    let default_model = use_state(|| initial.default_model.clone().unwrap_or_default());
+    let custom_headers = use_state(|| initial.custom_headers.clone());
+    let azure_api_version = use_state(|| initial.azure.api_version.clone());
+    let azure_auth = use_state(|| initial.azure.auth);
+    let azure_deployments = use_state(|| initial.azure.deployments.clone());
+    let custom_endpoint = use_state(|| !AVAILABLE_ENDPOINTS.contains(&initial.endpoint.as_str()));
+
+    // The profile as currently shown in the form.
+    let current = {
+        let profiles = profiles.clone();
+        let profile_id = profile_id.clone();
+        let name = name.clone();
         let api_key = api_key.clone();
         let api_endpoint = api_endpoint.clone();
+        let provider = provider.clone();
+        let default_model = default_model.clone();
+        let custom_headers = custom_headers.clone();
+        let azure_api_version = azure_api_version.clone();
+        let azure_auth = azure_auth.clone();
+        let azure_deployments = azure_deployments.clone();
+        move || {
+            let mut profile = profiles
+                .iter()
+                .find(|p| p.id == *profile_id)
+                .cloned()
+                .unwrap_or_default();
+            profile.name = (*name).clone();
+            profile.api_key = Some((*api_key).clone()).filter(|k| !k.is_empty());
+            profile.endpoint = (*api_endpoint).clone();
+            profile.provider = (*provider).clone();
+            profile.default_model = Some((*default_model).clone()).filter(|m| !m.is_empty());
+            profile.custom_headers = (*custom_headers).clone();
+            profile.azure = AzureSettings {
+                deployments: (*azure_deployments).clone(),
+                api_version: (*azure_api_version).clone(),
+                auth: *azure_auth,
+            };
+            profile
+        }
+    };
+
+    let load = {
+        let profile_id = profile_id.clone();
+        let name = name.clone();
+        let api_key = api_key.clone();
+        let api_endpoint = api_endpoint.clone();
+        let provider = provider.clone();
+        let default_model = default_model.clone();
+        let custom_headers = custom_headers.clone();
+        let azure_api_version = azure_api_version.clone();
+        let azure_auth = azure_auth.clone();
+        let azure_deployments = azure_deployments.clone();
+        let custom_endpoint = custom_endpoint.clone();
+        move |profile: &ApiProfile| {
+            profile_id.set(profile.id.clone());
+            name.set(profile.name.clone());
+            api_key.set(profile.api_key.clone().unwrap_or_default());
+            api_endpoint.set(profile.endpoint.clone());
+            provider.set(profile.provider.clone());
+            default_model.set(profile.default_model.clone().unwrap_or_default());
+            custom_headers.set(profile.custom_headers.clone());
+            azure_api_version.set(profile.azure.api_version.clone());
+            azure_auth.set(profile.azure.auth);
+            azure_deployments.set(profile.azure.deployments.clone());
+            custom_endpoint.set(!AVAILABLE_ENDPOINTS.contains(&profile.endpoint.as_str()));
+        }
+    };
+
+    let select_profile = {
+        let profiles = profiles.clone();
+        let current = current.clone();
+        let load = load.clone();
+        Callback::from(move |id: Option<String>| {
+            let edited = commit_profile(&profiles, current());
+            if let Some(profile) = edited.iter().find(|p| Some(&p.id) == id.as_ref()) {
+                load(profile);
+            }
+            profiles.set(edited);
+        })
+    };
+
+    let new_profile = {
+        let profiles = profiles.clone();
+        let current = current.clone();
+        let load = load.clone();
+        let t = t.clone();
+        move |_e| {
+            let mut edited = commit_profile(&profiles, current());
+            let profile = ApiProfile::new(
+                t("profile.untitled".to_string(), Some(Namespace { ns: "api".to_string() })),
+                OFFICIAL_APIENDPOINT.to_string(),
+                None,
+            );
+            load(&profile);
+            edited.push(profile);
+            profiles.set(edited);
+        }
+    };
+
+    let delete_profile = {
+        let profiles = profiles.clone();
+        let default_profile = default_profile.clone();
+        let profile_id = profile_id.clone();
+        let load = load.clone();
+        move |_e| {
+            if profiles.len() <= 1 {
+                return;
+            }
+            let remaining: Vec<ApiProfile> = profiles
+                .iter()
+                .filter(|p| p.id != *profile_id)
+                .cloned()
+                .collect();
+            if *default_profile == *profile_id {
+                default_profile.set(remaining[0].id.clone());
+            }
+            load(&remaining[0]);
+            profiles.set(remaining);
+        }
+    };
+
+    let handle_save = {
+        let profiles = profiles.clone();
+        let default_profile = default_profile.clone();
         let set_is_modal_open = set_is_modal_open.clone();
-        let _dispatch = state_dispatch.clone();
+        let current = current.clone();
         move |_e| {
-          _dispatch.reduce_mut(|d| {
-            d.api_key = Some((*api_key).clone());
-            d.api_endpoint = Some((*api_endpoint).clone());
+            let edited = commit_profile(&profiles, current());
+            state_dispatch.reduce_mut(|d| {
+                d.profiles = edited;
+                d.default_profile = (*default_profile).clone();
+            });
             set_is_modal_open.emit(false);
-          });
+        }
+    };
+
+    let set_name = {
+        let name = name.clone();
+        move |e: Event| {
+            let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+            name.set(input.value());
         }
     };
 
@@ -86,6 +236,14 @@ pub fn ApiMenu(ApiMenuProps { set_is_modal_open }: &ApiMenuProps) -> Html {
         }
     };
 
+    let set_default_model = {
+        let default_model = default_model.clone();
+        move |e: Event| {
+            let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+            default_model.set(input.value().trim().to_string());
+        }
+    };
+
     let toggle_custom_endpoint = {
         let custom_endpoint = custom_endpoint.clone();
         let api_endpoint = api_endpoint.clone();
@@ -99,6 +257,25 @@ pub fn ApiMenu(ApiMenuProps { set_is_modal_open }: &ApiMenuProps) -> Html {
         }
     };
 
+    let make_default = {
+        let default_profile = default_profile.clone();
+        let profile_id = profile_id.clone();
+        move |_e| default_profile.set((*profile_id).clone())
+    };
+
+    // Headers reqwest couldn't send are rejected before they are stored.
+    let validate_header = {
+        let t = t.clone();
+        Callback::from(move |(name, value): (String, String)| {
+            parse_header(&name, &value).err().map(|e| {
+                format!("{} ({})", t("headers.invalid".to_string(), Some(Namespace { ns: "api".to_string() })), e)
+            })
+        })
+    };
+
+    // Show unsaved name edits in the selector as well.
+    let listed = commit_profile(&profiles, current());
+
     html! {
       <PopupModal
         title={t("api".to_string(), None)}
@@ -106,6 +283,62 @@ pub fn ApiMenu(ApiMenuProps { set_is_modal_open }: &ApiMenuProps) -> Html {
         handle_confirm={handle_save}
       >
         <div class="p-6 border-b border-gray-200 dark:border-gray-600">
+          <div class="flex gap-2 items-center mb-4">
+            <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm">
+              {t("profile.label".to_string(), Some(Namespace { ns: "api".to_string() }))}
+            </div>
+            <ApiProfileSelector
+              profiles={listed}
+              selected={Some((*profile_id).clone())}
+              on_select={select_profile}
+            />
+            <button class="btn btn-neutral btn-small" type="button" onclick={new_profile}>
+              {t("profile.new".to_string(), Some(Namespace { ns: "api".to_string() }))}
+            </button>
+            <button
+              class="btn btn-neutral btn-small"
+              type="button"
+              disabled={profiles.len() <= 1}
+              onclick={delete_profile}
+            >
+              {t("profile.delete".to_string(), Some(Namespace { ns: "api".to_string() }))}
+            </button>
+          </div>
+
+          <div class="flex gap-2 items-center mb-4">
+            <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm">
+              {t("profile.name".to_string(), Some(Namespace { ns: "api".to_string() }))}
+            </div>
+            <input
+              type="text"
+              class="text-gray-800 dark:text-white p-3 text-sm border-none bg-gray-200 dark:bg-gray-600 rounded-md m-0 w-full mr-0 h-8 focus:outline-none"
+              value={name.to_string()}
+              onchange={set_name}
+            />
+          </div>
+
+          <label class="flex gap-2 text-gray-900 dark:text-gray-300 text-sm items-center mb-4">
+            <input
+              type="checkbox"
+              checked={*default_profile == *profile_id}
+              disabled={*default_profile == *profile_id}
+              class="w-4 h-4"
+              onchange={make_default}
+            />
+            {t("profile.default".to_string(), Some(Namespace { ns: "api".to_string() }))}
+          </label>
+
+          <div class="flex gap-2 items-center mb-4">
+            <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm">
+              {t("provider.inputLabel".to_string(), Some(Namespace { ns: "api".to_string() }))}
+            </div>
+            <ProviderSelector
+              provider={provider.clone()}
+              api_endpoint={api_endpoint.clone()}
+              custom_endpoint={*custom_endpoint}
+            />
+          </div>
+
           <label class="flex gap-2 text-gray-900 dark:text-gray-300 text-sm items-center mb-4">
             <input
               type="checkbox"
@@ -144,6 +377,34 @@ pub fn ApiMenu(ApiMenuProps { set_is_modal_open }: &ApiMenuProps) -> Html {
             />
           </div>
 
+          <div class="flex gap-2 items-center justify-center mt-4">
+            <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm">
+              {t("profile.defaultModel".to_string(), Some(Namespace { ns: "api".to_string() }))}
+            </div>
+            <input
+              type="text"
+              class="text-gray-800 dark:text-white p-3 text-sm border-none bg-gray-200 dark:bg-gray-600 rounded-md m-0 w-full mr-0 h-8 focus:outline-none"
+              value={default_model.to_string()}
+              onchange={set_default_model}
+            />
+          </div>
+
+          if *provider == ProviderKind::Azure {
+            <AzureSettingsEditor
+              api_version={azure_api_version.clone()}
+              auth={azure_auth.clone()}
+              deployments={azure_deployments.clone()}
+            />
+          }
+
+          <KeyValueEditor
+            entries={custom_headers.clone()}
+            label={t("headers.label".to_string(), Some(Namespace { ns: "api".to_string() }))}
+            key_label={t("headers.name".to_string(), Some(Namespace { ns: "api".to_string() }))}
+            value_label={t("headers.value".to_string(), Some(Namespace { ns: "api".to_string() }))}
+            validate={validate_header}
+          />
+
           <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm flex flex-col gap-3 leading-relaxed">
             <p class="mt-4">
               <Trans
@@ -172,6 +433,261 @@ pub fn ApiMenu(ApiMenuProps { set_is_modal_open }: &ApiMenuProps) -> Html {
     }
 }
 
+#[derive(Debug, Properties, PartialEq)]
+pub struct KeyValueEditorProps {
+    pub entries: UseStateHandle<BTreeMap<String, String>>,
+    pub label: String,
+    pub key_label: String,
+    pub value_label: String,
+    /// Returns why a new `(key, value)` pair can't be added, if it can't.
+    #[prop_or_default]
+    pub validate: Option<Callback<(String, String), Option<String>>>,
+}
+
+/// Editable key/value table, used for a profile's custom entries and Azure
+/// deployment names.
+#[function_component]
+pub(crate) fn KeyValueEditor(
+    KeyValueEditorProps { entries, label, key_label, value_label, validate }: &KeyValueEditorProps,
+) -> Html {
+    let name = use_state(String::new);
+    let value = use_state(String::new);
+    let error = use_state(String::new);
+
+    let add_entry = {
+        let entries = entries.clone();
+        let name = name.clone();
+        let value = value.clone();
+        let error = error.clone();
+        let validate = validate.clone();
+        move |_e| {
+            let key = name.trim().to_string();
+            if key.is_empty() {
+                return;
+            }
+            if let Some(message) = validate.as_ref().and_then(|v| v.emit((key.clone(), value.to_string()))) {
+                error.set(message);
+                return;
+            }
+            let mut edited = (*entries).clone();
+            edited.insert(key, value.trim().to_string());
+            entries.set(edited);
+            name.set(String::new());
+            value.set(String::new());
+            error.set(String::new());
+        }
+    };
+
+    html! {
+      <div class="mt-4">
+        <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm mb-2">
+          {label.clone()}
+        </div>
+        {
+          entries.iter().map(|(key, val)| {
+            html! {
+              <div class="flex gap-2 items-center mb-2" key={key.clone()}>
+                <div class="w-1/3 truncate text-gray-900 dark:text-gray-300 text-sm">{key.clone()}</div>
+                <div class="flex-1 truncate text-gray-900 dark:text-gray-300 text-sm">{val.clone()}</div>
+                <button
+                  class="btn btn-neutral btn-small"
+                  type="button"
+                  aria-label="remove entry"
+                  onclick={
+                    let entries = entries.clone();
+                    let key = key.clone();
+                    move |_e| {
+                      let mut edited = (*entries).clone();
+                      edited.remove(&key);
+                      entries.set(edited);
+                    }
+                  }
+                >
+                  <CrossIcon />
+                </button>
+              </div>
+            }
+          }).collect::<Html>()
+        }
+        <div class="flex gap-2 items-center">
+          <input
+            type="text"
+            class="text-gray-800 dark:text-white p-3 text-sm border-none bg-gray-200 dark:bg-gray-600 rounded-md m-0 w-1/3 h-8 focus:outline-none"
+            placeholder={key_label.clone()}
+            value={name.to_string()}
+            onchange={
+              let name = name.clone();
+              move |e: Event| name.set(e.target_unchecked_into::<web_sys::HtmlInputElement>().value())
+            }
+          />
+          <input
+            type="text"
+            class="text-gray-800 dark:text-white p-3 text-sm border-none bg-gray-200 dark:bg-gray-600 rounded-md m-0 flex-1 h-8 focus:outline-none"
+            placeholder={value_label.clone()}
+            value={value.to_string()}
+            onchange={
+              let value = value.clone();
+              move |e: Event| value.set(e.target_unchecked_into::<web_sys::HtmlInputElement>().value())
+            }
+          />
+          <button class="btn btn-neutral btn-small" type="button" onclick={add_entry}>
+            {"+"}
+          </button>
+        </div>
+        if !error.is_empty() {
+          <div class="text-red-500 text-sm mt-2">{error.to_string()}</div>
+        }
+      </div>
+    }
+}
+
+#[derive(Debug, Properties, PartialEq)]
+pub struct AzureSettingsEditorProps {
+    pub api_version: UseStateHandle<String>,
+    pub auth: UseStateHandle<AzureAuth>,
+    pub deployments: UseStateHandle<BTreeMap<String, String>>,
+}
+
+#[function_component]
+pub(crate) fn AzureSettingsEditor(
+    AzureSettingsEditorProps { api_version, auth, deployments }: &AzureSettingsEditorProps,
+) -> Html {
+    let t = use_translation(vec!["api".to_string()]);
+    let input_class = "text-gray-800 dark:text-white p-3 text-sm border-none bg-gray-200 dark:bg-gray-600 rounded-md m-0 w-full mr-0 h-8 focus:outline-none";
+    html! {
+      <div class="mt-4 pt-4 border-t border-gray-500">
+        <div class="flex gap-2 items-center mb-4">
+          <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm">
+            {t("azure.apiVersion".to_string(), Some(Namespace { ns: "api".to_string() }))}
+          </div>
+          <input
+            type="text"
+            class={input_class}
+            value={api_version.to_string()}
+            onchange={
+              let api_version = api_version.clone();
+              move |e: Event| {
+                let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+                let value = input.value().trim().to_string();
+                api_version.set(if value.is_empty() { AZURE_API_VERSION.to_string() } else { value });
+              }
+            }
+          />
+        </div>
+        <div class="flex gap-2 items-center mb-4">
+          <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm">
+            {t("azure.auth".to_string(), Some(Namespace { ns: "api".to_string() }))}
+          </div>
+          <select
+            class={input_class}
+            onchange={
+              let auth = auth.clone();
+              move |e: Event| {
+                  if let Some(target) = e.target_dyn_into::<web_sys::HtmlSelectElement>()
+                      && let Some(kind) = AzureAuth::ALL.iter().find(|a| a.to_string() == target.value())
+                  {
+                      auth.set(*kind);
+                  }
+              }
+            }
+          >
+            {
+              AzureAuth::ALL.iter().map(|kind| html! {
+                <option value={kind.to_string()} selected={**auth == *kind}>{kind.to_string()}</option>
+              }).collect::<Html>()
+            }
+          </select>
+        </div>
+        if **auth == AzureAuth::EntraId {
+          <div class="min-w-fit text-gray-500 dark:text-gray-300 text-sm mb-4">
+            {t("azure.entraIdHint".to_string(), Some(Namespace { ns: "api".to_string() }))}
+          </div>
+        }
+        <KeyValueEditor
+          entries={deployments.clone()}
+          label={t("azure.deployments".to_string(), Some(Namespace { ns: "api".to_string() }))}
+          key_label={t("azure.model".to_string(), Some(Namespace { ns: "api".to_string() }))}
+          value_label={t("azure.deployment".to_string(), Some(Namespace { ns: "api".to_string() }))}
+        />
+      </div>
+    }
+}
+
+#[derive(Debug, Properties, PartialEq)]
+pub struct ApiProfileSelectorProps {
+    pub profiles: Vec<ApiProfile>,
+    pub selected: Option<String>,
+    pub on_select: Callback<Option<String>>,
+    /// Label of an extra first entry selecting `None`, i.e. "use the default profile".
+    #[prop_or_default]
+    pub default_label: Option<String>,
+}
+
+#[function_component]
+pub(crate) fn ApiProfileSelector(
+    ApiProfileSelectorProps { profiles, selected, on_select, default_label }: &ApiProfileSelectorProps,
+) -> Html {
+    let (drop_down, drop_down_ref) = use_hideon_outside_click();
+    let label = selected
+        .as_ref()
+        .and_then(|id| profiles.iter().find(|p| &p.id == id))
+        .map(|p| p.name.clone())
+        .or_else(|| default_label.clone())
+        .unwrap_or_default();
+    let entries = default_label
+        .iter()
+        .map(|label| (None, label.clone()))
+        .chain(profiles.iter().map(|p| (Some(p.id.clone()), p.name.clone())))
+        .collect::<Vec<_>>();
+    html! {
+      <div class="w-[40vw] relative flex-1">
+        <button
+          class="btn btn-neutral btn-small flex justify-between w-full"
+          type="button"
+          aria-label="expand profile menu"
+          onclick={
+            let drop_down = drop_down.clone();
+            move |_e| drop_down.set(!(*drop_down))
+          }
+        >
+          <span class="truncate">{ label }</span>
+          <DownChevronArrow />
+        </button>
+        <div
+          id="dropdown"
+          ref={drop_down_ref}
+          class={classes!("absolute", "top-100", "bottom-100", "z-10", "bg-white", "rounded-lg", "shadow-xl", "border-b", "border-black/10", "dark:border-gray-900/50", "text-gray-800", "dark:text-gray-100", "group", "dark:bg-gray-800", "opacity-90", "w-32", "w-full", if *drop_down {""} else {"hidden"}) }
+        >
+          <ul
+            class="text-sm text-gray-700 dark:text-gray-200 p-0 m-0"
+            aria-labelledby="dropdownDefaultButton"
+          >
+            {
+              entries.into_iter().map(|(id, name)| {
+                html! {
+                  <li
+                    class="px-4 py-2 hover:bg-gray-100 dark:hover:bg-gray-600 dark:hover:text-white cursor-pointer truncate"
+                    key={id.clone().unwrap_or_default()}
+                    onclick={
+                      let drop_down = drop_down.clone();
+                      let on_select = on_select.clone();
+                      move |_e| {
+                        on_select.emit(id.clone());
+                        drop_down.set(false);
+                      }
+                    }
+                  >
+                    {name}
+                  </li>
+                }
+              }).collect::<Html>()
+           }
+          </ul>
+        </div>
+      </div>
+    }
+}
+
 #[derive(Debug, Properties, PartialEq)]
 pub struct EndpointSelectorProps {
     pub api_endpoint: UseStateHandle<String>,
@@ -230,3 +746,71 @@ pub(crate) fn ApiEndpointSelector(
       </div>
     }
 }
+
+#[derive(Debug, Properties, PartialEq)]
+pub struct ProviderSelectorProps {
+    pub provider: UseStateHandle<ProviderKind>,
+    pub api_endpoint: UseStateHandle<String>,
+    pub custom_endpoint: bool,
+}
+
+#[function_component]
+pub(crate) fn ProviderSelector(
+    ProviderSelectorProps { provider, api_endpoint, custom_endpoint }: &ProviderSelectorProps,
+) -> Html {
+    let (drop_down, drop_down_ref) = use_hideon_outside_click();
+    html! {
+      <div class="w-[40vw] relative flex-1">
+        <button
+          class="btn btn-neutral btn-small flex justify-between w-full"
+          type="button"
+          aria-label="expand provider menu"
+          onclick={
+            let drop_down = drop_down.clone();
+            move |_e| drop_down.set(!(*drop_down))
+          }
+        >
+          <span class="truncate">{ provider.to_string() }</span>
+          <DownChevronArrow />
+        </button>
+        <div
+          id="dropdown"
+          ref={drop_down_ref}
+          class={classes!("absolute", "top-100", "bottom-100", "z-10", "bg-white", "rounded-lg", "shadow-xl", "border-b", "border-black/10", "dark:border-gray-900/50", "text-gray-800", "dark:text-gray-100", "group", "dark:bg-gray-800", "opacity-90", "w-32", "w-full", if *drop_down {""} else {"hidden"}) }
+        >
+          <ul
+            class="text-sm text-gray-700 dark:text-gray-200 p-0 m-0"
+            aria-labelledby="dropdownDefaultButton"
+          >
+            {
+              ProviderKind::ALL.iter().map(|kind| {
+                html! {
+                  <li
+                    class="px-4 py-2 hover:bg-gray-100 dark:hover:bg-gray-600 dark:hover:text-white cursor-pointer truncate"
+                    onclick={
+                      let drop_down = drop_down.clone();
+                      let provider = provider.clone();
+                      let api_endpoint = api_endpoint.clone();
+                      let custom_endpoint = *custom_endpoint;
+                      let kind = kind.clone();
+                      move |_e| {
+                        // Point the preset endpoint at the new provider; custom URLs are left alone.
+                        if !custom_endpoint && let Some(endpoint) = kind.default_endpoint() {
+                          api_endpoint.set(endpoint.to_string());
+                        }
+                        provider.set(kind.clone());
+                        drop_down.set(false);
+                      }
+                    }
+                    key={kind.to_string()}
+                  >
+                    {kind.to_string()}
+                  </li>
+                }
+              }).collect::<Html>()
+           }
+          </ul>
+        </div>
+      </div>
+    }
+}
@@ -4,7 +4,7 @@ use yewdux::use_store;
 use crate::{
   components::{
     chat::config_menu::{
-      FrequencyPenaltySlider, MaxTokenSlider, ModelSelector, PresencePenaltySlider,
+      AdvancedParams, FrequencyPenaltySlider, MaxTokenSlider, ModelSelector, PresencePenaltySlider,
       TemperatureSlider, TopPSlider,
     },
     popup_modal::PopupModal,
@@ -12,7 +12,7 @@ use crate::{
   constants::DEFAULT_SYSTEM_MESSAGE,
   hooks::translation::use_translation,
   store::ConfigSlice,
-  types::chat::{ConfigInterface, ModelOptions},
+  types::chat::ConfigInterface,
 };
 
 #[function_component]
@@ -54,12 +54,18 @@ pub(crate) fn ChatConfigPopup(ConfigPopupProps { set_is_modal_open }: &ConfigPop
 
   let config = state.default_chat_config.clone();
   let system_message = use_state(|| state.default_system_message.clone());
-  let model = use_state(|| ModelOptions::Gpt35Turbo);
+  let model = use_state(|| config.model.clone());
   let max_token = use_state(|| config.max_tokens);
   let temperature = use_state(|| config.temperature);
   let top_p = use_state(|| config.top_p);
   let pres_penalty = use_state(|| config.presence_penalty);
   let freq_penalty = use_state(|| config.frequency_penalty);
+  let stop = use_state(|| config.stop.clone());
+  let seed = use_state(|| config.seed);
+  let n = use_state(|| config.n);
+  let logit_bias = use_state(|| config.logit_bias.clone());
+  let response_format = use_state(|| config.response_format);
+  let user = use_state(|| config.user.clone());
   let handle_save = {
     let _dipatch = _dispath.clone();
     let model = model.clone();
@@ -68,6 +74,12 @@ pub(crate) fn ChatConfigPopup(ConfigPopupProps { set_is_modal_open }: &ConfigPop
     let top_p = top_p.clone();
     let pres_penalty = pres_penalty.clone();
     let freq_penalty = freq_penalty.clone();
+    let stop = stop.clone();
+    let seed = seed.clone();
+    let n = n.clone();
+    let logit_bias = logit_bias.clone();
+    let response_format = response_format.clone();
+    let user = user.clone();
     let system_message = system_message.clone();
     move |_val| {
       _dipatch.reduce_mut(|c| {
@@ -78,6 +90,12 @@ pub(crate) fn ChatConfigPopup(ConfigPopupProps { set_is_modal_open }: &ConfigPop
           presence_penalty: *pres_penalty,
           top_p: *top_p,
           frequency_penalty: *freq_penalty,
+          stop: (*stop).clone(),
+          seed: *seed,
+          n: *n,
+          logit_bias: (*logit_bias).clone(),
+          response_format: *response_format,
+          user: (*user).clone(),
         };
 
         c.default_system_message = (*system_message).clone();
@@ -91,6 +109,12 @@ pub(crate) fn ChatConfigPopup(ConfigPopupProps { set_is_modal_open }: &ConfigPop
     let top_p = top_p.clone();
     let pres_penalty = pres_penalty.clone();
     let freq_penalty = freq_penalty.clone();
+    let stop = stop.clone();
+    let seed = seed.clone();
+    let n = n.clone();
+    let logit_bias = logit_bias.clone();
+    let response_format = response_format.clone();
+    let user = user.clone();
     let system_message = system_message.clone();
     move |_e| {
       let default_chat_config = ConfigInterface::default();
@@ -100,6 +124,12 @@ pub(crate) fn ChatConfigPopup(ConfigPopupProps { set_is_modal_open }: &ConfigPop
       top_p.set(default_chat_config.top_p);
       pres_penalty.set(default_chat_config.presence_penalty);
       freq_penalty.set(default_chat_config.frequency_penalty);
+      stop.set(default_chat_config.stop);
+      seed.set(default_chat_config.seed);
+      n.set(default_chat_config.n);
+      logit_bias.set(default_chat_config.logit_bias);
+      response_format.set(default_chat_config.response_format);
+      user.set(default_chat_config.user);
       system_message.set(DEFAULT_SYSTEM_MESSAGE.to_string());
     }
   };
@@ -121,6 +151,7 @@ pub(crate) fn ChatConfigPopup(ConfigPopupProps { set_is_modal_open }: &ConfigPop
           presence_penalty={pres_penalty}
       />
       <FrequencyPenaltySlider {freq_penalty} />
+      <AdvancedParams {stop} {seed} {n} {logit_bias} {response_format} {user} />
       <div
         class="btn btn-neutral cursor-pointer mt-5"
         onclick={handle_reset}
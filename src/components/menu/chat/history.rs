@@ -4,7 +4,8 @@ use yew::prelude::*;
 use yewdux::use_store;
 
 use crate::{
-    components::icons::{ChatIcon, CrossIcon, DeleteIcon, EditIcon, TickIcon},
+    components::icons::{ChatIcon, CrossIcon, DeleteIcon, EditIcon, RefreshIcon, TickIcon},
+    hooks::{submit::use_generate_title, translation::use_translation},
     store::{ChatSlice, ConfigSlice},
     types::chat::{ChatInterface, ConfigInterface},
 };
@@ -48,8 +49,19 @@ pub fn ChatHistory(ChatHistoryProps { title, chat_index }: &ChatHistoryProps) ->
 
     let is_delete = use_state(|| false);
     let is_edit = use_state(|| false);
+    let title_prop = title.clone();
     let title = use_state(|| title.to_string());
     let input_ref = use_node_ref();
+    let generate_title = use_generate_title();
+    let t = use_translation(vec![]);
+
+    // Titles also change outside this item (auto titles, "regenerate title").
+    {
+        let title = title.clone();
+        use_effect_with(title_prop, move |title_prop| {
+            title.set(title_prop.clone());
+        });
+    }
 
     let edit_title = {
         let store = chat_store.clone();
@@ -164,7 +176,8 @@ pub fn ChatHistory(ChatHistoryProps { title, chat_index }: &ChatHistoryProps) ->
         onclick={
             let set_curr_chat_index = set_curr_chat_index.clone();
             let chat_index = *chat_index;
-            move |_e| if !generating { set_curr_chat_index(chat_index) }
+            // Switching away while generating aborts the generation, see `use_abort_on_chat_change`.
+            move |_e| set_curr_chat_index(chat_index)
         }
         draggable="true"
         ondragstart={handle_drag_start}
@@ -220,6 +233,23 @@ pub fn ChatHistory(ChatHistoryProps { title, chat_index }: &ChatHistoryProps) ->
                 >
                   <EditIcon />
                 </button>
+                <button
+                  class="p-1 hover:text-white"
+                  onclick={
+                    let generate_title = generate_title.clone();
+                    let chat_id = chat_store.chats.get(*chat_index as usize).map(|c| c.id.clone());
+                    move |e: MouseEvent| {
+                      e.stop_propagation();
+                      if let Some(chat_id) = chat_id.clone() {
+                        generate_title.emit(chat_id);
+                      }
+                    }
+                  }
+                  aria-label="regenerate chat title"
+                  title={t("regenerateTitle".to_string(), None)}
+                >
+                  <RefreshIcon />
+                </button>
                 <button
                   class="p-1 hover:text-white"
                   onclick={let is_delete = is_delete.clone(); move |_| is_delete.set(true) }
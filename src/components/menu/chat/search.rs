@@ -1,8 +1,20 @@
+use std::rc::Rc;
+
+use chrono::{Local, NaiveDate};
 use yew::prelude::*;
 use yew_hooks::prelude::*;
 use yewdux::use_store;
 
-use crate::{components::search_bar::SearchBar, store::ChatSlice};
+use crate::{
+    components::search_bar::SearchBar,
+    hooks::translation::use_translation,
+    search::{MAX_HITS, SearchFilter, SearchHit, SearchIndex},
+    store::ChatSlice,
+    types::chat::{Folder, Role},
+};
+
+const SELECT_CLASS: &str = "w-full text-xs p-1 rounded-md text-gray-100 bg-gray-800 border border-white/20";
+
 #[derive(Debug, Properties, PartialEq)]
 pub struct ChatSearchProp {
     pub filter: UseStateHandle<String>,
@@ -13,12 +25,11 @@ pub fn ChatSearch(ChatSearchProp { filter }: &ChatSearchProp) -> Html {
     let filter = filter.clone();
     let (state, _) = use_store::<ChatSlice>();
     let generating = state.generating;
-    let value = use_state(|| "".to_string());
+    let value = use_state(|| filter.to_string());
     let handle_change = {
-        let filter = filter.clone();
-        move |e| filter.set(e)
+        let value = value.clone();
+        move |e| value.set(e)
     };
-    // TODO: Revisit this impl
     let debounced = {
         let value = value.clone();
         let filter = filter.clone();
@@ -30,14 +41,183 @@ pub fn ChatSearch(ChatSearchProp { filter }: &ChatSearchProp) -> Html {
         )
     };
 
-    use_effect(move || debounced.run());
+    use_effect_with((*value).clone(), move |_| debounced.run());
+
+    html! {
+        <>
+            <SearchBar
+                value={(*value).clone()}
+                {handle_change}
+                class_name="h-8 mb-2"
+                disabled={generating}
+            />
+            <MessageSearch query={filter.to_string()} />
+        </>
+    }
+}
+
+#[derive(Debug, Properties, PartialEq)]
+pub(crate) struct MessageSearchProps {
+    pub(crate) query: String,
+}
+
+/// The messages matching `query`, with filters. A hit opens its chat at
+/// that message.
+#[function_component]
+pub(crate) fn MessageSearch(MessageSearchProps { query }: &MessageSearchProps) -> Html {
+    let translate = use_translation(vec![]);
+    let t = move |key: &str| translate(key.to_string(), None);
+    let (state, dispatch) = use_store::<ChatSlice>();
+    // The index, and the store it was last brought up to date with.
+    let index = use_mut_ref(|| (SearchIndex::default(), None::<Rc<ChatSlice>>));
+    let filter = use_state(SearchFilter::default);
+    let show_filters = use_state(|| false);
+
+    if query.trim().is_empty() {
+        return html! {};
+    }
+    let (hits, models) = {
+        let mut index = index.borrow_mut();
+        let (index, indexed) = &mut *index;
+        if !indexed.as_ref().is_some_and(|indexed| Rc::ptr_eq(indexed, &state)) {
+            index.update(&state.chats);
+            *indexed = Some(state.clone());
+        }
+        let models: Vec<String> = index.models().into_iter().map(str::to_string).collect();
+        (index.search(&state.chats, query, &filter), models)
+    };
+    let mut folders: Vec<&Folder> = state.folders.values().collect();
+    folders.sort_by_key(|f| f.order);
+
+    let set_filter = |update: fn(&mut SearchFilter, String)| {
+        let filter = filter.clone();
+        move |e: Event| {
+            let value = e.target_unchecked_into::<web_sys::HtmlInputElement>().value();
+            let mut next = (*filter).clone();
+            update(&mut next, value);
+            filter.set(next);
+        }
+    };
+    let jump = |hit: &SearchHit| {
+        let dispatch = dispatch.clone();
+        let (chat_id, message) = (hit.chat_id.clone(), hit.message);
+        move |_: MouseEvent| {
+            dispatch.reduce_mut(|s| {
+                if let Some(index) = s.chats.iter().position(|c| c.id == chat_id) {
+                    s.curr_chat_index = index as i32;
+                    s.jump_to = Some(message);
+                }
+            });
+        }
+    };
+    let count = if hits.len() == MAX_HITS {
+        format!("{}+", MAX_HITS)
+    } else {
+        hits.len().to_string()
+    };
 
     html! {
-        <SearchBar
-            value={filter.to_string()}
-            {handle_change}
-            class_name="h-8 mb-2"
-            disabled={generating}
-        />
+        <div class="mb-2 text-sm text-gray-100">
+            <div class="flex justify-between items-center px-2 mb-1 text-xs text-gray-400">
+                <span>{ format!("{} ({})", t("messageSearch.messages"), count) }</span>
+                <a
+                    class={classes!("cursor-pointer", "hover:text-gray-100", (!filter.is_empty()).then_some("text-gray-100"))}
+                    onclick={
+                        let show_filters = show_filters.clone();
+                        move |_| show_filters.set(!*show_filters)
+                    }
+                >
+                    { t("messageSearch.filters") }
+                </a>
+            </div>
+            if *show_filters {
+                <div class="grid grid-cols-2 gap-1 px-2 mb-2 text-xs">
+                    <select class={SELECT_CLASS} onchange={set_filter(|f, value| f.role = value.parse().ok())}>
+                        <option value="" selected={filter.role.is_none()}>{ t("messageSearch.anyRole") }</option>
+                        {
+                            [Role::User, Role::Assistant, Role::System].into_iter().map(|role| html! {
+                                <option value={role.to_string()} selected={filter.role.as_ref() == Some(&role)}>
+                                    { t(&role.to_string()) }
+                                </option>
+                            }).collect::<Html>()
+                        }
+                    </select>
+                    <select class={SELECT_CLASS} onchange={set_filter(|f, value| f.model = Some(value).filter(|v| !v.is_empty()))}>
+                        <option value="" selected={filter.model.is_none()}>{ t("messageSearch.anyModel") }</option>
+                        {
+                            models.iter().map(|model| html! {
+                                <option value={model.clone()} selected={filter.model.as_ref() == Some(model)}>{ model }</option>
+                            }).collect::<Html>()
+                        }
+                    </select>
+                    <select class={classes!(SELECT_CLASS, "col-span-2")} onchange={set_filter(|f, value| f.folder = Some(value).filter(|v| !v.is_empty()))}>
+                        <option value="" selected={filter.folder.is_none()}>{ t("messageSearch.anyFolder") }</option>
+                        {
+                            folders.iter().map(|folder| html! {
+                                <option value={folder.id.clone()} selected={filter.folder.as_ref() == Some(&folder.id)}>
+                                    { &folder.name }
+                                </option>
+                            }).collect::<Html>()
+                        }
+                    </select>
+                    <label class="flex flex-col gap-1 text-gray-400">
+                        { t("messageSearch.from") }
+                        <input type="date" class={SELECT_CLASS} onchange={set_filter(|f, value| f.from = day_start(&value))} />
+                    </label>
+                    <label class="flex flex-col gap-1 text-gray-400">
+                        { t("messageSearch.to") }
+                        <input type="date" class={SELECT_CLASS} onchange={set_filter(|f, value| f.to = day_end(&value))} />
+                    </label>
+                </div>
+            }
+            <div class="max-h-80 overflow-y-auto hide-scroll-bar">
+                if hits.is_empty() {
+                    <div class="px-2 py-1 text-gray-400">{ t("messageSearch.noResults") }</div>
+                }
+                {
+                    hits.iter().map(|hit| {
+                        let title = state
+                            .chats
+                            .iter()
+                            .find(|c| c.id == hit.chat_id)
+                            .and_then(|c| c.title.clone())
+                            .unwrap_or_default();
+                        html! {
+                            <a
+                                class="block px-2 py-2 rounded-md hover:bg-gray-500/10 cursor-pointer"
+                                key={format!("{}-{}", hit.chat_id, hit.message)}
+                                onclick={jump(hit)}
+                            >
+                                <div class="text-xs text-gray-400 truncate">
+                                    { format!("{} · {}", title, t(&hit.role.to_string())) }
+                                </div>
+                                <div class="break-words">
+                                    {
+                                        hit.snippet.iter().map(|fragment| if fragment.highlight {
+                                            html! { <mark class="bg-yellow-300/40 text-inherit rounded-sm">{ &fragment.text }</mark> }
+                                        } else {
+                                            html! { { &fragment.text } }
+                                        }).collect::<Html>()
+                                    }
+                                </div>
+                            </a>
+                        }
+                    }).collect::<Html>()
+                }
+            </div>
+        </div>
     }
 }
+
+/// The first millisecond of `date`, a `YYYY-MM-DD` date in local time.
+fn day_start(date: &str) -> Option<i64> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let start = date.and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest()?;
+    Some(start.timestamp_millis())
+}
+
+/// The last millisecond of `date`.
+fn day_end(date: &str) -> Option<i64> {
+    let next = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?.succ_opt()?;
+    Some(day_start(&next.format("%Y-%m-%d").to_string())? - 1)
+}
@@ -3,7 +3,9 @@ use std::collections::HashMap;
 use yew::prelude::*;
 use yewdux::prelude::*;
 use crate::components::icons::{ArrowBottom, DeleteIcon, LogoutIcon, MoonIcon, PersonIcon, SunIcon};
+use crate::components::cloud_sync::CloudSyncMenu;
 use crate::components::import_export_chat::ImportExportChat;
+use crate::components::vault::VaultMenu;
 use crate::components::menu::about::AboutMenu;
 use crate::components::menu::api::Api;
 use crate::components::menu::settings::{SettingsMenu, TotalTokenCostDisplay};
@@ -16,7 +18,7 @@ use crate::types::chat::Theme;
 #[function_component]
 pub(crate) fn MenuOptions() -> Html {
   let (state, _dispatch) = use_store::<ConfigSlice>();
-  let google_client_id = None::<String>;
+  let google_client_id = option_env!("GOOGLE_CLIENT_ID");
   html! {
     <>
       <CollapseOptions />
@@ -26,9 +28,8 @@ pub(crate) fn MenuOptions() -> Html {
         if state.count_total_tokens {
           <TotalTokenCostDisplay />
         }
-        if let Some(id) = google_client_id {
-          // <GoogleSync clientId={id.clone()} />
-        }
+        <CloudSyncMenu google_client_id={google_client_id.map(str::to_string)} />
+        <VaultMenu />
         <AboutMenu />
         <ImportExportChat />
         <Api />
@@ -15,9 +15,10 @@ use crate::{
         popup_modal::PopupModal,
         toggle::Toggle,
     },
+    constants::DEFAULT_TITLE_PROMPT,
     hooks::translation::{Namespace, use_translation},
     store::ConfigSlice,
-    types::chat::{ModelOptions, TokenUsage},
+    types::{chat::TokenUsage, model::ModelRegistry},
 };
 
 #[function_component]
@@ -56,6 +57,8 @@ pub(crate) fn SettingsMenu() -> Html {
                 <AdvancedModeToggle />
                 <TotalTokenCostToggle />
               </div>
+              <TitleSettings />
+              <AutoContinueSetting />
               <ClearConversation />
               <PromptLibraryMenu />
               <ChatConfigMenu />
@@ -147,29 +150,104 @@ fn AutoTitleToggle() -> Html {
     }
 }
 
+/// Model and prompt used for automatic and regenerated chat titles.
+#[function_component]
+fn TitleSettings() -> Html {
+    let t = use_translation(vec![]);
+    let (config, dispatch) = use_store::<ConfigSlice>();
+    let input_class = "text-gray-800 dark:text-white p-3 text-sm border-none bg-gray-200 dark:bg-gray-600 rounded-md m-0 w-full mr-0 focus:outline-none";
+    html! {
+      <div class="flex flex-col gap-2 w-full">
+        <label class="text-sm text-gray-900 dark:text-gray-300">
+          {t("titleModel".to_string(), None)}
+        </label>
+        <input
+          type="text"
+          class={classes!(input_class, "h-8")}
+          placeholder={t("titleModelPlaceholder".to_string(), None)}
+          value={config.title_model.clone().unwrap_or_default()}
+          onchange={
+            let dispatch = dispatch.clone();
+            move |e: Event| {
+                let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+                let model = input.value().trim().to_string();
+                dispatch.reduce_mut(|d| d.title_model = (!model.is_empty()).then_some(model));
+            }
+          }
+        />
+        <label class="text-sm text-gray-900 dark:text-gray-300">
+          {t("titlePrompt".to_string(), None)}
+        </label>
+        <textarea
+          class={input_class}
+          rows="4"
+          value={config.title_prompt.clone()}
+          onchange={
+            let dispatch = dispatch.clone();
+            move |e: Event| {
+                let input = e.target_unchecked_into::<web_sys::HtmlTextAreaElement>();
+                let prompt = input.value();
+                dispatch.reduce_mut(|d| {
+                    d.title_prompt = if prompt.trim().is_empty() { DEFAULT_TITLE_PROMPT.to_string() } else { prompt };
+                });
+            }
+          }
+        />
+        <div class="text-gray-500 dark:text-gray-300 text-sm">
+          {t("titlePromptDescription".to_string(), None)}
+        </div>
+      </div>
+    }
+}
+
+/// Most continuations of one answer that can be asked for, so that a model
+/// that never stops can't run up the bill unattended.
+const MAX_AUTO_CONTINUE: u32 = 10;
+
+#[function_component]
+fn AutoContinueSetting() -> Html {
+    let t = use_translation(vec![]);
+    let (config, dispatch) = use_store::<ConfigSlice>();
+    html! {
+      <div class="flex flex-col gap-2 w-full">
+        <label class="text-sm text-gray-900 dark:text-gray-300">
+          {t("autoContinue".to_string(), None)}
+        </label>
+        <input
+          type="number"
+          min="0"
+          max={MAX_AUTO_CONTINUE.to_string()}
+          class="text-gray-800 dark:text-white p-3 text-sm border-none bg-gray-200 dark:bg-gray-600 rounded-md m-0 w-full mr-0 h-8 focus:outline-none"
+          value={config.auto_continue.to_string()}
+          onchange={move |e: Event| {
+              let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+              let times = input.value().parse::<u32>().unwrap_or(0).min(MAX_AUTO_CONTINUE);
+              dispatch.reduce_mut(|d| d.auto_continue = times);
+          }}
+        />
+        <div class="text-gray-500 dark:text-gray-300 text-sm">
+          {t("autoContinueDescription".to_string(), None)}
+        </div>
+      </div>
+    }
+}
+
 #[function_component]
 pub(crate) fn TotalTokenCostDisplay() -> Html {
     let t  = use_translation(vec![]);
     let (state, _dispatch) = use_store::<ConfigSlice>();
+    let (registry, _) = use_store::<ModelRegistry>();
 
-    let total_cost = use_state(|| 0);
-    {
-        let mut updated_total_cost = 0;
-        let total_cost = total_cost.clone();
-        let state = state.clone();
-        use_effect_with(total_cost, move |total_cost| {
-            for (model, token_cost) in state.total_token_used.iter() {
-                let cost = token_cost_to_cost(token_cost, model);
-                updated_total_cost += cost;
-            }
-            total_cost.set(updated_total_cost);
-        });
-    }
+    let total_cost: f64 = state
+        .total_token_used
+        .iter()
+        .map(|(model, token_cost)| token_cost_to_cost(token_cost, model, &registry))
+        .sum();
 
     html! {
       <a class="flex py-2 px-2 items-center gap-3 rounded-md hover:bg-gray-500/10 transition-colors duration-200 text-white text-sm">
         <CalculatorIcon />
-        { format!("USD: {}", total_cost.to_string()) }
+        { format!("USD: {:.4}", total_cost) }
       </a>
     }
 }
@@ -193,41 +271,37 @@ pub fn TotalTokenCostToggle() -> Html {
     }
 }
 
+#[derive(PartialEq)]
 struct CostMapping {
-    model: ModelOptions,
-    cost: i32,
+    model: String,
+    cost: f64,
 }
 
-fn token_cost_to_cost(token_cost: &TokenUsage, model: &ModelOptions) -> i32 {
-    // TODO: Fix me
-    // todo!()
-    0
+fn token_cost_to_cost(token_cost: &TokenUsage, model: &str, registry: &ModelRegistry) -> f64 {
+    registry.info(model).cost(token_cost)
 }
 
 #[function_component]
 pub fn TotalTokenCost() -> Html {
     let t = use_translation(vec![]);
     let (state, store_dispatch) = use_store::<ConfigSlice>();
-    let total_token = use_state(|| state.total_token_used.clone());
-    // let count_total_token = use_state(|| state.count_total_tokens);
-    let cost_mapping = use_state(|| Vec::<CostMapping>::new());
-
-    let total_cost = 0.0;
-
-    {
-        let cost_mapping = cost_mapping.clone();
-        use_effect_with(total_token, move |total_token| {
-            let mut updated_cost: Vec<CostMapping> = vec![];
-            for (model, token_cost) in total_token.iter() {
-                let cost = token_cost_to_cost(token_cost, model);
-                updated_cost.push(CostMapping {
+    let (registry, _) = use_store::<ModelRegistry>();
+    let cost_mapping = use_memo(
+        (state.total_token_used.clone(), registry),
+        |(total_token, registry)| {
+            let mut cost_mapping = total_token
+                .iter()
+                .map(|(model, token_cost)| CostMapping {
                     model: model.clone(),
-                    cost,
-                });
-            }
-            cost_mapping.set(updated_cost);
-        });
-    }
+                    cost: token_cost_to_cost(token_cost, model, registry),
+                })
+                .collect::<Vec<_>>();
+            cost_mapping.sort_by(|a, b| a.model.cmp(&b.model));
+            cost_mapping
+        },
+    );
+    let total_cost: f64 = cost_mapping.iter().map(|c| c.cost).sum();
+
     html! {
       if state.count_total_tokens {
         <div class="flex flex-col items-center gap-2">
@@ -248,7 +322,7 @@ pub fn TotalTokenCost() -> Html {
                       class="bg-white border-b dark:bg-gray-800 dark:border-gray-700 hover:bg-gray-50 dark:hover:bg-gray-700"
                     >
                       <td class="px-4 py-2">{model.to_string()}</td>
-                      <td class="px-4 py-2">{*cost}</td>
+                      <td class="px-4 py-2">{format!("{:.4}", cost)}</td>
                     </tr>
                   }
                 }).collect::<Html>()
@@ -256,7 +330,7 @@ pub fn TotalTokenCost() -> Html {
               <tr class="bg-white border-b dark:bg-gray-800 dark:border-gray-700 font-bold">
                 <td class="px-4 py-2">{t("total".to_string(), Some(Namespace { ns: "main".to_string() }))}</td>
                 <td class="px-4 py-2">
-                  { total_cost }
+                  { format!("{:.4}", total_cost) }
                 </td>
               </tr>
             </tbody>
@@ -14,3 +14,5 @@ pub(crate) mod mobile_bar;
 pub(crate) mod language_selector;
 pub(crate) mod stop_gen_btn;
 pub(crate) mod import_export_chat;
+pub(crate) mod cloud_sync;
+pub(crate) mod vault;
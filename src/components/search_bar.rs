@@ -20,10 +20,11 @@ pub(crate) fn SearchBar(
     }: &SearchBarProps,
 ) -> Html {
     let t = use_translation(vec![]);
-    let on_change = {
+    let on_input = {
         let cb = handle_change.clone();
-        Callback::from(|e| {
-          // cb(e);
+        Callback::from(move |e: InputEvent| {
+          let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+          cb.emit(input.value());
         })
     };
     html! {
@@ -34,7 +35,7 @@ pub(crate) fn SearchBar(
           class="text-gray-800 dark:text-white p-3 text-sm bg-transparent disabled:opacity-40  disabled:cursor-not-allowed transition-opacity m-0 w-full h-full focus:outline-none rounded border border-white/20"
           placeholder={t("search".to_string(), None) }
           value={value.clone()}
-          onchange={ on_change }
+          oninput={ on_input }
         />
       </div>
     }
@@ -1,12 +1,18 @@
 use yew::prelude::*;
+use yewdux::use_store;
 
+use crate::store::ChatSlice;
 
 #[function_component]
 pub(crate) fn StopGeneratingButton() -> Html {
+  let (state, dispatch) = use_store::<ChatSlice>();
+  if !state.generating {
+    return html! {};
+  }
   html! {
     <div
       class="absolute bottom-6 left-0 right-0 m-auto flex md:w-full md:m-auto gap-0 md:gap-2 justify-center"
-      onclick={|e| {}}
+      onclick={move |_e| dispatch.reduce_mut(|s| s.stop_generating())}
     >
       <button
         class="btn relative btn-neutral border-0 md:border"
@@ -32,6 +32,9 @@ pub(crate) fn Toast() -> Html {
             }
         });
     }
+    if !toast_slice.show {
+        return html! {};
+    }
     html! {
       <div
         class="flex fixed right-5 bottom-5 z-[1000] items-center w-3/4 md:w-full max-w-xs p-4 mb-4 text-gray-500 dark:text-gray-400 rounded-lg shadow-md border border-gray-400/30 animate-bounce"
@@ -1,6 +1,9 @@
 use yew::prelude::*;
 use yewdux::prelude::*;
-use crate::{store::ChatSlice, types::chat::ModelOptions};
+use crate::{
+    store::ChatSlice,
+    types::{chat::{TokenUsage, DEFAULT_MODEL}, model::ModelRegistry},
+};
 
 #[function_component]
 pub(crate) fn TokenCount() -> Html {
@@ -12,7 +15,8 @@ pub(crate) fn TokenCount() -> Html {
     } else {
         vec![]
     };
-    let mut model = ModelOptions::Gpt35Turbo;
+    let (registry, _) = use_store::<ModelRegistry>();
+    let mut model = DEFAULT_MODEL.to_string();
     if !state.chats.is_empty() {
         if let Some(config) = &state.chats[state.curr_chat_index as usize].config {
           model = config.model.clone();
@@ -20,10 +24,9 @@ pub(crate) fn TokenCount() -> Html {
     };
     let cost = {
       let token_count = token_count.clone();
-      use_memo((model, token_count), |(model, token_count)| {
-        // let price = model
-        
-        0
+      use_memo((model, *token_count), |(model, token_count)| {
+        let usage = TokenUsage { prompt_tokens: *token_count as u32, completion_tokens: 0 };
+        format!("${:.4}", registry.info(model).cost(&usage))
       })
     };
 
@@ -0,0 +1,280 @@
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::use_store;
+
+use crate::{
+    components::{icons::LockIcon, popup_modal::PopupModal},
+    hooks::translation::{Namespace, use_translation},
+    store::VaultSlice,
+    vault::{SALT_LEN, Vault, VaultError, WebCryptoPassphrase, random_bytes},
+};
+
+const MENU_ITEM_CLASS: &str = "flex py-2 px-2 items-center gap-3 rounded-md hover:bg-gray-500/10 transition-colors duration-200 text-white cursor-pointer text-sm";
+const INPUT_CLASS: &str = "text-gray-800 dark:text-white p-3 text-sm border-none bg-gray-200 dark:bg-gray-600 rounded-md m-0 w-full mr-0 h-8 focus:outline-none";
+/// Shorter passphrases are refused when setting up the vault.
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Shown instead of the app while the stored state is encrypted and the
+/// passphrase wasn't given yet.
+#[function_component]
+pub(crate) fn UnlockScreen() -> Html {
+    let translate = use_translation(vec!["vault".to_string()]);
+    let t = move |key: &str| translate(key.to_string(), Some(Namespace { ns: "vault".to_string() }));
+    let (vault, vault_dispatch) = use_store::<VaultSlice>();
+    let passphrase = use_state(String::new);
+    let unlocking = use_state(|| false);
+    let error = use_state(|| None::<String>);
+
+    let on_submit = {
+        let passphrase = passphrase.clone();
+        let unlocking = unlocking.clone();
+        let error = error.clone();
+        let t = t.clone();
+        let header = vault.header.clone();
+        move |e: SubmitEvent| {
+            e.prevent_default();
+            let Some(header) = header.clone() else {
+                return;
+            };
+            if *unlocking {
+                return;
+            }
+            unlocking.set(true);
+            error.set(None);
+            let passphrase = (*passphrase).clone();
+            let unlocking = unlocking.clone();
+            let error = error.clone();
+            let vault_dispatch = vault_dispatch.clone();
+            let t = t.clone();
+            spawn_local(async move {
+                let unlocked = match WebCryptoPassphrase::import(&passphrase).await {
+                    Ok(passphrase) => Vault::unlock(header, passphrase).await,
+                    Err(e) => Err(e),
+                };
+                unlocking.set(false);
+                match unlocked {
+                    Ok(vault) => vault_dispatch.reduce_mut(|s| s.vault = Some(vault)),
+                    Err(VaultError::WrongPassphrase) => error.set(Some(t("unlock.wrong"))),
+                    Err(e) => error.set(Some(format!("{}{}", t("unlock.failed"), e))),
+                }
+            });
+        }
+    };
+
+    // Nothing to ask for until the header was read.
+    if vault.header.is_none() {
+        return html! {};
+    }
+
+    html! {
+      <div class="flex items-center justify-center w-full h-full bg-gray-50 dark:bg-gray-800">
+        <form class="flex flex-col gap-4 w-full max-w-sm p-6" onsubmit={on_submit}>
+          <div class="flex items-center gap-2 text-lg font-semibold text-gray-900 dark:text-white">
+            <LockIcon />
+            { t("unlock.title") }
+          </div>
+          <div class="text-sm text-gray-900 dark:text-gray-300">{ t("unlock.message") }</div>
+          <input
+            type="password"
+            class={INPUT_CLASS}
+            placeholder={t("unlock.passphrase")}
+            autofocus=true
+            value={(*passphrase).clone()}
+            oninput={
+              let passphrase = passphrase.clone();
+              move |e: InputEvent| {
+                  let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+                  passphrase.set(input.value());
+              }
+            }
+          />
+          if let Some(error) = &*error {
+            <div class="text-sm text-red-500">{ error }</div>
+          }
+          <button class="btn btn-primary" type="submit" disabled={*unlocking || passphrase.is_empty()}>
+            { if *unlocking { t("unlock.unlocking") } else { t("unlock.button") } }
+          </button>
+        </form>
+      </div>
+    }
+}
+
+/// Menu entries to turn the vault on, lock it or turn it off again.
+#[function_component]
+pub(crate) fn VaultMenu() -> Html {
+    let translate = use_translation(vec!["vault".to_string()]);
+    let t = move |key: &str| translate(key.to_string(), Some(Namespace { ns: "vault".to_string() }));
+    let (vault, vault_dispatch) = use_store::<VaultSlice>();
+    let is_setup_open = use_state(|| false);
+    let is_disable_open = use_state(|| false);
+
+    let on_lock = |_e: MouseEvent| {
+        // The key only lives in this page.
+        let _ = gloo_utils::window().location().reload();
+    };
+    let handle_disable = {
+        let is_disable_open = is_disable_open.clone();
+        move |_e| {
+            is_disable_open.set(false);
+            vault_dispatch.reduce_mut(|s| {
+                s.header = None;
+                s.vault = None;
+            });
+        }
+    };
+    let set_is_setup_open = {
+        let is_setup_open = is_setup_open.clone();
+        move |open: bool| is_setup_open.set(open)
+    };
+    let set_is_disable_open = {
+        let is_disable_open = is_disable_open.clone();
+        move |open: bool| is_disable_open.set(open)
+    };
+
+    html! {
+      <>
+        if vault.vault.is_none() {
+          <a
+            class={MENU_ITEM_CLASS}
+            onclick={
+              let is_setup_open = is_setup_open.clone();
+              move |_e| is_setup_open.set(true)
+            }
+          >
+            <LockIcon />
+            { t("menu.encrypt") }
+          </a>
+        } else {
+          <a class={MENU_ITEM_CLASS} onclick={on_lock}>
+            <LockIcon />
+            { t("menu.lock") }
+          </a>
+          <a
+            class={MENU_ITEM_CLASS}
+            onclick={
+              let is_disable_open = is_disable_open.clone();
+              move |_e| is_disable_open.set(true)
+            }
+          >
+            { t("menu.disable") }
+          </a>
+        }
+        if *is_setup_open {
+          <VaultSetup set_is_modal_open={set_is_setup_open} />
+        }
+        if *is_disable_open {
+          <PopupModal
+            title={t("disable.title")}
+            message={Some(t("disable.message"))}
+            set_is_modal_open={set_is_disable_open}
+            handle_confirm={handle_disable}
+          >
+            <></>
+          </PopupModal>
+        }
+      </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct VaultSetupProps {
+    set_is_modal_open: Callback<bool>,
+}
+
+/// Asks for the passphrase twice and turns the vault on.
+#[function_component]
+fn VaultSetup(VaultSetupProps { set_is_modal_open }: &VaultSetupProps) -> Html {
+    let translate = use_translation(vec!["vault".to_string()]);
+    let t = move |key: &str| translate(key.to_string(), Some(Namespace { ns: "vault".to_string() }));
+    let (_, vault_dispatch) = use_store::<VaultSlice>();
+    let passphrase = use_state(String::new);
+    let confirmation = use_state(String::new);
+    let creating = use_state(|| false);
+    let error = use_state(|| None::<String>);
+
+    let handle_confirm = {
+        let passphrase = passphrase.clone();
+        let confirmation = confirmation.clone();
+        let creating = creating.clone();
+        let error = error.clone();
+        let set_is_modal_open = set_is_modal_open.clone();
+        let t = t.clone();
+        move |_e| {
+            if *creating {
+                return;
+            }
+            if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+                error.set(Some(t("setup.tooShort")));
+                return;
+            }
+            if *passphrase != *confirmation {
+                error.set(Some(t("setup.mismatch")));
+                return;
+            }
+            creating.set(true);
+            let passphrase = (*passphrase).clone();
+            let creating = creating.clone();
+            let error = error.clone();
+            let set_is_modal_open = set_is_modal_open.clone();
+            let vault_dispatch = vault_dispatch.clone();
+            let t = t.clone();
+            spawn_local(async move {
+                let created = async {
+                    let salt = random_bytes(SALT_LEN)?;
+                    Vault::create(WebCryptoPassphrase::import(&passphrase).await?, &salt).await
+                };
+                match created.await {
+                    Ok(vault) => {
+                        vault_dispatch.reduce_mut(|s| {
+                            s.header = Some(vault.header().clone());
+                            s.vault = Some(vault);
+                        });
+                        set_is_modal_open.emit(false);
+                    }
+                    Err(e) => {
+                        creating.set(false);
+                        error.set(Some(format!("{}{}", t("setup.failed"), e)));
+                    }
+                }
+            });
+        }
+    };
+
+    let field = |label: &str, value: &UseStateHandle<String>| {
+        let value = value.clone();
+        html! {
+          <div class="flex gap-2 items-center mb-4">
+            <div class="min-w-fit text-gray-900 dark:text-gray-300 text-sm">{ t(label) }</div>
+            <input
+              type="password"
+              class={INPUT_CLASS}
+              value={(*value).clone()}
+              oninput={move |e: InputEvent| {
+                  let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+                  value.set(input.value());
+              }}
+            />
+          </div>
+        }
+    };
+
+    html! {
+      <PopupModal
+        title={t("setup.title")}
+        message={Some(t("setup.message"))}
+        set_is_modal_open={set_is_modal_open.clone()}
+        {handle_confirm}
+      >
+        <div class="p-6 border-b border-gray-200 dark:border-gray-600">
+          { field("setup.passphrase", &passphrase) }
+          { field("setup.confirm", &confirmation) }
+          if *creating {
+            <div class="text-sm text-gray-900 dark:text-gray-300">{ t("setup.creating") }</div>
+          }
+          if let Some(error) = &*error {
+            <div class="text-sm text-red-500">{ error }</div>
+          }
+        </div>
+      </PopupModal>
+    }
+}
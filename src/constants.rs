@@ -1,6 +1,8 @@
 #![allow(unused)]
 use std::collections::HashMap;
 
+use crate::types::{api::ProviderKind, model::{ModelCapabilities, ModelInfo}};
+
 pub const CODE_LANGUAGE_SUBSET: &[&str] = &[
     "python",
     "javascript",
@@ -71,45 +73,7 @@ pub const SELECTABLE_LANGUAGES: &[&str] = &[
     "zh-TW",
 ];
 
-// Define a struct for cost details (prompt and completion)
-#[derive(Debug)]
-pub struct CostDetail {
-    pub price: f64,
-    pub unit: u32,
-}
-
-// Define a struct for model cost (prompt and completion costs)
-#[derive(Debug)]
-pub struct ModelCost {
-    pub prompt: CostDetail,
-    pub completion: CostDetail,
-}
-
 lazy_static::lazy_static! {
-    pub static ref MODEL_MAX_TOKEN: HashMap<&'static str, i32> = {
-        let mut m = HashMap::new();
-        m.insert("gpt-3.5-turbo", 4096);
-        m.insert("gpt-3.5-turbo-0301", 4096);
-        m.insert("gpt-3.5-turbo-0613", 4096);
-        m.insert("gpt-3.5-turbo-16k", 16384);
-        m.insert("gpt-3.5-turbo-16k-0613", 16384);
-        m.insert("gpt-3.5-turbo-1106", 16384);
-        m.insert("gpt-3.5-turbo-0125", 16384);
-        m.insert("gpt-4", 8192);
-        m.insert("gpt-4-0314", 8192);
-        m.insert("gpt-4-0613", 8192);
-        m.insert("gpt-4-32k", 32768);
-        m.insert("gpt-4-32k-0314", 32768);
-        m.insert("gpt-4-32k-0613", 32768);
-        m.insert("gpt-4-1106-preview", 128000);
-        m.insert("gpt-4-0125-preview", 128000);
-        m.insert("gpt-4-turbo", 128000);
-        m.insert("gpt-4-turbo-2024-04-09", 128000);
-        m.insert("gpt-4o", 128000);
-        m.insert("gpt-4o-2024-05-13", 128000);
-        m
-    };
-
     pub static ref LANGUAGE_CODE_TO_NAME: HashMap<&'static str, &'static str> = {
         let mut m = HashMap::new();
         // m.insert("ar", "العربية");
@@ -137,99 +101,60 @@ lazy_static::lazy_static! {
         m
     };
 
-    pub static ref MODEL_COST: HashMap<&'static str, ModelCost> = {
-        let model_cost: HashMap<&str, ModelCost> = HashMap::from([
-            (
-                "gpt-3.5-turbo",
-                ModelCost {
-                    prompt: CostDetail { price: 0.0015, unit: 1000 },
-                    completion: CostDetail { price: 0.002, unit: 1000 },
-                },
-            ),
-            (
-                "gpt-3.5-turbo-0301",
-                ModelCost {
-                    prompt: CostDetail { price: 0.0015, unit: 1000 },
-                    completion: CostDetail { price: 0.002, unit: 1000 },
-                },
-            ),
-            (
-                "gpt-3.5-turbo-0613",
-                ModelCost {
-                    prompt: CostDetail { price: 0.0015, unit: 1000 },
-                    completion: CostDetail { price: 0.002, unit: 1000 },
-                },
-            ),
-            (
-                "gpt-3.5-turbo-16k",
-                ModelCost {
-                    prompt: CostDetail { price: 0.003, unit: 1000 },
-                    completion: CostDetail { price: 0.004, unit: 1000 },
-                },
-            ),
-            (
-                "gpt-3.5-turbo-16k-0613",
-                ModelCost {
-                    prompt: CostDetail { price: 0.003, unit: 1000 },
-                    completion: CostDetail { price: 0.004, unit: 1000 },
-                },
-            ),
-            (
-                "gpt-3.5-turbo-1106",
-                ModelCost {
-                    prompt: CostDetail { price: 0.001, unit: 1000 },
-                    completion: CostDetail { price: 0.0015, unit: 1000 },
-                },
-            ),
-            (
-                "gpt-3.5-turbo-0125",
-                ModelCost {
-                    prompt: CostDetail { price: 0.0005, unit: 1000 },
-                    completion: CostDetail { price: 0.0015, unit: 1000 },
-                },
-            ),
-            (
-                "gpt-4",
-                ModelCost {
-                    prompt: CostDetail { price: 0.03, unit: 1000 },
-                    completion: CostDetail { price: 0.06, unit: 1000 },
-                },
-            ),
-            (
-                "gpt-4o",
-                ModelCost {
-                    prompt: CostDetail { price: 0.005, unit: 1000 },
-                    completion: CostDetail { price: 0.015, unit: 1000 },
-                },
-            ),
-            (
-                "gpt-4o-2024-05-13",
-                ModelCost {
-                    prompt: CostDetail { price: 0.005, unit: 1000 },
-                    completion: CostDetail { price: 0.015, unit: 1000 },
-                },
-            ),
-        ]);
-        model_cost
-    };
+    /// Built-in model registry; extended at runtime, see `types::model::ModelRegistry`.
+    pub static ref DEFAULT_MODELS: Vec<ModelInfo> = vec![
+        model("gpt-3.5-turbo", "GPT-3.5 Turbo", ProviderKind::OpenAI, 4096, 4096, (0.0015, 0.002), (false, true)),
+        model("gpt-3.5-turbo-16k", "GPT-3.5 Turbo 16K", ProviderKind::OpenAI, 16384, 4096, (0.003, 0.004), (false, true)),
+        model("gpt-3.5-turbo-1106", "GPT-3.5 Turbo (1106)", ProviderKind::OpenAI, 16384, 4096, (0.001, 0.0015), (false, true)),
+        model("gpt-3.5-turbo-0125", "GPT-3.5 Turbo (0125)", ProviderKind::OpenAI, 16384, 4096, (0.0005, 0.0015), (false, true)),
+        model("gpt-4", "GPT-4", ProviderKind::OpenAI, 8192, 8192, (0.03, 0.06), (false, true)),
+        model("gpt-4-32k", "GPT-4 32K", ProviderKind::OpenAI, 32768, 32768, (0.06, 0.12), (false, true)),
+        model("gpt-4-1106-preview", "GPT-4 Turbo Preview (1106)", ProviderKind::OpenAI, 128000, 4096, (0.01, 0.03), (false, true)),
+        model("gpt-4-0125-preview", "GPT-4 Turbo Preview (0125)", ProviderKind::OpenAI, 128000, 4096, (0.01, 0.03), (false, true)),
+        model("gpt-4-turbo", "GPT-4 Turbo", ProviderKind::OpenAI, 128000, 4096, (0.01, 0.03), (true, true)),
+        model("gpt-4-turbo-2024-04-09", "GPT-4 Turbo (2024-04-09)", ProviderKind::OpenAI, 128000, 4096, (0.01, 0.03), (true, true)),
+        model("gpt-4o", "GPT-4o", ProviderKind::OpenAI, 128000, 4096, (0.005, 0.015), (true, true)),
+        model("gpt-4o-2024-05-13", "GPT-4o (2024-05-13)", ProviderKind::OpenAI, 128000, 4096, (0.005, 0.015), (true, true)),
+        model("claude-3-5-sonnet-latest", "Claude 3.5 Sonnet", ProviderKind::Anthropic, 200000, 8192, (0.003, 0.015), (true, true)),
+        model("claude-3-5-haiku-latest", "Claude 3.5 Haiku", ProviderKind::Anthropic, 200000, 8192, (0.0008, 0.004), (false, true)),
+        model("claude-3-opus-latest", "Claude 3 Opus", ProviderKind::Anthropic, 200000, 4096, (0.015, 0.075), (true, true)),
+    ];
 }
 
-pub const AVAILABLE_ENDPOINTS: &'static [&'static str] = &["https://api.openai.com/v1/chat/completions"];
+fn model(
+    id: &str,
+    name: &str,
+    provider: ProviderKind,
+    context_window: i32,
+    max_output: i32,
+    (prompt_price, completion_price): (f64, f64),
+    (vision, tools): (bool, bool),
+) -> ModelInfo {
+    ModelInfo {
+        id: id.to_string(),
+        name: name.to_string(),
+        provider,
+        context_window,
+        max_output,
+        prompt_price,
+        completion_price,
+        capabilities: ModelCapabilities { vision, tools, streaming: true },
+    }
+}
 
-pub const MODEL_OPTIONS: &[&str] = &[
-    "gpt-3.5-turbo",
-  "gpt-3.5-turbo-16k",
-  "gpt-3.5-turbo-1106",
-  "gpt-3.5-turbo-0125",
-  "gpt-4",
-  "gpt-4-32k",
-  "gpt-4-1106-preview",
-  "gpt-4-0125-preview",
-  "gpt-4-turbo",
-  "gpt-4-turbo-2024-04-09",
-  "gpt-4o",
-  "gpt-4o-2024-05-13",
-  // "gpt-3.5-turbo-0301",
-  // "gpt-4-0314",
-  // "gpt-4-32k-0314",
+pub const AVAILABLE_ENDPOINTS: &'static [&'static str] = &[
+    "https://api.openai.com/v1/chat/completions",
+    "https://api.anthropic.com/v1/messages",
+    "http://localhost:11434/api/chat",
+    "http://localhost:8080/completion",
 ];
+
+/// Prompt asking for a chat title. `{user}` and `{assistant}` are replaced by
+/// the first exchange of the chat.
+pub const DEFAULT_TITLE_PROMPT: &str = "Generate a title in less than 6 words for the conversation below. \
+Write the title in the same language as the conversation and reply with the title only.\n\
+User: {user}\n\
+Assistant: {assistant}";
+
+/// Context window assumed for models we know nothing about (local and custom models).
+pub const DEFAULT_MODEL_MAX_TOKEN: i32 = 4096;
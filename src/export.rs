@@ -0,0 +1,503 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use pulldown_cmark::{Event, Options, Parser, html};
+
+use crate::{
+    hooks::submit::count_tokens,
+    types::chat::{ChatInterface, DEFAULT_MODEL, FolderCollection, MessageInterface, Role},
+};
+
+/// Human readable formats a chat can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DocumentFormat {
+    Markdown,
+    Html,
+}
+
+impl DocumentFormat {
+    pub(crate) const ALL: [DocumentFormat; 2] = [DocumentFormat::Markdown, DocumentFormat::Html];
+
+    fn extension(&self) -> &'static str {
+        match self {
+            DocumentFormat::Markdown => "md",
+            DocumentFormat::Html => "html",
+        }
+    }
+
+    fn mime(&self) -> &'static str {
+        match self {
+            DocumentFormat::Markdown => "text/markdown",
+            DocumentFormat::Html => "text/html",
+        }
+    }
+}
+
+impl std::fmt::Display for DocumentFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DocumentFormat::Markdown => "Markdown",
+            DocumentFormat::Html => "HTML",
+        }
+        .fmt(f)
+    }
+}
+
+/// A file ready to be handed to `utils::download_bytes`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ExportFile {
+    pub(crate) name: String,
+    pub(crate) mime: &'static str,
+    pub(crate) data: Vec<u8>,
+}
+
+/// Renders `chats` in `format`: a single document for one chat, otherwise a
+/// zip with one document per chat, grouped into a directory per folder.
+pub(crate) fn export_chats(
+    chats: &[&ChatInterface],
+    folders: &FolderCollection,
+    format: DocumentFormat,
+    now: NaiveDateTime,
+) -> ExportFile {
+    let render = |chat: &ChatInterface| match format {
+        DocumentFormat::Markdown => chat_to_markdown(chat, folders, now.date()),
+        DocumentFormat::Html => chat_to_html(chat, folders, now.date()),
+    };
+    if let [chat] = chats {
+        return ExportFile {
+            name: format!("{}.{}", file_stem(chat_title(chat)), format.extension()),
+            mime: format.mime(),
+            data: render(chat).into_bytes(),
+        };
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    let mut files = Vec::new();
+    for chat in chats {
+        let directory = folder_name(chat, folders).map(|f| format!("{}/", file_stem(f))).unwrap_or_default();
+        let stem = format!("{}{}", directory, file_stem(chat_title(chat)));
+        let mut name = format!("{}.{}", stem, format.extension());
+        let mut n = 1;
+        while names.contains(&name) {
+            n += 1;
+            name = format!("{}-{}.{}", stem, n, format.extension());
+        }
+        names.push(name.clone());
+        files.push((name, render(chat).into_bytes()));
+    }
+    ExportFile {
+        name: format!("chats-{}.zip", now.format("%Y-%m-%d")),
+        mime: "application/zip",
+        data: zip(&files, now),
+    }
+}
+
+/// Markdown with a YAML front matter block, one section per message.
+pub(crate) fn chat_to_markdown(chat: &ChatInterface, folders: &FolderCollection, today: NaiveDate) -> String {
+    let mut markdown = String::from("---\n");
+    for (key, value) in front_matter(chat, folders, today) {
+        markdown.push_str(&format!("{}: {}\n", key, yaml_string(&value)));
+    }
+    markdown.push_str(&format!("---\n\n# {}\n", chat_title(chat)));
+    for message in chat.messages.iter().filter(|m| !m.content.trim().is_empty()) {
+        markdown.push_str(&format!("\n## {}\n\n{}\n", speaker(&message.role), message.content.trim_end()));
+    }
+    markdown
+}
+
+/// A standalone page with inline styles; message contents are rendered as
+/// Markdown, with any raw HTML in them escaped.
+pub(crate) fn chat_to_html(chat: &ChatInterface, folders: &FolderCollection, today: NaiveDate) -> String {
+    let title = escape_html(chat_title(chat));
+    let meta = front_matter(chat, folders, today)
+        .into_iter()
+        .skip(1)
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| format!("<span>{}: {}</span>", key, escape_html(&value)))
+        .collect::<Vec<_>>()
+        .join("");
+    let messages = chat
+        .messages
+        .iter()
+        .filter(|m| !m.content.trim().is_empty())
+        .map(|m| {
+            format!(
+                "<section class=\"message {}\"><h2>{}</h2>{}</section>\n",
+                m.role,
+                speaker(&m.role),
+                markdown_to_html(&m.content)
+            )
+        })
+        .collect::<String>();
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<p class=\"meta\">{meta}</p>\n{messages}</body>\n</html>\n"
+    )
+}
+
+const HTML_STYLE: &str = "body{max-width:48rem;margin:2rem auto;padding:0 1rem;font-family:system-ui,sans-serif;line-height:1.6;color:#1f2937}\
+.meta{color:#6b7280;font-size:.875rem}.meta span{margin-right:1rem}\
+.message{border-radius:.5rem;padding:.25rem 1rem;margin:1rem 0;background:#f9fafb}\
+.message.user{background:#eef2ff}.message.system{background:#fefce8}\
+.message h2{font-size:.75rem;text-transform:uppercase;letter-spacing:.05em;color:#6b7280}\
+pre{background:#111827;color:#f9fafb;padding:.75rem;border-radius:.375rem;overflow-x:auto}\
+code{font-family:ui-monospace,monospace;font-size:.875em}\
+table{border-collapse:collapse}td,th{border:1px solid #d1d5db;padding:.25rem .5rem}\
+blockquote{border-left:3px solid #d1d5db;margin-left:0;padding-left:1rem;color:#4b5563}";
+
+fn markdown_to_html(markdown: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    let parser = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        event => event,
+    });
+    let mut output = String::new();
+    html::push_html(&mut output, parser);
+    output
+}
+
+/// `title`, `model`, `date` and `folder`, in that order. The date is the one
+/// of the first message that has a timestamp, otherwise `today`.
+fn front_matter(chat: &ChatInterface, folders: &FolderCollection, today: NaiveDate) -> Vec<(&'static str, String)> {
+    let date = chat
+        .messages
+        .iter()
+        .find_map(|m| m.created_at)
+        .and_then(chrono::DateTime::from_timestamp_millis)
+        .map(|d| d.date_naive())
+        .unwrap_or(today);
+    vec![
+        ("title", chat_title(chat).to_string()),
+        ("model", chat.config.as_ref().map(|c| c.model.clone()).unwrap_or_default()),
+        ("date", date.format("%Y-%m-%d").to_string()),
+        ("folder", folder_name(chat, folders).unwrap_or_default().to_string()),
+    ]
+}
+
+fn chat_title(chat: &ChatInterface) -> &str {
+    chat.title.as_deref().filter(|t| !t.is_empty()).unwrap_or("New Chat")
+}
+
+fn folder_name<'a>(chat: &ChatInterface, folders: &'a FolderCollection) -> Option<&'a str> {
+    chat.folder.as_ref().and_then(|id| folders.get(id)).map(|f| f.name.as_str())
+}
+
+fn speaker(role: &Role) -> &'static str {
+    match role {
+        Role::User => "User",
+        Role::Assistant => "Assistant",
+        Role::System => "System",
+    }
+}
+
+/// JSON strings are valid double-quoted YAML scalars.
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// File name for `title`, safe on every platform.
+fn file_stem(title: &str) -> String {
+    let stem = title
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let stem: String = stem.chars().take(60).collect();
+    if stem.is_empty() { "chat".to_string() } else { stem }
+}
+
+/// Writes an uncompressed zip archive. Chats are small text files, so
+/// deflating them isn't worth pulling in a compression library.
+fn zip(files: &[(String, Vec<u8>)], modified: NaiveDateTime) -> Vec<u8> {
+    let time = ((modified.hour() << 11) | (modified.minute() << 5) | (modified.second() / 2)) as u16;
+    let date = (((modified.year().max(1980) - 1980) as u32) << 9 | (modified.month() << 5) | modified.day()) as u16;
+
+    let mut archive = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in files {
+        let offset = archive.len() as u32;
+        let crc = crc32(data);
+        // Version needed, flags (bit 11: UTF-8 names), method (stored), time, date,
+        // CRC, compressed and uncompressed size, name length, extra length.
+        let mut header = Vec::new();
+        header.extend(20u16.to_le_bytes());
+        header.extend(0x0800u16.to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend(time.to_le_bytes());
+        header.extend(date.to_le_bytes());
+        header.extend(crc.to_le_bytes());
+        header.extend((data.len() as u32).to_le_bytes());
+        header.extend((data.len() as u32).to_le_bytes());
+        header.extend((name.len() as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+
+        archive.extend(0x04034b50u32.to_le_bytes());
+        archive.extend(&header);
+        archive.extend(name.as_bytes());
+        archive.extend(data);
+
+        // Version made by, the local header, then comment length, disk number,
+        // internal and external attributes and the local header's offset.
+        central.extend(0x02014b50u32.to_le_bytes());
+        central.extend(20u16.to_le_bytes());
+        central.extend(&header);
+        central.extend([0u8; 12]);
+        central.extend(offset.to_le_bytes());
+        central.extend(name.as_bytes());
+    }
+    let central_offset = archive.len() as u32;
+    archive.extend(&central);
+
+    archive.extend(0x06054b50u32.to_le_bytes());
+    archive.extend([0u8; 4]);
+    archive.extend((files.len() as u16).to_le_bytes());
+    archive.extend((files.len() as u16).to_le_bytes());
+    archive.extend((central.len() as u32).to_le_bytes());
+    archive.extend(central_offset.to_le_bytes());
+    archive.extend(0u16.to_le_bytes());
+    archive
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Largest example OpenAI accepts for fine-tuning `gpt-3.5-turbo`.
+pub(crate) const DEFAULT_FINE_TUNE_TOKEN_LIMIT: usize = 16_385;
+
+/// Which parts of the selected chats become fine-tuning examples.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FineTuneOptions {
+    pub(crate) drop_system: bool,
+    /// Only the branch currently shown; otherwise every branch of every chat
+    /// becomes an example of its own.
+    pub(crate) active_branch_only: bool,
+    /// Examples with more tokens than this are skipped.
+    pub(crate) token_limit: usize,
+}
+
+impl Default for FineTuneOptions {
+    fn default() -> Self {
+        Self {
+            drop_system: false,
+            active_branch_only: true,
+            token_limit: DEFAULT_FINE_TUNE_TOKEN_LIMIT,
+        }
+    }
+}
+
+/// Why a conversation didn't make it into the dataset.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SkipReason {
+    /// There is nothing for the model to learn from.
+    NoAssistantMessage,
+    TooLong { tokens: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SkippedExample {
+    pub(crate) title: String,
+    /// Number of the branch within its chat, starting at 1.
+    pub(crate) branch: usize,
+    pub(crate) reason: SkipReason,
+}
+
+impl std::fmt::Display for SkippedExample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (branch {}): ", self.title, self.branch)?;
+        match &self.reason {
+            SkipReason::NoAssistantMessage => write!(f, "no assistant message"),
+            SkipReason::TooLong { tokens } => write!(f, "{} tokens", tokens),
+        }
+    }
+}
+
+/// The JSONL dataset and the examples that were left out of it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct FineTuneDataset {
+    pub(crate) jsonl: String,
+    pub(crate) examples: usize,
+    pub(crate) skipped: Vec<SkippedExample>,
+}
+
+/// Turns `chats` into OpenAI chat fine-tuning JSONL, one
+/// `{"messages": [...]}` object per line. Trailing user messages are cut
+/// off, since only assistant messages are trained on.
+pub(crate) fn fine_tune_dataset(chats: &[&ChatInterface], options: &FineTuneOptions) -> FineTuneDataset {
+    let mut dataset = FineTuneDataset::default();
+    for chat in chats {
+        let model = chat.config.as_ref().map(|c| c.model.as_str()).unwrap_or(DEFAULT_MODEL);
+        let paths = if options.active_branch_only {
+            vec![chat.messages.clone()]
+        } else {
+            branches(&chat.messages)
+        };
+        for (i, path) in paths.into_iter().enumerate() {
+            let mut messages: Vec<MessageInterface> = path
+                .into_iter()
+                .filter(|m| !m.content.trim().is_empty())
+                .filter(|m| !(options.drop_system && m.role == Role::System))
+                .collect();
+            while messages.last().is_some_and(|m| m.role != Role::Assistant) {
+                messages.pop();
+            }
+            let skip = |reason| SkippedExample {
+                title: chat_title(chat).to_string(),
+                branch: i + 1,
+                reason,
+            };
+            if messages.is_empty() {
+                dataset.skipped.push(skip(SkipReason::NoAssistantMessage));
+                continue;
+            }
+            let tokens = count_tokens(&messages, model);
+            if tokens > options.token_limit {
+                dataset.skipped.push(skip(SkipReason::TooLong { tokens }));
+                continue;
+            }
+            let messages = messages
+                .iter()
+                .map(|m| serde_json::json!({ "role": m.role.to_string(), "content": m.content }))
+                .collect::<Vec<_>>();
+            dataset.jsonl.push_str(&serde_json::json!({ "messages": messages }).to_string());
+            dataset.jsonl.push('\n');
+            dataset.examples += 1;
+        }
+    }
+    dataset
+}
+
+/// Every path through a message tree, the active one first.
+fn branches(messages: &[MessageInterface]) -> Vec<Vec<MessageInterface>> {
+    if messages.is_empty() {
+        return vec![Vec::new()];
+    }
+    let active = messages[0].branch.min(messages[0].alternatives.len());
+    let mut versions = ChatInterface::siblings(messages.to_vec());
+    versions[..=active].rotate_right(1);
+    versions
+        .into_iter()
+        .flat_map(|version| {
+            let head = version[0].clone();
+            branches(&version[1..]).into_iter().map(move |mut tail| {
+                tail.insert(0, head.clone());
+                tail
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::chat::{ConfigInterface, Folder, MessageInterface};
+
+    fn chat(title: &str, folder: Option<&str>) -> ChatInterface {
+        ChatInterface {
+            id: title.to_string(),
+            title: Some(title.to_string()),
+            folder: folder.map(|f| f.to_string()),
+            messages: vec![
+                MessageInterface { role: Role::User, content: "Show <b>bold</b>".into(), created_at: Some(1_700_000_000_000), ..Default::default() },
+                MessageInterface { role: Role::Assistant, content: "**bold**".into(), ..Default::default() },
+            ],
+            config: Some(ConfigInterface { model: "gpt-4o".into(), ..Default::default() }),
+            ..Default::default()
+        }
+    }
+
+    fn folders() -> FolderCollection {
+        let folder = Folder { id: "f".into(), name: "Work: \"Q3\"".into(), expanded: true, order: 0, color: None };
+        FolderCollection::from([("f".to_string(), folder)])
+    }
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 5, 6).unwrap().and_hms_opt(7, 8, 10).unwrap()
+    }
+
+    #[test]
+    fn markdown_has_front_matter_and_sections() {
+        let markdown = chat_to_markdown(&chat("Review", Some("f")), &folders(), now().date());
+        assert_eq!(
+            markdown,
+            "---\ntitle: \"Review\"\nmodel: \"gpt-4o\"\ndate: \"2023-11-14\"\nfolder: \"Work: \\\"Q3\\\"\"\n---\n\n# Review\n\n## User\n\nShow <b>bold</b>\n\n## Assistant\n\n**bold**\n"
+        );
+    }
+
+    #[test]
+    fn html_renders_markdown_and_escapes_raw_html() {
+        let html = chat_to_html(&chat("A <script>", None), &folders(), now().date());
+        assert!(html.contains("<title>A &lt;script&gt;</title>"));
+        assert!(html.contains("Show &lt;b&gt;bold&lt;/b&gt;"));
+        assert!(html.contains("<strong>bold</strong>"));
+    }
+
+    #[test]
+    fn several_chats_are_zipped_by_folder() {
+        let (a, b, c) = (chat("Plan", Some("f")), chat("Plan", Some("f")), chat("Notes / ideas", None));
+        let file = export_chats(&[&a, &b, &c], &folders(), DocumentFormat::Markdown, now());
+        assert_eq!(file.name, "chats-2024-05-06.zip");
+        let names = ["Work-Q3/Plan.md", "Work-Q3/Plan-2.md", "Notes-ideas.md"];
+        for name in names {
+            assert!(file.data.windows(name.len()).any(|w| w == name.as_bytes()), "{}", name);
+        }
+        // End of central directory record with three entries.
+        let end = &file.data[file.data.len() - 22..];
+        assert_eq!(&end[..4], &0x06054b50u32.to_le_bytes());
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 3);
+
+        let single = export_chats(&[&c], &folders(), DocumentFormat::Html, now());
+        assert_eq!((single.name.as_str(), single.mime), ("Notes-ideas.html", "text/html"));
+    }
+
+    #[test]
+    fn fine_tune_examples_per_branch_with_report() {
+        let message = |role, content: &str| MessageInterface { role, content: content.into(), ..Default::default() };
+        let mut branched = chat("Branched", None);
+        branched.messages.insert(0, message(Role::System, "be brief"));
+        branched.branch_off(2, vec![message(Role::Assistant, "__bold__")]);
+        branched.messages.push(message(Role::User, "thanks"));
+        let unanswered = ChatInterface { messages: vec![message(Role::User, "hello?")], ..chat("Unanswered", None) };
+
+        let options = FineTuneOptions { drop_system: true, ..Default::default() };
+        let dataset = fine_tune_dataset(&[&branched, &unanswered], &options);
+        assert_eq!(dataset.examples, 1);
+        assert_eq!(
+            dataset.jsonl,
+            "{\"messages\":[{\"content\":\"Show <b>bold</b>\",\"role\":\"user\"},{\"content\":\"__bold__\",\"role\":\"assistant\"}]}\n"
+        );
+        assert_eq!(dataset.skipped[0].to_string(), "Unanswered (branch 1): no assistant message");
+
+        let options = FineTuneOptions { active_branch_only: false, token_limit: 100, ..Default::default() };
+        let dataset = fine_tune_dataset(&[&branched], &options);
+        assert_eq!(dataset.examples, 2);
+        let lines: Vec<&str> = dataset.jsonl.lines().collect();
+        assert!(lines[0].contains("be brief") && lines[0].contains("__bold__"));
+        assert!(lines[1].contains("**bold**"));
+
+        let options = FineTuneOptions { token_limit: 5, ..Default::default() };
+        let dataset = fine_tune_dataset(&[&branched], &options);
+        assert!(matches!(dataset.skipped[0].reason, SkipReason::TooLong { tokens } if tokens > 5));
+    }
+
+    #[test]
+    fn crc32_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
@@ -1,175 +1,347 @@
-use reqwest::{Client, multipart::{Form, Part}};
-use serde::{Deserialize, Serialize};
-use anyhow::{Result, anyhow};
-use std::io::Read;
-use tokio::time::{sleep, Duration};
+//! The bits of Google Drive and Google sign-in that cloud sync needs.
 
-use crate::types::api::{GoogleFileList, GoogleFileResource, GoogleTokenInfo};
+use std::collections::HashMap;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PersistStorageState {
-    // Define your persisted state fields
+use reqwest::{Client, RequestBuilder, Response, StatusCode, header::CONTENT_TYPE};
+use thiserror::Error;
+use url::Url;
+
+use crate::types::api::{GoogleFileList, GoogleFileResource};
+
+const GOOGLE_APIS: &str = "https://www.googleapis.com";
+const OAUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+/// Only gives access to files this app created.
+const DRIVE_SCOPE: &str = "https://www.googleapis.com/auth/drive.file";
+/// The file metadata every call asks for.
+const FILE_FIELDS: &str = "id,name,mimeType,modifiedTime,version";
+
+#[derive(Debug, Error)]
+pub(crate) enum DriveError {
+    #[error("the Google sign-in has expired")]
+    Unauthorized,
+    #[error("Google Drive returned {status}: {body}")]
+    Status { status: u16, body: String },
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
 }
 
-// Multipart helper
-async fn create_multipart_body(
-    metadata: &serde_json::Value,
-    file_path: &str,
-    boundary: &str,
-) -> Result<Form> {
-    // Serialize metadata into JSON string
-    let metadata_json = serde_json::to_string(&metadata)?;
-
-    // Create metadata part
-    let metadata_part = Part::text(metadata_json)
-        .mime_str("application/json; charset=UTF-8")?;
-
-    // Read the file contents
-    let mut file = std::fs::File::open(std::path::Path::new(file_path))?;
-    let mut file_contents = Vec::new();
-    file.read_to_end(&mut file_contents)?;
-
-    // Create file part
-    let file_part = Part::bytes(file_contents)
-        .mime_str("application/octet-stream")?; // Adjust MIME type as needed
-
-    // Construct multipart form with boundary
-    let form = Form::new()
-        .text(format!("--{}", boundary), "".to_string()) // Start boundary
-        .part("metadata", metadata_part)
-        .text(format!("--{}", boundary), "".to_string()) // File boundary
-        .part("file", file_part)
-        .text(format!("--{}--", boundary), "".to_string()); // End boundary
-
-    Ok(form)
+pub(crate) struct DriveClient {
+    client: Client,
+    api: String,
+    upload_api: String,
+    access_token: String,
 }
 
-// API functions
-pub async fn create_drive_file(
-    file_path: &str,
-    access_token: &str,
-) -> Result<GoogleFileResource> {
-    let client = Client::new();
-    let boundary = "better_chatgpt";
-    
-    let metadata = serde_json::json!({
-        "name": std::path::Path::new(file_path)
-            .file_name()
-            .unwrap()
-            .to_string_lossy(),
-        "mimeType": mime_guess::from_path(file_path)
-            .first_or_octet_stream()
-            .to_string()
-    });
-
-    let form = create_multipart_body(&metadata, file_path, boundary).await?;
-
-    let response = client
-        .post("https://www.googleapis.com/upload/drive/v3/files?uploadType=multipart")
-        .bearer_auth(access_token)
-        .multipart(form)
-        .send()
-        .await?;
-
-    if response.status().is_success() {
-        Ok(response.json().await?)
-    } else {
-        Err(anyhow!("Error uploading file: {}", response.status()))
+impl DriveClient {
+    pub(crate) fn new(access_token: String) -> Self {
+        Self::with_base_url(GOOGLE_APIS, access_token)
+    }
+
+    /// A client for a Drive compatible server at `base_url`.
+    pub(crate) fn with_base_url(base_url: &str, access_token: String) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        Self {
+            client: Client::new(),
+            api: format!("{}/drive/v3", base_url),
+            upload_api: format!("{}/upload/drive/v3", base_url),
+            access_token,
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response, DriveError> {
+        let response = request.bearer_auth(&self.access_token).send().await?;
+        match response.status() {
+            StatusCode::UNAUTHORIZED => Err(DriveError::Unauthorized),
+            status if status.is_success() => Ok(response),
+            status => Err(DriveError::Status {
+                status: status.as_u16(),
+                body: response.text().await.unwrap_or_default(),
+            }),
+        }
+    }
+
+    /// The most recently changed file called `name`.
+    pub(crate) async fn find_file(&self, name: &str) -> Result<Option<GoogleFileResource>, DriveError> {
+        let query = format!("name = '{}' and trashed = false", name.replace('\'', "\\'"));
+        let fields = format!("files({})", FILE_FIELDS);
+        let request = self.client.get(format!("{}/files", self.api)).query(&[
+            ("q", query.as_str()),
+            ("fields", fields.as_str()),
+            ("orderBy", "modifiedTime desc"),
+        ]);
+        let list: GoogleFileList = self.send(request).await?.json().await?;
+        Ok(list.files.into_iter().next())
     }
-}
 
-pub async fn get_drive_file<S: serde::de::DeserializeOwned>(
-    file_id: &str,
-    access_token: &str,
-) -> Result<S> {
-    let client = Client::new();
-    let response = client
-        .get(&format!("https://content.googleapis.com/drive/v3/files/{}?alt=media", file_id))
-        .bearer_auth(access_token)
-        .send()
-        .await?;
-
-    Ok(response.json().await?)
+    /// `None` when the file is gone.
+    pub(crate) async fn metadata(&self, id: &str) -> Result<Option<GoogleFileResource>, DriveError> {
+        let request = self
+            .client
+            .get(format!("{}/files/{}", self.api, id))
+            .query(&[("fields", FILE_FIELDS)]);
+        match self.send(request).await {
+            Ok(response) => Ok(Some(response.json().await?)),
+            Err(DriveError::Status { status: 404, .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub(crate) async fn download(&self, id: &str) -> Result<String, DriveError> {
+        let request = self
+            .client
+            .get(format!("{}/files/{}", self.api, id))
+            .query(&[("alt", "media")]);
+        Ok(self.send(request).await?.text().await?)
+    }
+
+    pub(crate) async fn create_file(&self, name: &str, data: &str) -> Result<GoogleFileResource, DriveError> {
+        let metadata = serde_json::json!({ "name": name, "mimeType": "application/json" });
+        let boundary = uuid::Uuid::new_v4().simple().to_string();
+        let request = self
+            .client
+            .post(format!("{}/files", self.upload_api))
+            .query(&[("uploadType", "multipart"), ("fields", FILE_FIELDS)])
+            .header(CONTENT_TYPE, format!("multipart/related; boundary={}", boundary))
+            .body(multipart_related(&metadata, data, &boundary));
+        Ok(self.send(request).await?.json().await?)
+    }
+
+    /// Replaces the content of the file.
+    pub(crate) async fn update_file(&self, id: &str, data: &str) -> Result<GoogleFileResource, DriveError> {
+        let request = self
+            .client
+            .patch(format!("{}/files/{}", self.upload_api, id))
+            .query(&[("uploadType", "media"), ("fields", FILE_FIELDS)])
+            .header(CONTENT_TYPE, "application/json")
+            .body(data.to_string());
+        Ok(self.send(request).await?.json().await?)
+    }
 }
 
-pub async fn list_drive_files(access_token: &str) -> Result<GoogleFileList> {
-    let client = Client::new();
-    let response = client
-        .get("https://www.googleapis.com/drive/v3/files?orderBy=createdTime desc")
-        .bearer_auth(access_token)
-        .send()
-        .await?;
+/// The body of a Drive multipart upload: the file metadata, then its content.
+fn multipart_related(metadata: &serde_json::Value, data: &str, boundary: &str) -> String {
+    format!(
+        "--{boundary}\r\n\
+         Content-Type: application/json; charset=UTF-8\r\n\r\n\
+         {metadata}\r\n\
+         --{boundary}\r\n\
+         Content-Type: application/json\r\n\r\n\
+         {data}\r\n\
+         --{boundary}--\r\n"
+    )
+}
 
-    Ok(response.json().await?)
+/// Where to send the user to sign in. Google redirects back to
+/// `redirect_uri` with the token in the fragment, see [`parse_oauth_redirect`].
+pub(crate) fn authorization_url(client_id: &str, redirect_uri: &str, state: &str) -> String {
+    let mut url = Url::parse(OAUTH_URL).expect("the OAuth URL is valid");
+    url.query_pairs_mut()
+        .append_pair("client_id", client_id)
+        .append_pair("redirect_uri", redirect_uri)
+        .append_pair("response_type", "token")
+        .append_pair("scope", DRIVE_SCOPE)
+        .append_pair("include_granted_scopes", "true")
+        .append_pair("state", state);
+    url.into()
 }
 
-pub async fn update_drive_file(
-    file_path: &str,
-    file_id: &str,
-    access_token: &str,
-) -> Result<GoogleFileResource> {
-    let client = Client::new();
-    let response = client
-        .patch(&format!("https://www.googleapis.com/upload/drive/v3/files/{}", file_id))
-        .bearer_auth(access_token)
-        .body(tokio::fs::read(file_path).await?)
-        .send()
-        .await?;
-
-    Ok(response.json().await?)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct OAuthToken {
+    pub(crate) access_token: String,
+    pub(crate) expires_in: u64,
+    /// Echoes the `state` passed to [`authorization_url`].
+    pub(crate) state: Option<String>,
 }
 
-pub async fn update_drive_file_name(
-    file_name: &str,
-    file_id: &str,
-    access_token: &str,
-) -> Result<GoogleFileResource> {
-    let client = Client::new();
-    let response = client
-        .patch(&format!("https://www.googleapis.com/drive/v3/files/{}", file_id))
-        .bearer_auth(access_token)
-        .json(&serde_json::json!({ "name": file_name }))
-        .send()
-        .await?;
-
-    Ok(response.json().await?)
+/// Reads the token off the URL fragment Google redirected back with.
+pub(crate) fn parse_oauth_redirect(fragment: &str) -> Option<OAuthToken> {
+    let params: HashMap<String, String> =
+        url::form_urlencoded::parse(fragment.trim_start_matches('#').as_bytes())
+            .into_owned()
+            .collect();
+    Some(OAuthToken {
+        access_token: params.get("access_token")?.clone(),
+        expires_in: params
+            .get("expires_in")
+            .and_then(|e| e.parse().ok())
+            .unwrap_or(3600),
+        state: params.get("state").cloned(),
+    })
 }
 
-pub async fn delete_drive_file(
-    file_id: &str,
-    access_token: &str,
-) -> Result<()> {
-    let client = Client::new();
-    let response = client
-        .delete(&format!("https://www.googleapis.com/drive/v3/files/{}", file_id))
-        .bearer_auth(access_token)
-        .send()
-        .await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        Err(anyhow!("Error deleting file: {}", response.status()))
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    /// Just enough of the Drive API to exercise [`DriveClient`]. Only the
+    /// token "valid" is accepted.
+    #[derive(Default)]
+    struct MockDrive {
+        files: Mutex<Vec<(GoogleFileResource, String)>>,
     }
-}
 
-pub async fn validate_google_oauth2_token(
-    access_token: &str,
-) -> Result<GoogleTokenInfo> {
-    let client = Client::new();
-    let response = client
-        .get(&format!("https://oauth2.googleapis.com/tokeninfo?access_token={}", access_token))
-        .send()
-        .await?;
+    impl MockDrive {
+        async fn start() -> (String, Arc<Self>) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base_url = format!("http://{}", listener.local_addr().unwrap());
+            let drive = Arc::new(Self::default());
+            tokio::spawn({
+                let drive = drive.clone();
+                async move {
+                    while let Ok((socket, _)) = listener.accept().await {
+                        let drive = drive.clone();
+                        tokio::spawn(async move { drive.serve(socket).await });
+                    }
+                }
+            });
+            (base_url, drive)
+        }
 
-    Ok(response.json().await?)
-}
+        /// What another device editing the file looks like.
+        fn edit_remotely(&self, data: &str) {
+            let mut files = self.files.lock().unwrap();
+            let (file, content) = &mut files[0];
+            Self::bump(file);
+            *content = data.to_string();
+        }
+
+        fn bump(file: &mut GoogleFileResource) {
+            let version = file.version.as_deref().map_or(0, |v| v.parse::<u64>().unwrap()) + 1;
+            file.version = Some(version.to_string());
+            file.modified_time = Some(format!("2026-10-18T12:00:{:02}Z", version));
+        }
+
+        async fn serve(&self, mut socket: TcpStream) {
+            let mut buffer = Vec::new();
+            let mut chunk = [0; 4096];
+            let head_end = loop {
+                let read = socket.read(&mut chunk).await.unwrap();
+                if read == 0 {
+                    return;
+                }
+                buffer.extend_from_slice(&chunk[..read]);
+                if let Some(i) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break i + 4;
+                }
+            };
+            let head = String::from_utf8_lossy(&buffer[..head_end]).to_lowercase();
+            let length = head
+                .lines()
+                .find_map(|l| l.strip_prefix("content-length:"))
+                .map_or(0, |l| l.trim().parse::<usize>().unwrap());
+            while buffer.len() < head_end + length {
+                let read = socket.read(&mut chunk).await.unwrap();
+                buffer.extend_from_slice(&chunk[..read]);
+            }
+            let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+            let body = String::from_utf8_lossy(&buffer[head_end..head_end + length]).to_string();
+
+            let (status, response) = if head.to_lowercase().contains("authorization: bearer valid\r\n") {
+                self.route(&head, &body)
+            } else {
+                (401, "{}".to_string())
+            };
+            let response = format!(
+                "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response.len(),
+                response
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+
+        fn route(&self, head: &str, body: &str) -> (u16, String) {
+            let mut request_line = head.split_whitespace();
+            let method = request_line.next().unwrap();
+            let url = Url::parse(&format!("http://mock{}", request_line.next().unwrap())).unwrap();
+            let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+            let mut files = self.files.lock().unwrap();
+            let json = |value: &GoogleFileResource| serde_json::to_string(value).unwrap();
+
+            match (method, url.path().split('/').collect::<Vec<_>>().as_slice()) {
+                ("GET", ["", "drive", "v3", "files"]) => {
+                    let name = query["q"].split('\'').nth(1).unwrap();
+                    let list = GoogleFileList {
+                        next_page_token: None,
+                        files: files.iter().filter(|(f, _)| f.name == name).map(|(f, _)| f.clone()).collect(),
+                    };
+                    (200, serde_json::to_string(&list).unwrap())
+                }
+                ("GET", ["", "drive", "v3", "files", id]) => match files.iter().find(|(f, _)| f.id == *id) {
+                    Some((_, content)) if query.get("alt").is_some_and(|a| a == "media") => (200, content.clone()),
+                    Some((file, _)) => (200, json(file)),
+                    None => (404, "{}".to_string()),
+                },
+                ("POST", ["", "upload", "drive", "v3", "files"]) => {
+                    let boundary = head.split("boundary=").nth(1).unwrap().lines().next().unwrap();
+                    let parts: Vec<_> = body.split(&format!("--{}", boundary)).collect();
+                    let part = |i: usize| parts[i].split("\r\n\r\n").nth(1).unwrap().trim_end().to_string();
+                    let mut file: GoogleFileResource = serde_json::from_str(&part(1)).unwrap();
+                    file.id = format!("file-{}", files.len() + 1);
+                    Self::bump(&mut file);
+                    files.push((file.clone(), part(2)));
+                    (200, json(&file))
+                }
+                ("PATCH", ["", "upload", "drive", "v3", "files", id]) => {
+                    match files.iter_mut().find(|(f, _)| f.id == *id) {
+                        Some((file, content)) => {
+                            Self::bump(file);
+                            *content = body.to_string();
+                            (200, json(file))
+                        }
+                        None => (404, "{}".to_string()),
+                    }
+                }
+                _ => (404, "{}".to_string()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn files_round_trip_through_drive() {
+        let (base_url, drive) = MockDrive::start().await;
+        let client = DriveClient::with_base_url(&base_url, "valid".to_string());
+        assert_eq!(client.find_file("yewmind.json").await.unwrap(), None);
+
+        let created = client.create_file("yewmind.json", r#"{"version":10}"#).await.unwrap();
+        assert_eq!(created.version.as_deref(), Some("1"));
+        let found = client.find_file("yewmind.json").await.unwrap().unwrap();
+        assert_eq!(found, created);
+        assert_eq!(client.download(&created.id).await.unwrap(), r#"{"version":10}"#);
+
+        let updated = client.update_file(&created.id, r#"{"version":11}"#).await.unwrap();
+        assert_eq!(updated.version.as_deref(), Some("2"));
+        drive.edit_remotely("{}");
+        let metadata = client.metadata(&created.id).await.unwrap().unwrap();
+        assert_eq!(metadata.version.as_deref(), Some("3"), "the edit elsewhere shows");
+        assert_eq!(client.metadata("missing").await.unwrap(), None);
+    }
 
-pub async fn update_drive_file_debounced(
-    file_path: &str,
-    file_id: &str,
-    access_token: &str,
-) -> Result<GoogleFileResource> {
-    sleep(Duration::from_secs(5)).await;
-    update_drive_file(file_path, file_id, access_token).await
+    #[tokio::test]
+    async fn an_expired_token_is_reported_as_such() {
+        let (base_url, _) = MockDrive::start().await;
+        let client = DriveClient::with_base_url(&base_url, "expired".to_string());
+        assert!(matches!(client.find_file("yewmind.json").await, Err(DriveError::Unauthorized)));
+    }
+
+    #[test]
+    fn sign_in_redirect_carries_the_token() {
+        let url = authorization_url("client", "https://app.example/", "nonce");
+        assert!(url.starts_with(OAUTH_URL));
+        assert!(url.contains("redirect_uri=https%3A%2F%2Fapp.example%2F"));
+        assert!(url.contains("response_type=token"));
+
+        let token = parse_oauth_redirect("#state=nonce&access_token=ya29.a%2Fb&token_type=Bearer&expires_in=3599").unwrap();
+        assert_eq!(token.access_token, "ya29.a/b");
+        assert_eq!(token.expires_in, 3599);
+        assert_eq!(token.state.as_deref(), Some("nonce"));
+        assert_eq!(parse_oauth_redirect("#error=access_denied"), None);
+    }
 }
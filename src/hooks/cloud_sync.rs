@@ -0,0 +1,429 @@
+use std::{cell::Cell, rc::Rc};
+
+use gloo_storage::{SessionStorage, Storage};
+use gloo_timers::future::TimeoutFuture;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::{Dispatch, use_store};
+
+use crate::{
+    cloud::{self, ConflictChoice, RemoteCopy, RemoteFile, SyncAction, SyncBackend, SyncError},
+    google_api,
+    storage::PersistedState,
+    store::{AuthSlice, ChatSlice, CloudAuthSlice, ConfigSlice, PromptSlice, VaultSlice},
+    types::api::SyncStatus,
+    vault::{SealedFile, VaultError},
+};
+
+/// How long the state has to stay unchanged before it is uploaded.
+const AUTOSAVE_DELAY_MS: u32 = 5_000;
+/// Session key of the nonce sent along with a sign-in.
+const OAUTH_STATE_KEY: &str = "yewmind-oauth-state";
+
+/// Cloud sync, acting on the stores.
+#[derive(Clone)]
+pub(crate) struct CloudSync {
+    chat: Dispatch<ChatSlice>,
+    prompt: Dispatch<PromptSlice>,
+    auth: Dispatch<AuthSlice>,
+    config: Dispatch<ConfigSlice>,
+    cloud: Dispatch<CloudAuthSlice>,
+    vault: Dispatch<VaultSlice>,
+}
+
+#[hook]
+pub(crate) fn use_cloud_sync() -> CloudSync {
+    let (_, chat) = use_store::<ChatSlice>();
+    let (_, prompt) = use_store::<PromptSlice>();
+    let (_, auth) = use_store::<AuthSlice>();
+    let (_, config) = use_store::<ConfigSlice>();
+    let (_, cloud) = use_store::<CloudAuthSlice>();
+    let (_, vault) = use_store::<VaultSlice>();
+    CloudSync {
+        chat,
+        prompt,
+        auth,
+        config,
+        cloud,
+        vault,
+    }
+}
+
+/// Syncs once the stores have stayed unchanged for [`AUTOSAVE_DELAY_MS`].
+#[hook]
+pub(crate) fn use_cloud_autosave() {
+    let sync = use_cloud_sync();
+    use_effect_with((), move |_| {
+        let changes = Rc::new(Cell::new(0u32));
+        let schedule = Rc::new({
+            let sync = sync.clone();
+            move || {
+                let change = changes.get().wrapping_add(1);
+                changes.set(change);
+                let changes = changes.clone();
+                let sync = sync.clone();
+                spawn_local(async move {
+                    TimeoutFuture::new(AUTOSAVE_DELAY_MS).await;
+                    if changes.get() == change {
+                        sync.sync().await;
+                    }
+                });
+            }
+        });
+        let subscriptions = (
+            sync.chat.clone().subscribe_silent({
+                let schedule = schedule.clone();
+                move |_: Rc<ChatSlice>| schedule()
+            }),
+            sync.prompt.clone().subscribe_silent({
+                let schedule = schedule.clone();
+                move |_: Rc<PromptSlice>| schedule()
+            }),
+            sync.config.clone().subscribe_silent({
+                let schedule = schedule.clone();
+                move |_: Rc<ConfigSlice>| schedule()
+            }),
+            // Turning the vault on or off changes what the cloud should hold.
+            sync.vault.clone().subscribe_silent(move |_: Rc<VaultSlice>| schedule()),
+        );
+        move || drop(subscriptions)
+    });
+}
+
+impl CloudSync {
+    /// Sends the user to Google. The app is loaded again with the token,
+    /// see [`CloudSync::finish_sign_in`].
+    pub(crate) fn sign_in(&self, client_id: &str) {
+        let location = gloo_utils::window().location();
+        let redirect_uri = format!(
+            "{}{}",
+            location.origin().unwrap_or_default(),
+            location.pathname().unwrap_or_default()
+        );
+        let nonce = uuid::Uuid::new_v4().to_string();
+        if let Err(e) = SessionStorage::set(OAUTH_STATE_KEY, &nonce) {
+            log::error!("Could not start signing in: {}", e);
+            return;
+        }
+        let url = google_api::authorization_url(client_id, &redirect_uri, &nonce);
+        let _ = location.set_href(&url);
+    }
+
+    /// Picks up the token when Google redirected back to the app, and turns
+    /// syncing on.
+    pub(crate) fn finish_sign_in(&self) {
+        let window = gloo_utils::window();
+        let Some(token) = window
+            .location()
+            .hash()
+            .ok()
+            .and_then(|hash| google_api::parse_oauth_redirect(&hash))
+        else {
+            return;
+        };
+        let expected = SessionStorage::get::<String>(OAUTH_STATE_KEY).ok();
+        SessionStorage::delete(OAUTH_STATE_KEY);
+        // The token stays out of the history.
+        if let Ok(history) = window.history() {
+            let path = window.location().pathname().unwrap_or_default();
+            let _ = history.replace_state_with_url(&JsValue::NULL, "", Some(&path));
+        }
+        if expected.is_none() || token.state != expected {
+            log::warn!("Ignoring a Google sign-in this tab didn't start");
+            return;
+        }
+        self.cloud.reduce_mut(|c| {
+            c.google_access_token = Some(token.access_token);
+            c.cloud_sync = true;
+        });
+    }
+
+    pub(crate) fn stop(&self) {
+        self.cloud.reduce_mut(|c| {
+            c.cloud_sync = false;
+            c.google_access_token = None;
+            c.sync_status = SyncStatus::Unauthenticated;
+            c.conflict = None;
+        });
+    }
+
+    /// Brings the cloud and this browser in line, unless syncing is off or
+    /// not set up, a sync is running, a conflict waits for the user or a
+    /// reply is streaming in.
+    pub(crate) async fn sync(&self) {
+        let cloud = self.cloud.get();
+        let Some(backend) = cloud::backend(&cloud) else {
+            return;
+        };
+        let busy = matches!(cloud.sync_status, SyncStatus::Syncing | SyncStatus::Conflict);
+        if !cloud.cloud_sync || busy || self.chat.get().generating {
+            return;
+        }
+        self.set_status(SyncStatus::Syncing);
+        let status = match self.try_sync(backend.as_ref(), &cloud).await {
+            Ok(status) => status,
+            Err(e) if matches!(e.downcast_ref(), Some(SyncError::Unauthorized)) => {
+                self.cloud.reduce_mut(|c| c.google_access_token = None);
+                SyncStatus::Unauthenticated
+            }
+            Err(e) => {
+                log::error!("Cloud sync failed: {}", e);
+                SyncStatus::Failed
+            }
+        };
+        self.set_status(status);
+    }
+
+    async fn try_sync(&self, backend: &dyn SyncBackend, cloud: &CloudAuthSlice) -> anyhow::Result<SyncStatus> {
+        let payload = cloud::payload(&self.local_state())?;
+        let fingerprint = self.fingerprint(&payload);
+        let remote = backend.head().await?;
+        let action = cloud::plan(
+            &fingerprint,
+            cloud.synced_fingerprint.as_deref(),
+            cloud.synced_version.as_deref(),
+            remote.as_ref().map(|f| f.version.as_str()),
+        );
+        match (action, remote) {
+            (SyncAction::UpToDate, _) => {}
+            (SyncAction::Upload, remote) => match backend.upload(&self.seal(payload).await?, remote.as_ref()).await {
+                Ok(file) => self.record(file, Some(fingerprint)),
+                // Another device got there first.
+                Err(SyncError::Conflict) => {
+                    let file = backend.head().await?.ok_or(SyncError::Conflict)?;
+                    return self.conflict(backend, file).await;
+                }
+                Err(e) => return Err(e.into()),
+            },
+            (SyncAction::Download, Some(file)) => {
+                let state = cloud::parse_payload(&self.open(backend.download(&file).await?).await?)?;
+                self.apply(state);
+                let fingerprint = self.local_fingerprint()?;
+                self.record(file, Some(fingerprint));
+            }
+            (SyncAction::Conflict, Some(file)) => return self.conflict(backend, file).await,
+            (SyncAction::Download | SyncAction::Conflict, None) => unreachable!("there is no remote copy"),
+        }
+        Ok(SyncStatus::Synced)
+    }
+
+    /// Downloads the remote copy for the user to pick from.
+    async fn conflict(&self, backend: &dyn SyncBackend, file: RemoteFile) -> anyhow::Result<SyncStatus> {
+        let state = cloud::parse_payload(&self.open(backend.download(&file).await?).await?)?;
+        self.cloud
+            .reduce_mut(|c| c.conflict = Some(Box::new(RemoteCopy { state, file })));
+        Ok(SyncStatus::Conflict)
+    }
+
+    /// Settles a conflict and syncs again.
+    pub(crate) async fn resolve(&self, choice: ConflictChoice) {
+        let Some(remote) = self.cloud.get().conflict.clone() else {
+            return;
+        };
+        let RemoteCopy { state, file } = *remote;
+        let fingerprint = match choice {
+            ConflictChoice::KeepLocal => None,
+            ConflictChoice::KeepRemote => {
+                self.apply(state);
+                self.local_fingerprint().ok()
+            }
+            ConflictChoice::Merge => {
+                self.apply(cloud::merge(self.local_state(), state));
+                None
+            }
+        };
+        // The remote copy counts as seen, so anything but the remote state
+        // itself is uploaded over it.
+        self.record(file, fingerprint);
+        self.cloud.reduce_mut(|c| {
+            c.conflict = None;
+            c.sync_status = SyncStatus::Synced;
+        });
+        self.sync().await;
+    }
+
+    fn set_status(&self, status: SyncStatus) {
+        self.cloud.reduce_mut(|c| c.sync_status = status);
+    }
+
+    fn record(&self, file: RemoteFile, fingerprint: Option<String>) {
+        self.cloud.reduce_mut(|c| {
+            c.file_id = Some(file.id);
+            c.synced_version = Some(file.version);
+            c.synced_fingerprint = fingerprint;
+        });
+    }
+
+    fn local_state(&self) -> PersistedState {
+        PersistedState::from_stores(
+            &self.chat.get(),
+            &self.prompt.get(),
+            &self.auth.get(),
+            &self.config.get(),
+            &self.cloud.get(),
+        )
+    }
+
+    fn local_fingerprint(&self) -> serde_json::Result<String> {
+        Ok(self.fingerprint(&cloud::payload(&self.local_state())?))
+    }
+
+    /// Also tells apart the vault the payload is sealed with, so turning it
+    /// on or off uploads again.
+    fn fingerprint(&self, payload: &str) -> String {
+        match &self.vault.get().vault {
+            Some(vault) => cloud::fingerprint(&format!("{}\n{}", vault.header().salt, payload)),
+            None => cloud::fingerprint(payload),
+        }
+    }
+
+    /// What is uploaded for `payload`: a [`SealedFile`] when the vault is on.
+    async fn seal(&self, payload: String) -> Result<String, VaultError> {
+        match &self.vault.get().vault {
+            Some(vault) => vault.seal_file(&payload).await,
+            None => Ok(payload),
+        }
+    }
+
+    /// The payload in a downloaded file, which may be sealed by this or
+    /// another device.
+    async fn open(&self, raw: String) -> Result<String, VaultError> {
+        let Some(file) = SealedFile::parse(&raw) else {
+            return Ok(raw);
+        };
+        match &self.vault.get().vault {
+            Some(vault) => vault.open_file(&file).await,
+            None => Err(VaultError::Locked),
+        }
+    }
+
+    /// Replaces the chats, prompts and settings. API profiles and the open
+    /// chat stay.
+    fn apply(&self, state: PersistedState) {
+        self.prompt.reduce_mut(|s| s.prompts = state.prompts);
+        self.config.reduce_mut(|c| {
+            *c = ConfigSlice {
+                open_config: c.open_config,
+                ..state.config
+            }
+        });
+        self.chat.reduce_mut(|s| {
+            let current = usize::try_from(s.curr_chat_index)
+                .ok()
+                .and_then(|i| s.chats.get(i))
+                .map(|c| c.id.clone());
+            s.chats = state.chats;
+            s.folders = state.folders;
+            let max_index = s.chats.len().saturating_sub(1) as i32;
+            s.curr_chat_index = current
+                .and_then(|id| s.chats.iter().position(|c| c.id == id))
+                .map_or(0, |i| i as i32)
+                .clamp(0, max_index);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yewdux::Context;
+
+    use super::*;
+    use crate::{
+        cloud::stand_in::ObjectStore,
+        types::{
+            api::{S3Settings, SyncProvider, WebDavSettings},
+            chat::ChatInterface,
+        },
+        vault::{Vault, stand_in::ToyPassphrase},
+    };
+
+    /// A browser syncing to the WebDAV folder at `url`.
+    fn device(url: &str) -> CloudSync {
+        let cx = Context::new();
+        let sync = CloudSync {
+            chat: Dispatch::new(&cx),
+            prompt: Dispatch::new(&cx),
+            auth: Dispatch::new(&cx),
+            config: Dispatch::new(&cx),
+            cloud: Dispatch::new(&cx),
+            vault: Dispatch::new(&cx),
+        };
+        sync.cloud.reduce_mut(|c| {
+            let webdav = WebDavSettings {
+                url: format!("{}/dav", url),
+                ..WebDavSettings::default()
+            };
+            c.configure(SyncProvider::WebDav, webdav, S3Settings::default());
+            c.cloud_sync = true;
+        });
+        sync
+    }
+
+    fn add_chat(sync: &CloudSync, id: &str) {
+        let chat = ChatInterface {
+            id: id.to_string(),
+            title: Some(id.to_uppercase()),
+            ..ChatInterface::default()
+        };
+        sync.chat.reduce_mut(|s| s.chats.insert(0, chat));
+    }
+
+    fn titles(sync: &CloudSync) -> Vec<String> {
+        sync.chat.get().chats.iter().filter_map(|c| c.title.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn devices_sync_through_webdav_and_settle_conflicts() {
+        let store = ObjectStore::start(|_| true).await;
+        let (laptop, phone) = (device(&store.url), device(&store.url));
+        add_chat(&laptop, "a");
+        laptop.sync().await;
+        phone.sync().await;
+        assert_eq!(phone.cloud.get().sync_status, SyncStatus::Conflict, "never synced with that file");
+        phone.resolve(ConflictChoice::KeepRemote).await;
+        assert_eq!(phone.cloud.get().sync_status, SyncStatus::Synced);
+        assert_eq!(titles(&phone), ["A"]);
+
+        add_chat(&laptop, "b");
+        add_chat(&phone, "c");
+        laptop.sync().await;
+        phone.sync().await;
+        assert_eq!(phone.cloud.get().sync_status, SyncStatus::Conflict);
+        assert_eq!(titles(&phone), ["C", "A"], "nothing changes before the user picks");
+
+        phone.resolve(ConflictChoice::Merge).await;
+        assert_eq!(phone.cloud.get().sync_status, SyncStatus::Synced);
+        assert_eq!(titles(&phone), ["B", "C", "A"]);
+        laptop.sync().await;
+        assert_eq!(titles(&laptop), ["B", "C", "A"]);
+    }
+
+    #[tokio::test]
+    async fn the_synced_file_is_sealed_with_the_vault() {
+        let store = ObjectStore::start(|_| true).await;
+        let (laptop, phone) = (device(&store.url), device(&store.url));
+        let salt = b"laptop-salt-0001";
+        let vault = Vault::create(ToyPassphrase::new("pass"), salt).await.unwrap();
+        laptop.vault.reduce_mut(|s| s.vault = Some(vault));
+        add_chat(&laptop, "secret");
+        laptop.sync().await;
+        assert_eq!(laptop.cloud.get().sync_status, SyncStatus::Synced);
+        let uploaded = store.object("/dav/yewmind.json").unwrap();
+        assert!(!uploaded.contains("SECRET"));
+
+        phone.sync().await;
+        assert_eq!(phone.cloud.get().sync_status, SyncStatus::Failed, "locked without the passphrase");
+        let vault = Vault::create(ToyPassphrase::new("pass"), b"phone-salt-00001").await.unwrap();
+        phone.vault.reduce_mut(|s| s.vault = Some(vault));
+        phone.cloud.reduce_mut(|c| c.sync_status = SyncStatus::Synced);
+        phone.sync().await;
+        phone.resolve(ConflictChoice::KeepRemote).await;
+        assert_eq!(titles(&phone), ["SECRET"]);
+
+        // Turning the vault off uploads the state in the clear.
+        laptop.vault.reduce_mut(|s| s.vault = None);
+        laptop.sync().await;
+        assert!(store.object("/dav/yewmind.json").unwrap().contains("SECRET"));
+    }
+}
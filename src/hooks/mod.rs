@@ -3,4 +3,5 @@ pub(crate) mod add_chat;
 pub(crate) mod hide_on_outside;
 pub(crate) mod submit;
 pub(crate) mod initialise_chat;
-pub(crate) mod save_to_local;
+pub(crate) mod persist;
+pub(crate) mod cloud_sync;
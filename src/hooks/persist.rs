@@ -0,0 +1,428 @@
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    rc::{Rc, Weak},
+    sync::Arc,
+};
+
+use gloo_timers::{callback::Interval, future::TimeoutFuture};
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::{Dispatch, use_store};
+
+use crate::{
+    components::toast::ToastStatus,
+    hooks::translation::{Namespace, use_translation},
+    storage::{
+        self, PersistedState, Snapshot, StorageBackend, StorageError,
+        sync::{
+            STREAM_LOCK_RENEW_MS, STREAM_LOCK_TTL_MS, SharedState, SyncMessage, TabChannel, merge_chats,
+        },
+    },
+    store::{
+        AuthSlice, ChatSlice, CloudAuthSlice, ConfigSlice, PromptSlice, StreamLock, TabSyncSlice, ToastSlice,
+        VaultSlice,
+    },
+    vault::{self, Vault},
+};
+
+/// Delay before a change is saved, so a burst of them is written at once.
+const SAVE_DELAY_MS: u32 = 300;
+/// Delay while a reply is streaming in, where every token is a change.
+const GENERATING_SAVE_DELAY_MS: u32 = 2_000;
+
+type Translate = Arc<dyn Fn(String, Option<Namespace>) -> String + Send + Sync>;
+
+/// Loads the persisted state into the stores once the vault is unlocked,
+/// calls `on_loaded`, then saves the stores whenever they change and keeps
+/// them in step with other tabs.
+#[hook]
+pub(crate) fn use_persistence(on_loaded: Callback<()>) {
+    let (_, chat_dispatch) = use_store::<ChatSlice>();
+    let (_, prompt_dispatch) = use_store::<PromptSlice>();
+    let (_, auth_dispatch) = use_store::<AuthSlice>();
+    let (_, config_dispatch) = use_store::<ConfigSlice>();
+    let (_, cloud_dispatch) = use_store::<CloudAuthSlice>();
+    let (_, sync_dispatch) = use_store::<TabSyncSlice>();
+    let (_, toast_dispatch) = use_store::<ToastSlice>();
+    let (vault_state, vault_dispatch) = use_store::<VaultSlice>();
+    let translate = use_translation(vec!["main".to_string()]);
+
+    use_effect_with(!vault_state.is_locked(), move |ready| {
+        let subscriptions = Rc::new(RefCell::new(None));
+        if !*ready {
+            return Box::new(|| ()) as Box<dyn FnOnce()>;
+        }
+        spawn_local({
+            let subscriptions = subscriptions.clone();
+            async move {
+                let vault = vault_dispatch.get().vault.clone();
+                let backend = storage::open(vault.clone()).await;
+                let loaded = match backend.load().await {
+                    Ok(state) => state,
+                    Err(e @ StorageError::Vault(_)) => {
+                        // Saving now would overwrite what couldn't be read.
+                        log::error!("Could not open the stored state: {}", e);
+                        toast_dispatch.reduce_mut(|t| {
+                            t.status = ToastStatus::Error;
+                            t.message = translate(
+                                "storageUnreadable".to_string(),
+                                Some(Namespace { ns: "main".to_string() }),
+                            );
+                            t.show = true;
+                        });
+                        on_loaded.emit(());
+                        return;
+                    }
+                    Err(e) => {
+                        log::error!("Could not load the stored state: {}", e);
+                        None
+                    }
+                };
+                let snapshot = loaded.as_ref().map(Snapshot::of).unwrap_or_default();
+                if let Some(state) = loaded {
+                    auth_dispatch.set(state.auth);
+                    config_dispatch.set(state.config);
+                    cloud_dispatch.set(state.cloud);
+                    prompt_dispatch.reduce_mut(|s| s.prompts = state.prompts);
+                    chat_dispatch.reduce_mut(|s| {
+                        s.chats = state.chats;
+                        s.folders = state.folders;
+                        // Validate current chat index
+                        let max_index = s.chats.len().saturating_sub(1);
+                        s.curr_chat_index = state.current_chat_index.clamp(0, max_index as i32);
+                    });
+                }
+                on_loaded.emit(());
+
+                let saver = Rc::new(Saver {
+                    backend: RefCell::new(backend),
+                    vault: RefCell::new(vault),
+                    tab: uuid::Uuid::new_v4().to_string(),
+                    channel: OnceCell::new(),
+                    snapshot: RefCell::new(snapshot),
+                    scheduled: Cell::new(false),
+                    saving: Cell::new(false),
+                    dirty: Cell::new(false),
+                    warned: Cell::new(false),
+                    streaming: RefCell::new(None),
+                    chat: chat_dispatch.clone(),
+                    prompt: prompt_dispatch.clone(),
+                    auth: auth_dispatch.clone(),
+                    config: config_dispatch.clone(),
+                    cloud: cloud_dispatch.clone(),
+                    sync: sync_dispatch,
+                    toast: toast_dispatch,
+                    translate,
+                });
+                let _ = saver.channel.set(TabChannel::open({
+                    let saver = Rc::downgrade(&saver);
+                    move |message| {
+                        if let Some(saver) = Weak::upgrade(&saver) {
+                            saver.receive(message);
+                        }
+                    }
+                }));
+                // Whatever the initial load adjusted is saved right away.
+                saver.schedule();
+                *subscriptions.borrow_mut() = Some((
+                    chat_dispatch.subscribe_silent({
+                        let saver = saver.clone();
+                        move |state: Rc<ChatSlice>| {
+                            saver.track_stream(&state);
+                            saver.schedule();
+                        }
+                    }),
+                    prompt_dispatch.subscribe_silent({
+                        let saver = saver.clone();
+                        move |_: Rc<PromptSlice>| saver.schedule()
+                    }),
+                    auth_dispatch.subscribe_silent({
+                        let saver = saver.clone();
+                        move |_: Rc<AuthSlice>| saver.schedule()
+                    }),
+                    config_dispatch.subscribe_silent({
+                        let saver = saver.clone();
+                        move |_: Rc<ConfigSlice>| saver.schedule()
+                    }),
+                    cloud_dispatch.subscribe_silent({
+                        let saver = saver.clone();
+                        move |_: Rc<CloudAuthSlice>| saver.schedule()
+                    }),
+                    vault_dispatch.subscribe_silent(move |state: Rc<VaultSlice>| saver.rekey(state.vault.clone())),
+                ));
+            }
+        });
+
+        Box::new(move || drop(subscriptions.take()))
+    });
+}
+
+/// Writes the stores to the backend a while after they change, and tells
+/// the other tabs about it.
+struct Saver {
+    /// Replaced when the vault is turned on or off.
+    backend: RefCell<Rc<dyn StorageBackend>>,
+    /// The vault `backend` seals with.
+    vault: RefCell<Option<Vault>>,
+    /// Identifies this tab to the others.
+    tab: String,
+    channel: OnceCell<TabChannel>,
+    /// What the backend holds.
+    snapshot: RefCell<Snapshot>,
+    scheduled: Cell<bool>,
+    saving: Cell<bool>,
+    /// Set when a save is asked for while one is running.
+    dirty: Cell<bool>,
+    /// Whether the quota warning was shown since the last successful save.
+    warned: Cell<bool>,
+    /// The chat this tab streams into, and the timer renewing its lock.
+    streaming: RefCell<Option<(String, Interval)>>,
+    chat: Dispatch<ChatSlice>,
+    prompt: Dispatch<PromptSlice>,
+    auth: Dispatch<AuthSlice>,
+    config: Dispatch<ConfigSlice>,
+    cloud: Dispatch<CloudAuthSlice>,
+    sync: Dispatch<TabSyncSlice>,
+    toast: Dispatch<ToastSlice>,
+    translate: Translate,
+}
+
+impl Saver {
+    /// Saves after a delay, unless a save is already scheduled. The state
+    /// is read when the timer fires, so that save picks up every change made
+    /// in the meantime.
+    fn schedule(self: &Rc<Self>) {
+        if self.scheduled.replace(true) {
+            return;
+        }
+        let delay = if self.chat.get().generating {
+            GENERATING_SAVE_DELAY_MS
+        } else {
+            SAVE_DELAY_MS
+        };
+        let saver = self.clone();
+        spawn_local(async move {
+            TimeoutFuture::new(delay).await;
+            saver.scheduled.set(false);
+            saver.save().await;
+        });
+    }
+
+    async fn save(self: Rc<Self>) {
+        if self.saving.replace(true) {
+            self.dirty.set(true);
+            return;
+        }
+        loop {
+            let mut state = PersistedState::from_stores(
+                &self.chat.get(),
+                &self.prompt.get(),
+                &self.auth.get(),
+                &self.config.get(),
+                &self.cloud.get(),
+            );
+            let changes = self.snapshot.borrow().changes(&state);
+            let backend = self.backend.borrow().clone();
+            let saved = match changes {
+                Ok(changes) => {
+                    changes.stamp(&mut state.chats);
+                    backend.save(&state, &changes).await.map(|_| changes)
+                }
+                Err(e) => Err(e),
+            };
+            match saved {
+                // Rekeyed meanwhile: the new backend gets everything anyway.
+                Ok(_) if !Rc::ptr_eq(&backend, &self.backend.borrow()) => {}
+                Ok(changes) => {
+                    if !changes.upserted.is_empty() {
+                        self.chat.reduce_mut(|s| changes.stamp(&mut s.chats));
+                    }
+                    let message = SyncMessage::saved(
+                        &self.tab,
+                        &changes,
+                        &state.chats,
+                        &state.folders,
+                        &state.prompts,
+                        &state.config,
+                    );
+                    if let Some(message) = message {
+                        self.post(&message);
+                    }
+                    self.snapshot.borrow_mut().apply(changes);
+                    self.warned.set(false);
+                }
+                Err(StorageError::QuotaExceeded) => {
+                    log::error!("Could not save the state: {}", StorageError::QuotaExceeded);
+                    if !self.warned.replace(true) {
+                        self.warn("storageQuotaExceeded");
+                    }
+                }
+                Err(e) => log::error!("Could not save the state: {}", e),
+            }
+            if !self.dirty.replace(false) {
+                break;
+            }
+        }
+        self.saving.set(false);
+    }
+
+    /// Rewrites the stored state under `vault`, or in the clear when it is
+    /// `None`, and has the other tabs reload to pick that up.
+    fn rekey(self: &Rc<Self>, vault: Option<Vault>) {
+        if *self.vault.borrow() == vault {
+            return;
+        }
+        *self.vault.borrow_mut() = vault.clone();
+        let saver = self.clone();
+        spawn_local(async move {
+            // The header goes first when turning the vault on, and last when
+            // turning it off, so a reload halfway still finds the header for
+            // any sealed data.
+            if let Some(vault) = &vault
+                && let Err(e) = vault::store_header(Some(vault.header()))
+            {
+                log::error!("Could not store the vault: {}", e);
+                return;
+            }
+            while saver.saving.get() {
+                TimeoutFuture::new(SAVE_DELAY_MS).await;
+            }
+            *saver.backend.borrow_mut() = storage::open(vault.clone()).await;
+            *saver.snapshot.borrow_mut() = Snapshot::default();
+            saver.clone().save().await;
+            if vault.is_none() {
+                let _ = vault::store_header(None);
+            }
+            saver.post(&SyncMessage::VaultChanged { tab: saver.tab.clone() });
+        });
+    }
+
+    fn post(&self, message: &SyncMessage) {
+        if let Some(channel) = self.channel.get() {
+            channel.post(message);
+        }
+    }
+
+    /// Takes and releases the stream lock as generations start and stop.
+    fn track_stream(self: &Rc<Self>, state: &ChatSlice) {
+        let chat_id = state.generation.as_ref().map(|g| g.chat_id.clone());
+        let mut streaming = self.streaming.borrow_mut();
+        if streaming.as_ref().map(|(id, _)| id) == chat_id.as_ref() {
+            return;
+        }
+        if let Some((chat_id, _)) = streaming.take() {
+            self.post(&SyncMessage::Released {
+                tab: self.tab.clone(),
+                chat_id,
+            });
+        }
+        if let Some(chat_id) = chat_id {
+            let lock = SyncMessage::Streaming {
+                tab: self.tab.clone(),
+                chat_id: chat_id.clone(),
+            };
+            self.post(&lock);
+            let renew = Interval::new(STREAM_LOCK_RENEW_MS, {
+                let saver = self.clone();
+                move || saver.post(&lock)
+            });
+            *streaming = Some((chat_id, renew));
+        }
+    }
+
+    fn receive(self: &Rc<Self>, message: SyncMessage) {
+        match message {
+            SyncMessage::Saved {
+                chats,
+                removed,
+                shared,
+                ..
+            } => {
+                {
+                    let mut snapshot = self.snapshot.borrow_mut();
+                    self.chat.reduce_mut(|s| {
+                        let order = shared.as_ref().map(|shared| shared.order.as_slice());
+                        merge_chats(
+                            &mut s.chats,
+                            &mut s.curr_chat_index,
+                            &mut snapshot,
+                            chats,
+                            &removed,
+                            order,
+                        );
+                        if let Some(shared) = &shared {
+                            s.folders = shared.folders.clone();
+                        }
+                    });
+                }
+                if let Some(shared) = shared {
+                    let SharedState { prompts, config, .. } = *shared;
+                    self.prompt.reduce_mut(|s| s.prompts = prompts);
+                    self.config.reduce_mut(|c| {
+                        *c = ConfigSlice {
+                            open_config: c.open_config,
+                            ..config
+                        }
+                    });
+                }
+            }
+            message @ SyncMessage::Outdated { .. } => {
+                let saver = self.clone();
+                spawn_local(async move {
+                    let backend = saver.backend.borrow().clone();
+                    match backend.load().await {
+                        Ok(Some(stored)) => saver.receive(message.refill(stored)),
+                        Ok(None) => {}
+                        Err(e) => log::error!("Could not read what another tab saved: {}", e),
+                    }
+                });
+            }
+            SyncMessage::Streaming { tab, chat_id } => {
+                let ours = self
+                    .streaming
+                    .borrow()
+                    .as_ref()
+                    .is_some_and(|(id, _)| *id == chat_id);
+                // Both tabs started at once: the lower tab id keeps streaming.
+                if ours && tab > self.tab {
+                    self.post(&SyncMessage::Streaming {
+                        tab: self.tab.clone(),
+                        chat_id,
+                    });
+                    return;
+                }
+                if ours {
+                    self.chat.reduce_mut(|s| s.stop_generating());
+                    self.warn("chatBusyInAnotherTab");
+                }
+                let expires_at = js_sys::Date::now() + STREAM_LOCK_TTL_MS;
+                self.sync.reduce_mut(|s| {
+                    s.stream_locks.insert(chat_id, StreamLock { tab, expires_at });
+                });
+            }
+            SyncMessage::Released { tab, chat_id } => {
+                self.sync.reduce_mut(|s| {
+                    if s.stream_locks.get(&chat_id).is_some_and(|lock| lock.tab == tab) {
+                        s.stream_locks.remove(&chat_id);
+                    }
+                });
+            }
+            SyncMessage::VaultChanged { .. } => {
+                // This tab holds the old key, or none at all.
+                let _ = gloo_utils::window().location().reload();
+            }
+        }
+    }
+
+    fn warn(&self, key: &str) {
+        let message = (self.translate)(
+            key.to_string(),
+            Some(Namespace { ns: "main".to_string() }),
+        );
+        self.toast.reduce_mut(|t| {
+            t.status = ToastStatus::Warning;
+            t.message = message;
+            t.show = true;
+        });
+    }
+}
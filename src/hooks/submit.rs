@@ -1,250 +1,534 @@
-use crate::store::{AuthSlice, ChatSlice, ConfigSlice};
+use crate::api::error::{ApiError, Severity};
+use crate::hooks::translation::{use_translation, Namespace};
+use crate::components::toast::ToastStatus;
+use crate::store::{AuthSlice, ChatSlice, ConfigSlice, Generation, TabSyncSlice, ToastSlice};
 use crate::types::api::EventSourceData;
-use crate::types::chat::{MessageInterface, ModelOptions, Role, TokenUsage};
-use crate::{api::get_chat_completion_stream, types::chat::ConfigInterface};
+use crate::types::chat::{MessageInterface, Role, TokenUsage};
+use crate::types::model::ModelRegistry;
+use crate::{api::{get_chat_completion, get_chat_completion_stream, provider::provider_for}, types::chat::ConfigInterface};
 use futures::StreamExt;
+use futures::future::{AbortHandle, Abortable};
 use std::future::Future;
 use std::sync::Arc;
-use tiktoken_rs::{ChatCompletionRequestMessage, cl100k_base, get_chat_completion_max_tokens};
+use tiktoken_rs::{ChatCompletionRequestMessage, cl100k_base, num_tokens_from_messages};
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 use yewdux::{Dispatch, use_store};
-use crate::api::parse_event_source;
+use crate::api::sse::SseDecoder;
 
 // type HandleAsyncFunc = Arc<dyn Fn() -> Box<dyn Future<Output = Option<String>> + Send + Sync>>;
 
 #[hook]
 pub fn use_submit() -> (Callback<()>, String) {
+    let (state, _) = use_store::<ChatSlice>();
+    (use_generate(false), state.error.clone())
+}
+
+/// Streams more of the current chat's last answer into it, after the model
+/// stopped at the token limit.
+#[hook]
+pub fn use_continue() -> Callback<()> {
+    use_generate(true)
+}
+
+/// Streams an answer to the current chat into a new (or regenerated) last
+/// message, or with `continuing` into the cut off last answer. Cut off answers
+/// are continued on their own up to `ConfigSlice::auto_continue` times.
+#[hook]
+fn use_generate(continuing: bool) -> Callback<()> {
     let (auth_state, _) = use_store::<AuthSlice>();
     let (config_store, config_dispath) = use_store::<ConfigSlice>();
     let (state, chat_dispatch) = use_store::<ChatSlice>();
-    let error = state.error.clone();
+    let (_, toast_dispatch) = use_store::<ToastSlice>();
+    let (sync_state, _) = use_store::<TabSyncSlice>();
+    let (registry, _) = use_store::<ModelRegistry>();
+    let translate = use_translation(vec!["api".to_string(), "main".to_string()]);
 
-    let handle_submit = {
+    {
         let chat_dispatch = chat_dispatch.clone();
         let auth_state = auth_state.clone();
         let (config_store, config_dispath) = (config_store.clone(), config_dispath.clone());
         let state = state.clone();
         Callback::from(move |_| {
             let current_chat_index = state.curr_chat_index as usize;
+            let busy = state
+                .chats
+                .get(current_chat_index)
+                .is_some_and(|chat| sync_state.is_locked(&chat.id, js_sys::Date::now()));
+            if busy {
+                let message = translate("chatBusyInAnotherTab".to_string(), Some(Namespace { ns: "main".to_string() }));
+                toast_dispatch.reduce_mut(|d| {
+                    d.status = ToastStatus::Warning;
+                    d.message = message;
+                    d.show = true;
+                });
+                return;
+            }
 
             spawn_local({
                 let chat_dispatch = chat_dispatch.clone();
                 let auth_state = auth_state.clone();
                 let (config_store, config_dispath) = (config_store.clone(), config_dispath.clone());
+                let toast_dispatch = toast_dispatch.clone();
+                let translate = translate.clone();
+                let registry = registry.clone();
                 async move {
+                    let (abort_handle, abort_registration) = AbortHandle::new_pair();
+                    let mut chat_id = None;
                     chat_dispatch.reduce_mut(|s| {
-                        if s.generating || s.chats.is_empty() {
+                        if s.generating || current_chat_index >= s.chats.len() {
                             return;
                         }
 
-                        // Clone current chat state
-                        let mut chats = s.chats.clone();
-                        let chat = &mut chats[current_chat_index];
-
-                        // Add assistant message placeholder
-                        chat.messages.push(MessageInterface {
-                            role: Role::Assistant,
-                            content: String::new(),
-                            folder: None,
-                            messages: vec![],
-                        });
+                        let chat = &mut s.chats[current_chat_index];
+                        if continuing {
+                            let Some(reply) = chat.messages.last_mut().filter(|m| m.is_cut_off()) else {
+                                return;
+                            };
+                            // Stopping the continuation shouldn't leave it looking cut off.
+                            reply.finish_reason = None;
+                        } else {
+                            // Add assistant message placeholder, unless a regeneration
+                            // already left an empty branch to stream into.
+                            let has_placeholder = chat
+                                .messages
+                                .last()
+                                .is_some_and(|m| m.role == Role::Assistant && m.content.is_empty());
+                            if !has_placeholder {
+                                chat.messages.push(MessageInterface {
+                                    role: Role::Assistant,
+                                    ..Default::default()
+                                });
+                            }
+                            if let Some(placeholder) = chat.messages.last_mut() {
+                                placeholder.created_at = Some(js_sys::Date::now() as i64);
+                            }
+                        }
 
-                        s.chats = chats;
+                        chat_id = Some(chat.id.clone());
                         s.generating = true;
+                        s.generation = Some(Generation::new(chat.id.clone(), abort_handle));
                     });
+                    let Some(chat_id) = chat_id else {
+                        return;
+                    };
 
-                    let result = async {
+                    // Streamed chunks are written by chat id rather than index, so
+                    // reordering the chat list mid-stream can't redirect them.
+                    let result = Abortable::new(async {
                         let state = chat_dispatch.get();
                         let chat = &state.chats[current_chat_index];
 
-                        if chat.messages.is_empty() {
+                        // The active branch, minus the empty placeholder we just added;
+                        // alternatives never leave the browser. A cut off answer is sent
+                        // along for the model to pick up where it stopped.
+                        let mut history = if continuing {
+                            chat.messages.clone()
+                        } else {
+                            chat.messages[..chat.messages.len() - 1].to_vec()
+                        };
+                        if history.is_empty() {
                             anyhow::bail!("No messages submitted!");
                         }
 
+                        let profile = auth_state.profile(chat.api_profile.as_deref());
+                        if profile.endpoint.is_empty() {
+                            anyhow::bail!("No API endpoint defined!");
+                        }
+
                         let config = if let Some(config) = &chat.config {
                             config.clone()
                         } else {
-                            ConfigInterface::default()
+                            let mut config = ConfigInterface::default();
+                            if let Some(model) = &profile.default_model {
+                                config.model = model.clone();
+                            }
+                            config
                         };
-                        let messages = limit_message_tokens(
-                            &chat.messages,
-                            config.max_tokens as usize,
-                            &config.model.clone(),
-                        );
-
-                        if auth_state.api_endpoint.is_none() {
-                            anyhow::bail!("No API endpoint defined!");
-                        }
-                        let endpoint = auth_state.api_endpoint.clone().unwrap();
-                        
-                        // Handle streaming response
-                        let mut stream = get_chat_completion_stream(
-                            endpoint,
-                            &messages,
-                            &config,
-                            &auth_state.api_key,
-                            None,
-                        )
-                        .await?;
-
-                        while let Some(event) = stream.next().await {
-                            let data = String::from_utf8(event.unwrap().to_vec()).unwrap();
-                            let mut reading = true;
-                            let data: Vec<EventSourceData> = parse_event_source(data);
-                            if reading && state.generating {
-                                if let EventSourceData::Done(_inner) = &data[0] {
-                                    reading = false;
-                                    break;
-                                } 
-
-                                let data = data.iter().fold(String::new(), |mut output, curr| {
-                                    if let EventSourceData::Data(inner_data) = &curr {
-                                        let delta = inner_data.choices.get(0).and_then(|choice| Some(choice.delta.clone())).unwrap();
-                                        if let Some(content) = &delta.content {
-                                            output.push_str(content);
+                        let provider = provider_for(&profile);
+                        let mut continuations = 0;
+                        loop {
+                            let messages = if continuing || continuations > 0 {
+                                // The cut off reply is about `max_tokens` long by itself,
+                                // so the history is budgeted against the context window.
+                                let context_window = registry.info(&config.model).context_window.max(0) as usize;
+                                let budget = context_window.saturating_sub(config.max_tokens as usize);
+                                continuation_messages(&history, budget, &config.model)
+                            } else {
+                                limit_message_tokens(
+                                    &history,
+                                    config.max_tokens as usize,
+                                    &config.model.clone(),
+                                )
+                            };
+
+                            // Handle streaming response
+                            let mut stream = get_chat_completion_stream(
+                                provider.as_ref(),
+                                profile.endpoint.clone(),
+                                &messages,
+                                &config,
+                                &profile.api_key,
+                                Some(profile.custom_headers.clone().into_iter().collect()),
+                            )
+                            .await?;
+
+                            let mut usage: Option<TokenUsage> = None;
+                            let mut finish_reason: Option<String> = None;
+                            let mut model: Option<String> = None;
+                            let mut decoder = SseDecoder::new(provider.stream_format());
+                            let mut done = false;
+                            let mut received_data = false;
+                            let mut received = String::new();
+                            while !done {
+                                let events = match stream.next().await {
+                                    Some(Ok(chunk)) => decoder.feed(&chunk),
+                                    Some(Err(e)) => {
+                                        // Nothing resumes a completion, but what the server said
+                                        // about resuming tells where the answer broke off.
+                                        log::warn!(
+                                            "The stream broke off after event {:?} (retry hint {:?} ms)",
+                                            decoder.last_event_id(),
+                                            decoder.retry()
+                                        );
+                                        return Err(ApiError::from(e).into());
+                                    }
+                                    None => {
+                                        done = true;
+                                        let events = decoder.finish();
+                                        if !received_data && events.is_empty() {
+                                            return Err(ApiError::MalformedStream("the response ended without any data".to_string()).into());
                                         }
+                                        events
                                     }
-                                    output
-                                });
+                                };
+
+                                let mut content = String::new();
+                                let mut stream_error = None;
+                                for event in events.into_iter().filter_map(|e| e.into_data(provider.as_ref())) {
+                                    match event {
+                                        EventSourceData::Data(inner_data) => {
+                                            received_data = true;
+                                            // With `n > 1` the other choices are interleaved; only the first is shown.
+                                            if let Some(choice) = inner_data.choices.iter().find(|choice| choice.index == 0) {
+                                                if let Some(delta) = &choice.delta.content {
+                                                    content.push_str(delta);
+                                                }
+                                                if let Some(reason) = &choice.finish_reason {
+                                                    finish_reason = Some(reason.clone());
+                                                }
+                                            }
+                                            if !inner_data.model.is_empty() {
+                                                model = Some(inner_data.model.clone());
+                                            }
+                                            if let Some(chunk_usage) = &inner_data.usage {
+                                                merge_usage(&mut usage, chunk_usage);
+                                            }
+                                        }
+                                        EventSourceData::Done(_) => {
+                                            done = true;
+                                            break;
+                                        }
+                                        EventSourceData::Error(message) => {
+                                            stream_error = Some(ApiError::Server { status: None, message });
+                                            break;
+                                        }
+                                        EventSourceData::ContentFiltered(categories) => {
+                                            stream_error = Some(ApiError::ContentFiltered(categories));
+                                            break;
+                                        }
+                                    }
+                                }
 
                                 // Update the chat state 
-                                chat_dispatch.reduce_mut(|s| {
-                                    let chat = &mut s.chats[current_chat_index];
-                                    if let Some(last_msg) = chat.messages.last_mut() {
-                                        last_msg.content.push_str(&data);
-                                    }
-                                });
+                                received.push_str(&content);
+                                if !content.is_empty() {
+                                    chat_dispatch.reduce_mut(|s| {
+                                        if let Some(last_msg) = s.chat_mut(&chat_id).and_then(|c| c.messages.last_mut()) {
+                                            last_msg.content.push_str(&content);
+                                        }
+                                    });
+                                }
+                                if let Some(error) = stream_error {
+                                    return Err(error.into());
+                                }
+                            }
+                            // Dropping the stream here (or when the generation is aborted)
+                            // cancels the underlying request.
+                            drop(stream);
+
+                            // Each request is accounted for on its own, continuations included.
+                            let usage = request_usage(usage, &messages, &received, &config.model);
+                            let again = finish_reason.as_deref() == Some("length") && continuations < config_store.auto_continue;
+                            chat_dispatch.reduce_mut(|s| {
+                                if let Some(reply) = s.chat_mut(&chat_id).and_then(|c| c.messages.last_mut()) {
+                                    reply.model = Some(model.unwrap_or_else(|| config.model.clone()));
+                                    let total = reply.usage.get_or_insert_with(TokenUsage::default);
+                                    total.prompt_tokens += usage.prompt_tokens;
+                                    total.completion_tokens += usage.completion_tokens;
+                                    // Like the Continue button, going on clears the mark.
+                                    reply.finish_reason = if again { None } else { finish_reason };
+                                }
+                            });
+
+                            // Update token usage in chatting
+                            if config_store.count_total_tokens {
+                                update_total_token_used(config_dispath.clone(), config.model.clone(), usage);
                             }
 
-                            // If User cancelled the generation of token, cancel the stream
-                            if !state.generating {
+                            if !again {
                                 break;
                             }
+                            continuations += 1;
+                            let state = chat_dispatch.get();
+                            let Some(chat) = state.chats.iter().find(|c| c.id == chat_id) else {
+                                return Ok(());
+                            };
+                            history = chat.messages.clone();
                         }
 
-                        // Update token usage in chatting
-                        if !state.chats.is_empty() && config_store.count_total_tokens {
-                            let model = state.chats[current_chat_index]
-                                .config
-                                .as_ref()
-                                .unwrap()
-                                .model
-                                .clone();
-                            let messages = state.chats[current_chat_index].messages.clone();
-                            let last_message = messages.last().unwrap().clone();
-                            update_total_token_used(
-                                config_dispath.clone(),
-                                model,
-                                messages,
-                                last_message,
-                            );
-                        }
+                        let state = chat_dispatch.get();
+                        let Some(chat_index) = state.chats.iter().position(|c| c.id == chat_id) else {
+                            return Ok(());
+                        };
 
                         // Generate title if needed
-                        if config_store.auto_title && !state.chats[current_chat_index].title_set {
-                            generate(chat_dispatch.clone(), config_dispath.clone(), current_chat_index).await?;
+                        // A failed title shouldn't turn a successful answer into an error.
+                        if config_store.auto_title && !state.chats[chat_index].title_set
+                            && let Err(e) = generate_title(&chat_id, &auth_state, chat_dispatch.clone(), config_dispath.clone()).await
+                        {
+                            log::warn!("Failed to generate title: {}", e);
                         }
 
                         Ok(())
-                    }
+                    }, abort_registration)
                     .await;
 
-                    chat_dispatch.reduce_mut(|s| {
-                        s.generating = false;
-                        if let Err(e) = result {
-                            s.error = e.to_string();
+                    // An aborted generation was already wound down by `ChatSlice::stop_generating`.
+                    if let Ok(result) = result {
+                        if let Some(api_error) = result.as_ref().err().and_then(|e| e.downcast_ref::<ApiError>()) {
+                            let (severity, key) = api_error.explain();
+                            let message = translate(key.to_string(), Some(Namespace { ns: "api".to_string() }));
+                            toast_dispatch.reduce_mut(|d| {
+                                d.status = toast_status(severity);
+                                d.message = message;
+                                d.show = true;
+                            });
                         }
-                    });
+                        chat_dispatch.reduce_mut(|s| {
+                            s.generating = false;
+                            s.generation = None;
+                            if let Err(e) = result {
+                                s.error = e.to_string();
+                            }
+                        });
+                    }
                 }
             });
         })
-    };
+    }
+}
 
-    (handle_submit, error)
+/// Stops the running generation once its chat is no longer the one on screen,
+/// either because the user switched to another chat or deleted it.
+#[hook]
+pub fn use_abort_on_chat_change() {
+    let (state, chat_dispatch) = use_store::<ChatSlice>();
+    let current_chat_id = state
+        .chats
+        .get(state.curr_chat_index as usize)
+        .map(|c| c.id.clone());
+    let generating_chat_id = state.generation.as_ref().map(|g| g.chat_id.clone());
+
+    use_effect_with((current_chat_id, generating_chat_id), move |(current, generating)| {
+        if generating.is_some() && current != generating {
+            chat_dispatch.reduce_mut(|s| s.stop_generating());
+        }
+        || ()
+    });
 }
 
-fn update_total_token_used(
-    config_dispatch: Dispatch<ConfigSlice>,
-    model: ModelOptions,
-    prompt_msg: Vec<MessageInterface>,
-    message: MessageInterface,
-) {
-    config_dispatch.reduce_mut(|d| {
-        let current_usage = d
-            .total_token_used
-            .get(&model)
-            .cloned()
-            .unwrap_or(TokenUsage {
-                prompt_tokens: 0,
-                completion_tokens: 0,
-            });
+fn toast_status(severity: Severity) -> ToastStatus {
+    match severity {
+        Severity::Warning => ToastStatus::Warning,
+        Severity::Error => ToastStatus::Error,
+    }
+}
+
+/// Providers report usage in pieces (Anthropic sends prompt tokens on
+/// `message_start` and completion tokens on `message_delta`); keep the latest
+/// non-zero value of each counter.
+fn merge_usage(usage: &mut Option<TokenUsage>, chunk: &TokenUsage) {
+    let usage = usage.get_or_insert_with(TokenUsage::default);
+    if chunk.prompt_tokens > 0 {
+        usage.prompt_tokens = chunk.prompt_tokens;
+    }
+    if chunk.completion_tokens > 0 {
+        usage.completion_tokens = chunk.completion_tokens;
+    }
+}
+
+/// What one request took: the provider's own accounting, or else a tiktoken
+/// estimate of the messages sent and the text received.
+fn request_usage(reported: Option<TokenUsage>, sent: &[MessageInterface], received: &str, model: &str) -> TokenUsage {
+    reported.unwrap_or_else(|| {
+        let reply = MessageInterface {
+            role: Role::Assistant,
+            content: received.to_string(),
+            ..Default::default()
+        };
+        TokenUsage {
+            prompt_tokens: count_tokens(sent, model) as u32,
+            completion_tokens: count_tokens(&[reply], model) as u32,
+        }
+    })
+}
 
-        let new_prompt_token = count_tokens(&prompt_msg, &model) as u32;
-        let new_completion_token = count_tokens(&[message], &model) as u32;
-        // Update the total token usage for the given model
-        d.total_token_used.insert(
-            model,
-            TokenUsage {
-                prompt_tokens: current_usage.prompt_tokens + new_prompt_token,
-                completion_tokens: current_usage.completion_tokens + new_completion_token,
-            },
-        );
+fn update_total_token_used(config_dispatch: Dispatch<ConfigSlice>, model: String, usage: TokenUsage) {
+    config_dispatch.reduce_mut(|d| {
+        let total = d.total_token_used.entry(model).or_default();
+        total.prompt_tokens += usage.prompt_tokens;
+        total.completion_tokens += usage.completion_tokens;
     });
 }
 
-async fn generate_title(messages: &[MessageInterface]) -> anyhow::Result<String> {
-    // todo!()
-    Ok("".to_string())
+/// Longest excerpt of a message put into the title prompt, in characters.
+const TITLE_EXCERPT_CHARS: usize = 1000;
+
+/// Fills the title prompt template with the first exchange of the chat:
+/// `{user}` is the first user message, `{assistant}` the reply to it.
+pub(crate) fn title_prompt(template: &str, messages: &[MessageInterface]) -> Option<String> {
+    let user = messages.iter().position(|m| m.role == Role::User && !m.content.trim().is_empty())?;
+    let assistant = messages[user..]
+        .iter()
+        .find(|m| m.role == Role::Assistant)
+        .map(|m| m.content.as_str())
+        .unwrap_or_default();
+    let excerpt = |text: &str| text.trim().chars().take(TITLE_EXCERPT_CHARS).collect::<String>();
+    Some(
+        template
+            .replace("{user}", &excerpt(&messages[user].content))
+            .replace("{assistant}", &excerpt(assistant)),
+    )
+}
+
+/// Models like to answer `Title: "Foo"` instead of just `Foo`.
+pub(crate) fn clean_title(raw: &str) -> String {
+    let line = raw.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or_default();
+    let line = line
+        .strip_prefix("Title:")
+        .or_else(|| line.strip_prefix("title:"))
+        .unwrap_or(line);
+    line.trim()
+        .trim_matches(|c| matches!(c, '"' | '\'' | '“' | '”' | '*' | '#'))
+        .trim()
+        .to_string()
 }
 
-async fn generate(chat_dispatch: Dispatch<ChatSlice>, config_dispatch: Dispatch<ConfigSlice>, index: usize) -> anyhow::Result<()> {
+/// Asks the chat's API profile for a title, using the title model from the
+/// settings (or the chat's own model) with a non-streaming request.
+pub(crate) async fn generate_title(
+    chat_id: &str,
+    auth: &AuthSlice,
+    chat_dispatch: Dispatch<ChatSlice>,
+    config_dispatch: Dispatch<ConfigSlice>,
+) -> anyhow::Result<()> {
     let state = chat_dispatch.get();
-    let chat = &state.chats[index];
-
-    let messages = &chat.messages;
-    let user_message = messages[messages.len() - 2].content.clone();
-    let assistant_message = messages[messages.len() - 1].content.clone();
-
-    let prompt = format!(
-        "Generate a title in less than 6 words for the following message:\n\
-        User: {}\n\
-        Assistant: {}",
-        user_message, assistant_message
-    );
+    let Some(chat) = state.chats.iter().find(|c| c.id == chat_id) else {
+        return Ok(());
+    };
+    let settings = config_dispatch.get();
+    let Some(prompt) = title_prompt(&settings.title_prompt, &chat.messages) else {
+        anyhow::bail!("Nothing to generate a title from");
+    };
     let message = MessageInterface {
         role: Role::User,
         content: prompt,
-        folder: None,
-        messages: vec![]
+        ..Default::default()
     };
 
-    let title = generate_title(&[message.clone()]).await
-        .unwrap_or_default()
-        .trim_matches('"')
-        .to_string();
-
+    let profile = auth.profile(chat.api_profile.as_deref());
+    if profile.endpoint.is_empty() {
+        anyhow::bail!("No API endpoint defined!");
+    }
+    let chat_model = chat.config.as_ref().map(|c| c.model.clone()).or(profile.default_model.clone());
+    let config = ConfigInterface {
+        model: settings
+            .title_model
+            .clone()
+            .filter(|m| !m.is_empty())
+            .or(chat_model)
+            .unwrap_or_else(|| ConfigInterface::default().model),
+        max_tokens: 100,
+        ..ConfigInterface::default()
+    };
+    let provider = provider_for(&profile);
+    let response = get_chat_completion(
+        provider.as_ref(),
+        profile.endpoint.clone(),
+        std::slice::from_ref(&message),
+        &config,
+        &profile.api_key,
+        Some(profile.custom_headers.clone().into_iter().collect()),
+    )
+    .await?;
+
+    let title = clean_title(&provider.response_content(&response).unwrap_or_default());
+    if title.is_empty() {
+        return Err(ApiError::MalformedStream("the title response was empty".to_string()).into());
+    }
     chat_dispatch.reduce_mut({
         let title = title.clone();
         move |s| {
-            if let Some(chat) = s.chats.get_mut(index) {
+            if let Some(chat) = s.chat_mut(chat_id) {
                 chat.title = Some(title);
                 chat.title_set = true;
             }
         }
     });
 
-    let config = config_dispatch.get();
-    if config.count_total_tokens {
-        let assistant_msg = MessageInterface { role: Role::Assistant, content: title.clone(), folder: None, messages: vec![] };
-        let model = ConfigInterface::default().model;
-        update_total_token_used(config_dispatch.clone(), model, vec![message.clone()], assistant_msg);
+    if settings.count_total_tokens {
+        let usage = request_usage(provider.usage(&response), &[message], &title, &config.model);
+        update_total_token_used(config_dispatch, config.model, usage);
     }
 
     Ok(())
 }
 
-pub fn count_tokens(messages: &[MessageInterface], model: &ModelOptions) -> usize {
+/// Regenerates the title of the chat with the given id, e.g. from the chat
+/// history menu. API errors are reported as a toast.
+#[hook]
+pub fn use_generate_title() -> Callback<String> {
+    let (auth_state, _) = use_store::<AuthSlice>();
+    let (_, chat_dispatch) = use_store::<ChatSlice>();
+    let (_, config_dispatch) = use_store::<ConfigSlice>();
+    let (_, toast_dispatch) = use_store::<ToastSlice>();
+    let translate = use_translation(vec!["api".to_string()]);
+
+    Callback::from(move |chat_id: String| {
+        let auth_state = auth_state.clone();
+        let chat_dispatch = chat_dispatch.clone();
+        let config_dispatch = config_dispatch.clone();
+        let toast_dispatch = toast_dispatch.clone();
+        let translate = translate.clone();
+        spawn_local(async move {
+            if let Err(e) = generate_title(&chat_id, &auth_state, chat_dispatch, config_dispatch).await {
+                log::warn!("Failed to generate title: {}", e);
+                if let Some(api_error) = e.downcast_ref::<ApiError>() {
+                    let (severity, key) = api_error.explain();
+                    let message = translate(key.to_string(), Some(Namespace { ns: "api".to_string() }));
+                    toast_dispatch.reduce_mut(|d| {
+                        d.status = toast_status(severity);
+                        d.message = message;
+                        d.show = true;
+                    });
+                }
+            }
+        });
+    })
+}
+
+pub fn count_tokens(messages: &[MessageInterface], model: &str) -> usize {
     if messages.is_empty() {
         return 0;
     }
@@ -260,16 +544,32 @@ pub fn count_tokens(messages: &[MessageInterface], model: &ModelOptions) -> usiz
         })
         .collect();
 
-    // Get appropriate model name string
-    let model_name = model.to_string();
+    // Models tiktoken doesn't know (other providers, local models) are
+    // estimated with the GPT-4 tokenizer.
+    num_tokens_from_messages(model, &chat_messages)
+        .or_else(|_| num_tokens_from_messages("gpt-4", &chat_messages))
+        .unwrap_or(0)
+}
 
-    get_chat_completion_max_tokens(&model_name, &chat_messages).unwrap_or(0)
+/// The messages sent to continue the cut off reply at the end of `history`:
+/// the reply and the user turn before it always go along, what came earlier
+/// as far as `budget` allows.
+pub(crate) fn continuation_messages(history: &[MessageInterface], budget: usize, model: &str) -> Vec<MessageInterface> {
+    let mut keep_from = history.len().saturating_sub(1);
+    if keep_from > 0 && history[keep_from - 1].role == Role::User {
+        keep_from -= 1;
+    }
+    let tail = &history[keep_from..];
+    let rest = budget.saturating_sub(count_tokens(tail, model));
+    let mut messages = limit_message_tokens(&history[..keep_from], rest, model);
+    messages.extend_from_slice(tail);
+    messages
 }
 
 pub fn limit_message_tokens(
     messages: &[MessageInterface],
     limit: usize,
-    model: &ModelOptions,
+    model: &str,
 ) -> Vec<MessageInterface> {
     if messages.is_empty() {
         return vec![];
@@ -294,7 +594,7 @@ pub fn limit_message_tokens(
     // Iterate through messages in reverse order, adding them to the limitedMessages array
     // until the token limit is reached (excludes first message)
     for msg in messages.iter().rev().take(messages.len() - 1) {
-        let msg_tokens = count_tokens(&[msg.clone()], model);
+        let msg_tokens = count_tokens(std::slice::from_ref(msg), model);
         if token_count + msg_tokens > limit {
             break;
         }
@@ -315,3 +615,66 @@ pub fn limit_message_tokens(
 
     limited_messages
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::DEFAULT_TITLE_PROMPT;
+
+    #[test]
+    fn title_prompt_uses_the_first_exchange() {
+        let message = |role, content: &str| MessageInterface { role, content: content.to_string(), ..Default::default() };
+        let messages = vec![
+            message(Role::System, "You are helpful."),
+            message(Role::User, "¿Cómo hago una tortilla?"),
+            message(Role::Assistant, "Bate los huevos..."),
+            message(Role::User, "¿Y sin cebolla?"),
+        ];
+        let prompt = title_prompt(DEFAULT_TITLE_PROMPT, &messages).unwrap();
+        assert!(prompt.contains("User: ¿Cómo hago una tortilla?\nAssistant: Bate los huevos..."));
+        assert!(prompt.contains("same language"));
+        assert!(title_prompt(DEFAULT_TITLE_PROMPT, &messages[..1]).is_none());
+    }
+
+    #[test]
+    fn each_request_is_accounted_from_the_report_or_an_estimate() {
+        let sent = vec![MessageInterface { role: Role::User, content: "Write a long story.".to_string(), ..Default::default() }];
+        let reported = TokenUsage { prompt_tokens: 12, completion_tokens: 4096 };
+        assert_eq!(request_usage(Some(reported.clone()), &sent, "Once upon", "gpt-4o"), reported);
+
+        let short = request_usage(None, &sent, "Once upon", "gpt-4o");
+        let long = request_usage(None, &sent, "Once upon a time, in a land far away, there lived a dragon.", "gpt-4o");
+        assert!(short.prompt_tokens > 0);
+        assert_eq!(short.prompt_tokens, long.prompt_tokens);
+        assert!(long.completion_tokens > short.completion_tokens);
+    }
+
+    #[test]
+    fn a_continuation_keeps_the_partial_reply_and_its_question() {
+        let message = |role, content: &str| MessageInterface { role, content: content.to_string(), ..Default::default() };
+        let partial = "Once upon a time, ".repeat(200);
+        let history = vec![
+            message(Role::System, "You are a storyteller."),
+            message(Role::User, "Tell me a joke."),
+            message(Role::Assistant, "Why did the chicken cross the road?"),
+            message(Role::User, "Now a long story."),
+            message(Role::Assistant, &partial),
+        ];
+        let contents = |messages: Vec<MessageInterface>| messages.into_iter().map(|m| m.content).collect::<Vec<_>>();
+
+        // Even without room to spare, the request picks up where the reply stopped.
+        let sent = contents(continuation_messages(&history, 100, "gpt-4o"));
+        assert_eq!(sent[sent.len() - 2..], ["Now a long story.".to_string(), partial.clone()]);
+
+        let sent = continuation_messages(&history, 128_000, "gpt-4o");
+        assert_eq!(sent.len(), history.len());
+        assert_eq!(sent.last().unwrap().content, partial);
+    }
+
+    #[test]
+    fn titles_are_cleaned_up() {
+        assert_eq!(clean_title("\"Tortilla sin cebolla\""), "Tortilla sin cebolla");
+        assert_eq!(clean_title("Title: **Rust lifetimes**\n\nExplanation..."), "Rust lifetimes");
+        assert_eq!(clean_title("  “Grüße”  "), "Grüße");
+    }
+}
@@ -1,4 +1,5 @@
 mod api;
+mod cloud;
 mod components;
 mod google_api;
 mod hooks;
@@ -6,14 +7,22 @@ mod store;
 mod types;
 mod utils;
 mod constants;
+mod export;
+mod search;
+mod storage;
+mod vault;
 
-use components::{api_popup::ApiPopup, chat::Chat, menu::Menu, toast::Toast};
+use components::{api_popup::ApiPopup, chat::Chat, menu::Menu, toast::Toast, vault::UnlockScreen};
 use gloo::events::EventListener;
-use gloo_storage::{LocalStorage, Storage};
-use hooks::initialise_chat::use_initialise_chat;
-use store::{AuthSlice, ChatSlice, ConfigSlice};
-use types::chat::ChatInterface;
+use hooks::{
+    cloud_sync::{use_cloud_autosave, use_cloud_sync},
+    initialise_chat::use_initialise_chat,
+    persist::use_persistence,
+};
+use store::{ChatSlice, VaultSlice};
+use types::model::ModelRegistry;
 use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 use yewdux::use_store;
 
@@ -21,9 +30,9 @@ use yewdux::use_store;
 #[function_component]
 pub fn App() -> Html {
     let init_new_chat = use_initialise_chat();
-    let (chat_store, chat_dispatch) = use_store::<ChatSlice>();
-    let (_, config_dispatch) = use_store::<ConfigSlice>();
-    let (_, auth_dispatch) = use_store::<AuthSlice>();
+    let (_, chat_dispatch) = use_store::<ChatSlice>();
+    let (_, registry_dispatch) = use_store::<ModelRegistry>();
+    let (vault, vault_dispatch) = use_store::<VaultSlice>();
 
     {
         // Replace with dynamic i18n.language value
@@ -56,52 +65,43 @@ pub fn App() -> Html {
         );
     }
     {
-        let dispatch = auth_dispatch.clone();
-        let init_new_chat = init_new_chat.clone();
-        use_effect(move || {
-
-            // Handle legacy localStorage migration
-            if let Ok(Some(api_key)) = LocalStorage::get("apiKey") {
-                dispatch.reduce_mut(|s| s.api_key = api_key);
-                LocalStorage::delete("apiKey");
-            }
-    
-            if let Ok(Some(theme)) = LocalStorage::get("theme") {
-                config_dispatch.reduce_mut(|s| s.theme = theme);
-                LocalStorage::delete("theme");
-            }
-    
-            match LocalStorage::get::<Option<String>>("chats") {
-                Ok(Some(old_chats)) => {
-                    let old_chats = serde_json::from_str::<Vec<ChatInterface>>(&old_chats);
-                    match old_chats {
-                        Ok(chats) if !chats.is_empty() => {
-                            chat_dispatch.reduce_mut(|s| {
-                                s.chats = chats;
-                                s.curr_chat_index = 0;
-                            });
-                        }
-                        _ => {
-                            init_new_chat();
+        use_effect_with((), move |_| {
+            let base = gloo_utils::window().location().href().unwrap_or_default();
+            spawn_local(async move {
+                match api::fetch_model_overrides(&base).await {
+                    Ok(json) => registry_dispatch.reduce_mut(|r| {
+                        if let Err(e) = r.merge_json(&json) {
+                            log::warn!("Ignoring invalid models.json: {}", e);
                         }
-                    }
-                    LocalStorage::delete("chats");
-                }
-                _ => {
-                    // let state = dispatch.get();
-                    if chat_store.chats.is_empty() {
-                        init_new_chat();
-                    } else {
-                        // Validate current chat index
-                        let max_index = chat_store.chats.len().saturating_sub(1);
-                        chat_dispatch.reduce_mut(|s| s.curr_chat_index = s.curr_chat_index.min(max_index as i32) );
-                    }
+                    }),
+                    Err(e) => log::debug!("No models.json loaded: {}", e),
                 }
-            }
-    
+            });
+            || ()
+        });
+    }
+    {
+        use_effect_with((), move |_| {
+            vault_dispatch.reduce_mut(|s| {
+                s.header = vault::stored_header();
+                s.checked = true;
+            });
             || ()
         });
     }
+    let cloud_sync = use_cloud_sync();
+    use_cloud_autosave();
+    use_persistence(Callback::from(move |_| {
+        if chat_dispatch.get().chats.is_empty() {
+            init_new_chat();
+        }
+        cloud_sync.finish_sign_in();
+        let cloud_sync = cloud_sync.clone();
+        spawn_local(async move { cloud_sync.sync().await });
+    }));
+    if vault.is_locked() {
+        return html! { <UnlockScreen /> };
+    }
     html! {
         <div class="overflow-hidden w-full h-full relative">
             <Menu />
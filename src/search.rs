@@ -0,0 +1,450 @@
+//! Full-text search over the messages of all chats.
+//!
+//! [`SearchIndex`] maps every word to the messages containing it. It is
+//! brought up to date with [`SearchIndex::update`], which only reindexes the
+//! chats that changed since the last call.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ops::Bound,
+};
+
+use crate::types::chat::{ChatInterface, MessageInterface, Role};
+
+/// Hits returned at most by [`SearchIndex::search`].
+pub(crate) const MAX_HITS: usize = 100;
+/// Characters kept before the first match in a snippet.
+const SNIPPET_LEAD: usize = 40;
+/// Length of a snippet in characters.
+const SNIPPET_LEN: usize = 160;
+
+#[derive(Debug, Clone, PartialEq)]
+struct IndexedMessage {
+    role: Role,
+    content: String,
+    /// The model of the message, or else the one the chat is set to.
+    model: Option<String>,
+    created_at: Option<i64>,
+}
+
+impl IndexedMessage {
+    fn of(message: &MessageInterface, chat: &ChatInterface) -> Self {
+        Self {
+            role: message.role.clone(),
+            content: message.content.clone(),
+            model: model(message, chat).map(str::to_string),
+            created_at: message.created_at,
+        }
+    }
+
+    fn is_current(&self, message: &MessageInterface, chat: &ChatInterface) -> bool {
+        self.role == message.role
+            && self.content == message.content
+            && self.model.as_deref() == model(message, chat)
+            && self.created_at == message.created_at
+    }
+}
+
+fn model<'a>(message: &'a MessageInterface, chat: &'a ChatInterface) -> Option<&'a str> {
+    message
+        .model
+        .as_deref()
+        .or_else(|| chat.config.as_ref().map(|c| c.model.as_str()))
+}
+
+/// What is indexed of a chat: the messages on its active branch.
+#[derive(Debug, Clone, PartialEq)]
+struct IndexedChat {
+    folder: Option<String>,
+    messages: Vec<IndexedMessage>,
+}
+
+impl IndexedChat {
+    fn of(chat: &ChatInterface) -> Self {
+        Self {
+            folder: chat.folder.clone(),
+            messages: chat.messages.iter().map(|m| IndexedMessage::of(m, chat)).collect(),
+        }
+    }
+
+    fn is_current(&self, chat: &ChatInterface) -> bool {
+        self.folder == chat.folder
+            && self.messages.len() == chat.messages.len()
+            && self
+                .messages
+                .iter()
+                .zip(&chat.messages)
+                .all(|(indexed, message)| indexed.is_current(message, chat))
+    }
+}
+
+/// Narrows a search down. Unset fields match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct SearchFilter {
+    pub(crate) role: Option<Role>,
+    pub(crate) model: Option<String>,
+    /// Id of the folder the chat is in.
+    pub(crate) folder: Option<String>,
+    /// Unix time in milliseconds. Messages without a time don't match when
+    /// either bound is set.
+    pub(crate) from: Option<i64>,
+    pub(crate) to: Option<i64>,
+}
+
+impl SearchFilter {
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn accepts(&self, chat: &IndexedChat, message: &IndexedMessage) -> bool {
+        let dated = self.from.is_none() && self.to.is_none()
+            || message.created_at.is_some_and(|at| {
+                self.from.is_none_or(|from| at >= from) && self.to.is_none_or(|to| at <= to)
+            });
+        self.role.as_ref().is_none_or(|role| *role == message.role)
+            && self.model.as_ref().is_none_or(|model| message.model.as_ref() == Some(model))
+            && self.folder.as_ref().is_none_or(|folder| chat.folder.as_ref() == Some(folder))
+            && dated
+    }
+}
+
+/// A piece of a [`SearchHit::snippet`].
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Fragment {
+    pub(crate) text: String,
+    /// Whether this is a word the query matched.
+    pub(crate) highlight: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SearchHit {
+    pub(crate) chat_id: String,
+    /// Index of the message in the chat's active branch.
+    pub(crate) message: usize,
+    pub(crate) role: Role,
+    /// The text around the first match.
+    pub(crate) snippet: Vec<Fragment>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SearchIndex {
+    chats: HashMap<String, IndexedChat>,
+    /// Word → chat id → indices of the messages containing it. Ordered so
+    /// the words starting with a prefix can be found.
+    postings: BTreeMap<String, HashMap<String, BTreeSet<usize>>>,
+}
+
+impl SearchIndex {
+    /// Reindexes the chats that changed since the last update, and drops
+    /// the ones that are gone.
+    pub(crate) fn update(&mut self, chats: &[ChatInterface]) {
+        let mut present = HashSet::new();
+        for chat in chats {
+            present.insert(chat.id.as_str());
+            if self.chats.get(&chat.id).is_some_and(|indexed| indexed.is_current(chat)) {
+                continue;
+            }
+            self.remove(&chat.id);
+            self.insert(chat);
+        }
+        let gone: Vec<String> = self
+            .chats
+            .keys()
+            .filter(|id| !present.contains(id.as_str()))
+            .cloned()
+            .collect();
+        for id in gone {
+            self.remove(&id);
+        }
+    }
+
+    fn insert(&mut self, chat: &ChatInterface) {
+        let indexed = IndexedChat::of(chat);
+        for (index, message) in indexed.messages.iter().enumerate() {
+            for term in terms(&message.content) {
+                self.postings
+                    .entry(term)
+                    .or_default()
+                    .entry(chat.id.clone())
+                    .or_default()
+                    .insert(index);
+            }
+        }
+        self.chats.insert(chat.id.clone(), indexed);
+    }
+
+    fn remove(&mut self, chat_id: &str) {
+        let Some(chat) = self.chats.remove(chat_id) else {
+            return;
+        };
+        for message in &chat.messages {
+            for term in terms(&message.content) {
+                if let Some(chats) = self.postings.get_mut(&term) {
+                    chats.remove(chat_id);
+                    if chats.is_empty() {
+                        self.postings.remove(&term);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The models of the indexed messages, to filter by.
+    pub(crate) fn models(&self) -> BTreeSet<&str> {
+        self.chats
+            .values()
+            .flat_map(|chat| chat.messages.iter().filter_map(|m| m.model.as_deref()))
+            .collect()
+    }
+
+    /// The messages containing every word of `query`, each as a prefix of a
+    /// word so results show up while typing. Hits follow the order of
+    /// `chats`, then of the messages.
+    pub(crate) fn search(&self, chats: &[ChatInterface], query: &str, filter: &SearchFilter) -> Vec<SearchHit> {
+        let mut query_terms: Vec<String> = terms(query).collect();
+        query_terms.sort();
+        query_terms.dedup();
+        let Some(found) = query_terms
+            .iter()
+            .map(|term| self.matching(term))
+            .reduce(intersect)
+        else {
+            return Vec::new();
+        };
+
+        let mut hits = Vec::new();
+        for chat in chats {
+            let (Some(messages), Some(indexed)) = (found.get(chat.id.as_str()), self.chats.get(&chat.id)) else {
+                continue;
+            };
+            for &index in messages {
+                let message = &indexed.messages[index];
+                if !filter.accepts(indexed, message) {
+                    continue;
+                }
+                hits.push(SearchHit {
+                    chat_id: chat.id.clone(),
+                    message: index,
+                    role: message.role.clone(),
+                    snippet: snippet(&message.content, &query_terms),
+                });
+                if hits.len() == MAX_HITS {
+                    return hits;
+                }
+            }
+        }
+        hits
+    }
+
+    /// The messages with a word starting with `prefix`, by chat.
+    fn matching(&self, prefix: &str) -> HashMap<&str, BTreeSet<usize>> {
+        let mut found: HashMap<&str, BTreeSet<usize>> = HashMap::new();
+        let words = self
+            .postings
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(word, _)| word.starts_with(prefix));
+        for (_, chats) in words {
+            for (chat_id, messages) in chats {
+                found.entry(chat_id.as_str()).or_default().extend(messages);
+            }
+        }
+        found
+    }
+}
+
+fn intersect<'a>(
+    a: HashMap<&'a str, BTreeSet<usize>>,
+    b: HashMap<&'a str, BTreeSet<usize>>,
+) -> HashMap<&'a str, BTreeSet<usize>> {
+    a.into_iter()
+        .filter_map(|(chat_id, messages)| {
+            let both: BTreeSet<usize> = messages.intersection(b.get(chat_id)?).copied().collect();
+            (!both.is_empty()).then_some((chat_id, both))
+        })
+        .collect()
+}
+
+/// Byte ranges of the words in `text`.
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, text.len()));
+    }
+    words
+}
+
+/// The lowercased words of `text`.
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    words(text).into_iter().map(|(start, end)| text[start..end].to_lowercase())
+}
+
+/// About [`SNIPPET_LEN`] characters of `content` around its first word
+/// matching `query_terms`, with the matching words highlighted.
+fn snippet(content: &str, query_terms: &[String]) -> Vec<Fragment> {
+    let matches: Vec<(usize, usize)> = words(content)
+        .into_iter()
+        .filter(|&(start, end)| {
+            let word = content[start..end].to_lowercase();
+            query_terms.iter().any(|term| word.starts_with(term.as_str()))
+        })
+        .collect();
+    let first = matches.first().map_or(0, |&(start, _)| start);
+    let boundaries: Vec<usize> = content.char_indices().map(|(i, _)| i).chain([content.len()]).collect();
+    let first_char = boundaries.partition_point(|&i| i < first);
+    let start_char = first_char.saturating_sub(SNIPPET_LEAD);
+    let start = boundaries[start_char];
+    let end = boundaries[(start_char + SNIPPET_LEN).min(boundaries.len() - 1)];
+
+    let mut fragments = Vec::new();
+    let mut push = |text: &str, highlight: bool| {
+        if !text.is_empty() {
+            fragments.push(Fragment {
+                text: text.replace(['\n', '\r'], " "),
+                highlight,
+            });
+        }
+    };
+    let mut at = start;
+    if start > 0 {
+        push("…", false);
+    }
+    for (word_start, word_end) in matches {
+        let (word_start, word_end) = (word_start.max(start), word_end.min(end));
+        if word_start >= word_end {
+            continue;
+        }
+        push(&content[at..word_start], false);
+        push(&content[word_start..word_end], true);
+        at = word_end;
+    }
+    push(&content[at..end], false);
+    if end < content.len() {
+        push("…", false);
+    }
+    fragments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::chat::ConfigInterface;
+
+    fn message(role: Role, content: &str, created_at: i64) -> MessageInterface {
+        MessageInterface {
+            role,
+            content: content.to_string(),
+            created_at: Some(created_at),
+            ..MessageInterface::default()
+        }
+    }
+
+    fn chat(id: &str, folder: Option<&str>, messages: Vec<MessageInterface>) -> ChatInterface {
+        ChatInterface {
+            id: id.to_string(),
+            folder: folder.map(str::to_string),
+            messages,
+            config: Some(ConfigInterface {
+                model: "gpt-4o".to_string(),
+                ..ConfigInterface::default()
+            }),
+            ..ChatInterface::default()
+        }
+    }
+
+    /// Each hit as chat id and message index, e.g. `a1`.
+    fn found(hits: &[SearchHit]) -> Vec<String> {
+        hits.iter().map(|h| format!("{}{}", h.chat_id, h.message)).collect()
+    }
+
+    fn highlighted(hit: &SearchHit) -> Vec<&str> {
+        hit.snippet.iter().filter(|f| f.highlight).map(|f| f.text.as_str()).collect()
+    }
+
+    #[test]
+    fn updates_reindex_only_what_changed() {
+        let mut chats = vec![
+            chat("a", None, vec![message(Role::User, "How do I parse JSON in Rust?", 1)]),
+            chat("b", None, vec![message(Role::User, "Write a haiku about rust", 2)]),
+        ];
+        let mut index = SearchIndex::default();
+        index.update(&chats);
+        let filter = SearchFilter::default();
+        assert_eq!(found(&index.search(&chats, "rust", &filter)), ["a0", "b0"]);
+        assert_eq!(found(&index.search(&chats, "PARS rus", &filter)), ["a0"], "every word, as a prefix");
+        assert!(index.search(&chats, "  ", &filter).is_empty());
+
+        chats[1].messages[0].content = "Write a haiku about autumn".to_string();
+        chats.remove(0);
+        index.update(&chats);
+        assert!(index.search(&chats, "rust", &filter).is_empty());
+        assert!(!index.postings.contains_key("json"), "no postings are left behind");
+        assert_eq!(found(&index.search(&chats, "autumn", &filter)), ["b0"]);
+    }
+
+    #[test]
+    fn filters_narrow_the_hits() {
+        let mut answer = message(Role::Assistant, "Use serde_json to parse it.", 2_000);
+        answer.model = Some("claude-3".to_string());
+        let chats = vec![
+            chat("a", Some("work"), vec![message(Role::User, "Parse this file", 1_000), answer]),
+            chat("b", None, vec![message(Role::User, "parse dates", 3_000), message(Role::System, "parse", 0)]),
+        ];
+        let mut index = SearchIndex::default();
+        index.update(&chats);
+        let search = |filter: SearchFilter| found(&index.search(&chats, "parse", &filter));
+        assert_eq!(search(SearchFilter::default()).len(), 4);
+        let role = SearchFilter {
+            role: Some(Role::User),
+            ..SearchFilter::default()
+        };
+        assert_eq!(search(role), ["a0", "b0"]);
+        let model = SearchFilter {
+            model: Some("claude-3".to_string()),
+            ..SearchFilter::default()
+        };
+        assert_eq!(search(model), ["a1"]);
+        let chat_model = SearchFilter {
+            model: Some("gpt-4o".to_string()),
+            ..SearchFilter::default()
+        };
+        assert_eq!(search(chat_model), ["a0", "b0", "b1"], "falls back to the chat's model");
+        let folder = SearchFilter {
+            folder: Some("work".to_string()),
+            ..SearchFilter::default()
+        };
+        assert_eq!(search(folder), ["a0", "a1"]);
+        let dates = SearchFilter {
+            from: Some(1_500),
+            to: Some(3_000),
+            ..SearchFilter::default()
+        };
+        assert_eq!(search(dates), ["a1", "b0"]);
+        assert_eq!(index.models().into_iter().collect::<Vec<_>>(), ["claude-3", "gpt-4o"]);
+    }
+
+    #[test]
+    fn snippets_highlight_matches_around_the_first_one() {
+        let long = format!("{} Ünïcode needle here, and another Needles.\nEnd", "word ".repeat(40));
+        let chats = vec![chat("a", None, vec![message(Role::User, &long, 1)])];
+        let mut index = SearchIndex::default();
+        index.update(&chats);
+        let hits = index.search(&chats, "needle ünï", &SearchFilter::default());
+        let hit = &hits[0];
+        assert_eq!(highlighted(hit), ["Ünïcode", "needle", "Needles"]);
+        assert_eq!(hit.snippet.first().unwrap().text, "…");
+        let text: String = hit.snippet.iter().map(|f| f.text.as_str()).collect();
+        assert!(text.ends_with("Needles. End"), "{}", text);
+        assert!(text.chars().count() <= SNIPPET_LEN + 1);
+    }
+}
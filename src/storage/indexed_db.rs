@@ -0,0 +1,232 @@
+use futures::future::LocalBoxFuture;
+use gloo_storage::{LocalStorage, Storage};
+use js_sys::{Array, Promise};
+use wasm_bindgen::{JsCast, JsValue, closure::Closure};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbRequest, IdbTransaction, IdbTransactionMode};
+
+use crate::vault::Vault;
+
+use super::{
+    PersistedState, STATE_VERSION, STORAGE_KEY, Snapshot, StateChanges, StorageBackend, StorageError,
+    assemble, backup_key, decode, encode, local,
+};
+
+const DB_NAME: &str = "yewmind";
+const DB_VERSION: u32 = 2;
+/// One record per chat, keyed by its id.
+const CHATS: &str = "chats";
+/// The [`Envelope::meta`](super::Envelope::meta) of the state, under [`META_KEY`].
+const META: &str = "meta";
+const META_KEY: &str = "state";
+/// The raw records of a state as they were before a migration, keyed by
+/// [`backup_key`] of the version they were stored at.
+const BACKUPS: &str = "backups";
+
+/// Keeps every chat as a record of its own, so a save only writes the
+/// chats that changed. Each record is sealed on its own when there is a
+/// vault.
+pub(crate) struct IndexedDbBackend {
+    db: IdbDatabase,
+    vault: Option<Vault>,
+}
+
+impl IndexedDbBackend {
+    pub(crate) async fn open(vault: Option<Vault>) -> Result<Self, StorageError> {
+        let factory = gloo_utils::window()
+            .indexed_db()
+            .map_err(StorageError::from_js)?
+            .ok_or_else(|| StorageError::Browser("IndexedDB is not supported".to_string()))?;
+        let request = factory
+            .open_with_u32(DB_NAME, DB_VERSION)
+            .map_err(StorageError::from_js)?;
+
+        let on_upgrade = Closure::<dyn FnMut()>::new({
+            let request = request.clone();
+            move || {
+                let Ok(db) = request.result() else { return };
+                let db = db.unchecked_into::<IdbDatabase>();
+                for store in [CHATS, META, BACKUPS] {
+                    if !db.object_store_names().contains(store) {
+                        let _ = db.create_object_store(store);
+                    }
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+        let db = settle(&request).await;
+        request.set_onupgradeneeded(None);
+
+        Ok(Self {
+            db: db?.unchecked_into(),
+            vault,
+        })
+    }
+
+    fn transaction(&self, mode: IdbTransactionMode) -> Result<IdbTransaction, StorageError> {
+        let stores = Array::of2(&JsValue::from_str(CHATS), &JsValue::from_str(META));
+        self.db
+            .transaction_with_str_sequence_and_mode(&stores, mode)
+            .map_err(StorageError::from_js)
+    }
+
+    async fn write(&self, changes: &StateChanges) -> Result<(), StorageError> {
+        // Serialized up front, so a failure can't leave half a save behind.
+        let vault = self.vault.as_ref();
+        let mut upserted = Vec::with_capacity(changes.upserted.len());
+        for chat in &changes.upserted {
+            upserted.push((chat.id.as_str(), encode(vault, serde_json::to_string(chat)?).await?));
+        }
+        let meta = match &changes.meta {
+            Some(meta) => Some(encode(vault, serde_json::to_string(meta)?).await?),
+            None => None,
+        };
+
+        let tx = self.transaction(IdbTransactionMode::Readwrite)?;
+        let chats = tx.object_store(CHATS).map_err(StorageError::from_js)?;
+        for (id, chat) in upserted {
+            chats
+                .put_with_key(&JsValue::from_str(&chat), &JsValue::from_str(id))
+                .map_err(StorageError::from_js)?;
+        }
+        for id in &changes.removed {
+            chats.delete(&JsValue::from_str(id)).map_err(StorageError::from_js)?;
+        }
+        if let Some(meta) = meta {
+            tx.object_store(META)
+                .and_then(|store| store.put_with_key(&JsValue::from_str(&meta), &JsValue::from_str(META_KEY)))
+                .map_err(StorageError::from_js)?;
+        }
+        committed(&tx).await
+    }
+
+    /// Keeps the records of a state stored at `version` as they are, before
+    /// its migration overwrites them.
+    async fn backup(&self, version: u32, meta: &str, chats: &[String]) -> Result<(), StorageError> {
+        let records = serde_json::json!({ "meta": meta, "chats": chats }).to_string();
+        let tx = self
+            .db
+            .transaction_with_str_and_mode(BACKUPS, IdbTransactionMode::Readwrite)
+            .map_err(StorageError::from_js)?;
+        tx.object_store(BACKUPS)
+            .and_then(|store| store.put_with_key(&JsValue::from_str(&records), &JsValue::from_str(&backup_key(version))))
+            .map_err(StorageError::from_js)?;
+        committed(&tx).await
+    }
+
+    /// Moves a state left in LocalStorage, by an older build or while
+    /// IndexedDB was unavailable, over here.
+    async fn import_local_storage(&self) -> Result<Option<PersistedState>, StorageError> {
+        let Some(state) = local::load(self.vault.as_ref()).await? else {
+            return Ok(None);
+        };
+        let written = match Snapshot::default().changes(&state) {
+            Ok(changes) => self.write(&changes).await,
+            Err(e) => Err(e),
+        };
+        match written {
+            Ok(()) => {
+                log::info!("Moved the stored state from LocalStorage to IndexedDB");
+                LocalStorage::delete(STORAGE_KEY);
+            }
+            Err(e) => log::error!("Could not move the stored state to IndexedDB: {}", e),
+        }
+        Ok(Some(state))
+    }
+}
+
+impl StorageBackend for IndexedDbBackend {
+    fn load(&self) -> LocalBoxFuture<'_, Result<Option<PersistedState>, StorageError>> {
+        Box::pin(async move {
+            let tx = self.transaction(IdbTransactionMode::Readonly)?;
+            let meta = tx
+                .object_store(META)
+                .and_then(|store| store.get(&JsValue::from_str(META_KEY)))
+                .map_err(StorageError::from_js)?;
+            let chats = tx
+                .object_store(CHATS)
+                .and_then(|store| store.get_all())
+                .map_err(StorageError::from_js)?;
+            let meta = settle(&meta).await?;
+            let chats = settle(&chats).await?;
+
+            let Some(raw_meta) = meta.as_string() else {
+                return self.import_local_storage().await;
+            };
+            let raw_chats: Vec<String> = Array::from(&chats).iter().filter_map(|chat| chat.as_string()).collect();
+            let vault = self.vault.as_ref();
+            let (meta, mut plain) = decode(vault, raw_meta.clone()).await?;
+            let mut decoded = Vec::new();
+            for chat in raw_chats.iter().cloned() {
+                let chat = match decode(vault, chat).await {
+                    Ok((chat, was_plain)) => {
+                        plain |= was_plain;
+                        serde_json::from_str(&chat).map_err(StorageError::from)
+                    }
+                    Err(e) => Err(e),
+                };
+                match chat {
+                    Ok(chat) => decoded.push(chat),
+                    Err(e) => log::error!("Skipping a stored chat that can't be read: {}", e),
+                }
+            }
+            let (state, version) =
+                assemble(&meta, decoded).map_err(|e| StorageError::Browser(e.to_string()))?;
+            if version < STATE_VERSION {
+                log::info!("Migrated stored state from version {} to {}", version, STATE_VERSION);
+                self.backup(version, &raw_meta, &raw_chats).await?;
+            }
+            // Records from before the vault was set up are sealed now.
+            if version < STATE_VERSION || (plain && vault.is_some()) {
+                self.write(&Snapshot::default().changes(&state)?).await?;
+            }
+            Ok(Some(state))
+        })
+    }
+
+    fn save<'a>(
+        &'a self,
+        _state: &'a PersistedState,
+        changes: &'a StateChanges,
+    ) -> LocalBoxFuture<'a, Result<(), StorageError>> {
+        Box::pin(async move {
+            if changes.is_empty() {
+                return Ok(());
+            }
+            self.write(changes).await
+        })
+    }
+}
+
+/// Waits for `request` and returns its result.
+async fn settle(request: &IdbRequest) -> Result<JsValue, StorageError> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    let outcome = JsFuture::from(promise).await;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+    match outcome {
+        Ok(_) => request.result().map_err(StorageError::from_js),
+        Err(_) => Err(match request.error() {
+            Ok(Some(e)) => StorageError::from_js(e.into()),
+            _ => StorageError::Browser("IndexedDB request failed".to_string()),
+        }),
+    }
+}
+
+/// Waits for `tx` to be committed.
+async fn committed(tx: &IdbTransaction) -> Result<(), StorageError> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        tx.set_oncomplete(Some(&resolve));
+        tx.set_onabort(Some(&reject));
+    });
+    let outcome = JsFuture::from(promise).await;
+    tx.set_oncomplete(None);
+    tx.set_onabort(None);
+    outcome.map(|_| ()).map_err(|_| match tx.error() {
+        Some(e) => StorageError::from_js(e.into()),
+        None => StorageError::Browser("IndexedDB transaction aborted".to_string()),
+    })
+}
@@ -0,0 +1,114 @@
+use futures::future::LocalBoxFuture;
+use gloo_storage::{LocalStorage, Storage};
+
+use crate::{
+    types::chat::{ChatInterface, Theme},
+    vault::Vault,
+};
+
+use super::{
+    Envelope, PersistedState, STATE_VERSION, STORAGE_KEY, StateChanges, StorageBackend, StorageError,
+    backup_key, decode, encode, parse,
+};
+
+/// Keeps the whole state as one JSON blob under [`STORAGE_KEY`], sealed
+/// when there is a vault.
+///
+/// Used where IndexedDB isn't available. Every save rewrites the blob, and
+/// browsers cap LocalStorage at around 5MB.
+pub(crate) struct LocalStorageBackend {
+    pub(crate) vault: Option<Vault>,
+}
+
+impl StorageBackend for LocalStorageBackend {
+    fn load(&self) -> LocalBoxFuture<'_, Result<Option<PersistedState>, StorageError>> {
+        Box::pin(async { load(self.vault.as_ref()).await })
+    }
+
+    fn save<'a>(
+        &'a self,
+        state: &'a PersistedState,
+        changes: &'a StateChanges,
+    ) -> LocalBoxFuture<'a, Result<(), StorageError>> {
+        Box::pin(async move {
+            if changes.is_empty() {
+                return Ok(());
+            }
+            save(state, self.vault.as_ref()).await
+        })
+    }
+}
+
+/// Reads the persisted state, migrating and re-saving an older layout, or
+/// one stored in the clear while there is a vault now.
+///
+/// The blob as it was before the migration is kept under [`backup_key`].
+/// One that can't be read at all is backed up as well and `None` returned,
+/// so starting afresh doesn't lose it. One that can't be opened is left
+/// alone, and the error returned.
+pub(crate) async fn load(vault: Option<&Vault>) -> Result<Option<PersistedState>, StorageError> {
+    let storage = LocalStorage::raw();
+    let Ok(Some(raw)) = storage.get_item(STORAGE_KEY) else {
+        return Ok(load_legacy_keys(vault).await);
+    };
+    let (json, plain) = decode(vault, raw.clone()).await?;
+    match parse(&json) {
+        Ok((state, version)) => {
+            if version < STATE_VERSION {
+                log::info!("Migrated stored state from version {} to {}", version, STATE_VERSION);
+                let _ = storage.set_item(&backup_key(version), &raw);
+            }
+            if (version < STATE_VERSION || (plain && vault.is_some()))
+                && let Err(e) = save(&state, vault).await
+            {
+                log::error!("Could not save the migrated state: {}", e);
+            }
+            Ok(Some(state))
+        }
+        Err(e) => {
+            log::error!("Could not read the stored state, a backup was kept: {}", e);
+            let version = serde_json::from_str::<Envelope>(&json).map_or(0, |e| e.version);
+            let _ = storage.set_item(&backup_key(version), &raw);
+            Ok(None)
+        }
+    }
+}
+
+pub(crate) async fn save(state: &PersistedState, vault: Option<&Vault>) -> Result<(), StorageError> {
+    let raw = encode(vault, serde_json::to_string(&Envelope::of(state)?)?).await?;
+    LocalStorage::raw()
+        .set_item(STORAGE_KEY, &raw)
+        .map_err(StorageError::from_js)
+}
+
+/// Early builds of this app kept the chats, API key and theme under keys of
+/// their own. They are folded into a state and removed.
+async fn load_legacy_keys(vault: Option<&Vault>) -> Option<PersistedState> {
+    let chats = LocalStorage::get::<String>("chats")
+        .ok()
+        .and_then(|chats| serde_json::from_str::<Vec<ChatInterface>>(&chats).ok());
+    let api_key = LocalStorage::get::<String>("apiKey").ok();
+    let theme = LocalStorage::get::<Theme>("theme").ok();
+    if chats.is_none() && api_key.is_none() && theme.is_none() {
+        return None;
+    }
+
+    let mut state = PersistedState {
+        chats: chats.unwrap_or_default(),
+        ..PersistedState::default()
+    };
+    if let Some(api_key) = api_key {
+        state.auth.default_profile_mut().api_key = Some(api_key);
+    }
+    if let Some(theme) = theme {
+        state.config.theme = theme;
+    }
+    if let Err(e) = save(&state, vault).await {
+        log::error!("Could not save the state: {}", e);
+        return Some(state);
+    }
+    for key in ["chats", "apiKey", "theme"] {
+        LocalStorage::delete(key);
+    }
+    Some(state)
+}
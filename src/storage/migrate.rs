@@ -0,0 +1,190 @@
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::types::chat::{
+    LocalStorageInterfaceV0ToV1, LocalStorageInterfaceV1ToV2, LocalStorageInterfaceV2ToV3,
+    LocalStorageInterfaceV3ToV4, LocalStorageInterfaceV4ToV5, LocalStorageInterfaceV5ToV6,
+    LocalStorageInterfaceV6ToV7, LocalStorageInterfaceV7ToV8, LocalStorageInterfaceV8ToV9,
+};
+
+use super::{PersistedState, STATE_VERSION};
+
+type Step = fn(Value) -> serde_json::Result<Value>;
+
+/// First version stored as a [`PersistedState`]. Later versions only add
+/// fields with defaults (11: when, by which model and at what cost each
+/// message was written), so they read as the current layout; the version is
+/// bumped so that older builds refuse them rather than drop the new fields.
+const FIRST_PERSISTED_VERSION: u32 = 10;
+
+/// `STEPS[n]` turns a state stored at version `n` into one of version `n + 1`.
+const STEPS: [Step; 9] = [
+    |state| step::<LocalStorageInterfaceV0ToV1, LocalStorageInterfaceV1ToV2>(state, LocalStorageInterfaceV0ToV1::migrate_v0),
+    |state| step::<LocalStorageInterfaceV1ToV2, LocalStorageInterfaceV2ToV3>(state, LocalStorageInterfaceV1ToV2::migrate_v1),
+    |state| step::<LocalStorageInterfaceV2ToV3, LocalStorageInterfaceV3ToV4>(state, LocalStorageInterfaceV2ToV3::migrate_v2),
+    |state| step::<LocalStorageInterfaceV3ToV4, LocalStorageInterfaceV4ToV5>(state, LocalStorageInterfaceV3ToV4::migrate_v3),
+    |state| step::<LocalStorageInterfaceV4ToV5, LocalStorageInterfaceV5ToV6>(state, LocalStorageInterfaceV4ToV5::migrate_v4),
+    |state| step::<LocalStorageInterfaceV5ToV6, LocalStorageInterfaceV6ToV7>(state, LocalStorageInterfaceV5ToV6::migrate_v5),
+    |state| step::<LocalStorageInterfaceV6ToV7, LocalStorageInterfaceV7ToV8>(state, LocalStorageInterfaceV6ToV7::migrate_v6),
+    |state| step::<LocalStorageInterfaceV7ToV8, LocalStorageInterfaceV8ToV9>(state, LocalStorageInterfaceV7ToV8::migrate_v7),
+    |state| step::<LocalStorageInterfaceV8ToV9, LocalStorageInterfaceV8ToV9>(state, LocalStorageInterfaceV8ToV9::migrate_v8),
+];
+
+/// Reads `state` in the layout of version `from`, applies `migrate` and
+/// writes it out in the layout of the next version.
+fn step<From, To>(state: Value, migrate: fn(&From) -> From) -> serde_json::Result<Value>
+where
+    From: DeserializeOwned,
+    To: std::convert::From<From> + Serialize,
+{
+    serde_json::to_value(To::from(migrate(&serde_json::from_value(state)?)))
+}
+
+/// Upgrades `state`, stored at `version`, one step at a time to the current
+/// layout.
+pub(crate) fn migrate(version: u32, mut state: Value) -> serde_json::Result<PersistedState> {
+    if version >= FIRST_PERSISTED_VERSION {
+        debug_assert!(version <= STATE_VERSION);
+        return serde_json::from_value(state);
+    }
+    for step in STEPS.iter().skip(version as usize) {
+        state = step(state)?;
+    }
+    let legacy: LocalStorageInterfaceV8ToV9 = serde_json::from_value(state)?;
+    Ok(legacy.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        storage::{PersistedState, STATE_VERSION, parse},
+        types::{
+            api::OFFICIAL_APIENDPOINT,
+            chat::{DEFAULT_MODEL, DEFAULT_USER_MAX_TOKEN, Role, Theme},
+        },
+    };
+
+    const FIXTURES: [&str; 12] = [
+        include_str!("fixtures/v0.json"),
+        include_str!("fixtures/v1.json"),
+        include_str!("fixtures/v2.json"),
+        include_str!("fixtures/v3.json"),
+        include_str!("fixtures/v4.json"),
+        include_str!("fixtures/v5.json"),
+        include_str!("fixtures/v6.json"),
+        include_str!("fixtures/v7.json"),
+        include_str!("fixtures/v8.json"),
+        include_str!("fixtures/v9.json"),
+        include_str!("fixtures/v10.json"),
+        include_str!("fixtures/v11.json"),
+    ];
+
+    fn load(version: usize) -> PersistedState {
+        let (state, stored_at) = parse(FIXTURES[version]).unwrap();
+        assert_eq!(stored_at as usize, version);
+        state
+    }
+
+    fn endpoint(state: &PersistedState) -> String {
+        state.auth.profile(None).endpoint.clone()
+    }
+
+    #[test]
+    fn every_version_keeps_chats_and_credentials() {
+        assert_eq!(FIXTURES.len() as u32, STATE_VERSION + 1);
+        for version in 0..FIXTURES.len() {
+            let state = load(version);
+            let chat = &state.chats[0];
+            assert_eq!(chat.title.as_deref(), Some("Greeting"), "v{}", version);
+            assert!(!chat.id.is_empty(), "v{}", version);
+            assert_eq!(chat.messages[1].role, Role::Assistant, "v{}", version);
+            assert_eq!(chat.messages[1].content, "Hello!", "v{}", version);
+            assert!(chat.config.is_some(), "v{}", version);
+            assert_eq!(
+                state.auth.profile(None).api_key.as_deref(),
+                Some(format!("sk-v{}", version).as_str()),
+                "v{}",
+                version
+            );
+            assert!(!state.auth.first_version || version < 6, "v{}", version);
+        }
+    }
+
+    #[test]
+    fn early_versions_gain_config_endpoint_and_defaults() {
+        let v0 = load(0);
+        assert_eq!(endpoint(&v0), "https://free.example.com/v1/chat");
+        assert_eq!(v0.config.theme, Theme::Dark);
+        assert!(!v0.chats[0].title_set);
+
+        assert_eq!(endpoint(&load(1)), OFFICIAL_APIENDPOINT);
+
+        let v2 = load(2);
+        assert_eq!(endpoint(&v2), "https://proxy.example.com/v1/chat/completions");
+        let config = v2.chats[0].config.as_ref().unwrap();
+        assert_eq!((config.max_tokens, config.temperature), (DEFAULT_USER_MAX_TOKEN, 0.5));
+        assert_eq!((config.top_p, config.frequency_penalty), (1.0, 0.0));
+        assert!(!v2.config.auto_title);
+    }
+
+    #[test]
+    fn later_steps_reset_models_and_replace_dead_endpoints() {
+        // v3 had no prompts yet, v4 resets every chat to the default model.
+        assert!(load(3).prompts.is_empty());
+        let v4 = load(4);
+        assert_eq!(v4.prompts[0].name, "Reviewer");
+        assert_eq!(v4.chats[0].config.as_ref().unwrap().model, DEFAULT_MODEL);
+
+        let v5 = load(5);
+        assert_eq!(v5.chats[0].config.as_ref().unwrap().max_tokens, DEFAULT_USER_MAX_TOKEN);
+        assert!(v5.config.auto_title);
+
+        let v6 = load(6);
+        assert_eq!(endpoint(&v6), OFFICIAL_APIENDPOINT);
+        assert_eq!(v6.config.default_system_message, "Be terse.");
+        assert_eq!(v6.config.default_chat_config.temperature, 0.7);
+        assert!(v6.config.hide_menu_options && v6.config.hide_side_menu);
+        assert_eq!(v6.config.theme, Theme::Light);
+    }
+
+    #[test]
+    fn folder_names_become_folder_ids() {
+        let v7 = load(7);
+        assert_eq!(v7.current_chat_index, 1);
+        let folder_id = v7.chats[0].folder.clone().unwrap();
+        assert_eq!(v7.folders[&folder_id].name, "Work");
+        assert!(v7.folders[&folder_id].expanded);
+        assert_eq!(v7.chats[1].folder, None);
+        assert_eq!(v7.chats[0].id, "c1");
+
+        let v8 = load(8);
+        assert_eq!(v8.chats[0].folder.as_deref(), Some("f1"));
+        assert_eq!(v8.chats[0].id, "c1");
+        assert!(v8.chats[0].config.as_ref().unwrap().stop.is_empty());
+
+        let v9 = load(9);
+        let config = v9.chats[0].config.as_ref().unwrap();
+        assert_eq!((config.stop.as_slice(), config.seed), (["END".to_string()].as_slice(), Some(7)));
+    }
+
+    #[test]
+    fn current_layout_round_trips() {
+        let v10 = load(10);
+        assert_eq!(v10.chats[0].api_profile.as_deref(), Some("work"));
+        assert_eq!(v10.config.title_prompt, "Name it.");
+        assert!(v10.config.title_model.is_none());
+
+        let v11 = load(11);
+        let reply = &v11.chats[0].messages[1];
+        assert_eq!(reply.created_at, Some(1_700_000_001_000));
+        assert_eq!(reply.model.as_deref(), Some("gpt-4o-2024-08-06"));
+        assert_eq!(reply.usage.as_ref().map(|u| (u.prompt_tokens, u.completion_tokens)), Some((9, 3)));
+        assert_eq!(reply.finish_reason.as_deref(), Some("stop"));
+
+        let envelope = serde_json::json!({ "version": STATE_VERSION, "state": v11 });
+        let (again, _) = parse(&envelope.to_string()).unwrap();
+        assert_eq!(again, v11);
+
+        assert!(parse(r#"{"version": 99, "state": {}}"#).is_err());
+    }
+}
@@ -0,0 +1,430 @@
+//! The app state as kept in the browser between sessions.
+
+mod indexed_db;
+mod local;
+mod migrate;
+pub(crate) mod sync;
+
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::DomException;
+
+use crate::{
+    store::{AuthSlice, ChatSlice, CloudAuthSlice, ConfigSlice, PromptSlice},
+    types::chat::{ChatInterface, FolderCollection, LocalStorageInterfaceV8ToV9, Prompt},
+    vault::{Sealed, Vault, VaultError},
+};
+
+use indexed_db::IndexedDbBackend;
+use local::LocalStorageBackend;
+
+/// LocalStorage key of the persisted state. It is the one Better ChatGPT
+/// uses, so data left behind by it is picked up and migrated.
+pub(crate) const STORAGE_KEY: &str = "free-chat-gpt";
+
+/// Layout written by this version. 0 to 9 are the flat layouts described by
+/// the `LocalStorageInterface*` types in `types::chat`; 11 added message
+/// metadata.
+pub(crate) const STATE_VERSION: u32 = 11;
+
+/// What is stored under [`STORAGE_KEY`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Envelope {
+    pub(crate) version: u32,
+    pub(crate) state: serde_json::Value,
+}
+
+/// Everything that survives a reload, grouped by the store it belongs to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct PersistedState {
+    pub(crate) chats: Vec<ChatInterface>,
+    pub(crate) current_chat_index: i32,
+    pub(crate) folders: FolderCollection,
+    pub(crate) prompts: Vec<Prompt>,
+    pub(crate) auth: AuthSlice,
+    pub(crate) config: ConfigSlice,
+    pub(crate) cloud: CloudAuthSlice,
+}
+
+impl PersistedState {
+    pub(crate) fn from_stores(
+        chat: &ChatSlice,
+        prompt: &PromptSlice,
+        auth: &AuthSlice,
+        config: &ConfigSlice,
+        cloud: &CloudAuthSlice,
+    ) -> Self {
+        Self {
+            chats: chat.chats.clone(),
+            current_chat_index: chat.curr_chat_index,
+            folders: chat.folders.clone(),
+            prompts: prompt.prompts.clone(),
+            auth: auth.clone(),
+            config: config.clone(),
+            cloud: cloud.clone(),
+        }
+    }
+}
+
+impl From<LocalStorageInterfaceV8ToV9> for PersistedState {
+    fn from(v9: LocalStorageInterfaceV8ToV9) -> Self {
+        let api_key = Some(v9.api_key).filter(|k| !k.is_empty());
+        let api_endpoint = Some(v9.api_endpoint).filter(|e| !e.is_empty());
+        Self {
+            chats: v9.chats,
+            current_chat_index: v9.current_chat_index as i32,
+            folders: v9.folders,
+            prompts: v9.prompts,
+            auth: AuthSlice::new(api_key, api_endpoint, v9.first_visit),
+            config: ConfigSlice {
+                theme: v9.theme,
+                auto_title: v9.auto_title,
+                default_chat_config: v9.default_chat_config,
+                default_system_message: v9.default_system_message,
+                hide_menu_options: v9.hide_menu_options,
+                hide_side_menu: v9.hide_side_menu,
+                ..ConfigSlice::default()
+            },
+            cloud: CloudAuthSlice::default(),
+        }
+    }
+}
+
+/// Parses a stored envelope of any version up to [`STATE_VERSION`] and
+/// migrates it. Also returns the version it was stored at.
+pub(crate) fn parse(raw: &str) -> anyhow::Result<(PersistedState, u32)> {
+    migrate_envelope(serde_json::from_str(raw)?)
+}
+
+/// Puts the chats kept apart from an [`Envelope::meta`] back in its place,
+/// in the order it lists them, and migrates the result like [`parse`].
+pub(crate) fn assemble(
+    meta: &str,
+    chats: Vec<serde_json::Value>,
+) -> anyhow::Result<(PersistedState, u32)> {
+    let mut envelope: Envelope = serde_json::from_str(meta)?;
+    let ids: Vec<String> = serde_json::from_value(envelope.state["chats"].take())?;
+    let mut chats: HashMap<String, serde_json::Value> = chats
+        .into_iter()
+        .filter_map(|chat| Some((chat.get("id")?.as_str()?.to_string(), chat)))
+        .collect();
+    let ordered = ids
+        .iter()
+        .filter_map(|id| {
+            let chat = chats.remove(id);
+            if chat.is_none() {
+                log::warn!("Stored chat {} is missing", id);
+            }
+            chat
+        })
+        .collect();
+    envelope.state["chats"] = serde_json::Value::Array(ordered);
+    migrate_envelope(envelope)
+}
+
+fn migrate_envelope(envelope: Envelope) -> anyhow::Result<(PersistedState, u32)> {
+    if envelope.version > STATE_VERSION {
+        anyhow::bail!("stored state is from a newer version ({})", envelope.version);
+    }
+    let state = migrate::migrate(envelope.version, envelope.state)?;
+    Ok((state, envelope.version))
+}
+
+impl Envelope {
+    pub(crate) fn of(state: &PersistedState) -> serde_json::Result<Self> {
+        Ok(Self {
+            version: STATE_VERSION,
+            state: serde_json::to_value(state)?,
+        })
+    }
+
+    /// Like [`Envelope::of`], with the chats replaced by their ids. Backends
+    /// that keep each chat apart store this next to them.
+    pub(crate) fn meta(state: &PersistedState) -> serde_json::Result<Self> {
+        let mut envelope = Self::of(&PersistedState {
+            chats: Vec::new(),
+            current_chat_index: state.current_chat_index,
+            folders: state.folders.clone(),
+            prompts: state.prompts.clone(),
+            auth: state.auth.clone(),
+            config: state.config.clone(),
+            cloud: state.cloud.clone(),
+        })?;
+        let ids = state.chats.iter().map(|c| c.id.as_str()).collect::<Vec<_>>();
+        envelope.state["chats"] = serde_json::to_value(ids)?;
+        Ok(envelope)
+    }
+}
+
+/// Key the blob stored at `version` is copied to before it is replaced.
+pub(crate) fn backup_key(version: u32) -> String {
+    format!("{}-backup-v{}", STORAGE_KEY, version)
+}
+
+/// Where the state is kept, picked by [`open`].
+pub(crate) trait StorageBackend {
+    /// Reads the persisted state, migrated to [`STATE_VERSION`], or `None`
+    /// when nothing was saved yet.
+    fn load(&self) -> LocalBoxFuture<'_, Result<Option<PersistedState>, StorageError>>;
+
+    /// Writes `state`. `changes` is what differs from the last successful
+    /// save, backends that store chats one by one only write those.
+    fn save<'a>(
+        &'a self,
+        state: &'a PersistedState,
+        changes: &'a StateChanges,
+    ) -> LocalBoxFuture<'a, Result<(), StorageError>>;
+}
+
+/// IndexedDB when the browser allows it (it doesn't in some private
+/// windows), LocalStorage otherwise. With a `vault`, everything is sealed
+/// with it.
+pub(crate) async fn open(vault: Option<Vault>) -> Rc<dyn StorageBackend> {
+    match IndexedDbBackend::open(vault.clone()).await {
+        Ok(backend) => Rc::new(backend),
+        Err(e) => {
+            log::warn!("IndexedDB unavailable, falling back to LocalStorage: {}", e);
+            Rc::new(LocalStorageBackend { vault })
+        }
+    }
+}
+
+/// Seals a serialized value when there is a vault.
+async fn encode(vault: Option<&Vault>, json: String) -> Result<String, StorageError> {
+    match vault {
+        Some(vault) => Ok(vault.seal(&json).await?),
+        None => Ok(json),
+    }
+}
+
+/// Reads a value written by [`encode`]. Also tells whether it was stored in
+/// the clear, so it can be sealed when a vault was set up since.
+async fn decode(vault: Option<&Vault>, raw: String) -> Result<(String, bool), StorageError> {
+    match Sealed::parse(&raw) {
+        Some(sealed) => Ok((vault.ok_or(VaultError::Locked)?.open(&sealed).await?, false)),
+        None => Ok((raw, true)),
+    }
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum StorageError {
+    #[error("the browser storage quota is exceeded")]
+    QuotaExceeded,
+    #[error("{0}")]
+    Browser(String),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Vault(#[from] VaultError),
+}
+
+impl StorageError {
+    /// Maps an exception thrown by LocalStorage or IndexedDB.
+    pub(crate) fn from_js(error: JsValue) -> Self {
+        match error.dyn_into::<DomException>() {
+            Ok(e) if e.name() == "QuotaExceededError" => Self::QuotaExceeded,
+            Ok(e) => Self::Browser(format!("{}: {}", e.name(), e.message())),
+            Err(e) => Self::Browser(format!("{:?}", e)),
+        }
+    }
+}
+
+/// The stored state split the way [`StorageBackend::save`] writes it.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct StateChanges {
+    /// Everything but the chats' contents, see [`Envelope::meta`]. `None`
+    /// when it is unchanged.
+    pub(crate) meta: Option<Envelope>,
+    /// Chats that are new or changed, with their revision bumped.
+    pub(crate) upserted: Vec<ChatInterface>,
+    /// Ids of deleted chats.
+    pub(crate) removed: Vec<String>,
+}
+
+impl StateChanges {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.meta.is_none() && self.upserted.is_empty() && self.removed.is_empty()
+    }
+
+    /// Gives `chats` the revisions this save bumped them to.
+    pub(crate) fn stamp(&self, chats: &mut [ChatInterface]) {
+        for saved in &self.upserted {
+            if let Some(chat) = chats.iter_mut().find(|c| c.id == saved.id) {
+                chat.revision = saved.revision;
+            }
+        }
+    }
+}
+
+/// What the backend holds after the last successful save, to find out what
+/// the next one has to write.
+#[derive(Debug, Default)]
+pub(crate) struct Snapshot {
+    meta: Option<Envelope>,
+    chats: HashMap<String, ChatInterface>,
+}
+
+impl Snapshot {
+    pub(crate) fn of(state: &PersistedState) -> Self {
+        Self {
+            meta: Envelope::meta(state).ok(),
+            chats: state.chats.iter().map(|c| (c.id.clone(), c.clone())).collect(),
+        }
+    }
+
+    pub(crate) fn changes(&self, state: &PersistedState) -> Result<StateChanges, StorageError> {
+        let meta = Envelope::meta(state)?;
+        let ids: HashSet<&str> = state.chats.iter().map(|c| c.id.as_str()).collect();
+        Ok(StateChanges {
+            meta: (self.meta.as_ref() != Some(&meta)).then_some(meta),
+            upserted: state
+                .chats
+                .iter()
+                .filter_map(|chat| {
+                    let saved = self.chats.get(&chat.id);
+                    if saved == Some(chat) {
+                        return None;
+                    }
+                    let revision = saved.map_or(0, |s| s.revision).max(chat.revision) + 1;
+                    Some(ChatInterface {
+                        revision,
+                        ..chat.clone()
+                    })
+                })
+                .collect(),
+            removed: self
+                .chats
+                .keys()
+                .filter(|id| !ids.contains(id.as_str()))
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// The chat as last saved.
+    pub(crate) fn chat(&self, id: &str) -> Option<&ChatInterface> {
+        self.chats.get(id)
+    }
+
+    /// Records a chat saved by another tab.
+    pub(crate) fn record(&mut self, chat: ChatInterface) {
+        self.chats.insert(chat.id.clone(), chat);
+    }
+
+    /// Records a chat deleted by another tab.
+    pub(crate) fn forget(&mut self, id: &str) {
+        self.chats.remove(id);
+    }
+
+    /// Records `changes` as saved.
+    pub(crate) fn apply(&mut self, changes: StateChanges) {
+        if changes.meta.is_some() {
+            self.meta = changes.meta;
+        }
+        for id in &changes.removed {
+            self.chats.remove(id);
+        }
+        for chat in changes.upserted {
+            self.chats.insert(chat.id.clone(), chat);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::stand_in::ToyPassphrase;
+
+    fn chat(id: &str, title: &str) -> ChatInterface {
+        ChatInterface {
+            id: id.to_string(),
+            title: Some(title.to_string()),
+            ..ChatInterface::default()
+        }
+    }
+
+    fn state(chats: Vec<ChatInterface>) -> PersistedState {
+        PersistedState {
+            chats,
+            ..PersistedState::default()
+        }
+    }
+
+    #[test]
+    fn only_changed_chats_are_written() {
+        let mut snapshot = Snapshot::default();
+        let mut first = state(vec![chat("a", "A"), chat("b", "B")]);
+        let changes = snapshot.changes(&first).unwrap();
+        assert!(changes.meta.is_some());
+        assert_eq!(changes.upserted.len(), 2);
+        assert!(changes.upserted.iter().all(|c| c.revision == 1));
+        changes.stamp(&mut first.chats);
+        snapshot.apply(changes);
+        assert!(snapshot.changes(&first).unwrap().is_empty());
+
+        // The default auth profile gets a fresh id, so the rest is kept.
+        let mut edited = first.clone();
+        edited.chats[1].title = Some("Renamed".to_string());
+        let changes = snapshot.changes(&edited).unwrap();
+        assert_eq!(changes.meta, None);
+        assert_eq!(
+            changes.upserted,
+            vec![ChatInterface {
+                revision: 2,
+                ..chat("b", "Renamed")
+            }]
+        );
+        changes.stamp(&mut edited.chats);
+        snapshot.apply(changes);
+
+        let reordered = PersistedState {
+            chats: vec![edited.chats[1].clone(), chat("c", "C")],
+            ..first
+        };
+        let changes = snapshot.changes(&reordered).unwrap();
+        assert!(changes.meta.is_some(), "the chat order changed");
+        assert_eq!(changes.upserted.len(), 1);
+        assert_eq!(changes.upserted[0].id, "c");
+        assert_eq!(changes.removed, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn meta_and_chats_reassemble_in_order() {
+        let original = PersistedState {
+            current_chat_index: 1,
+            ..state(vec![chat("a", "A"), chat("b", "B")])
+        };
+        let meta = Envelope::meta(&original).unwrap();
+        assert_eq!(meta.state["chats"], serde_json::json!(["a", "b"]));
+
+        let stored = vec![
+            serde_json::to_value(chat("b", "B")).unwrap(),
+            serde_json::to_value(chat("a", "A")).unwrap(),
+            serde_json::to_value(chat("orphan", "Orphan")).unwrap(),
+        ];
+        let (state, version) = assemble(&serde_json::to_string(&meta).unwrap(), stored).unwrap();
+        assert_eq!(version, STATE_VERSION);
+        assert_eq!(state, original);
+    }
+
+    #[tokio::test]
+    async fn records_are_sealed_with_the_vault() {
+        let vault = Vault::create(ToyPassphrase::new("pass"), b"0123456789abcdef").await.unwrap();
+        let json = serde_json::to_string(&chat("a", "Secret plans")).unwrap();
+
+        let sealed = encode(Some(&vault), json.clone()).await.unwrap();
+        assert!(!sealed.contains("Secret plans"));
+        assert_eq!(decode(Some(&vault), sealed.clone()).await.unwrap(), (json.clone(), false));
+        assert_eq!(decode(Some(&vault), json.clone()).await.unwrap(), (json.clone(), true), "stored before the vault");
+        assert!(matches!(decode(None, sealed).await, Err(StorageError::Vault(VaultError::Locked))));
+        assert_eq!(encode(None, json.clone()).await.unwrap(), json);
+    }
+}
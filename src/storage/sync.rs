@@ -0,0 +1,377 @@
+//! Keeps tabs that share the storage in step.
+//!
+//! A tab announces what it saved, and other tabs fold that into their
+//! stores. Chats carry a revision that every save bumps: a chat saved
+//! elsewhere replaces the local copy unless this tab changed it since its
+//! own last save, in which case the local copy is saved next and, having the
+//! higher revision, wins everywhere. Edits to different chats never clash.
+
+use gloo::events::EventListener;
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{BroadcastChannel, MessageEvent, StorageEvent};
+
+use crate::{
+    store::ConfigSlice,
+    types::chat::{ChatInterface, FolderCollection, Prompt},
+};
+
+use super::{PersistedState, Snapshot, StateChanges};
+
+const CHANNEL: &str = "yewmind-sync";
+/// How long another tab's stream lock holds unless renewed.
+pub(crate) const STREAM_LOCK_TTL_MS: f64 = 15_000.0;
+/// How often a tab renews the lock on the chat it streams into.
+pub(crate) const STREAM_LOCK_RENEW_MS: u32 = 5_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub(crate) enum SyncMessage {
+    /// What `tab` just saved.
+    Saved {
+        tab: String,
+        chats: Vec<ChatInterface>,
+        removed: Vec<String>,
+        /// Set when the chat order or the settings changed.
+        shared: Option<Box<SharedState>>,
+    },
+    /// A [`SyncMessage::Saved`] stripped down to ids, for the `storage` event
+    /// fallback: whatever goes through it lands in LocalStorage in the clear,
+    /// vault or not. The other tabs read the chats back from the storage.
+    Outdated {
+        tab: String,
+        chats: Vec<String>,
+        removed: Vec<String>,
+        shared: bool,
+    },
+    /// `tab` streams into `chat_id`. Renewed every [`STREAM_LOCK_RENEW_MS`].
+    Streaming { tab: String, chat_id: String },
+    /// `tab` stopped streaming into `chat_id`.
+    Released { tab: String, chat_id: String },
+    /// `tab` turned the vault on or off, so the stored state was rewritten
+    /// under another key.
+    VaultChanged { tab: String },
+}
+
+/// The part of the state that isn't the chats themselves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SharedState {
+    pub(crate) order: Vec<String>,
+    pub(crate) folders: FolderCollection,
+    pub(crate) prompts: Vec<Prompt>,
+    pub(crate) config: ConfigSlice,
+}
+
+impl SyncMessage {
+    /// Describes a save, `None` when it wrote nothing.
+    pub(crate) fn saved(
+        tab: &str,
+        changes: &StateChanges,
+        chats: &[ChatInterface],
+        folders: &FolderCollection,
+        prompts: &[Prompt],
+        config: &ConfigSlice,
+    ) -> Option<Self> {
+        if changes.is_empty() {
+            return None;
+        }
+        let shared = changes.meta.as_ref().map(|_| {
+            Box::new(SharedState {
+                order: chats.iter().map(|c| c.id.clone()).collect(),
+                folders: folders.clone(),
+                prompts: prompts.to_vec(),
+                config: config.clone(),
+            })
+        });
+        Some(Self::Saved {
+            tab: tab.to_string(),
+            chats: changes.upserted.clone(),
+            removed: changes.removed.clone(),
+            shared,
+        })
+    }
+}
+
+impl SyncMessage {
+    /// This message without any chat contents or settings.
+    pub(crate) fn without_data(self) -> Self {
+        match self {
+            Self::Saved {
+                tab,
+                chats,
+                removed,
+                shared,
+            } => Self::Outdated {
+                tab,
+                chats: chats.into_iter().map(|c| c.id).collect(),
+                removed,
+                shared: shared.is_some(),
+            },
+            message => message,
+        }
+    }
+
+    /// The [`SyncMessage::Saved`] an [`SyncMessage::Outdated`] stood for,
+    /// filled in from `stored`, what the storage holds now.
+    pub(crate) fn refill(self, stored: PersistedState) -> Self {
+        let Self::Outdated {
+            tab,
+            chats,
+            removed,
+            shared,
+        } = self
+        else {
+            return self;
+        };
+        let shared = shared.then(|| {
+            Box::new(SharedState {
+                order: stored.chats.iter().map(|c| c.id.clone()).collect(),
+                folders: stored.folders,
+                prompts: stored.prompts,
+                config: stored.config,
+            })
+        });
+        Self::Saved {
+            tab,
+            chats: stored.chats.into_iter().filter(|c| chats.contains(&c.id)).collect(),
+            removed,
+            shared,
+        }
+    }
+}
+
+/// Folds chats another tab saved into `chats`, keeping `current` on the same
+/// chat. `snapshot` is brought in line with what the storage now holds.
+pub(crate) fn merge_chats(
+    chats: &mut Vec<ChatInterface>,
+    current: &mut i32,
+    snapshot: &mut Snapshot,
+    remote: Vec<ChatInterface>,
+    removed: &[String],
+    order: Option<&[String]>,
+) {
+    let current_id = usize::try_from(*current)
+        .ok()
+        .and_then(|i| chats.get(i))
+        .map(|c| c.id.clone());
+    let unsaved = |chats: &[ChatInterface], snapshot: &Snapshot, id: &str| {
+        chats.iter().find(|c| c.id == id) != snapshot.chat(id)
+    };
+
+    for chat in remote {
+        if snapshot.chat(&chat.id).is_some_and(|saved| saved.revision >= chat.revision) {
+            continue;
+        }
+        let keep_local = unsaved(chats, snapshot, &chat.id);
+        snapshot.record(chat.clone());
+        if keep_local {
+            continue;
+        }
+        match chats.iter_mut().find(|c| c.id == chat.id) {
+            Some(local) => *local = chat,
+            None => chats.insert(0, chat),
+        }
+    }
+    for id in removed {
+        let keep_local = unsaved(chats, snapshot, id);
+        snapshot.forget(id);
+        if !keep_local {
+            chats.retain(|c| &c.id != id);
+        }
+    }
+    if let Some(order) = order {
+        // Chats the other tab doesn't know about yet stay on top.
+        chats.sort_by_key(|c| order.iter().position(|id| *id == c.id));
+    }
+
+    let max_index = chats.len().saturating_sub(1) as i32;
+    *current = current_id
+        .and_then(|id| chats.iter().position(|c| c.id == id))
+        .map_or(*current, |i| i as i32)
+        .clamp(0, max_index);
+}
+
+/// Posts [`SyncMessage`]s to the other tabs of this origin and hands theirs
+/// to a callback. Uses a `BroadcastChannel`, or `storage` events where
+/// that isn't supported; those only carry ids, see [`SyncMessage::Outdated`].
+pub(crate) struct TabChannel {
+    channel: Option<BroadcastChannel>,
+    _listener: EventListener,
+}
+
+impl TabChannel {
+    pub(crate) fn open(on_message: impl Fn(SyncMessage) + 'static) -> Self {
+        let parse = move |raw: String| match serde_json::from_str(&raw) {
+            Ok(message) => on_message(message),
+            Err(e) => log::warn!("Ignoring a sync message that can't be read: {}", e),
+        };
+        match BroadcastChannel::new(CHANNEL) {
+            Ok(channel) => {
+                let listener = EventListener::new(&channel, "message", move |event| {
+                    if let Some(raw) = event.dyn_ref::<MessageEvent>().and_then(|e| e.data().as_string()) {
+                        parse(raw);
+                    }
+                });
+                Self {
+                    channel: Some(channel),
+                    _listener: listener,
+                }
+            }
+            Err(_) => {
+                let listener = EventListener::new(&gloo_utils::window(), "storage", move |event| {
+                    let Some(event) = event.dyn_ref::<StorageEvent>() else { return };
+                    if event.key().as_deref() == Some(CHANNEL)
+                        && let Some(raw) = event.new_value()
+                    {
+                        parse(raw);
+                    }
+                });
+                Self {
+                    channel: None,
+                    _listener: listener,
+                }
+            }
+        }
+    }
+
+    pub(crate) fn post(&self, message: &SyncMessage) {
+        let message = match &self.channel {
+            Some(_) => message.clone(),
+            None => message.clone().without_data(),
+        };
+        let raw = match serde_json::to_string(&message) {
+            Ok(raw) => raw,
+            Err(e) => return log::error!("Could not serialize a sync message: {}", e),
+        };
+        let posted = match &self.channel {
+            Some(channel) => channel.post_message(&JsValue::from_str(&raw)),
+            // Other tabs see the write as a `storage` event, the removal
+            // has them ignore it.
+            None => {
+                let storage = LocalStorage::raw();
+                storage
+                    .set_item(CHANNEL, &raw)
+                    .and_then(|_| storage.remove_item(CHANNEL))
+            }
+        };
+        if let Err(e) = posted {
+            log::error!("Could not reach the other tabs: {:?}", e);
+        }
+    }
+}
+
+impl Drop for TabChannel {
+    fn drop(&mut self) {
+        if let Some(channel) = &self.channel {
+            channel.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::PersistedState;
+
+    fn chat(id: &str, title: &str, revision: u64) -> ChatInterface {
+        ChatInterface {
+            id: id.to_string(),
+            title: Some(title.to_string()),
+            revision,
+            ..ChatInterface::default()
+        }
+    }
+
+    fn saved(chats: &[ChatInterface]) -> Snapshot {
+        Snapshot::of(&PersistedState {
+            chats: chats.to_vec(),
+            ..PersistedState::default()
+        })
+    }
+
+    #[test]
+    fn remote_saves_of_other_chats_are_merged() {
+        let mut chats = vec![chat("a", "A", 1), chat("b", "B", 1)];
+        let mut snapshot = saved(&chats);
+        // This tab renamed `a` but hasn't saved it yet.
+        chats[0].title = Some("Local".to_string());
+        let mut current = 1;
+
+        merge_chats(
+            &mut chats,
+            &mut current,
+            &mut snapshot,
+            vec![chat("b", "Remote", 2), chat("c", "New", 1)],
+            &[],
+            None,
+        );
+
+        let titles: Vec<_> = chats.iter().map(|c| c.title.clone().unwrap()).collect();
+        assert_eq!(titles, ["New", "Local", "Remote"]);
+        assert_eq!(chats[current as usize].id, "b", "still on the same chat");
+        assert_eq!(snapshot.chat("b"), Some(&chat("b", "Remote", 2)));
+    }
+
+    #[test]
+    fn unsaved_local_edits_outlive_a_concurrent_remote_save() {
+        let mut chats = vec![chat("a", "A", 3)];
+        let mut snapshot = saved(&chats);
+        chats[0].title = Some("Local".to_string());
+        let mut current = 0;
+
+        merge_chats(&mut chats, &mut current, &mut snapshot, vec![chat("a", "Remote", 4)], &[], None);
+        assert_eq!(chats[0].title.as_deref(), Some("Local"));
+
+        // The next save outranks what the other tab wrote.
+        let changes = snapshot
+            .changes(&PersistedState {
+                chats: chats.clone(),
+                ..PersistedState::default()
+            })
+            .unwrap();
+        assert_eq!(changes.upserted[0].revision, 5);
+    }
+
+    #[test]
+    fn the_storage_fallback_only_carries_ids() {
+        let stored = PersistedState {
+            chats: vec![chat("a", "Secret", 2), chat("b", "B", 1)],
+            ..PersistedState::default()
+        };
+        let changes = StateChanges {
+            upserted: vec![chat("a", "Secret", 2)],
+            removed: vec!["c".to_string()],
+            meta: Some(crate::storage::Envelope::of(&stored).unwrap()),
+        };
+        let saved = SyncMessage::saved("tab", &changes, &stored.chats, &stored.folders, &stored.prompts, &stored.config)
+            .unwrap();
+
+        let stripped = saved.clone().without_data();
+        assert!(!serde_json::to_string(&stripped).unwrap().contains("Secret"));
+        assert_eq!(stripped.refill(stored), saved);
+    }
+
+    #[test]
+    fn stale_saves_removals_and_order_are_applied() {
+        let mut chats = vec![chat("a", "A", 5), chat("b", "B", 1), chat("c", "C", 1)];
+        let mut snapshot = saved(&chats);
+        let mut current = 2;
+        let order = ["c".to_string(), "a".to_string()];
+
+        merge_chats(
+            &mut chats,
+            &mut current,
+            &mut snapshot,
+            vec![chat("a", "Old", 4)],
+            &["b".to_string()],
+            Some(&order),
+        );
+
+        let ids: Vec<_> = chats.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["c", "a"]);
+        assert_eq!(chats[1].title.as_deref(), Some("A"));
+        assert_eq!(current, 0);
+        assert_eq!(snapshot.chat("b"), None);
+    }
+}
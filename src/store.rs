@@ -1,8 +1,16 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use futures::future::AbortHandle;
+use serde::{Deserialize, Serialize};
 use yewdux::prelude::*;
 
-use crate::{components::toast::ToastStatus, constants::DEFAULT_SYSTEM_MESSAGE, types::{
-    api::SyncStatus,
+use crate::{cloud::RemoteCopy, vault::{Vault, VaultHeader}, components::toast::ToastStatus, constants::{DEFAULT_SYSTEM_MESSAGE, DEFAULT_TITLE_PROMPT}, types::{
+    api::{ApiProfile, S3Settings, SyncProvider, SyncStatus, WebDavSettings, OFFICIAL_APIENDPOINT},
     chat::{ChatInterface, ConfigInterface, FolderCollection, MessageInterface, Prompt, Role, Theme, TotalTokenUsed},
+    export::ExportSettings,
 }};
 
 #[derive(Debug, Store, Default, Clone, PartialEq)]
@@ -27,21 +35,57 @@ impl ToastSlice {
     }
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Store)]
+#[derive(Debug, Clone, PartialEq, Store, Serialize, Deserialize)]
+#[serde(default)]
 pub(crate) struct AuthSlice {
-    pub(crate) api_key: Option<String>,
-    pub(crate) api_endpoint: Option<String>,
+    pub(crate) profiles: Vec<ApiProfile>,
+    /// Id of the profile used by chats that haven't picked one.
+    pub(crate) default_profile: String,
     pub(crate) first_version: bool,
 }
 
+impl Default for AuthSlice {
+    fn default() -> Self {
+        let profile = ApiProfile::default();
+        Self {
+            default_profile: profile.id.clone(),
+            profiles: vec![profile],
+            first_version: false,
+        }
+    }
+}
+
 impl AuthSlice {
     pub fn new(api_key: Option<String>, api_endpoint: Option<String>, first_version: bool) -> Self {
+        let endpoint = api_endpoint.unwrap_or_else(|| OFFICIAL_APIENDPOINT.to_string());
+        let profile = ApiProfile::new("Default".to_string(), endpoint, api_key);
         Self {
-            api_key,
-            api_endpoint,
+            default_profile: profile.id.clone(),
+            profiles: vec![profile],
             first_version,
         }
     }
+
+    /// The profile with `id`, falling back to the default profile for chats
+    /// that never picked one or whose profile was deleted.
+    pub(crate) fn profile(&self, id: Option<&str>) -> ApiProfile {
+        id.and_then(|id| self.profiles.iter().find(|p| p.id == id))
+            .or_else(|| self.profiles.iter().find(|p| p.id == self.default_profile))
+            .or_else(|| self.profiles.first())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn default_profile_mut(&mut self) -> &mut ApiProfile {
+        if !self.profiles.iter().any(|p| p.id == self.default_profile) {
+            if self.profiles.is_empty() {
+                self.profiles.push(ApiProfile::default());
+            }
+            self.default_profile = self.profiles[0].id.clone();
+        }
+        let id = self.default_profile.clone();
+        self.profiles.iter_mut().find(|p| p.id == id).unwrap()
+    }
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Store)]
@@ -50,26 +94,166 @@ pub(crate) struct ChatSlice {
     pub(crate) chats: Vec<ChatInterface>,
     pub(crate) curr_chat_index: i32,
     pub(crate) generating: bool,
+    pub(crate) generation: Option<Generation>,
     pub(crate) error: String,
     pub(crate) folders: FolderCollection,
+    /// Message of the current chat to scroll to once it is shown, e.g. a
+    /// search hit.
+    pub(crate) jump_to: Option<usize>,
+}
+
+impl ChatSlice {
+    pub(crate) fn chat_mut(&mut self, chat_id: &str) -> Option<&mut ChatInterface> {
+        self.chats.iter_mut().find(|c| c.id == chat_id)
+    }
+
+    /// Aborts the in-flight generation, keeping what was streamed so far and
+    /// marking that assistant message as interrupted.
+    pub(crate) fn stop_generating(&mut self) {
+        if let Some(generation) = self.generation.take() {
+            generation.handle.abort();
+            let partial = self
+                .chat_mut(&generation.chat_id)
+                .and_then(|c| c.messages.last_mut())
+                .filter(|m| m.role == Role::Assistant);
+            if let Some(message) = partial {
+                message.interrupted = true;
+            }
+        }
+        self.generating = false;
+    }
+}
+
+/// The generation currently streaming into `chat_id`.
+///
+/// Aborting drops the response stream; on wasm reqwest ties every `fetch` to
+/// an `AbortController` that fires on drop, so the request is cancelled too.
+#[derive(Debug, Clone)]
+pub(crate) struct Generation {
+    id: u32,
+    pub(crate) chat_id: String,
+    handle: AbortHandle,
+}
+
+impl Generation {
+    pub(crate) fn new(chat_id: String, handle: AbortHandle) -> Self {
+        static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            chat_id,
+            handle,
+        }
+    }
+}
+
+impl PartialEq for Generation {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+/// Chats other tabs are streaming into. See `storage::sync`.
+#[derive(Debug, Store, Default, Clone, PartialEq)]
+pub(crate) struct TabSyncSlice {
+    /// When each lock lapses, in milliseconds since the epoch, by chat id.
+    pub(crate) stream_locks: HashMap<String, StreamLock>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StreamLock {
+    pub(crate) tab: String,
+    pub(crate) expires_at: f64,
 }
 
-#[derive(Debug)]
+impl TabSyncSlice {
+    pub(crate) fn is_locked(&self, chat_id: &str, now: f64) -> bool {
+        self.stream_locks
+            .get(chat_id)
+            .is_some_and(|lock| lock.expires_at > now)
+    }
+}
+
+/// The passphrase vault, see [`crate::vault`].
+#[derive(Debug, Default, Clone, PartialEq, Store)]
+pub(crate) struct VaultSlice {
+    /// Whether [`VaultSlice::header`] was read from storage yet.
+    pub(crate) checked: bool,
+    /// Set when this browser keeps its data encrypted.
+    pub(crate) header: Option<VaultHeader>,
+    /// The unlocked vault. Lost on reload, which locks it again.
+    pub(crate) vault: Option<Vault>,
+}
+
+impl VaultSlice {
+    /// Whether the stored state can't be read yet.
+    pub(crate) fn is_locked(&self) -> bool {
+        !self.checked || (self.header.is_some() && self.vault.is_none())
+    }
+}
+
+/// Cloud sync. Besides the provider settings only the link to the synced
+/// file is persisted; a Google access token lasts an hour and is asked for
+/// again on the next visit.
+#[derive(Debug, Clone, Default, PartialEq, Store, Serialize, Deserialize)]
+#[serde(default)]
 pub(crate) struct CloudAuthSlice {
-    pub(crate) google_access_token: Option<String>,
-    pub(crate) google_refresh_token: Option<String>,
     pub(crate) cloud_sync: bool,
-    pub(crate) sync_status: SyncStatus,
+    pub(crate) provider: SyncProvider,
+    pub(crate) webdav: WebDavSettings,
+    pub(crate) s3: S3Settings,
+    /// `cloud::RemoteFile::id` of the synced file.
     pub(crate) file_id: Option<String>,
+    /// `cloud::RemoteFile::version` of the file as of the last sync.
+    pub(crate) synced_version: Option<String>,
+    /// `cloud::fingerprint` of the state as of the last sync.
+    pub(crate) synced_fingerprint: Option<String>,
+    #[serde(skip)]
+    pub(crate) google_access_token: Option<String>,
+    #[serde(skip)]
+    pub(crate) sync_status: SyncStatus,
+    /// The copy in the cloud, while the user picks between it and this one.
+    #[serde(skip)]
+    pub(crate) conflict: Option<Box<RemoteCopy>>,
 }
 
-pub type StoreState = CloudAuthSlice;
+impl CloudAuthSlice {
+    /// Switches to other provider settings. The link to the synced file is
+    /// dropped when they point somewhere else, so the first sync there
+    /// compares both sides instead of overwriting one.
+    pub(crate) fn configure(&mut self, provider: SyncProvider, webdav: WebDavSettings, s3: S3Settings) {
+        let moved = match provider {
+            SyncProvider::GoogleDrive => self.provider != provider,
+            SyncProvider::WebDav => self.provider != provider || self.webdav.url != webdav.url,
+            SyncProvider::S3 => {
+                let target = |s: &S3Settings| {
+                    (s.endpoint.clone(), s.bucket.clone(), s.key.clone(), s.auth, s.presigned_get_url.clone())
+                };
+                self.provider != provider || target(&self.s3) != target(&s3)
+            }
+        };
+        if moved {
+            self.file_id = None;
+            self.synced_version = None;
+            self.synced_fingerprint = None;
+        }
+        self.provider = provider;
+        self.webdav = webdav;
+        self.s3 = s3;
+        self.conflict = None;
+    }
+}
 
-#[derive(Debug, Clone, PartialEq, Store)]
+#[derive(Debug, Clone, PartialEq, Store, Serialize, Deserialize)]
+#[serde(default)]
 pub(crate) struct ConfigSlice {
+    #[serde(skip)]
     pub(crate) open_config: bool,
     pub(crate) theme: Theme,
     pub(crate) auto_title: bool,
+    /// Model used for titles; `None` uses the chat's own model.
+    pub(crate) title_model: Option<String>,
+    /// See [`DEFAULT_TITLE_PROMPT`] for the placeholders.
+    pub(crate) title_prompt: String,
     pub(crate) hide_menu_options: bool,
     pub(crate) advanced_mode: bool,
     pub(crate) default_chat_config: ConfigInterface,
@@ -79,6 +263,9 @@ pub(crate) struct ConfigSlice {
     pub(crate) inline_latex: bool,
     pub(crate) markdown_mode: bool,
     pub(crate) count_total_tokens: bool,
+    /// How many times an answer cut off by the token limit is continued
+    /// without asking; 0 leaves it to the Continue button.
+    pub(crate) auto_continue: u32,
     pub(crate) total_token_used: TotalTokenUsed,
 }
 
@@ -90,6 +277,8 @@ impl Default for ConfigSlice {
             hide_menu_options: false,
             hide_side_menu: false,
             auto_title: false,
+            title_model: None,
+            title_prompt: DEFAULT_TITLE_PROMPT.to_string(),
             enter_to_submit: true,
             advanced_mode: true,
             default_chat_config: ConfigInterface::default(),
@@ -97,11 +286,46 @@ impl Default for ConfigSlice {
             inline_latex: false,
             markdown_mode: true,
             count_total_tokens: false,
+            auto_continue: 0,
             total_token_used: TotalTokenUsed::new(),
         }
     }
 }
 
+impl ConfigSlice {
+    pub(crate) fn export_settings(&self) -> ExportSettings {
+        ExportSettings {
+            theme: self.theme.clone(),
+            auto_title: self.auto_title,
+            title_model: self.title_model.clone(),
+            title_prompt: self.title_prompt.clone(),
+            advanced_mode: self.advanced_mode,
+            default_chat_config: self.default_chat_config.clone(),
+            default_system_message: self.default_system_message.clone(),
+            enter_to_submit: self.enter_to_submit,
+            inline_latex: self.inline_latex,
+            markdown_mode: self.markdown_mode,
+            count_total_tokens: self.count_total_tokens,
+            auto_continue: self.auto_continue,
+        }
+    }
+
+    pub(crate) fn import_settings(&mut self, settings: ExportSettings) {
+        self.theme = settings.theme;
+        self.auto_title = settings.auto_title;
+        self.title_model = settings.title_model;
+        self.title_prompt = settings.title_prompt;
+        self.advanced_mode = settings.advanced_mode;
+        self.default_chat_config = settings.default_chat_config;
+        self.default_system_message = settings.default_system_message;
+        self.enter_to_submit = settings.enter_to_submit;
+        self.inline_latex = settings.inline_latex;
+        self.markdown_mode = settings.markdown_mode;
+        self.count_total_tokens = settings.count_total_tokens;
+        self.auto_continue = settings.auto_continue;
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Store)]
 pub struct InputSlice {
     pub input_role: Role
@@ -112,3 +336,54 @@ impl Default for InputSlice {
         Self { input_role: Role::User }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::api::ProviderKind;
+    use futures::future::{Abortable, Aborted};
+
+    #[test]
+    fn stop_generating_aborts_and_marks_the_partial_message() {
+        let (handle, registration) = AbortHandle::new_pair();
+        let mut chat = ChatInterface {
+            id: "chat".to_string(),
+            ..Default::default()
+        };
+        chat.messages.push(MessageInterface {
+            role: Role::Assistant,
+            content: "Once upon".to_string(),
+            ..Default::default()
+        });
+        let mut state = ChatSlice {
+            chats: vec![chat],
+            generating: true,
+            generation: Some(Generation::new("chat".to_string(), handle)),
+            ..Default::default()
+        };
+
+        state.stop_generating();
+
+        assert!(!state.generating);
+        assert!(state.generation.is_none());
+        assert!(state.chats[0].messages[0].interrupted);
+        let stream = Abortable::new(futures::future::pending::<()>(), registration);
+        assert_eq!(futures::executor::block_on(stream), Err(Aborted));
+    }
+
+    #[test]
+    fn chats_fall_back_to_the_default_profile() {
+        let mut state = AuthSlice::new(Some("sk-default".to_string()), None, false);
+        let local = ApiProfile::new("Local".to_string(), "http://localhost:11434/api/chat".to_string(), None);
+        state.profiles.push(local.clone());
+
+        assert_eq!(state.profile(Some(&local.id)).provider, ProviderKind::Ollama);
+        assert_eq!(state.profile(None).api_key.as_deref(), Some("sk-default"));
+        assert_eq!(state.profile(Some("deleted")).api_key.as_deref(), Some("sk-default"));
+
+        state.default_profile = local.id.clone();
+        state.profiles.retain(|p| p.id != local.id);
+        assert_eq!(state.default_profile_mut().api_key.as_deref(), Some("sk-default"));
+        assert_eq!(state.default_profile, state.profiles[0].id);
+    }
+}
@@ -1,44 +1,314 @@
+use std::collections::BTreeMap;
+
 use serde::{Serialize, Deserialize};
 
+use super::chat::TokenUsage;
+
 pub(crate) const OFFICIAL_APIENDPOINT: &str = "https://api.openai.com/v1/chat/completions";
 pub(crate) const CUSTOM_APIENDPOINT: &str = "https://chatgpt-api.shn.hk/v1/";
+pub(crate) const ANTHROPIC_APIENDPOINT: &str = "https://api.anthropic.com/v1/messages";
+pub(crate) const OLLAMA_APIENDPOINT: &str = "http://localhost:11434/api/chat";
+pub(crate) const LLAMACPP_APIENDPOINT: &str = "http://localhost:8080/completion";
+/// Latest GA version of the Azure OpenAI data-plane API.
+pub(crate) const AZURE_API_VERSION: &str = "2024-06-01";
 // pub const defaultAPIEndpoint = VITE_DEFAULT_API_ENDPOINT || officialAPIEndpoint;
 
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A Drive file, as far as the requested `fields` go.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct GoogleFileResource {
-    pub kind: String,
     pub id: String,
     pub name: String,
     pub mime_type: String,
+    pub modified_time: Option<String>,
+    /// Bumped by Drive on every change to the file.
+    pub version: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct GoogleTokenInfo {
-    pub azp: String,
-    pub aud: String,
-    pub sub: String,
-    pub scope: String,
-    pub exp: String,
-    pub expires_in: String,
-    pub email: String,
-    pub email_verified: String,
-    pub access_type: String,
-}
-
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct GoogleFileList {
     pub next_page_token: Option<String>,
-    pub kind: String,
-    pub incomplete_search: bool,
     pub files: Vec<GoogleFileResource>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub enum SyncStatus {
+    #[default]
     Unauthenticated,
     Syncing,
     Synced,
+    /// Both sides changed since the last sync, waiting for the user to pick.
+    Conflict,
+    Failed,
+}
+
+/// Where cloud sync keeps the state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncProvider {
+    #[default]
+    GoogleDrive,
+    /// A WebDAV folder, e.g. on Nextcloud.
+    WebDav,
+    /// An S3 compatible bucket, e.g. on MinIO.
+    S3,
+}
+
+impl SyncProvider {
+    pub const ALL: [SyncProvider; 3] = [Self::GoogleDrive, Self::WebDav, Self::S3];
+}
+
+impl std::fmt::Display for SyncProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GoogleDrive => "Google Drive",
+            Self::WebDav => "WebDAV",
+            Self::S3 => "S3",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebDavSettings {
+    /// The folder the synced file is kept in, e.g.
+    /// `https://cloud.example.com/remote.php/dav/files/alice/YewMind`.
+    pub url: String,
+    pub username: String,
+    /// Preferably an app password.
+    pub password: String,
+}
+
+/// How requests to an S3 bucket authenticate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum S3Auth {
+    /// Requests are signed with an access key (Signature Version 4).
+    #[default]
+    AccessKey,
+    /// Requests go to URLs presigned elsewhere, so no key is kept here.
+    Presigned,
+}
+
+impl S3Auth {
+    pub const ALL: [S3Auth; 2] = [Self::AccessKey, Self::Presigned];
+}
+
+impl std::fmt::Display for S3Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AccessKey => "Access key",
+            Self::Presigned => "Presigned URLs",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct S3Settings {
+    /// e.g. `http://localhost:9000` for MinIO.
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    /// Object key of the synced file.
+    pub key: String,
+    /// Addresses the bucket as `{endpoint}/{bucket}` rather than
+    /// `{bucket}.{endpoint host}`. MinIO only supports the former by default.
+    pub path_style: bool,
+    pub auth: S3Auth,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Only used with [`S3Auth::Presigned`].
+    pub presigned_get_url: String,
+    pub presigned_put_url: String,
+}
+
+impl Default for S3Settings {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            region: "us-east-1".to_string(),
+            bucket: String::new(),
+            key: "yewmind.json".to_string(),
+            path_style: true,
+            auth: S3Auth::default(),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            presigned_get_url: String::new(),
+            presigned_put_url: String::new(),
+        }
+    }
+}
+
+/// The wire format spoken by the configured API endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ProviderKind {
+    #[default]
+    OpenAI,
+    Azure,
+    Anthropic,
+    Ollama,
+    LlamaCpp,
+}
+
+impl ProviderKind {
+    pub const ALL: [ProviderKind; 5] = [
+        Self::OpenAI,
+        Self::Azure,
+        Self::Anthropic,
+        Self::Ollama,
+        Self::LlamaCpp,
+    ];
+
+    /// Local servers run without a cloud API key.
+    pub fn is_local(&self) -> bool {
+        matches!(self, Self::Ollama | Self::LlamaCpp)
+    }
+
+    /// Endpoint preset for a freshly picked provider. Azure endpoints are
+    /// per-resource, so there is nothing sensible to suggest.
+    pub fn default_endpoint(&self) -> Option<&'static str> {
+        match self {
+            Self::OpenAI => Some(OFFICIAL_APIENDPOINT),
+            Self::Azure => None,
+            Self::Anthropic => Some(ANTHROPIC_APIENDPOINT),
+            Self::Ollama => Some(OLLAMA_APIENDPOINT),
+            Self::LlamaCpp => Some(LLAMACPP_APIENDPOINT),
+        }
+    }
+
+    /// Best guess for endpoints saved before the provider was stored explicitly.
+    pub fn from_endpoint(endpoint: &str) -> Self {
+        if endpoint.contains(".azure.com") {
+            Self::Azure
+        } else if endpoint.contains("anthropic.com") {
+            Self::Anthropic
+        } else if endpoint.ends_with("/api/chat") {
+            Self::Ollama
+        } else if endpoint.ends_with("/completion") {
+            Self::LlamaCpp
+        } else {
+            Self::OpenAI
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OpenAI => "OpenAI",
+            Self::Azure => "Azure OpenAI",
+            Self::Anthropic => "Anthropic",
+            Self::Ollama => "Ollama",
+            Self::LlamaCpp => "llama.cpp",
+        }
+        .fmt(f)
+    }
+}
+
+/// A named set of credentials for one API backend. Chats pick a profile by
+/// id; chats without one use the default profile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiProfile {
+    pub id: String,
+    pub name: String,
+    pub endpoint: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub provider: ProviderKind,
+    /// Extra headers sent with every request, e.g. for an authenticating proxy.
+    #[serde(default)]
+    pub custom_headers: BTreeMap<String, String>,
+    /// Model preselected for chats using this profile.
+    #[serde(default)]
+    pub default_model: Option<String>,
+    /// Only used when `provider` is [`ProviderKind::Azure`].
+    #[serde(default)]
+    pub azure: AzureSettings,
+}
+
+impl ApiProfile {
+    pub fn new(name: String, endpoint: String, api_key: Option<String>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            provider: ProviderKind::from_endpoint(&endpoint),
+            endpoint,
+            api_key,
+            custom_headers: BTreeMap::new(),
+            default_model: None,
+            azure: AzureSettings::default(),
+        }
+    }
+}
+
+impl Default for ApiProfile {
+    fn default() -> Self {
+        Self::new("Default".to_string(), OFFICIAL_APIENDPOINT.to_string(), None)
+    }
+}
+
+/// How requests to an Azure OpenAI resource authenticate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AzureAuth {
+    /// The resource key, sent as `api-key`.
+    #[default]
+    ApiKey,
+    /// A Microsoft Entra ID access token, sent as `Authorization: Bearer`.
+    EntraId,
+}
+
+impl AzureAuth {
+    pub const ALL: [AzureAuth; 2] = [Self::ApiKey, Self::EntraId];
+}
+
+impl std::fmt::Display for AzureAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ApiKey => "API key",
+            Self::EntraId => "Entra ID token",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AzureSettings {
+    /// Model id -> deployment name. Models without an entry are assumed to be
+    /// deployed under their own name.
+    #[serde(default)]
+    pub deployments: BTreeMap<String, String>,
+    #[serde(default = "default_azure_api_version")]
+    pub api_version: String,
+    #[serde(default)]
+    pub auth: AzureAuth,
+}
+
+impl AzureSettings {
+    pub fn deployment<'a>(&'a self, model: &'a str) -> &'a str {
+        self.deployments
+            .get(model)
+            .map(|d| d.as_str())
+            .filter(|d| !d.is_empty())
+            .unwrap_or(model)
+    }
+}
+
+impl Default for AzureSettings {
+    fn default() -> Self {
+        Self {
+            deployments: BTreeMap::new(),
+            api_version: default_azure_api_version(),
+            auth: AzureAuth::default(),
+        }
+    }
+}
+
+fn default_azure_api_version() -> String {
+    AZURE_API_VERSION.to_string()
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -48,6 +318,8 @@ pub struct EventSourceDataInterface {
     pub id: String,
     pub model: String,
     pub object: String,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -55,6 +327,13 @@ pub struct EventSourceDataInterface {
 pub enum EventSourceData {
     Data(EventSourceDataInterface),
     Done(String),
+    /// Error reported inside the stream, after the response headers were sent.
+    #[serde(skip)]
+    Error(String),
+    /// The provider's content filter cut the response off; carries the
+    /// categories that triggered it.
+    #[serde(skip)]
+    ContentFiltered(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -1,14 +1,20 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use yewdux::prelude::*;
 use super::api::OFFICIAL_APIENDPOINT;
+use crate::constants::DEFAULT_SYSTEM_MESSAGE;
+use super::model::deserialize_model_id;
 pub const DEFAULT_USER_MAX_TOKEN: i32 = 4000;
+pub const DEFAULT_MODEL: &str = "gpt-3.5-turbo";
 
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub enum Role {
     #[default]
+    #[serde(alias = "user")]
     User,
+    #[serde(alias = "assistant")]
     Assistant,
+    #[serde(alias = "system")]
     System,
 }
 impl std::fmt::Display for Role {
@@ -39,7 +45,42 @@ pub(crate) struct MessageInterface {
     pub(crate) role: Role,
     pub(crate) content: String,
     pub(crate) folder: Option<String>,
-    pub(crate) messages: Vec<MessageInterface>,
+    /// The generation was stopped before the model finished this message.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) interrupted: bool,
+    /// The other versions of this message (earlier edits or regenerations),
+    /// each one followed by the rest of its conversation. See
+    /// [`ChatInterface::switch_branch`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) alternatives: Vec<Vec<MessageInterface>>,
+    /// Position of this version among all of its versions.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub(crate) branch: usize,
+    /// Unix time in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) created_at: Option<i64>,
+    /// Model that generated an assistant message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) model: Option<String>,
+    /// Tokens the request and the reply took, as the provider reported them
+    /// or else as estimated; summed over the requests that continued it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) usage: Option<TokenUsage>,
+    /// Why the model stopped: `stop`, `length` when it ran out of tokens,
+    /// `content_filter`, …
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) finish_reason: Option<String>,
+}
+
+impl MessageInterface {
+    /// An answer the model stopped writing because it reached the token limit.
+    pub(crate) fn is_cut_off(&self) -> bool {
+        self.role == Role::Assistant && self.finish_reason.as_deref() == Some("length")
+    }
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
 }
 
 #[derive(Debug, Clone, Default, Store, PartialEq, Serialize, Deserialize)]
@@ -49,10 +90,19 @@ pub struct ChatInterface {
     pub(crate) folder: Option<String>,
     pub(crate) messages: Vec<MessageInterface>,
     pub(crate) config: Option<ConfigInterface>,
+    /// Id of the [`ApiProfile`](super::api::ApiProfile) this chat talks to,
+    /// `None` for the default profile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) api_profile: Option<String>,
     /// Indicates whether the title has been set.
     ///
     /// This boolean flag is used to track if the title has been assigned a value.
+    #[serde(default, alias = "titleSet")]
     pub(crate) title_set: bool,
+    /// Bumped by every save that changes the chat, so tabs sharing the
+    /// storage can tell which copy is newer.
+    #[serde(default)]
+    pub(crate) revision: u64,
 }
 
 impl ChatInterface {
@@ -68,41 +118,185 @@ impl ChatInterface {
             messages = vec![MessageInterface {
                 role: Role::System,
                 content: default_sys_msg,
-                folder: None,
-                messages: vec![],
+                ..Default::default()
             }]
         }
 
         Self {
-            id: "".to_string(), // Generate new uuid
+            id: uuid::Uuid::new_v4().to_string(),
             title: Some(title),
             folder,
             messages,
             config,
+            api_profile: None,
             title_set: false,
+            revision: 0,
+        }
+    }
+}
+
+/// A chat is a tree of messages. `messages` holds the active path through
+/// it, which is what gets rendered and sent to the API; every message on
+/// that path keeps its inactive siblings in `alternatives`.
+impl ChatInterface {
+    /// Number of versions of the message at `index`.
+    pub(crate) fn branch_count(&self, index: usize) -> usize {
+        self.messages.get(index).map_or(0, |m| m.alternatives.len() + 1)
+    }
+
+    /// Replaces everything from `index` on with `replacement`, keeping the
+    /// replaced messages as an alternative branch of `replacement[0]`.
+    pub(crate) fn branch_off(&mut self, index: usize, replacement: Vec<MessageInterface>) {
+        if index >= self.messages.len() || replacement.is_empty() {
+            self.messages.extend(replacement);
+            return;
+        }
+        let current = self.messages.split_off(index);
+        let mut siblings = Self::siblings(current);
+        let mut replacement = replacement;
+        replacement[0].branch = siblings.len();
+        replacement[0].alternatives = std::mem::take(&mut siblings);
+        self.messages.extend(replacement);
+    }
+
+    /// Makes version `branch` of the message at `index` the active one,
+    /// together with the conversation that followed it.
+    pub(crate) fn switch_branch(&mut self, index: usize, branch: usize) {
+        if branch >= self.branch_count(index) || self.messages[index].branch == branch {
+            return;
+        }
+        let current = self.messages.split_off(index);
+        let siblings = Self::siblings(current);
+        self.messages.extend(Self::join_branches(siblings, branch));
+    }
+
+    /// Deletes the message at `index`. A message with other versions takes the
+    /// conversation that followed it along, and the next version is shown in
+    /// its place.
+    pub(crate) fn delete_message(&mut self, index: usize) {
+        if index >= self.messages.len() {
+            return;
+        }
+        if self.messages[index].alternatives.is_empty() {
+            self.messages.remove(index);
+            return;
+        }
+        let current = self.messages.split_off(index);
+        let active = current[0].branch;
+        let mut siblings = Self::siblings(current);
+        siblings.remove(active.min(siblings.len() - 1));
+        let next = active.min(siblings.len() - 1);
+        self.messages.extend(Self::join_branches(siblings, next));
+    }
+
+    /// Whether the messages at `a` and `b` can trade places. Other versions of
+    /// a message continue from its position, so messages that have them stay
+    /// put.
+    pub(crate) fn can_swap(&self, a: usize, b: usize) -> bool {
+        let movable = |i: usize| self.messages.get(i).is_some_and(|m| m.alternatives.is_empty());
+        movable(a) && movable(b)
+    }
+
+    /// Swaps the messages at `a` and `b`, unless [`ChatInterface::can_swap`]
+    /// refuses.
+    pub(crate) fn swap_messages(&mut self, a: usize, b: usize) {
+        if self.can_swap(a, b) {
+            self.messages.swap(a, b);
         }
     }
+
+    /// All versions of `path[0]` in order, `path` itself included.
+    pub(crate) fn siblings(mut path: Vec<MessageInterface>) -> Vec<Vec<MessageInterface>> {
+        let mut siblings = std::mem::take(&mut path[0].alternatives);
+        let position = path[0].branch.min(siblings.len());
+        path[0].branch = 0;
+        siblings.insert(position, path);
+        siblings
+    }
+
+    /// The inverse of [`ChatInterface::siblings`]: version `active` of a
+    /// message, carrying all other versions as its alternatives.
+    pub(crate) fn join_branches(
+        mut versions: Vec<Vec<MessageInterface>>,
+        active: usize,
+    ) -> Vec<MessageInterface> {
+        let mut chosen = versions.remove(active);
+        chosen[0].branch = active;
+        chosen[0].alternatives = versions;
+        chosen
+    }
 }
 
 #[derive(Debug, Clone, Store, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub(crate) struct ConfigInterface {
-    pub(crate) model: ModelOptions,
+    /// Model id as understood by the provider, see `types::model::ModelRegistry`.
+    #[serde(deserialize_with = "deserialize_model_id")]
+    pub(crate) model: String,
     pub(crate) max_tokens: i32,
-    pub(crate) temperature: i32,
-    pub(crate) presence_penalty: i32,
-    pub(crate) top_p: i32,
-    pub(crate) frequency_penalty: i32,
+    pub(crate) temperature: f32,
+    pub(crate) presence_penalty: f32,
+    pub(crate) top_p: f32,
+    pub(crate) frequency_penalty: f32,
+    /// Sequences where the API stops generating further tokens.
+    #[serde(default)]
+    pub(crate) stop: Vec<String>,
+    /// Best-effort deterministic sampling.
+    #[serde(default)]
+    pub(crate) seed: Option<i64>,
+    /// Number of choices to generate. Only the first one is shown.
+    #[serde(default = "default_n")]
+    pub(crate) n: u32,
+    /// Token id to bias in [-100, 100].
+    #[serde(default)]
+    pub(crate) logit_bias: BTreeMap<String, i32>,
+    #[serde(default)]
+    pub(crate) response_format: ResponseFormat,
+    /// End-user identifier forwarded for abuse monitoring.
+    #[serde(default)]
+    pub(crate) user: Option<String>,
+}
+
+fn default_n() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    #[default]
+    Text,
+    JsonObject,
+}
+
+impl ResponseFormat {
+    pub const ALL: [ResponseFormat; 2] = [ResponseFormat::Text, ResponseFormat::JsonObject];
+}
+
+impl std::fmt::Display for ResponseFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResponseFormat::Text => "text",
+            ResponseFormat::JsonObject => "json_object",
+        }.fmt(f)
+    }
 }
 
 impl Default for ConfigInterface {
     fn default() -> Self {
         Self {
-            model: ModelOptions::Gpt35Turbo,
+            model: DEFAULT_MODEL.to_string(),
             max_tokens: DEFAULT_USER_MAX_TOKEN,
-            temperature: 1,
-            presence_penalty: 0,
-            top_p: 1,
-            frequency_penalty: 0,
+            temperature: 1.0,
+            presence_penalty: 0.0,
+            top_p: 1.0,
+            frequency_penalty: 0.0,
+            stop: vec![],
+            seed: None,
+            n: 1,
+            logit_bias: BTreeMap::new(),
+            response_format: ResponseFormat::Text,
+            user: None,
         }
     }
 }
@@ -127,74 +321,22 @@ pub struct Folder {
     pub(crate) color: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum ModelOptions {
-    Gpt4o,
-    Gpt4o20240513,
-    Gpt4,
-    Gpt432k,
-    Gpt41106Preview,
-    Gpt40125Preview,
-    Gpt4Turbo,
-    Gpt4Turbo20240409,
-    Gpt35Turbo,
-    Gpt35Turbo16k,
-    Gpt35Turbo1106,
-    Gpt35Turbo0125,
-}
-
-impl std::str::FromStr for ModelOptions {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "gpt-4o" => Ok(Self::Gpt4o),
-            "gpt-4o-2024-05-13" => Ok(Self::Gpt4o20240513),
-            "gpt-4" => Ok(Self::Gpt4),
-            "gpt-4-32k" => Ok(Self::Gpt432k),
-            "gpt-4-1106-preview" => Ok(Self::Gpt41106Preview),
-            "gpt-4-0125-preview" => Ok(Self::Gpt40125Preview),
-            "gpt-4-turbo" => Ok(Self::Gpt4Turbo),
-            "gpt-4-turbo-2024-04-09" => Ok(Self::Gpt4Turbo20240409),
-            "gpt-3.5-turbo" => Ok(Self::Gpt35Turbo),
-            "gpt-3.5-turbo-16k" => Ok(Self::Gpt35Turbo16k),
-            "gpt-3.5-turbo-1106" => Ok(Self::Gpt35Turbo1106),
-            "gpt-3.5-turbo-0125" => Ok(Self::Gpt35Turbo0125),
-            _ => Err(()),
-        }
-    }
-}
-
-
-impl std::fmt::Display for ModelOptions {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
-            Self::Gpt4o => "gpt-4o",
-            Self::Gpt4o20240513 => "gpt-4o-2024-05-13",
-            Self::Gpt4 => "gpt-4",
-            Self::Gpt432k => "gpt-4-32k",
-            Self::Gpt41106Preview => "gpt-4-1106-preview",
-            Self::Gpt40125Preview => "gpt-4-0125-preview",
-            Self::Gpt4Turbo => "gpt-4-turbo",
-            Self::Gpt4Turbo20240409 => "gpt-4-turbo-2024-04-09",
-            Self::Gpt35Turbo => "gpt-3.5-turbo",
-            Self::Gpt35Turbo16k => "gpt-3.5-turbo-16k",
-            Self::Gpt35Turbo1106 => "gpt-3.5-turbo-1106",
-            Self::Gpt35Turbo0125 => "gpt-3.5-turbo-0125",
-        };
-        write!(f, "{}", s)
-    }
-}
-
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct TokenUsage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
 }
 
-pub type TotalTokenUsed = HashMap<ModelOptions, TokenUsage>;
+/// Token usage keyed by model id.
+pub type TotalTokenUsed = HashMap<String, TokenUsage>;
+
+// Layouts of the state persisted by earlier versions, `VnToVn+1` being the
+// one stored at version `n` and handed to `migrate_vn`. Field names are the
+// camelCase ones those versions wrote; anything a blob lacks is defaulted.
+// `storage::migrate` chains the steps up to the current layout.
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct LocalStorageInterfaceV0ToV1 {
     pub chats: Vec<ChatInterface>,
     pub current_chat_index: usize,
@@ -214,15 +356,16 @@ impl LocalStorageInterfaceV0ToV1 {
                     Some(cfg) => cfg.clone(),
                     None => ConfigInterface::default(),
                 };
-                let new_chat = ChatInterface {
+                ChatInterface {
                     config: Some(chat_config),
+                    api_profile: None,
                     title_set: false,
                     id: c.id.clone(),
                     title: c.title.clone(),
                     folder: c.folder.clone(),
                     messages: c.messages.clone(),
-                };
-                new_chat
+                    revision: c.revision,
+                }
             })
             .collect();
         Self {
@@ -232,7 +375,8 @@ impl LocalStorageInterfaceV0ToV1 {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct LocalStorageInterfaceV1ToV2 {
     pub chats: Vec<ChatInterface>,
     pub current_chat_index: usize,
@@ -243,6 +387,20 @@ pub struct LocalStorageInterfaceV1ToV2 {
     pub theme: Theme,
 }
 
+impl From<LocalStorageInterfaceV0ToV1> for LocalStorageInterfaceV1ToV2 {
+    fn from(v0: LocalStorageInterfaceV0ToV1) -> Self {
+        Self {
+            chats: v0.chats,
+            current_chat_index: v0.current_chat_index,
+            api_key: v0.api_key,
+            api_free: v0.api_free,
+            api_free_endpoint: v0.api_free_endpoint,
+            api_endpoint: None,
+            theme: v0.theme,
+        }
+    }
+}
+
 impl LocalStorageInterfaceV1ToV2 {
     pub fn migrate_v1(other: &Self) -> Self {
         let api_endpoint = Some(if other.api_free {
@@ -257,7 +415,8 @@ impl LocalStorageInterfaceV1ToV2 {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct LocalStorageInterfaceV2ToV3 {
     pub chats: Vec<ChatInterface>,
     pub current_chat_index: usize,
@@ -269,23 +428,37 @@ pub struct LocalStorageInterfaceV2ToV3 {
     pub auto_title: bool,
 }
 
+impl From<LocalStorageInterfaceV1ToV2> for LocalStorageInterfaceV2ToV3 {
+    fn from(v1: LocalStorageInterfaceV1ToV2) -> Self {
+        Self {
+            chats: v1.chats,
+            current_chat_index: v1.current_chat_index,
+            api_key: v1.api_key,
+            api_free: v1.api_free,
+            api_free_endpoint: v1.api_free_endpoint,
+            api_endpoint: v1.api_endpoint,
+            theme: v1.theme,
+            auto_title: false,
+        }
+    }
+}
+
 impl LocalStorageInterfaceV2ToV3 {
     pub fn migrate_v2(other: &Self) -> Self {
         let chats = other
             .chats
             .iter()
             .map(|chat| {
-                let _c = chat.config.as_ref().unwrap().clone();
+                let _c = chat.config.clone().unwrap_or_default();
                 let config = Some(ConfigInterface {
                     top_p: ConfigInterface::default().top_p,
                     frequency_penalty: ConfigInterface::default().frequency_penalty,
                     .._c
                 });
-                let new_chat = ChatInterface {
+                ChatInterface {
                     config,
                     ..chat.clone()
-                };
-                new_chat
+                }
             })
             .collect();
         Self {
@@ -296,7 +469,8 @@ impl LocalStorageInterfaceV2ToV3 {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct LocalStorageInterfaceV3ToV4 {
     pub chats: Vec<ChatInterface>,
     pub current_chat_index: usize,
@@ -309,6 +483,22 @@ pub struct LocalStorageInterfaceV3ToV4 {
     pub prompts: Vec<Prompt>,
 }
 
+impl From<LocalStorageInterfaceV2ToV3> for LocalStorageInterfaceV3ToV4 {
+    fn from(v2: LocalStorageInterfaceV2ToV3) -> Self {
+        Self {
+            chats: v2.chats,
+            current_chat_index: v2.current_chat_index,
+            api_key: v2.api_key,
+            api_free: v2.api_free,
+            api_free_endpoint: v2.api_free_endpoint,
+            api_endpoint: v2.api_endpoint,
+            theme: v2.theme,
+            auto_title: v2.auto_title,
+            prompts: Vec::new(),
+        }
+    }
+}
+
 impl LocalStorageInterfaceV3ToV4 {
     pub fn migrate_v3(other: &Self) -> Self {
         Self {
@@ -316,12 +506,43 @@ impl LocalStorageInterfaceV3ToV4 {
             ..other.clone()
         }
     }
+
+    /// Resets every chat to the default model.
+    pub fn migrate_v4(other: &Self) -> Self {
+        Self {
+            chats: other.chats_with(|config| config.model = DEFAULT_MODEL.to_string()),
+            ..other.clone()
+        }
+    }
+
+    /// Raises every chat's `max_tokens` to the new default.
+    pub fn migrate_v5(other: &Self) -> Self {
+        Self {
+            chats: other.chats_with(|config| config.max_tokens = DEFAULT_USER_MAX_TOKEN),
+            ..other.clone()
+        }
+    }
+
+    fn chats_with(&self, update: impl Fn(&mut ConfigInterface)) -> Vec<ChatInterface> {
+        self.chats
+            .iter()
+            .map(|chat| {
+                let mut config = chat.config.clone().unwrap_or_default();
+                update(&mut config);
+                ChatInterface {
+                    config: Some(config),
+                    ..chat.clone()
+                }
+            })
+            .collect()
+    }
 }
 
 pub type LocalStorageInterfaceV4ToV5 = LocalStorageInterfaceV3ToV4;
 pub type LocalStorageInterfaceV5ToV6 = LocalStorageInterfaceV3ToV4;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct LocalStorageInterfaceV6ToV7 {
     pub chats: Vec<ChatInterface>,
     pub current_chat_index: usize,
@@ -338,7 +559,53 @@ pub struct LocalStorageInterfaceV6ToV7 {
     pub hide_side_menu: bool,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Default for LocalStorageInterfaceV6ToV7 {
+    fn default() -> Self {
+        LocalStorageInterfaceV5ToV6::default().into()
+    }
+}
+
+impl From<LocalStorageInterfaceV5ToV6> for LocalStorageInterfaceV6ToV7 {
+    fn from(v5: LocalStorageInterfaceV5ToV6) -> Self {
+        Self {
+            chats: v5.chats,
+            current_chat_index: v5.current_chat_index,
+            api_free: Some(v5.api_free),
+            api_key: v5.api_key,
+            api_endpoint: v5.api_endpoint.unwrap_or_else(|| OFFICIAL_APIENDPOINT.to_string()),
+            theme: v5.theme,
+            auto_title: v5.auto_title,
+            prompts: v5.prompts,
+            default_chat_config: ConfigInterface::default(),
+            default_system_message: DEFAULT_SYSTEM_MESSAGE.to_string(),
+            hide_menu_options: false,
+            first_visit: true,
+            hide_side_menu: false,
+        }
+    }
+}
+
+impl LocalStorageInterfaceV6ToV7 {
+    /// Moves users off free endpoints that have shut down.
+    pub fn migrate_v6(other: &Self) -> Self {
+        const DEAD_ENDPOINTS: [&str; 2] = [
+            "https://sharegpt.churchless.tech/share/v1/chat",
+            "https://cookie-api.onrender.com/v1/chat/completions",
+        ];
+        let api_endpoint = if DEAD_ENDPOINTS.contains(&other.api_endpoint.as_str()) {
+            OFFICIAL_APIENDPOINT.to_string()
+        } else {
+            other.api_endpoint.clone()
+        };
+        Self {
+            api_endpoint,
+            ..other.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct LocalStorageInterfaceV7ToV8 {
     pub chats: Vec<ChatInterface>,
     pub current_chat_index: usize,
@@ -358,7 +625,144 @@ pub struct LocalStorageInterfaceV7ToV8 {
     pub folders: FolderCollection,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl From<LocalStorageInterfaceV6ToV7> for LocalStorageInterfaceV7ToV8 {
+    fn from(v6: LocalStorageInterfaceV6ToV7) -> Self {
+        Self {
+            chats: v6.chats,
+            current_chat_index: v6.current_chat_index,
+            api_free: v6.api_free,
+            api_key: v6.api_key,
+            api_endpoint: v6.api_endpoint,
+            theme: v6.theme,
+            auto_title: v6.auto_title,
+            prompts: v6.prompts,
+            default_chat_config: v6.default_chat_config,
+            default_system_message: v6.default_system_message,
+            hide_menu_options: v6.hide_menu_options,
+            first_visit: v6.first_visit,
+            hide_side_menu: v6.hide_side_menu,
+            folders_name: Vec::new(),
+            folders_expanded: Vec::new(),
+            folders: FolderCollection::new(),
+        }
+    }
+}
+
+impl LocalStorageInterfaceV7ToV8 {
+    /// Folders used to be identified by name, with their state kept in
+    /// parallel lists; they become a collection keyed by id. Chats without
+    /// an id get one; existing ids are kept, as imports and sync match on them.
+    pub fn migrate_v7(other: &Self) -> Self {
+        let mut folders = other.folders.clone();
+        let mut ids = HashMap::new();
+        for (order, name) in other.folders_name.iter().enumerate() {
+            let id = uuid::Uuid::new_v4().to_string();
+            ids.insert(name.clone(), id.clone());
+            folders.insert(
+                id.clone(),
+                Folder {
+                    id,
+                    name: name.clone(),
+                    expanded: other.folders_expanded.get(order).copied().unwrap_or(false),
+                    order: order as i32,
+                    color: None,
+                },
+            );
+        }
+        let chats = other
+            .chats
+            .iter()
+            .map(|chat| ChatInterface {
+                id: if chat.id.is_empty() { uuid::Uuid::new_v4().to_string() } else { chat.id.clone() },
+                folder: chat.folder.as_ref().and_then(|name| ids.get(name).cloned()),
+                ..chat.clone()
+            })
+            .collect();
+        Self {
+            chats,
+            folders,
+            ..other.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LocalStorageInterfaceV8ToV9 {
+    pub chats: Vec<ChatInterface>,
+    pub current_chat_index: usize,
+    pub api_free: Option<bool>,
+    pub api_key: String,
+    pub api_endpoint: String,
+    pub theme: Theme,
+    pub auto_title: bool,
+    pub prompts: Vec<Prompt>,
+    pub default_chat_config: ConfigInterface,
+    pub default_system_message: String,
+    pub hide_menu_options: bool,
+    pub first_visit: bool,
+    pub hide_side_menu: bool,
+    pub folders_name: Vec<String>,
+    pub folders_expanded: Vec<bool>,
+    pub folders: FolderCollection,
+}
+
+impl From<LocalStorageInterfaceV7ToV8> for LocalStorageInterfaceV8ToV9 {
+    fn from(v7: LocalStorageInterfaceV7ToV8) -> Self {
+        Self {
+            chats: v7.chats,
+            current_chat_index: v7.current_chat_index,
+            api_free: v7.api_free,
+            api_key: v7.api_key,
+            api_endpoint: v7.api_endpoint,
+            theme: v7.theme,
+            auto_title: v7.auto_title,
+            prompts: v7.prompts,
+            default_chat_config: v7.default_chat_config,
+            default_system_message: v7.default_system_message,
+            hide_menu_options: v7.hide_menu_options,
+            first_visit: v7.first_visit,
+            hide_side_menu: v7.hide_side_menu,
+            folders_name: v7.folders_name,
+            folders_expanded: v7.folders_expanded,
+            folders: v7.folders,
+        }
+    }
+}
+
+impl LocalStorageInterfaceV8ToV9 {
+    /// Fills the sampling parameters added in v9 (`stop`, `seed`, `n`,
+    /// `logit_bias`, `response_format`, `user`) with their defaults.
+    pub fn migrate_v8(other: &Self) -> Self {
+        let with_defaults = |config: &ConfigInterface| {
+            let defaults = ConfigInterface::default();
+            ConfigInterface {
+                stop: defaults.stop,
+                seed: defaults.seed,
+                n: defaults.n,
+                logit_bias: defaults.logit_bias,
+                response_format: defaults.response_format,
+                user: defaults.user,
+                ..config.clone()
+            }
+        };
+        let chats = other
+            .chats
+            .iter()
+            .map(|chat| ChatInterface {
+                config: Some(with_defaults(chat.config.as_ref().unwrap_or(&ConfigInterface::default()))),
+                ..chat.clone()
+            })
+            .collect();
+        Self {
+            chats,
+            default_chat_config: with_defaults(&other.default_chat_config),
+            ..other.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Prompt {
     pub(crate) id: String,
     pub(crate) name: String,
@@ -375,10 +779,12 @@ impl Default for Prompt {
     }
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub enum Theme {
     #[default]
+    #[serde(alias = "light")]
     Light,
+    #[serde(alias = "dark")]
     Dark,
 }
 
@@ -390,3 +796,106 @@ impl std::fmt::Display for Theme {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: Role, content: &str) -> MessageInterface {
+        MessageInterface {
+            role,
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn contents(chat: &ChatInterface) -> Vec<&str> {
+        chat.messages.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    #[test]
+    fn editing_and_regenerating_keep_the_old_branches() {
+        let mut chat = ChatInterface {
+            messages: vec![message(Role::User, "hi"), message(Role::Assistant, "hello")],
+            ..Default::default()
+        };
+
+        // Regenerate the answer, then edit the question.
+        chat.branch_off(1, vec![message(Role::Assistant, "hey")]);
+        assert_eq!(contents(&chat), ["hi", "hey"]);
+        assert_eq!((chat.messages[1].branch, chat.branch_count(1)), (1, 2));
+
+        chat.branch_off(0, vec![message(Role::User, "bonjour"), message(Role::Assistant, "salut")]);
+        assert_eq!(contents(&chat), ["bonjour", "salut"]);
+        assert_eq!((chat.messages[0].branch, chat.branch_count(0)), (1, 2));
+
+        // The first question still remembers which answer was shown last.
+        chat.switch_branch(0, 0);
+        assert_eq!(contents(&chat), ["hi", "hey"]);
+        assert_eq!(chat.branch_count(1), 2);
+        chat.switch_branch(1, 0);
+        assert_eq!(contents(&chat), ["hi", "hello"]);
+
+        chat.switch_branch(0, 1);
+        assert_eq!(contents(&chat), ["bonjour", "salut"]);
+        chat.switch_branch(0, 5);
+        assert_eq!(contents(&chat), ["bonjour", "salut"]);
+    }
+
+    #[test]
+    fn deleting_a_version_shows_the_next_one() {
+        let mut chat = ChatInterface {
+            messages: vec![message(Role::User, "hi"), message(Role::Assistant, "hello")],
+            ..Default::default()
+        };
+        chat.branch_off(1, vec![message(Role::Assistant, "hey")]);
+        chat.branch_off(1, vec![message(Role::Assistant, "yo")]);
+        chat.switch_branch(1, 1);
+        assert_eq!(contents(&chat), ["hi", "hey"]);
+
+        chat.delete_message(1);
+        assert_eq!(contents(&chat), ["hi", "yo"]);
+        assert_eq!((chat.messages[1].branch, chat.branch_count(1)), (1, 2));
+        chat.switch_branch(1, 0);
+        assert_eq!(contents(&chat), ["hi", "hello"]);
+
+        chat.delete_message(1);
+        assert_eq!(contents(&chat), ["hi", "yo"]);
+        assert_eq!(chat.branch_count(1), 1);
+        chat.delete_message(0);
+        assert_eq!(contents(&chat), ["yo"]);
+    }
+
+    #[test]
+    fn messages_with_versions_are_not_moved() {
+        let mut chat = ChatInterface {
+            messages: vec![message(Role::System, "be brief"), message(Role::User, "hi"), message(Role::Assistant, "hello")],
+            ..Default::default()
+        };
+        chat.branch_off(2, vec![message(Role::Assistant, "hey")]);
+
+        chat.swap_messages(1, 2);
+        assert_eq!(contents(&chat), ["be brief", "hi", "hey"]);
+        chat.swap_messages(0, 1);
+        assert_eq!(contents(&chat), ["hi", "be brief", "hey"]);
+
+        // The other answer still continues from the right place.
+        chat.switch_branch(2, 0);
+        assert_eq!(contents(&chat), ["hi", "be brief", "hello"]);
+    }
+
+    #[test]
+    fn branches_survive_a_serde_round_trip() {
+        let mut chat = ChatInterface {
+            messages: vec![message(Role::User, "hi"), message(Role::Assistant, "hello")],
+            ..Default::default()
+        };
+        chat.branch_off(1, vec![message(Role::Assistant, "hey")]);
+        let json = serde_json::to_string(&chat).unwrap();
+        assert_eq!(serde_json::from_str::<ChatInterface>(&json).unwrap(), chat);
+
+        // Messages without branches serialize like before.
+        let plain = serde_json::to_value(message(Role::User, "hi")).unwrap();
+        assert!(plain.get("alternatives").is_none() && plain.get("branch").is_none());
+    }
+}
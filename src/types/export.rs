@@ -2,7 +2,7 @@
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 
-use super::chat::{ChatInterface, FolderCollection, Role};
+use super::chat::{ChatInterface, ConfigInterface, Folder, FolderCollection, Prompt, Theme, TotalTokenUsed};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ExportBase {
@@ -17,11 +17,235 @@ pub struct ExportV1 {
     pub folders: FolderCollection,
 }
 
+/// V1 plus message branches (`MessageInterface::alternatives`), which V1
+/// readers would silently drop, and optionally the user's prompts, settings
+/// and token usage.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportV2 {
+    #[serde(flatten)]
+    pub base: ExportBase,
+    pub chats: Vec<ChatInterface>,
+    pub folders: FolderCollection,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prompts: Vec<Prompt>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<ExportSettings>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub total_token_used: TotalTokenUsed,
+}
+
+pub const EXPORT_VERSION: u32 = 2;
+
+impl ExportV2 {
+    pub fn new(chats: Vec<ChatInterface>, folders: FolderCollection) -> Self {
+        Self {
+            base: ExportBase { version: EXPORT_VERSION },
+            chats,
+            folders,
+            prompts: Vec::new(),
+            settings: None,
+            total_token_used: TotalTokenUsed::new(),
+        }
+    }
+}
+
+impl From<ExportV1> for ExportV2 {
+    fn from(v1: ExportV1) -> Self {
+        Self::new(v1.chats, v1.folders)
+    }
+}
+
+/// The preferences from `ConfigSlice` worth carrying to another browser;
+/// UI state such as open menus stays behind.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportSettings {
+    pub theme: Theme,
+    pub auto_title: bool,
+    #[serde(default)]
+    pub title_model: Option<String>,
+    pub title_prompt: String,
+    pub advanced_mode: bool,
+    pub default_chat_config: ConfigInterface,
+    pub default_system_message: String,
+    pub enter_to_submit: bool,
+    pub inline_latex: bool,
+    pub markdown_mode: bool,
+    pub count_total_tokens: bool,
+    #[serde(default)]
+    pub auto_continue: u32,
+}
+
+/// Reads an export of any supported version and migrates it, one version
+/// at a time, to the current one.
+pub fn parse_export(json: &str) -> anyhow::Result<ExportV2> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|e| {
+        anyhow::anyhow!("The file is not valid JSON (line {}, column {})", e.line(), e.column())
+    })?;
+    let Some(version) = value["version"].as_u64() else {
+        anyhow::bail!("The file is not a chat export: it has no version");
+    };
+    let shape_error = |e: serde_json::Error| anyhow::anyhow!("The file is not a valid version {} export: {}", version, e);
+    match version {
+        1 => Ok(ExportV2::from(serde_json::from_value::<ExportV1>(value).map_err(shape_error)?)),
+        2 => Ok(serde_json::from_value::<ExportV2>(value).map_err(shape_error)?),
+        version => anyhow::bail!("Export version {} is not supported by this version of the app", version),
+    }
+}
+
+/// What to do with an imported chat whose id already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DuplicateStrategy {
+    #[default]
+    Skip,
+    Overwrite,
+    /// Import it as a new chat with a fresh id.
+    KeepBoth,
+}
+
+impl DuplicateStrategy {
+    pub const ALL: [DuplicateStrategy; 3] = [
+        DuplicateStrategy::Skip,
+        DuplicateStrategy::Overwrite,
+        DuplicateStrategy::KeepBoth,
+    ];
+}
+
+impl std::fmt::Display for DuplicateStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DuplicateStrategy::Skip => "skip",
+            DuplicateStrategy::Overwrite => "overwrite",
+            DuplicateStrategy::KeepBoth => "keepBoth",
+        }
+        .fmt(f)
+    }
+}
+
+/// The outcome of merging an export into the current chats, folders and
+/// prompts, computed without touching the store so it can be previewed.
+/// The lists of titles/names say what happened to each imported item.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportPlan {
+    pub chats: Vec<ChatInterface>,
+    pub folders: FolderCollection,
+    pub prompts: Vec<Prompt>,
+    pub added: Vec<String>,
+    pub overwritten: Vec<String>,
+    pub kept_both: Vec<String>,
+    pub skipped: Vec<String>,
+    /// Already present with the exact same content.
+    pub unchanged: Vec<String>,
+    pub new_folders: Vec<String>,
+    /// Imported folders whose id was taken by a different local folder.
+    pub remapped_folders: Vec<String>,
+    pub new_prompts: usize,
+    pub warnings: Vec<String>,
+}
+
+/// Merges `export` into the current state. Chats are matched by id, folders
+/// by id and name; new folders are ordered after the existing ones, and new
+/// chats go on top, in their exported order.
+pub fn plan_import(
+    export: &ExportV2,
+    chats: &[ChatInterface],
+    folders: &FolderCollection,
+    prompts: &[Prompt],
+    strategy: DuplicateStrategy,
+) -> ImportPlan {
+    let mut plan = ImportPlan {
+        folders: folders.clone(),
+        prompts: prompts.to_vec(),
+        ..Default::default()
+    };
+
+    // Imported folder id -> id of the folder it ends up in.
+    let mut folder_ids = HashMap::new();
+    let mut next_order = folders.values().map(|f| f.order + 1).max().unwrap_or(0);
+    let mut imported_folders: Vec<&Folder> = export.folders.values().collect();
+    imported_folders.sort_by_key(|f| (f.order, &f.id));
+    for folder in imported_folders {
+        let id = match plan.folders.get(&folder.id) {
+            Some(local) if local.name == folder.name => {
+                folder_ids.insert(folder.id.clone(), folder.id.clone());
+                continue;
+            }
+            Some(_) => {
+                plan.remapped_folders.push(folder.name.clone());
+                uuid::Uuid::new_v4().to_string()
+            }
+            None => {
+                plan.new_folders.push(folder.name.clone());
+                folder.id.clone()
+            }
+        };
+        folder_ids.insert(folder.id.clone(), id.clone());
+        plan.folders.insert(id.clone(), Folder { id, order: next_order, ..folder.clone() });
+        next_order += 1;
+    }
+
+    let mut new_chats: Vec<ChatInterface> = Vec::new();
+    let mut current = chats.to_vec();
+    for chat in &export.chats {
+        let mut chat = chat.clone();
+        let title = chat.title.clone().unwrap_or_default();
+        if let Some(folder) = chat.folder.take() {
+            match folder_ids.get(&folder) {
+                Some(id) => chat.folder = Some(id.clone()),
+                None => plan.warnings.push(format!("{}: its folder is missing from the file", title)),
+            }
+        }
+        if chat.id.is_empty() {
+            chat.id = uuid::Uuid::new_v4().to_string();
+        }
+        let existing = new_chats
+            .iter_mut()
+            .chain(current.iter_mut())
+            .find(|c| c.id == chat.id);
+        match (existing, strategy) {
+            (None, _) => {
+                plan.added.push(title);
+                new_chats.push(chat);
+            }
+            (Some(existing), _) if *existing == chat => plan.unchanged.push(title),
+            (Some(_), DuplicateStrategy::Skip) => plan.skipped.push(title),
+            (Some(existing), DuplicateStrategy::Overwrite) => {
+                *existing = chat;
+                plan.overwritten.push(title);
+            }
+            (Some(_), DuplicateStrategy::KeepBoth) => {
+                chat.id = uuid::Uuid::new_v4().to_string();
+                plan.kept_both.push(title);
+                new_chats.push(chat);
+            }
+        }
+    }
+    new_chats.extend(current);
+    plan.chats = new_chats;
+
+    for prompt in &export.prompts {
+        if !plan.prompts.iter().any(|p| p.id == prompt.id) {
+            plan.prompts.push(prompt.clone());
+            plan.new_prompts += 1;
+        }
+    }
+    plan
+}
+
+/// A conversation from ChatGPT's `conversations.json`. The messages form a
+/// tree in `mapping`; `current_node` is the leaf of the branch that was
+/// showing when the conversation was exported.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OpenAIChat {
-    pub title: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Unix time in seconds.
+    #[serde(default)]
+    pub create_time: Option<f64>,
     pub mapping: HashMap<String, OpenAIChatNode>,
-    pub current_node: String,
+    #[serde(default)]
+    pub current_node: Option<String>,
+    #[serde(default)]
+    pub default_model_slug: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -29,21 +253,159 @@ pub struct OpenAIChatNode {
     pub id: String,
     pub message: Option<OpenAIChatMessage>,
     pub parent: Option<String>,
+    #[serde(default)]
     pub children: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OpenAIChatMessage {
     pub author: OpenAIChatAuthor,
+    /// Unix time in seconds.
+    #[serde(default)]
+    pub create_time: Option<f64>,
     pub content: OpenAIChatContent,
+    #[serde(default)]
+    pub metadata: OpenAIChatMetadata,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OpenAIChatAuthor {
-    pub role: Role,
+    /// `user`, `assistant`, `system` or `tool`.
+    pub role: String,
+    /// Tool name for `tool` messages.
+    #[serde(default)]
+    pub name: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Message content. Which fields are set depends on `content_type`: `text`
+/// and `multimodal_text` have `parts`, `code` and `execution_output` have
+/// `text`, `tether_quote` has `title`, `url` and `text`, and
+/// `user_editable_context` has the custom instructions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct OpenAIChatContent {
-    pub parts: Vec<String>,
+    #[serde(default)]
+    pub content_type: String,
+    /// Strings, or objects such as image asset pointers or audio transcriptions.
+    #[serde(default)]
+    pub parts: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub user_profile: Option<String>,
+    #[serde(default)]
+    pub user_instructions: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpenAIChatMetadata {
+    #[serde(default)]
+    pub model_slug: Option<String>,
+    /// Set on the empty system prompt ChatGPT puts at the start of each conversation.
+    #[serde(default)]
+    pub is_visually_hidden_from_conversation: bool,
+    #[serde(default)]
+    pub finish_details: Option<OpenAIFinishDetails>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenAIFinishDetails {
+    /// `stop`, `max_tokens` or `interrupted`.
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::chat::{MessageInterface, TokenUsage};
+
+    #[test]
+    fn v1_exports_upgrade_and_v2_keeps_branches() {
+        let v1 = r#"{"version":1,"chats":[{"id":"a","title":"Hi","folder":null,"messages":[{"role":"User","content":"hi","folder":null,"messages":[]}],"config":null,"title_set":true}],"folders":{}}"#;
+        let export = parse_export(v1).unwrap();
+        assert_eq!(export.base.version, EXPORT_VERSION);
+        assert_eq!(export.chats[0].messages[0].content, "hi");
+
+        let mut chat = export.chats[0].clone();
+        chat.branch_off(0, vec![MessageInterface {
+            content: "hello".into(),
+            created_at: Some(1_700_000_000_000),
+            model: Some("gpt-4o".into()),
+            usage: Some(TokenUsage { prompt_tokens: 5, completion_tokens: 2 }),
+            finish_reason: Some("length".into()),
+            ..Default::default()
+        }]);
+        let json = serde_json::to_string(&ExportV2::new(vec![chat.clone()], export.folders)).unwrap();
+        assert_eq!(parse_export(&json).unwrap().chats, vec![chat]);
+
+        assert!(parse_export(r#"{"version":99,"chats":[],"folders":{}}"#).is_err());
+    }
+
+    #[test]
+    fn parse_errors_are_readable() {
+        let error = |json: &str| parse_export(json).unwrap_err().to_string();
+        assert_eq!(error("{\"version\":"), "The file is not valid JSON (line 1, column 11)");
+        assert_eq!(error("[]"), "The file is not a chat export: it has no version");
+        assert!(error(r#"{"version":2,"chats":{}}"#).starts_with("The file is not a valid version 2 export"));
+    }
+
+    fn folder(id: &str, name: &str, order: i32) -> (String, Folder) {
+        let folder = Folder { id: id.into(), name: name.into(), expanded: false, order, color: None };
+        (id.to_string(), folder)
+    }
+
+    fn chat(id: &str, title: &str, folder: Option<&str>) -> ChatInterface {
+        ChatInterface {
+            id: id.into(),
+            title: Some(title.into()),
+            folder: folder.map(|f| f.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn import_plan_dedupes_chats_and_remaps_folders() {
+        let local_folders = FolderCollection::from([folder("work", "Work", 0), folder("f2", "Home", 1)]);
+        let local_chats = vec![chat("a", "A", Some("work")), chat("b", "B", None)];
+
+        let mut changed = chat("b", "B renamed", None);
+        changed.messages.push(MessageInterface { content: "new".into(), ..Default::default() });
+        let mut export = ExportV2::new(
+            vec![chat("a", "A", Some("work")), changed, chat("c", "C", Some("f2")), chat("d", "D", Some("gone"))],
+            FolderCollection::from([folder("work", "Work", 0), folder("f2", "Travel", 0)]),
+        );
+        export.prompts.push(Prompt::default());
+
+        let plan = plan_import(&export, &local_chats, &local_folders, &[Prompt::default()], DuplicateStrategy::Skip);
+        assert_eq!(plan.unchanged, ["A"]);
+        assert_eq!(plan.skipped, ["B renamed"]);
+        assert_eq!(plan.added, ["C", "D"]);
+        assert_eq!(plan.remapped_folders, ["Travel"]);
+        assert!(plan.new_folders.is_empty());
+        assert_eq!(plan.warnings, ["D: its folder is missing from the file"]);
+        assert_eq!(plan.new_prompts, 0);
+        assert_eq!(plan.chats.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), ["c", "d", "a", "b"]);
+
+        let travel = plan.chats[0].folder.clone().unwrap();
+        assert_ne!(travel, "f2");
+        assert_eq!(plan.folders[&travel].name, "Travel");
+        assert_eq!(plan.folders[&travel].order, 2);
+        assert_eq!(plan.folders["f2"].name, "Home");
+
+        let plan = plan_import(&export, &local_chats, &local_folders, &[], DuplicateStrategy::Overwrite);
+        assert_eq!(plan.overwritten, ["B renamed"]);
+        assert_eq!(plan.chats[3].messages.len(), 1);
+        assert_eq!(plan.new_prompts, 1);
+
+        let plan = plan_import(&export, &local_chats, &local_folders, &[], DuplicateStrategy::KeepBoth);
+        assert_eq!(plan.kept_both, ["B renamed"]);
+        assert_eq!(plan.chats.len(), 5);
+        assert!(plan.chats[..3].iter().all(|c| c.id != "b"));
+    }
 }
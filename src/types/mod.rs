@@ -1,3 +1,4 @@
 pub mod chat;
 pub mod api;
 pub mod export;
+pub mod model;
@@ -0,0 +1,216 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use yewdux::prelude::*;
+
+use super::{api::ProviderKind, chat::TokenUsage};
+use crate::constants::{DEFAULT_MODELS, DEFAULT_MODEL_MAX_TOKEN};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelCapabilities {
+    pub vision: bool,
+    pub tools: bool,
+    pub streaming: bool,
+}
+
+impl Default for ModelCapabilities {
+    fn default() -> Self {
+        Self {
+            vision: false,
+            tools: false,
+            streaming: true,
+        }
+    }
+}
+
+/// Everything we know about a model. Prices are USD per 1K tokens.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub provider: ProviderKind,
+    #[serde(default = "default_context_window")]
+    pub context_window: i32,
+    #[serde(default = "default_context_window")]
+    pub max_output: i32,
+    #[serde(default)]
+    pub prompt_price: f64,
+    #[serde(default)]
+    pub completion_price: f64,
+    #[serde(default)]
+    pub capabilities: ModelCapabilities,
+}
+
+fn default_context_window() -> i32 {
+    DEFAULT_MODEL_MAX_TOKEN
+}
+
+impl ModelInfo {
+    /// Placeholder for ids we have no metadata for (fine-tunes, local models, ...).
+    pub fn unknown(id: &str, provider: ProviderKind) -> Self {
+        Self {
+            id: id.to_string(),
+            name: id.to_string(),
+            provider,
+            context_window: DEFAULT_MODEL_MAX_TOKEN,
+            max_output: DEFAULT_MODEL_MAX_TOKEN,
+            prompt_price: 0.0,
+            completion_price: 0.0,
+            capabilities: ModelCapabilities::default(),
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        if self.name.is_empty() { &self.id } else { &self.name }
+    }
+
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 / 1000.0) * self.prompt_price
+            + (usage.completion_tokens as f64 / 1000.0) * self.completion_price
+    }
+}
+
+/// The set of selectable models: the built-in list plus whatever was loaded at
+/// runtime from `models.json` or discovered from the provider's model listing.
+#[derive(Debug, Clone, PartialEq, Store)]
+pub struct ModelRegistry {
+    pub models: Vec<ModelInfo>,
+}
+
+impl Default for ModelRegistry {
+    fn default() -> Self {
+        Self {
+            models: DEFAULT_MODELS.clone(),
+        }
+    }
+}
+
+impl ModelRegistry {
+    pub fn get(&self, id: &str) -> Option<&ModelInfo> {
+        self.models.iter().find(|m| m.id == id)
+    }
+
+    /// Metadata for `id`, falling back to conservative defaults for unknown models.
+    pub fn info(&self, id: &str) -> ModelInfo {
+        self.get(id)
+            .cloned()
+            .unwrap_or_else(|| ModelInfo::unknown(id, ProviderKind::default()))
+    }
+
+    pub fn for_provider(&self, provider: &ProviderKind) -> Vec<&ModelInfo> {
+        self.models.iter().filter(|m| &m.provider == provider).collect()
+    }
+
+    /// Adds new models and replaces the metadata of known ids.
+    pub fn merge(&mut self, models: Vec<ModelInfo>) {
+        for model in models {
+            match self.models.iter_mut().find(|m| m.id == model.id) {
+                Some(existing) => *existing = model,
+                None => self.models.push(model),
+            }
+        }
+    }
+
+    /// Registers ids returned by a provider's model listing, keeping the
+    /// metadata of ids we already know.
+    pub fn merge_ids(&mut self, ids: Vec<String>, provider: &ProviderKind) {
+        for id in ids {
+            if self.get(&id).is_none() {
+                self.models.push(ModelInfo::unknown(&id, provider.clone()));
+            }
+        }
+    }
+
+    pub fn merge_json(&mut self, json: &str) -> serde_json::Result<()> {
+        let models: Vec<ModelInfo> = serde_json::from_str(json)?;
+        self.merge(models);
+        Ok(())
+    }
+}
+
+/// Reads `ConfigInterface.model`, accepting both plain ids and the variant
+/// names written by the old closed `ModelOptions` enum (`"Gpt4o"`,
+/// `{"Custom": "llama3"}`), so older exports keep importing.
+pub fn deserialize_model_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    enum Legacy {
+        Custom(String),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Id(String),
+        Legacy(Legacy),
+    }
+
+    Ok(match Stored::deserialize(deserializer)? {
+        Stored::Id(id) => legacy_model_id(&id).map(|s| s.to_string()).unwrap_or(id),
+        Stored::Legacy(Legacy::Custom(id)) => id,
+    })
+}
+
+fn legacy_model_id(variant: &str) -> Option<&'static str> {
+    Some(match variant {
+        "Gpt4o" => "gpt-4o",
+        "Gpt4o20240513" => "gpt-4o-2024-05-13",
+        "Gpt4" => "gpt-4",
+        "Gpt432k" => "gpt-4-32k",
+        "Gpt41106Preview" => "gpt-4-1106-preview",
+        "Gpt40125Preview" => "gpt-4-0125-preview",
+        "Gpt4Turbo" => "gpt-4-turbo",
+        "Gpt4Turbo20240409" => "gpt-4-turbo-2024-04-09",
+        "Gpt35Turbo" => "gpt-3.5-turbo",
+        "Gpt35Turbo16k" => "gpt-3.5-turbo-16k",
+        "Gpt35Turbo1106" => "gpt-3.5-turbo-1106",
+        "Gpt35Turbo0125" => "gpt-3.5-turbo-0125",
+        "Claude35SonnetLatest" => "claude-3-5-sonnet-latest",
+        "Claude35HaikuLatest" => "claude-3-5-haiku-latest",
+        "Claude3OpusLatest" => "claude-3-opus-latest",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::chat::ConfigInterface;
+
+    #[test]
+    fn every_builtin_model_has_a_price() {
+        let registry = ModelRegistry::default();
+        assert!(registry.get("gpt-4-turbo").unwrap().prompt_price > 0.0);
+        assert!(registry.models.iter().all(|m| m.prompt_price > 0.0 && m.completion_price > 0.0));
+    }
+
+    #[test]
+    fn merge_json_adds_and_overrides() {
+        let mut registry = ModelRegistry::default();
+        registry
+            .merge_json(r#"[{"id":"ft:gpt-4o:acme","context_window":128000},{"id":"gpt-4","prompt_price":1.0}]"#)
+            .unwrap();
+        assert_eq!(registry.info("ft:gpt-4o:acme").context_window, 128000);
+        assert_eq!(registry.info("gpt-4").prompt_price, 1.0);
+        assert_eq!(registry.info("no-such-model").context_window, DEFAULT_MODEL_MAX_TOKEN);
+    }
+
+    #[test]
+    fn legacy_and_custom_model_ids_round_trip() {
+        let legacy: ConfigInterface = serde_json::from_str(
+            r#"{"model":"Gpt4o","max_tokens":10,"temperature":1,"presence_penalty":0,"top_p":1,"frequency_penalty":0}"#,
+        )
+        .unwrap();
+        assert_eq!(legacy.model, "gpt-4o");
+
+        let custom: ConfigInterface = serde_json::from_str(
+            r#"{"model":{"Custom":"llama3"},"max_tokens":10,"temperature":1,"presence_penalty":0,"top_p":1,"frequency_penalty":0}"#,
+        )
+        .unwrap();
+        assert_eq!(custom.model, "llama3");
+
+        let exported = serde_json::to_string(&custom).unwrap();
+        let reimported: ConfigInterface = serde_json::from_str(&exported).unwrap();
+        assert_eq!(reimported.model, "llama3");
+    }
+}
@@ -1,25 +1,29 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
-use gloo_utils::format::JsValueSerdeExt;
 use serde::Serialize;
-use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen::JsCast;
 use web_sys::{Blob, BlobPropertyBag, Url};
 
-use crate::types::{chat::{ChatInterface, ConfigInterface, MessageInterface}, export::{OpenAIChat, OpenAIChatNode}};
+use crate::types::{
+    chat::{ChatInterface, ConfigInterface, MessageInterface, Role},
+    export::{OpenAIChat, OpenAIChatMessage, OpenAIChatNode},
+};
 
 pub fn get_today() -> String {
     chrono::Local::now().format("%Y-%m-%d").to_string()
 }
 
 pub fn download_file(data: &impl Serialize, filename: String) {
-    // Serialize data to JSON
-    let js_value: JsValue = JsValue::from_serde(&data).unwrap();
+    let json = serde_json::to_string(data).unwrap();
+    download_bytes(json.as_bytes(), "application/json", format!("{}.json", filename));
+}
 
+pub fn download_bytes(data: &[u8], mime: &str, filename: String) {
     // Create Blob
     let option = BlobPropertyBag::new();
-    option.set_type("application/json");
-    let blob =
-        Blob::new_with_str_sequence_and_options(&js_value, &option).expect("Failed to create blob");
+    option.set_type(mime);
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(data));
+    let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &option).expect("Failed to create blob");
 
     // Create object URL
     let url = Url::create_object_url_with_blob(&blob).unwrap();
@@ -40,59 +44,321 @@ pub fn download_file(data: &impl Serialize, filename: String) {
     Url::revoke_object_url(&url).unwrap();
 }
 
+/// The chats converted from a ChatGPT export, and everything that could not
+/// be carried over, prefixed with the title of the conversation it concerns.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpenAIImport {
+    pub chats: Vec<ChatInterface>,
+    pub warnings: Vec<String>,
+}
 
-// Conversion implementation
-pub fn convert_openai_to_my_format(openai_chat: &OpenAIChat) -> ChatInterface {
-    let mut messages = Vec::new();
-    
-    // Recursive traversal function
-    fn traverse_tree(
-        mapping: &HashMap<String, OpenAIChatNode>,
-        current_id: &str,
-        messages: &mut Vec<MessageInterface>,
-    ) {
-        if let Some(node) = mapping.get(current_id) {
-            // Process current node
-            if let Some(msg) = &node.message {
-                let content = msg.content.parts.join("");
-                if !content.is_empty() {
-                    messages.push(MessageInterface {
-                        role: msg.author.role.clone(),
-                        content,
-                        folder: None,
-                        messages: vec![],
-                    });
-                }
-            }
+/// Converts one ChatGPT conversation, following the branch that ends in its
+/// `current_node`. With `keep_branches` the other branches become
+/// alternatives of the message they fork from, see
+/// [`ChatInterface::switch_branch`]. Also returns what could not be imported.
+pub fn convert_openai_to_my_format(
+    openai_chat: &OpenAIChat,
+    keep_branches: bool,
+) -> (ChatInterface, Vec<String>) {
+    let mapping = &openai_chat.mapping;
+    let mut warnings = Vec::new();
+    let mut converter = OpenAIConverter {
+        mapping,
+        keep_branches,
+        active: HashSet::new(),
+        visited: HashSet::new(),
+        images: 0,
+        skipped: BTreeSet::new(),
+    };
 
-            // Process last child if exists
-            if let Some(last_child) = node.children.last() {
-                traverse_tree(mapping, last_child, messages);
+    let current_node = openai_chat
+        .current_node
+        .as_deref()
+        .filter(|id| mapping.contains_key(*id));
+    let root = match current_node {
+        Some(current_node) => {
+            let mut id = current_node;
+            while converter.active.insert(id.to_string())
+                && let Some(parent) = mapping[id].parent.as_deref().filter(|p| mapping.contains_key(*p))
+            {
+                id = parent;
             }
+            Some(id)
         }
-    }
-
-    // Find root node (first node in mapping)
-    if let Some(root_id) = openai_chat.mapping.keys().next() {
-        if let Some(root_node) = openai_chat.mapping.get(root_id) {
-            traverse_tree(&openai_chat.mapping, &root_node.id, &mut messages);
+        None => {
+            warnings.push("the selected branch is missing, imported the latest one instead".to_string());
+            mapping
+                .values()
+                .filter(|node| node.parent.as_ref().is_none_or(|p| !mapping.contains_key(p)))
+                .map(|node| node.id.as_str())
+                .min()
         }
+    };
+    let messages = root.map(|root| converter.thread(root)).unwrap_or_default();
+
+    if converter.images > 0 {
+        warnings.push(format!("{} image(s) could not be imported", converter.images));
+    }
+    if !converter.skipped.is_empty() {
+        let skipped = converter.skipped.into_iter().collect::<Vec<_>>().join(", ");
+        warnings.push(format!("skipped unsupported content ({})", skipped));
     }
 
-    ChatInterface {
+    let chat = ChatInterface {
         id: uuid::Uuid::new_v4().to_string(),
-        title: Some(openai_chat.title.clone()),
+        title: Some(openai_chat.title.clone().unwrap_or_else(|| "New Chat".to_string())),
         messages,
         config: Some(ConfigInterface::default()), // Implement your default config
+        api_profile: None,
         title_set: true,
         folder: None,
+        revision: 0,
+    };
+    (chat, warnings)
+}
+
+struct OpenAIConverter<'a> {
+    mapping: &'a HashMap<String, OpenAIChatNode>,
+    keep_branches: bool,
+    /// Nodes on the path from the root to `current_node`.
+    active: HashSet<String>,
+    /// Guards against cycles in a malformed mapping.
+    visited: HashSet<String>,
+    images: usize,
+    skipped: BTreeSet<String>,
+}
+
+impl OpenAIConverter<'_> {
+    /// The conversation from node `id` on, along the active branch.
+    fn thread(&mut self, id: &str) -> Vec<MessageInterface> {
+        let Some(node) = self.mapping.get(id) else {
+            return Vec::new();
+        };
+        if !self.visited.insert(id.to_string()) {
+            return Vec::new();
+        }
+        let mut thread: Vec<MessageInterface> =
+            node.message.as_ref().and_then(|m| self.message(m)).into_iter().collect();
+
+        // Without a marked child, ChatGPT shows the latest one.
+        let active = node
+            .children
+            .iter()
+            .position(|child| self.active.contains(child))
+            .unwrap_or(node.children.len().saturating_sub(1));
+        if self.keep_branches {
+            // Children whose own message is hidden fork further down, so
+            // their versions are collected rather than the children themselves.
+            let mut versions = Vec::new();
+            let mut active_version = 0;
+            for (i, child) in node.children.iter().enumerate() {
+                let path = self.thread(child);
+                let Some(first) = path.first() else {
+                    continue;
+                };
+                if i == active {
+                    active_version = versions.len() + first.branch.min(first.alternatives.len());
+                }
+                versions.extend(ChatInterface::siblings(path));
+            }
+            if !versions.is_empty() {
+                thread.extend(ChatInterface::join_branches(versions, active_version));
+            }
+        } else if let Some(child) = node.children.get(active) {
+            thread.extend(self.thread(child));
+        }
+        thread
+    }
+
+    fn message(&mut self, message: &OpenAIChatMessage) -> Option<MessageInterface> {
+        if message.metadata.is_visually_hidden_from_conversation {
+            return None;
+        }
+        let mut role = match message.author.role.as_str() {
+            "user" => Role::User,
+            "system" => Role::System,
+            // Tool output is shown as part of the assistant's answer.
+            _ => Role::Assistant,
+        };
+        let content = &message.content;
+        let text = match content.content_type.as_str() {
+            "text" | "multimodal_text" => self.parts(&content.parts),
+            "code" => {
+                let language = content.language.as_deref().filter(|l| *l != "unknown").unwrap_or_default();
+                fenced(language, content.text.as_deref().unwrap_or_default())
+            }
+            "execution_output" => fenced("", content.text.as_deref().unwrap_or_default()),
+            "tether_quote" => {
+                let quote = content
+                    .text
+                    .as_deref()
+                    .unwrap_or_default()
+                    .lines()
+                    .map(|line| format!("> {}", line))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                match (&content.title, &content.url) {
+                    (Some(title), Some(url)) => format!("{}\n>\n> — [{}]({})", quote, title, url),
+                    _ => quote,
+                }
+            }
+            "user_editable_context" => {
+                role = Role::System;
+                [&content.user_profile, &content.user_instructions]
+                    .into_iter()
+                    .flatten()
+                    .map(|s| s.trim())
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            }
+            // Browsing status and the model's reasoning are not part of the conversation.
+            "tether_browsing_display" | "thoughts" | "reasoning_recap" | "model_editable_context" => {
+                return None;
+            }
+            other => match &content.text {
+                Some(text) => text.clone(),
+                None if !content.parts.is_empty() => self.parts(&content.parts),
+                None => {
+                    self.skipped.insert(other.to_string());
+                    return None;
+                }
+            },
+        };
+        if text.trim().is_empty() {
+            return None;
+        }
+        let finish = message.metadata.finish_details.as_ref().map(|f| f.kind.as_str());
+        Some(MessageInterface {
+            model: message.metadata.model_slug.clone().filter(|_| role == Role::Assistant),
+            role,
+            content: text,
+            created_at: message.create_time.map(|t| (t * 1000.0).round() as i64),
+            interrupted: finish == Some("interrupted"),
+            finish_reason: match finish {
+                Some("max_tokens") => Some("length".to_string()),
+                Some("stop") => Some("stop".to_string()),
+                _ => None,
+            },
+            ..Default::default()
+        })
+    }
+
+    fn parts(&mut self, parts: &[serde_json::Value]) -> String {
+        parts
+            .iter()
+            .filter_map(|part| match part {
+                serde_json::Value::String(text) => Some(text.clone()),
+                part => match part["content_type"].as_str().unwrap_or("unknown") {
+                    "audio_transcription" => part["text"].as_str().map(|t| t.to_string()),
+                    "image_asset_pointer" => {
+                        self.images += 1;
+                        None
+                    }
+                    other => {
+                        self.skipped.insert(other.to_string());
+                        None
+                    }
+                },
+            })
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
     }
 }
 
-// Batch conversion function
-pub fn import_openai_chat_export(openai_chats: Vec<OpenAIChat>) -> Vec<ChatInterface> {
-    openai_chats
-        .into_iter()
-        .map(|chat| convert_openai_to_my_format(&chat))
-        .collect()
+fn fenced(language: &str, code: &str) -> String {
+    format!("```{}\n{}\n```", language, code.trim_end())
+}
+
+/// Converts a whole `conversations.json`. Conversations without any
+/// importable message are left out and reported.
+pub fn import_openai_chat_export(openai_chats: Vec<OpenAIChat>, keep_branches: bool) -> OpenAIImport {
+    let mut import = OpenAIImport::default();
+    for openai_chat in &openai_chats {
+        let (chat, warnings) = convert_openai_to_my_format(openai_chat, keep_branches);
+        let title = chat.title.clone().unwrap_or_default();
+        import
+            .warnings
+            .extend(warnings.into_iter().map(|w| format!("{}: {}", title, w)));
+        if chat.messages.is_empty() {
+            import.warnings.push(format!("{}: no messages to import, skipped", title));
+        } else {
+            import.chats.push(chat);
+        }
+    }
+    import
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// system (hidden) -> user "hi" -> assistant "one" / assistant "two" (current),
+    /// the second answer continuing with a code call and its output.
+    const CONVERSATION: &str = r#"{
+        "title": "Greeting",
+        "current_node": "out",
+        "mapping": {
+            "a1": {"id": "a1", "parent": "u", "children": [], "message": {
+                "author": {"role": "assistant"}, "create_time": 1700000001.5,
+                "content": {"content_type": "text", "parts": ["one"]},
+                "metadata": {"model_slug": "gpt-4", "finish_details": {"type": "max_tokens"}}}},
+            "u": {"id": "u", "parent": "sys", "children": ["a1", "a2"], "message": {
+                "author": {"role": "user"}, "create_time": 1700000000,
+                "content": {"content_type": "multimodal_text", "parts": [
+                    {"content_type": "image_asset_pointer", "asset_pointer": "file-service://x"}, "hi"]}}},
+            "root": {"id": "root", "parent": null, "children": ["sys"], "message": null},
+            "sys": {"id": "sys", "parent": "root", "children": ["u"], "message": {
+                "author": {"role": "system"}, "content": {"content_type": "text", "parts": [""]},
+                "metadata": {"is_visually_hidden_from_conversation": true}}},
+            "a2": {"id": "a2", "parent": "u", "children": ["out"], "message": {
+                "author": {"role": "assistant"}, "recipient": "python",
+                "content": {"content_type": "code", "language": "python", "text": "print(2)"},
+                "metadata": {"model_slug": "gpt-4o"}}},
+            "out": {"id": "out", "parent": "a2", "children": [], "message": {
+                "author": {"role": "tool", "name": "python"},
+                "content": {"content_type": "execution_output", "text": "2"}}}
+        }
+    }"#;
+
+    #[test]
+    fn follows_current_node_and_keeps_other_branches() {
+        let openai_chat: OpenAIChat = serde_json::from_str(CONVERSATION).unwrap();
+        let (mut chat, warnings) = convert_openai_to_my_format(&openai_chat, true);
+
+        let contents: Vec<&str> = chat.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["hi", "```python\nprint(2)\n```", "```\n2\n```"]);
+        assert_eq!(chat.messages[0].created_at, Some(1_700_000_000_000));
+        assert_eq!(chat.messages[1].model.as_deref(), Some("gpt-4o"));
+        assert_eq!(chat.branch_count(1), 2);
+        assert_eq!(warnings, ["1 image(s) could not be imported"]);
+
+        chat.switch_branch(1, 0);
+        assert_eq!(chat.messages.len(), 2);
+        assert_eq!(chat.messages[1].content, "one");
+        assert_eq!(chat.messages[1].created_at, Some(1_700_000_001_500));
+        assert_eq!(chat.messages[1].finish_reason.as_deref(), Some("length"));
+
+        let (chat, _) = convert_openai_to_my_format(&openai_chat, false);
+        assert_eq!(chat.messages.len(), 3);
+        assert_eq!(chat.branch_count(1), 1);
+    }
+
+    #[test]
+    fn empty_conversations_are_reported() {
+        let empty: OpenAIChat = serde_json::from_str(
+            r#"{"title": "Empty", "current_node": "gone", "mapping": {"r": {"id": "r", "parent": null, "children": [], "message": null}}}"#,
+        )
+        .unwrap();
+        let import = import_openai_chat_export(vec![empty], true);
+        assert!(import.chats.is_empty());
+        assert_eq!(
+            import.warnings,
+            [
+                "Empty: the selected branch is missing, imported the latest one instead",
+                "Empty: no messages to import, skipped",
+            ]
+        );
+    }
 }
@@ -0,0 +1,284 @@
+//! Optional passphrase protection of the stored and synced state.
+//!
+//! The passphrase is stretched with PBKDF2 into an AES-GCM key, which seals
+//! every stored chat, the rest of the state (prompts and API keys included),
+//! the synced file and encrypted exports. Only the [`VaultHeader`] stays
+//! readable: the salt, and a known text sealed with the key so a wrong
+//! passphrase can be told apart.
+
+#[cfg(test)]
+pub(crate) mod stand_in;
+mod web_crypto;
+
+use std::{fmt, rc::Rc};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures::future::LocalBoxFuture;
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub(crate) use web_crypto::{WebCryptoPassphrase, random_bytes};
+
+/// LocalStorage key of the [`VaultHeader`], kept apart from the state so it
+/// can be read before the state is unlocked.
+pub(crate) const VAULT_KEY: &str = "yewmind-vault";
+/// OWASP's recommendation for PBKDF2-HMAC-SHA256.
+pub(crate) const KDF_ITERATIONS: u32 = 600_000;
+/// Iteration counts accepted from a header, which may come from an imported
+/// or synced file: fewer would be too weak, more would freeze the tab.
+const KDF_ITERATIONS_ACCEPTED: std::ops::RangeInclusive<u32> = 100_000..=10_000_000;
+pub(crate) const SALT_LEN: usize = 16;
+const KDF: &str = "PBKDF2-SHA256";
+const ALGORITHM: &str = "AES-GCM";
+/// Sealed into [`VaultHeader::check`].
+const CHECK: &str = "yewmind vault";
+
+#[derive(Debug, Error)]
+pub(crate) enum VaultError {
+    #[error("the passphrase is wrong")]
+    WrongPassphrase,
+    #[error("the data is encrypted, and no passphrase was given")]
+    Locked,
+    #[error("unsupported key derivation {0}")]
+    UnsupportedKdf(String),
+    #[error("unsupported key derivation with {0} iterations")]
+    UnsupportedIterations(u32),
+    #[error("{0}")]
+    Crypto(String),
+    #[error(transparent)]
+    Encoding(#[from] base64::DecodeError),
+    #[error(transparent)]
+    Utf8(#[from] std::string::FromUtf8Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// Data encrypted with AES-GCM, which also tells when it was tampered with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Sealed {
+    pub(crate) alg: String,
+    /// Base64, never reused with the same key.
+    pub(crate) iv: String,
+    /// Base64 ciphertext, followed by the authentication tag.
+    pub(crate) data: String,
+}
+
+impl Sealed {
+    pub(crate) fn new(iv: &[u8], data: &[u8]) -> Self {
+        Self {
+            alg: ALGORITHM.to_string(),
+            iv: STANDARD.encode(iv),
+            data: STANDARD.encode(data),
+        }
+    }
+
+    /// The IV and ciphertext.
+    pub(crate) fn bytes(&self) -> Result<(Vec<u8>, Vec<u8>), VaultError> {
+        Ok((STANDARD.decode(&self.iv)?, STANDARD.decode(&self.data)?))
+    }
+
+    /// `raw` as written by [`Vault::seal`], `None` when it is plain JSON.
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        serde_json::from_str::<Self>(raw).ok().filter(|s| s.alg == ALGORITHM)
+    }
+}
+
+/// How to get from the passphrase to the key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct VaultHeader {
+    pub(crate) kdf: String,
+    pub(crate) iterations: u32,
+    /// Base64.
+    pub(crate) salt: String,
+    /// [`CHECK`], sealed with the key.
+    pub(crate) check: Sealed,
+}
+
+/// A file sealed with the key of the vault it names, so it can be opened
+/// anywhere with the same passphrase.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SealedFile {
+    pub(crate) vault: VaultHeader,
+    pub(crate) sealed: Sealed,
+}
+
+impl SealedFile {
+    /// `None` when `raw` is no sealed file, e.g. a plain export.
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        serde_json::from_str(raw).ok()
+    }
+}
+
+/// AES-GCM with one key.
+pub(crate) trait Cipher {
+    /// Encrypts `plain` under a fresh IV.
+    fn seal<'a>(&'a self, plain: &'a [u8]) -> LocalBoxFuture<'a, Result<Sealed, VaultError>>;
+
+    /// Fails with [`VaultError::WrongPassphrase`] when `sealed` wasn't made
+    /// with this key, or was changed since.
+    fn open<'a>(&'a self, sealed: &'a Sealed) -> LocalBoxFuture<'a, Result<Vec<u8>, VaultError>>;
+}
+
+/// The passphrase, as key material keys are derived from.
+pub(crate) trait Passphrase {
+    fn derive<'a>(&'a self, salt: &'a [u8], iterations: u32) -> LocalBoxFuture<'a, Result<Rc<dyn Cipher>, VaultError>>;
+}
+
+/// An unlocked vault.
+#[derive(Clone)]
+pub(crate) struct Vault {
+    header: VaultHeader,
+    /// Kept to open files sealed under another salt, see [`Vault::open_file`].
+    passphrase: Rc<dyn Passphrase>,
+    cipher: Rc<dyn Cipher>,
+}
+
+impl PartialEq for Vault {
+    fn eq(&self, other: &Self) -> bool {
+        self.header == other.header && Rc::ptr_eq(&self.cipher, &other.cipher)
+    }
+}
+
+impl fmt::Debug for Vault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Vault").field("header", &self.header).finish_non_exhaustive()
+    }
+}
+
+impl Vault {
+    /// A new vault for `passphrase`. `salt` has to be random, see
+    /// [`random_bytes`].
+    pub(crate) async fn create(passphrase: Rc<dyn Passphrase>, salt: &[u8]) -> Result<Self, VaultError> {
+        let cipher = passphrase.derive(salt, KDF_ITERATIONS).await?;
+        let header = VaultHeader {
+            kdf: KDF.to_string(),
+            iterations: KDF_ITERATIONS,
+            salt: STANDARD.encode(salt),
+            check: cipher.seal(CHECK.as_bytes()).await?,
+        };
+        Ok(Self {
+            header,
+            passphrase,
+            cipher,
+        })
+    }
+
+    pub(crate) async fn unlock(header: VaultHeader, passphrase: Rc<dyn Passphrase>) -> Result<Self, VaultError> {
+        let cipher = key(&header, passphrase.as_ref()).await?;
+        Ok(Self {
+            header,
+            passphrase,
+            cipher,
+        })
+    }
+
+    pub(crate) fn header(&self) -> &VaultHeader {
+        &self.header
+    }
+
+    /// `plain` as a [`Sealed`] JSON object.
+    pub(crate) async fn seal(&self, plain: &str) -> Result<String, VaultError> {
+        Ok(serde_json::to_string(&self.cipher.seal(plain.as_bytes()).await?)?)
+    }
+
+    /// Opens what [`Vault::seal`] wrote.
+    pub(crate) async fn open(&self, sealed: &Sealed) -> Result<String, VaultError> {
+        Ok(String::from_utf8(self.cipher.open(sealed).await?)?)
+    }
+
+    /// `plain` as a [`SealedFile`].
+    pub(crate) async fn seal_file(&self, plain: &str) -> Result<String, VaultError> {
+        Ok(serde_json::to_string(&SealedFile {
+            vault: self.header.clone(),
+            sealed: self.cipher.seal(plain.as_bytes()).await?,
+        })?)
+    }
+
+    /// Opens a file sealed by this vault, or by another one with the same
+    /// passphrase.
+    pub(crate) async fn open_file(&self, file: &SealedFile) -> Result<String, VaultError> {
+        if file.vault.salt == self.header.salt {
+            return self.open(&file.sealed).await;
+        }
+        open_file(file, self.passphrase.as_ref()).await
+    }
+}
+
+/// Opens `file` with a passphrase given for it, e.g. on import.
+pub(crate) async fn open_file(file: &SealedFile, passphrase: &dyn Passphrase) -> Result<String, VaultError> {
+    let cipher = key(&file.vault, passphrase).await?;
+    Ok(String::from_utf8(cipher.open(&file.sealed).await?)?)
+}
+
+/// The key `header` describes, checked against [`VaultHeader::check`].
+async fn key(header: &VaultHeader, passphrase: &dyn Passphrase) -> Result<Rc<dyn Cipher>, VaultError> {
+    if header.kdf != KDF {
+        return Err(VaultError::UnsupportedKdf(header.kdf.clone()));
+    }
+    if !KDF_ITERATIONS_ACCEPTED.contains(&header.iterations) {
+        return Err(VaultError::UnsupportedIterations(header.iterations));
+    }
+    let cipher = passphrase.derive(&STANDARD.decode(&header.salt)?, header.iterations).await?;
+    if cipher.open(&header.check).await? != CHECK.as_bytes() {
+        return Err(VaultError::WrongPassphrase);
+    }
+    Ok(cipher)
+}
+
+/// The header of the vault set up in this browser, if any.
+pub(crate) fn stored_header() -> Option<VaultHeader> {
+    LocalStorage::get(VAULT_KEY).ok()
+}
+
+pub(crate) fn store_header(header: Option<&VaultHeader>) -> Result<(), gloo_storage::errors::StorageError> {
+    match header {
+        Some(header) => LocalStorage::set(VAULT_KEY, header),
+        None => {
+            LocalStorage::delete(VAULT_KEY);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::stand_in::ToyPassphrase;
+
+    #[tokio::test]
+    async fn a_vault_unlocks_with_its_passphrase_only() {
+        let vault = Vault::create(ToyPassphrase::new("correct horse"), b"0123456789abcdef").await.unwrap();
+        let sealed = vault.seal(r#"{"api_key":"sk-secret"}"#).await.unwrap();
+        assert!(!sealed.contains("sk-secret"));
+        let parsed = Sealed::parse(&sealed).unwrap();
+        assert_eq!(Sealed::parse(r#"{"id":"chat","title":"Hello"}"#), None);
+
+        let header = serde_json::from_str(&serde_json::to_string(vault.header()).unwrap()).unwrap();
+        let unlocked = Vault::unlock(header, ToyPassphrase::new("correct horse")).await.unwrap();
+        assert_eq!(unlocked.open(&parsed).await.unwrap(), r#"{"api_key":"sk-secret"}"#);
+        let wrong = Vault::unlock(vault.header().clone(), ToyPassphrase::new("battery staple")).await;
+        assert!(matches!(wrong, Err(VaultError::WrongPassphrase)));
+
+        for iterations in [1, u32::MAX] {
+            let crafted = VaultHeader { iterations, ..vault.header().clone() };
+            let unlocked = Vault::unlock(crafted, ToyPassphrase::new("correct horse")).await;
+            assert!(matches!(unlocked, Err(VaultError::UnsupportedIterations(n)) if n == iterations));
+        }
+    }
+
+    #[tokio::test]
+    async fn files_open_under_any_vault_with_the_same_passphrase() {
+        let laptop = Vault::create(ToyPassphrase::new("pass"), b"laptop-salt-0001").await.unwrap();
+        let phone = Vault::create(ToyPassphrase::new("pass"), b"phone-salt-00001").await.unwrap();
+        let file = laptop.seal_file("export").await.unwrap();
+        let file = SealedFile::parse(&file).unwrap();
+        assert_eq!(phone.open_file(&file).await.unwrap(), "export");
+        assert_eq!(open_file(&file, ToyPassphrase::new("pass").as_ref()).await.unwrap(), "export");
+        assert!(matches!(
+            open_file(&file, ToyPassphrase::new("other").as_ref()).await,
+            Err(VaultError::WrongPassphrase)
+        ));
+        assert_eq!(SealedFile::parse(r#"{"version":2,"chats":[]}"#), None);
+    }
+}
@@ -0,0 +1,76 @@
+//! A stand-in for WebCrypto, which tests outside the browser can't reach.
+//! It keeps the contract of [`Cipher`] (wrong keys and changed data are
+//! told apart) without being any good as encryption.
+
+use std::rc::Rc;
+
+use futures::future::LocalBoxFuture;
+use sha2::{Digest, Sha256};
+
+use super::{Cipher, Passphrase, Sealed, VaultError};
+
+pub(crate) struct ToyPassphrase(String);
+
+impl ToyPassphrase {
+    pub(crate) fn new(passphrase: &str) -> Rc<Self> {
+        Rc::new(Self(passphrase.to_string()))
+    }
+}
+
+impl Passphrase for ToyPassphrase {
+    fn derive<'a>(&'a self, salt: &'a [u8], iterations: u32) -> LocalBoxFuture<'a, Result<Rc<dyn Cipher>, VaultError>> {
+        Box::pin(async move {
+            let key = Sha256::new()
+                .chain_update(self.0.as_bytes())
+                .chain_update(salt)
+                .chain_update(iterations.to_be_bytes())
+                .finalize()
+                .into();
+            Ok(Rc::new(ToyCipher(key)) as Rc<dyn Cipher>)
+        })
+    }
+}
+
+struct ToyCipher([u8; 32]);
+
+impl ToyCipher {
+    fn keystream(&self, iv: &[u8], data: &[u8]) -> Vec<u8> {
+        data.chunks(32)
+            .enumerate()
+            .flat_map(|(i, chunk)| {
+                let block = Sha256::new()
+                    .chain_update(self.0)
+                    .chain_update(iv)
+                    .chain_update((i as u64).to_be_bytes())
+                    .finalize();
+                chunk.iter().zip(block).map(|(b, k)| b ^ k).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn tag(&self, iv: &[u8], data: &[u8]) -> Vec<u8> {
+        Sha256::new().chain_update(self.0).chain_update(iv).chain_update(data).finalize()[..16].to_vec()
+    }
+}
+
+impl Cipher for ToyCipher {
+    fn seal<'a>(&'a self, plain: &'a [u8]) -> LocalBoxFuture<'a, Result<Sealed, VaultError>> {
+        Box::pin(async move {
+            let iv = uuid::Uuid::new_v4().as_bytes()[..12].to_vec();
+            let mut data = self.keystream(&iv, plain);
+            data.extend(self.tag(&iv, &data));
+            Ok(Sealed::new(&iv, &data))
+        })
+    }
+
+    fn open<'a>(&'a self, sealed: &'a Sealed) -> LocalBoxFuture<'a, Result<Vec<u8>, VaultError>> {
+        Box::pin(async move {
+            let (iv, data) = sealed.bytes()?;
+            let (data, tag) = data.split_at(data.len().checked_sub(16).ok_or(VaultError::WrongPassphrase)?);
+            if self.tag(&iv, data) != tag {
+                return Err(VaultError::WrongPassphrase);
+            }
+            Ok(self.keystream(&iv, data))
+        })
+    }
+}
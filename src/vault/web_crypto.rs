@@ -0,0 +1,115 @@
+//! [`Passphrase`] and [`Cipher`] on the browser's WebCrypto API. Keys are
+//! created non-extractable, so neither they nor the passphrase can be read
+//! back from them.
+
+use std::rc::Rc;
+
+use futures::future::LocalBoxFuture;
+use js_sys::{Array, ArrayBuffer, Object, Reflect, Uint8Array};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AesDerivedKeyParams, AesGcmParams, CryptoKey, Pbkdf2Params, SubtleCrypto};
+
+use super::{Cipher, Passphrase, Sealed, VaultError};
+
+const IV_LEN: usize = 12;
+const KEY_BITS: u32 = 256;
+
+/// The passphrase, imported as PBKDF2 key material.
+pub(crate) struct WebCryptoPassphrase {
+    material: CryptoKey,
+}
+
+impl WebCryptoPassphrase {
+    pub(crate) async fn import(passphrase: &str) -> Result<Rc<Self>, VaultError> {
+        let algorithm = Object::new();
+        Reflect::set(&algorithm, &"name".into(), &"PBKDF2".into()).map_err(crypto_error)?;
+        let promise = subtle()?
+            .import_key_with_object(
+                "raw",
+                &Uint8Array::from(passphrase.as_bytes()),
+                &algorithm,
+                false,
+                &Array::of1(&"deriveKey".into()),
+            )
+            .map_err(crypto_error)?;
+        let material = JsFuture::from(promise).await.map_err(crypto_error)?;
+        Ok(Rc::new(Self {
+            material: material.unchecked_into(),
+        }))
+    }
+}
+
+impl Passphrase for WebCryptoPassphrase {
+    fn derive<'a>(&'a self, salt: &'a [u8], iterations: u32) -> LocalBoxFuture<'a, Result<Rc<dyn Cipher>, VaultError>> {
+        Box::pin(async move {
+            let params = Pbkdf2Params::new("PBKDF2", &"SHA-256".into(), iterations, &Uint8Array::from(salt));
+            let promise = subtle()?
+                .derive_key_with_object_and_object(
+                    &params,
+                    &self.material,
+                    &AesDerivedKeyParams::new("AES-GCM", KEY_BITS),
+                    false,
+                    &Array::of2(&"encrypt".into(), &"decrypt".into()),
+                )
+                .map_err(crypto_error)?;
+            let key = JsFuture::from(promise).await.map_err(crypto_error)?;
+            Ok(Rc::new(WebCryptoCipher {
+                key: key.unchecked_into(),
+            }) as Rc<dyn Cipher>)
+        })
+    }
+}
+
+struct WebCryptoCipher {
+    key: CryptoKey,
+}
+
+impl Cipher for WebCryptoCipher {
+    fn seal<'a>(&'a self, plain: &'a [u8]) -> LocalBoxFuture<'a, Result<Sealed, VaultError>> {
+        Box::pin(async move {
+            let iv = random_bytes(IV_LEN)?;
+            let params = AesGcmParams::new("AES-GCM", &Uint8Array::from(iv.as_slice()));
+            let promise = subtle()?
+                .encrypt_with_object_and_u8_array(&params, &self.key, plain)
+                .map_err(crypto_error)?;
+            let data = JsFuture::from(promise).await.map_err(crypto_error)?;
+            Ok(Sealed::new(&iv, &bytes(data)))
+        })
+    }
+
+    fn open<'a>(&'a self, sealed: &'a Sealed) -> LocalBoxFuture<'a, Result<Vec<u8>, VaultError>> {
+        Box::pin(async move {
+            let (iv, data) = sealed.bytes()?;
+            let params = AesGcmParams::new("AES-GCM", &Uint8Array::from(iv.as_slice()));
+            let promise = subtle()?
+                .decrypt_with_object_and_u8_array(&params, &self.key, &data)
+                .map_err(crypto_error)?;
+            // The only way decryption fails is a tag that doesn't match.
+            let plain = JsFuture::from(promise).await.map_err(|_| VaultError::WrongPassphrase)?;
+            Ok(bytes(plain))
+        })
+    }
+}
+
+/// `len` bytes from the browser's CSPRNG.
+pub(crate) fn random_bytes(len: usize) -> Result<Vec<u8>, VaultError> {
+    let mut buffer = vec![0; len];
+    gloo_utils::window()
+        .crypto()
+        .and_then(|crypto| crypto.get_random_values_with_u8_array(&mut buffer))
+        .map_err(crypto_error)?;
+    Ok(buffer)
+}
+
+fn subtle() -> Result<SubtleCrypto, VaultError> {
+    Ok(gloo_utils::window().crypto().map_err(crypto_error)?.subtle())
+}
+
+fn bytes(buffer: JsValue) -> Vec<u8> {
+    Uint8Array::new(&buffer.unchecked_into::<ArrayBuffer>()).to_vec()
+}
+
+fn crypto_error(error: JsValue) -> VaultError {
+    VaultError::Crypto(format!("{:?}", error))
+}